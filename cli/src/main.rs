@@ -6,13 +6,11 @@ use crate::state::{State, FiniteStateMachine};
 use crate::color_state::ColorState;
 use crate::texture_state::TextureState;
 
-use gl_toolkit::Feature;
+use gl_toolkit::{Feature, GlContext, LinearColor};
 use glfw::SwapInterval;
-use lazy_static::lazy_static;
 use std::cell::Cell;
 use std::sync::mpsc::Receiver;
 use std::time::Instant;
-use vex::Matrix4;
 
 use glfw::{
 	Action,
@@ -25,10 +23,6 @@ use glfw::{
 	WindowMode,
 };
 
-lazy_static! {
-    static ref proj_mat: Matrix4 = Matrix4::ortho(0.0, 480.0, 0.0, 272.0, 0.0, 1000.0);
-}
-
 fn init_glfw() -> Glfw {
     let mut glfw = glfw::init(Some(glfw::Callback {
 		f: error_callback,
@@ -55,14 +49,17 @@ fn init_window(glfw: &Glfw) -> (Window, Receiver<(f64, WindowEvent)>) {
     (window, events)
 }
 
-fn init_gl(window: &mut Window) {
+fn init_gl(window: &mut Window) -> GlContext {
     let loader = |symbol| window.get_proc_address(symbol) as *const _;
 
     gl::load_with(loader);
-    gl_toolkit::init().unwrap();
-    gl_toolkit::set_clear_color(0.2, 0.3, 0.3, 1.0);
+
+    let context = gl_toolkit::init().unwrap();
+
+    gl_toolkit::set_clear_color_linear(LinearColor::make(0.2, 0.3, 0.3, 1.0));
     gl_toolkit::enable(Feature::CullFace);
     gl_toolkit::enable(Feature::Blend);
+    context
 }
 
 fn error_callback(_: glfw::Error, description: String, error_count: &Cell<usize>) {
@@ -82,6 +79,7 @@ fn process_events(window: &mut Window, events: &Receiver<(f64, WindowEvent)>) {
 }
 
 fn resize_frame(width: u32, height: u32) {
+    gl_toolkit::notify_resize(width, height);
     gl_toolkit::set_viewport(0, 0, width, height);
 }
 
@@ -89,7 +87,7 @@ fn main() {
     let mut glfw = init_glfw();
     let (mut window, events) = init_window(&glfw);
 
-    init_gl(&mut window);
+    let _context = init_gl(&mut window);
 
     let start_time = Instant::now();
     let mut fsm = FiniteStateMachine::new();
@@ -102,9 +100,11 @@ fn main() {
     while !window.should_close() {
         let elapsed_time = start_time.elapsed().as_secs_f32();
 
+        gl_toolkit::begin_frame();
         process_events(&mut window, &events);
         fsm.update(elapsed_time);
         fsm.render();
+        gl_toolkit::end_frame();
 
         window.swap_buffers();
         glfw.poll_events();
@@ -4,6 +4,7 @@ use lazy_static::lazy_static;
 
 use gl_toolkit::{
     SHADER_TEXTURE,
+    Color,
     WrapCoord,
     ClampMode,
     BufferMode,
@@ -14,13 +15,6 @@ use gl_toolkit::{
 };
 
 lazy_static! {
-    static ref TEXTURE_DATA: Vec<u8> = vec![
-        255, 255, 255, 255,
-          0,   0,   0, 255,
-          0,   0,   0, 255,
-        255, 255, 255, 255,
-    ];
-
     static ref VERTICES: Vec<TextureVertex> = vec![
         TextureVertex::new( 1.0,  1.0, 0.0, 8.0, 0.0),
         TextureVertex::new(-1.0,  1.0, 0.0, 0.0, 0.0),
@@ -37,7 +31,7 @@ pub struct TextureState {
 impl TextureState {
     pub fn new() -> TextureState {
         let mut result = TextureState {
-            texture: Texture::make(&TEXTURE_DATA, 2, 2, false).unwrap(),
+            texture: Texture::checkerboard(2, 2, 1, Color::WHITE, Color::BLACK).unwrap(),
             vbo: VBO::new(
                 BufferMode::StaticDraw,
                 PrimitiveKind::TriangleFan,
@@ -46,8 +40,8 @@ impl TextureState {
             ),
         };
 
-        result.texture.set_clamp(WrapCoord::S, ClampMode::Repeat);
-        result.texture.set_clamp(WrapCoord::T, ClampMode::Repeat);
+        result.texture.set_clamp(WrapCoord::S, ClampMode::Repeat).unwrap();
+        result.texture.set_clamp(WrapCoord::T, ClampMode::Repeat).unwrap();
         result
     }
 }
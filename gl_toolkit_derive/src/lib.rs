@@ -0,0 +1,110 @@
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Attribute, Data, DeriveInput, Error, Fields, Lit, Meta, NestedMeta, Result};
+
+#[derive(Default)]
+struct Options {
+    rename: Option<String>,
+    prefix: Option<String>,
+    skip: bool,
+}
+
+fn lit_str(lit: &Lit) -> Result<String> {
+    match lit {
+        Lit::Str(value) => Ok(value.value()),
+        _ => Err(Error::new_spanned(lit, "expected a string literal")),
+    }
+}
+
+fn parse_options(attrs: &[Attribute]) -> Result<Options> {
+    let mut result = Options::default();
+
+    for attr in attrs.iter().filter(|attr| attr.path.is_ident("uniform")) {
+        let list = match attr.parse_meta()? {
+            Meta::List(list) => list,
+            meta => return Err(Error::new_spanned(meta, "expected #[uniform(...)]")),
+        };
+
+        for nested in list.nested.iter() {
+            match nested {
+                NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => result.skip = true,
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("rename") => {
+                    result.rename = Some(lit_str(&pair.lit)?);
+                },
+                NestedMeta::Meta(Meta::NameValue(pair)) if pair.path.is_ident("prefix") => {
+                    result.prefix = Some(lit_str(&pair.lit)?);
+                },
+                _ => return Err(Error::new_spanned(nested, "expected `rename = \"...\"`, `prefix = \"...\"` or `skip`")),
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2> {
+    let container = parse_options(&input.attrs)?;
+
+    if container.rename.is_some() || container.skip {
+        return Err(Error::new_spanned(&input.ident, "only `prefix` applies to the struct itself"));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => return Err(Error::new_spanned(&input.ident, "Uniforms needs a struct with named fields")),
+        },
+        _ => return Err(Error::new_spanned(&input.ident, "Uniforms can only be derived for structs")),
+    };
+
+    let prefix = container.prefix.unwrap_or_default();
+    let mut uploads = Vec::new();
+
+    for field in fields.iter() {
+        let options = parse_options(&field.attrs)?;
+
+        if options.prefix.is_some() {
+            return Err(Error::new_spanned(field, "`prefix` only applies to the struct itself"));
+        }
+
+        if options.skip {
+            continue;
+        }
+
+        let ident = field.ident.as_ref().unwrap();
+
+        // a rename is the full uniform name, so the struct's prefix doesn't apply to it
+        let uniform = options.rename.unwrap_or_else(|| format!("{}{}", prefix, ident));
+
+        uploads.push(quote! {
+            ::gl_toolkit::Uniform::upload(&self.#ident, shader, &format!("{}{}", prefix, #uniform));
+        });
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics ::gl_toolkit::Uniforms for #name #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn apply_prefixed(&self, shader: &::gl_toolkit::Shader, prefix: &str) {
+                #(#uploads)*
+            }
+        }
+    })
+}
+
+// #[uniform(prefix = "u_")] on the struct prepends to every field's name; on fields,
+// #[uniform(rename = "u_name")] sets the full name and #[uniform(skip)] leaves the field out
+#[proc_macro_derive(Uniforms, attributes(uniform))]
+pub fn derive_uniforms(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match expand(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(err) => err.to_compile_error().into(),
+    }
+}
@@ -0,0 +1,220 @@
+use crate::error::{Error, Result};
+use crate::rect::Rect;
+use crate::texture::{Texture, TextureFormat};
+
+use std::collections::HashMap;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AtlasRegion {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    pub uv: Rect,
+}
+
+impl AtlasRegion {
+    pub fn pixel_rect(&self) -> Rect {
+        Rect::make(self.x as f32, self.y as f32, self.width as f32, self.height as f32)
+    }
+}
+
+pub struct AtlasImage<'a> {
+    pub name: &'a str,
+    pub pixels: &'a [u8],
+    pub width: usize,
+    pub height: usize,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+struct Space {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+}
+
+impl Space {
+    fn right(&self) -> usize {
+        self.x + self.width
+    }
+
+    fn bottom(&self) -> usize {
+        self.y + self.height
+    }
+
+    fn overlaps(&self, other: &Space) -> bool {
+        self.x < other.right() && other.x < self.right() && self.y < other.bottom() && other.y < self.bottom()
+    }
+
+    fn contains(&self, other: &Space) -> bool {
+        other.x >= self.x && other.y >= self.y && other.right() <= self.right() && other.bottom() <= self.bottom()
+    }
+}
+
+pub struct TextureAtlas {
+    texture: Texture,
+    padding: usize,
+    free: Vec<Space>,
+    entries: HashMap<String, AtlasRegion>,
+}
+
+impl TextureAtlas {
+    pub fn new(width: usize, height: usize) -> TextureAtlas {
+        TextureAtlas::with_padding(width, height, 1)
+    }
+
+    pub fn with_padding(width: usize, height: usize, padding: usize) -> TextureAtlas {
+        TextureAtlas {
+            texture: Texture::new_with_format(TextureFormat::Rgba8, width, height),
+            padding,
+            free: vec![Space { x: 0, y: 0, width, height }],
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn pack(width: usize, height: usize, padding: usize, images: &[AtlasImage]) -> Result<TextureAtlas> {
+        let mut result = TextureAtlas::with_padding(width, height, padding);
+        let mut order = images.iter().collect::<Vec<_>>();
+
+        // larger images first leaves far less fragmentation for the small ones
+        order.sort_by(|a, b| b.height.cmp(&a.height).then(b.width.cmp(&a.width)));
+
+        for image in order {
+            result.insert(image.name, image.pixels, image.width, image.height)?;
+        }
+
+        Ok(result)
+    }
+
+    pub fn insert(&mut self, name: &str, pixels: &[u8], width: usize, height: usize) -> Result<AtlasRegion> {
+        if width == 0 || height == 0 || pixels.len() != width * height * 4 {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        if self.entries.contains_key(name) {
+            return Err(Error::AtlasEntryExists(name.to_string()));
+        }
+
+        let space = match self.find_space(width + self.padding, height + self.padding) {
+            Some(space) => space,
+            None => return Err(Error::AtlasFull(name.to_string())),
+        };
+
+        self.split_free(&space);
+        self.texture.write(pixels, space.x, space.y, width, height);
+
+        let tex_width = self.texture.width() as f32;
+        let tex_height = self.texture.height() as f32;
+        let region = AtlasRegion {
+            x: space.x,
+            y: space.y,
+            width,
+            height,
+            uv: Rect::make(
+                space.x as f32 / tex_width,
+                space.y as f32 / tex_height,
+                width as f32 / tex_width,
+                height as f32 / tex_height,
+            ),
+        };
+
+        self.entries.insert(name.to_string(), region);
+
+        Ok(region)
+    }
+
+    // MaxRects, best short side fit
+    fn find_space(&self, width: usize, height: usize) -> Option<Space> {
+        let mut best: Option<(usize, usize, Space)> = None;
+
+        for free in self.free.iter() {
+            if free.width < width || free.height < height {
+                continue;
+            }
+
+            let short = (free.width - width).min(free.height - height);
+            let long = (free.width - width).max(free.height - height);
+            let better = match best {
+                Some((best_short, best_long, _)) => (short, long) < (best_short, best_long),
+                None => true,
+            };
+
+            if better {
+                best = Some((short, long, Space { x: free.x, y: free.y, width, height }));
+            }
+        }
+
+        best.map(|(_, _, space)| space)
+    }
+
+    fn split_free(&mut self, used: &Space) {
+        let mut next = Vec::with_capacity(self.free.len() + 4);
+
+        for free in self.free.iter() {
+            if !free.overlaps(used) {
+                next.push(*free);
+                continue;
+            }
+
+            if used.x > free.x {
+                next.push(Space { x: free.x, y: free.y, width: used.x - free.x, height: free.height });
+            }
+
+            if used.right() < free.right() {
+                next.push(Space { x: used.right(), y: free.y, width: free.right() - used.right(), height: free.height });
+            }
+
+            if used.y > free.y {
+                next.push(Space { x: free.x, y: free.y, width: free.width, height: used.y - free.y });
+            }
+
+            if used.bottom() < free.bottom() {
+                next.push(Space { x: free.x, y: used.bottom(), width: free.width, height: free.bottom() - used.bottom() });
+            }
+        }
+
+        // drop free rects that are fully covered by another
+        let mut pruned: Vec<Space> = Vec::with_capacity(next.len());
+
+        for (i, space) in next.iter().enumerate() {
+            let covered = next.iter().enumerate().any(|(j, other)| {
+                i != j && other.contains(space) && (other != space || j < i)
+            });
+
+            if !covered {
+                pruned.push(*space);
+            }
+        }
+
+        self.free = pruned;
+    }
+
+    pub fn get(&self, name: &str) -> Option<AtlasRegion> {
+        self.entries.get(name).copied()
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.contains_key(name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn padding(&self) -> usize {
+        self.padding
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn texture_mut(&mut self) -> &mut Texture {
+        &mut self.texture
+    }
+}
@@ -1,20 +1,31 @@
 use crate::color::Color;
+use crate::context::{self, ContextBound};
+use crate::math;
 use crate::shader::{Shader, Stage, StageKind};
+use crate::texture::Texture;
 use crate::vbo::{AttributeKind, Vertex};
 
 use lazy_static::lazy_static;
 
 use std::convert::From;
 use std::vec::Vec;
-use vex::{Vector2, Vector3};
+use vex::{Vector2, Vector3, Vector4};
 
 const SRC_BASIC_VERTEX: &str = r#"
     #version 330 core
 
+    uniform mat4 u_model;
+    uniform mat4 u_view;
+    uniform mat4 u_projection;
+    uniform vec4 u_clip_plane;
+
     layout (location = 0) in vec3 a_pos;
 
     void main() {
-        gl_Position = vec4(a_pos.x, a_pos.y, a_pos.z, 1.0);
+        vec4 world_pos = u_model * vec4(a_pos, 1.0);
+
+        gl_ClipDistance[0] = dot(world_pos, u_clip_plane);
+        gl_Position = u_projection * u_view * world_pos;
     }
 "#;
 
@@ -33,14 +44,22 @@ const SRC_BASIC_FRAGMENT: &str = r#"
 const SRC_COLOR_VERTEX: &str = r#"
     #version 330 core
 
+    uniform mat4 u_model;
+    uniform mat4 u_view;
+    uniform mat4 u_projection;
+    uniform vec4 u_clip_plane;
+
     layout (location = 0) in vec3 a_pos;
     layout (location = 1) in vec4 a_color;
 
     out vec4 v_color;
 
     void main() {
+        vec4 world_pos = u_model * vec4(a_pos, 1.0);
+
         v_color = a_color;
-        gl_Position = vec4(a_pos.x, a_pos.y, a_pos.z, 1.0);
+        gl_ClipDistance[0] = dot(world_pos, u_clip_plane);
+        gl_Position = u_projection * u_view * world_pos;
     }
 "#;
 
@@ -59,14 +78,22 @@ const SRC_COLOR_FRAGMENT: &str = r#"
 const SRC_TEXTURE_VERTEX: &str = r#"
     #version 330 core
 
+    uniform mat4 u_model;
+    uniform mat4 u_view;
+    uniform mat4 u_projection;
+    uniform vec4 u_clip_plane;
+
     layout (location = 0) in vec3 a_pos;
     layout (location = 1) in vec2 a_coord;
 
     out vec2 v_coord;
 
     void main() {
+        vec4 world_pos = u_model * vec4(a_pos, 1.0);
+
         v_coord = a_coord;
-        gl_Position = vec4(a_pos.x, a_pos.y, a_pos.z, 1.0);
+        gl_ClipDistance[0] = dot(world_pos, u_clip_plane);
+        gl_Position = u_projection * u_view * world_pos;
     }
 "#;
 
@@ -84,24 +111,283 @@ const SRC_TEXTURE_FRAGMENT: &str = r#"
     }
 "#;
 
-lazy_static! {
-    pub static ref SHADER_BASIC: Shader = Shader::new(&vec![
-        Stage::new(StageKind::Vertex, SRC_BASIC_VERTEX).unwrap(),
-        Stage::new(StageKind::Fragment, SRC_BASIC_FRAGMENT).unwrap(),
-    ])
-    .unwrap();
+// SHADER_TEXTURE has no per-vertex color to tint with and wants model/view/clip-plane uniforms, so batched
+// quads (sprites, text, particles) get their own shader with the projection as the only uniform
+const SRC_SPRITE_VERTEX: &str = r#"
+    #version 330 core
 
-    pub static ref SHADER_COLOR: Shader = Shader::new(&vec![
-        Stage::new(StageKind::Vertex, SRC_COLOR_VERTEX).unwrap(),
-        Stage::new(StageKind::Fragment, SRC_COLOR_FRAGMENT).unwrap(),
-    ])
-    .unwrap();
+    uniform mat4 u_projection;
+
+    layout (location = 0) in vec3 a_pos;
+    layout (location = 1) in vec2 a_coord;
+    layout (location = 2) in vec4 a_color;
+
+    out vec2 v_coord;
+    out vec4 v_color;
+
+    void main() {
+        v_coord = a_coord;
+        v_color = a_color;
+        gl_Position = u_projection * vec4(a_pos, 1.0);
+    }
+"#;
+
+const SRC_SPRITE_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_tex;
+
+    in vec2 v_coord;
+    in vec4 v_color;
+
+    out vec4 out_color;
+
+    void main() {
+        out_color = texture(u_tex, v_coord) * v_color;
+    }
+"#;
+
+pub(crate) const SRC_LIT_VERTEX: &str = r#"
+    #version 330 core
+
+    uniform mat4 u_model;
+    uniform mat4 u_view;
+    uniform mat4 u_projection;
+    uniform vec4 u_clip_plane;
+
+    layout (location = 0) in vec3 a_pos;
+    layout (location = 1) in vec3 a_normal;
+    layout (location = 2) in vec2 a_uv;
+
+    out vec3 v_world_pos;
+    out vec3 v_normal;
+    out vec2 v_uv;
+
+    void main() {
+        vec4 world_pos = u_model * vec4(a_pos, 1.0);
+
+        v_world_pos = world_pos.xyz;
+        v_normal = mat3(transpose(inverse(u_model))) * a_normal;
+        v_uv = a_uv;
+        gl_ClipDistance[0] = dot(world_pos, u_clip_plane);
+        gl_Position = u_projection * u_view * world_pos;
+    }
+"#;
+
+const SRC_LIT_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_tex;
+    uniform vec3 u_light_dir;
+    uniform vec3 u_light_color;
+    uniform vec3 u_ambient;
+    uniform vec3 u_camera_pos;
+    uniform float u_shininess;
+
+    in vec3 v_world_pos;
+    in vec3 v_normal;
+    in vec2 v_uv;
+
+    out vec4 out_color;
+
+    void main() {
+        vec4 albedo = texture(u_tex, v_uv);
+        vec3 n = normalize(v_normal);
+        vec3 l = normalize(-u_light_dir);
+        vec3 h = normalize(l + normalize(u_camera_pos - v_world_pos));
+        float diffuse = max(dot(n, l), 0.0);
+        float specular = diffuse > 0.0 ? pow(max(dot(n, h), 0.0), u_shininess) : 0.0;
+        vec3 lit = albedo.rgb * (u_ambient + u_light_color * diffuse) + u_light_color * specular;
+
+        out_color = vec4(lit, albedo.a);
+    }
+"#;
+
+const SRC_NORMAL_MAPPED_VERTEX: &str = r#"
+    #version 330 core
+
+    uniform mat4 u_model;
+    uniform mat4 u_view;
+    uniform mat4 u_projection;
+    uniform vec4 u_clip_plane;
+
+    layout (location = 0) in vec3 a_pos;
+    layout (location = 1) in vec3 a_normal;
+    layout (location = 2) in vec3 a_tangent;
+    layout (location = 3) in vec2 a_uv;
+
+    out vec3 v_world_pos;
+    out vec2 v_uv;
+    out mat3 v_tbn;
+
+    void main() {
+        vec4 world_pos = u_model * vec4(a_pos, 1.0);
+        mat3 normal_matrix = mat3(transpose(inverse(u_model)));
+        vec3 n = normalize(normal_matrix * a_normal);
+        vec3 t = normalize(normal_matrix * a_tangent);
+
+        t = normalize(t - dot(t, n) * n);
+
+        v_world_pos = world_pos.xyz;
+        v_uv = a_uv;
+        v_tbn = mat3(t, cross(n, t), n);
+        gl_ClipDistance[0] = dot(world_pos, u_clip_plane);
+        gl_Position = u_projection * u_view * world_pos;
+    }
+"#;
+
+const SRC_NORMAL_MAPPED_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_tex;
+    uniform sampler2D u_normal_map;
+    uniform vec3 u_light_dir;
+    uniform vec3 u_light_color;
+    uniform vec3 u_ambient;
+    uniform vec3 u_camera_pos;
+    uniform float u_shininess;
+
+    in vec3 v_world_pos;
+    in vec2 v_uv;
+    in mat3 v_tbn;
+
+    out vec4 out_color;
+
+    void main() {
+        vec4 albedo = texture(u_tex, v_uv);
+        vec3 n = normalize(v_tbn * (texture(u_normal_map, v_uv).xyz * 2.0 - 1.0));
+        vec3 l = normalize(-u_light_dir);
+        vec3 h = normalize(l + normalize(u_camera_pos - v_world_pos));
+        float diffuse = max(dot(n, l), 0.0);
+        float specular = diffuse > 0.0 ? pow(max(dot(n, h), 0.0), u_shininess) : 0.0;
+        vec3 lit = albedo.rgb * (u_ambient + u_light_color * diffuse) + u_light_color * specular;
+
+        out_color = vec4(lit, albedo.a);
+    }
+"#;
+
+// metallic-roughness shading for glTF materials with a single directional light; the factors multiply
+// the textures, which are optional (u_has_tex, u_has_normal_map)
+const SRC_PBR_FRAGMENT: &str = r#"
+    #version 330 core
+
+    const float PI = 3.14159265;
+
+    uniform sampler2D u_tex;
+    uniform sampler2D u_normal_map;
+    uniform int u_has_tex;
+    uniform int u_has_normal_map;
+    uniform vec4 u_base_color;
+    uniform float u_metallic;
+    uniform float u_roughness;
+    uniform vec3 u_light_dir;
+    uniform vec3 u_light_color;
+    uniform vec3 u_ambient;
+    uniform vec3 u_camera_pos;
+
+    in vec3 v_world_pos;
+    in vec2 v_uv;
+    in mat3 v_tbn;
+
+    out vec4 out_color;
+
+    void main() {
+        vec4 base = u_base_color;
+
+        if (u_has_tex != 0) {
+            base *= texture(u_tex, v_uv);
+        }
+
+        vec3 n = normalize(v_tbn[2]);
+
+        if (u_has_normal_map != 0) {
+            n = normalize(v_tbn * (texture(u_normal_map, v_uv).xyz * 2.0 - 1.0));
+        }
 
-    pub static ref SHADER_TEXTURE: Shader = Shader::new(&vec![
-        Stage::new(StageKind::Vertex, SRC_TEXTURE_VERTEX).unwrap(),
-        Stage::new(StageKind::Fragment, SRC_TEXTURE_FRAGMENT).unwrap(),
+        vec3 v = normalize(u_camera_pos - v_world_pos);
+        vec3 l = normalize(-u_light_dir);
+        vec3 h = normalize(l + v);
+        float n_dot_l = max(dot(n, l), 0.0);
+        float n_dot_v = max(dot(n, v), 0.0001);
+        float n_dot_h = max(dot(n, h), 0.0);
+        float alpha = max(u_roughness * u_roughness, 0.001);
+        float a2 = alpha * alpha;
+        float denom = n_dot_h * n_dot_h * (a2 - 1.0) + 1.0;
+        float d = a2 / (PI * denom * denom);
+        float k = alpha * 0.5;
+        float g = n_dot_l / (n_dot_l * (1.0 - k) + k) * n_dot_v / (n_dot_v * (1.0 - k) + k);
+        vec3 f0 = mix(vec3(0.04), base.rgb, u_metallic);
+        vec3 f = f0 + (1.0 - f0) * pow(1.0 - max(dot(h, v), 0.0), 5.0);
+        vec3 specular = d * g * f / max(4.0 * n_dot_l * n_dot_v, 0.0001);
+        vec3 diffuse = (1.0 - f) * (1.0 - u_metallic) * base.rgb;
+
+        // light_color is treated as irradiance, which folds the 1/PI of the diffuse term away
+        vec3 lit = (diffuse + specular * PI) * u_light_color * n_dot_l + u_ambient * base.rgb;
+
+        out_color = vec4(lit, base.a);
+    }
+"#;
+
+fn build_shader(vertex: &str, fragment: &str) -> Shader {
+    context::assert_initialized();
+
+    let result = Shader::new(&vec![
+        Stage::new(StageKind::Vertex, vertex).unwrap(),
+        Stage::new(StageKind::Fragment, fragment).unwrap(),
     ])
     .unwrap();
+
+    // identity transforms keep untransformed geometry in clip space as before
+    let identity = math::identity();
+
+    result.set_mvp(&identity, &identity, &identity);
+    result
+}
+
+pub(crate) fn build_color_shader() -> Shader {
+    build_shader(SRC_COLOR_VERTEX, SRC_COLOR_FRAGMENT)
+}
+
+fn build_lit_shader(vertex: &str, fragment: &str) -> Shader {
+    let result = build_shader(vertex, fragment);
+
+    // uniforms default to zero, which would leave lit geometry black
+    result.set_uniform_vec3("u_light_dir", &Vector3::make(0.0, -0.7071, -0.7071));
+    result.set_uniform_vec3("u_light_color", &Vector3::make(1.0, 1.0, 1.0));
+    result.set_uniform_vec3("u_ambient", &Vector3::make(0.1, 0.1, 0.1));
+    result.set_uniform_f32("u_shininess", 32.0);
+    result.set_uniform_i32("u_tex", 0);
+    result
+}
+
+lazy_static! {
+    pub static ref SHADER_BASIC: ContextBound<Shader> = ContextBound::new(build_shader(SRC_BASIC_VERTEX, SRC_BASIC_FRAGMENT));
+    pub static ref SHADER_COLOR: ContextBound<Shader> = ContextBound::new(build_shader(SRC_COLOR_VERTEX, SRC_COLOR_FRAGMENT));
+    pub static ref SHADER_TEXTURE: ContextBound<Shader> = ContextBound::new(build_shader(SRC_TEXTURE_VERTEX, SRC_TEXTURE_FRAGMENT));
+    pub static ref SHADER_SPRITE: ContextBound<Shader> = ContextBound::new(build_shader(SRC_SPRITE_VERTEX, SRC_SPRITE_FRAGMENT));
+    pub static ref SHADER_LIT: ContextBound<Shader> = ContextBound::new(build_lit_shader(SRC_LIT_VERTEX, SRC_LIT_FRAGMENT));
+    pub static ref SHADER_NORMAL_MAPPED: ContextBound<Shader> = {
+        let result = build_lit_shader(SRC_NORMAL_MAPPED_VERTEX, SRC_NORMAL_MAPPED_FRAGMENT);
+
+        result.set_uniform_i32("u_normal_map", 1);
+        ContextBound::new(result)
+    };
+    pub static ref SHADER_PBR: ContextBound<Shader> = {
+        let result = build_lit_shader(SRC_NORMAL_MAPPED_VERTEX, SRC_PBR_FRAGMENT);
+
+        result.set_uniform_i32("u_normal_map", 1);
+        result.set_uniform_vec4("u_base_color", &Vector4::make(1.0, 1.0, 1.0, 1.0));
+        result.set_uniform_f32("u_metallic", 1.0);
+        result.set_uniform_f32("u_roughness", 1.0);
+        ContextBound::new(result)
+    };
+
+    // lets SHADER_TEXTURE and SHADER_SPRITE draw plain vertex colors
+    pub static ref TEXTURE_WHITE: ContextBound<Texture> = {
+        context::assert_initialized();
+
+        ContextBound::new(Texture::solid(Color::WHITE).unwrap())
+    };
 }
 
 #[repr(C, packed)]
@@ -211,3 +497,102 @@ impl Vertex for TextureVertex {
         }
     }
 }
+
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug)]
+pub struct SpriteVertex {
+    pub pos: Vector3,
+    pub coord: Vector2,
+    pub color: Color,
+}
+
+impl SpriteVertex {
+    pub fn from_parts(pos: Vector3, coord: Vector2, color: Color) -> SpriteVertex {
+        SpriteVertex { pos, coord, color }
+    }
+}
+
+impl Vertex for SpriteVertex {
+    fn attrs() -> Vec<(bool, usize, AttributeKind)> {
+        vec![
+            (false, 3, AttributeKind::Float),
+            (false, 2, AttributeKind::Float),
+            (true, 4, AttributeKind::UnsignedByte),
+        ]
+    }
+
+    fn new() -> SpriteVertex {
+        SpriteVertex {
+            pos: Vector3::new(),
+            coord: Vector2::new(),
+            color: Color::new(),
+        }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug)]
+pub struct NormalVertex {
+    pub pos: Vector3,
+    pub normal: Vector3,
+    pub uv: Vector2,
+}
+
+impl NormalVertex {
+    pub fn from_parts(pos: Vector3, normal: Vector3, uv: Vector2) -> NormalVertex {
+        NormalVertex { pos, normal, uv }
+    }
+}
+
+impl Vertex for NormalVertex {
+    fn attrs() -> Vec<(bool, usize, AttributeKind)> {
+        vec![
+            (false, 3, AttributeKind::Float),
+            (false, 3, AttributeKind::Float),
+            (false, 2, AttributeKind::Float),
+        ]
+    }
+
+    fn new() -> NormalVertex {
+        NormalVertex {
+            pos: Vector3::new(),
+            normal: Vector3::new(),
+            uv: Vector2::new(),
+        }
+    }
+}
+
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug)]
+pub struct TangentVertex {
+    pub pos: Vector3,
+    pub normal: Vector3,
+    pub tangent: Vector3,
+    pub uv: Vector2,
+}
+
+impl TangentVertex {
+    pub fn from_parts(pos: Vector3, normal: Vector3, tangent: Vector3, uv: Vector2) -> TangentVertex {
+        TangentVertex { pos, normal, tangent, uv }
+    }
+}
+
+impl Vertex for TangentVertex {
+    fn attrs() -> Vec<(bool, usize, AttributeKind)> {
+        vec![
+            (false, 3, AttributeKind::Float),
+            (false, 3, AttributeKind::Float),
+            (false, 3, AttributeKind::Float),
+            (false, 2, AttributeKind::Float),
+        ]
+    }
+
+    fn new() -> TangentVertex {
+        TangentVertex {
+            pos: Vector3::new(),
+            normal: Vector3::new(),
+            tangent: Vector3::new(),
+            uv: Vector2::new(),
+        }
+    }
+}
@@ -0,0 +1,393 @@
+use crate::context::{self, Viewport};
+use crate::math;
+use crate::shader::Shader;
+use crate::ubo::{Std140Writer, UniformBuffer};
+
+use std::f32::consts::FRAC_PI_2;
+use vex::{Matrix4, Vector2, Vector3};
+
+// keeps orbiting cameras from flipping over the poles
+const MAX_PITCH: f32 = FRAC_PI_2 - 0.01;
+
+pub trait Camera {
+    fn view(&self) -> Matrix4;
+
+    fn projection(&self) -> Matrix4;
+
+    fn view_projection(&self) -> Matrix4 {
+        math::multiply(&self.projection(), &self.view())
+    }
+
+    // point is in window pixels against the current viewport; depth is a depth buffer value, 0.0 on the
+    // near plane and 1.0 on the far one (0.5 is z = 0 for OrthoCamera). None if the matrices can't invert
+    fn screen_to_world(&self, point: Vector2, depth: f32) -> Option<Vector3> {
+        let ndc = context::pixel_to_ndc(point.x, point.y);
+        let inverse = math::inverse(&self.view_projection())?;
+        let p = math::transform(&inverse, [ndc.x, ndc.y, depth * 2.0 - 1.0]);
+
+        if p[3] == 0.0 {
+            return None;
+        }
+
+        Some(Vector3::make(p[0] / p[3], p[1] / p[3], p[2] / p[3]))
+    }
+
+    // window pixels for a world position, None when it's behind the camera
+    fn world_to_screen(&self, point: &Vector3) -> Option<Vector2> {
+        let clip = math::transform(&self.view_projection(), math::to_array(point));
+
+        if clip[3] <= 0.0 {
+            return None;
+        }
+
+        Some(context::ndc_to_pixel(Vector2::make(clip[0] / clip[3], clip[1] / clip[3])))
+    }
+
+    fn upload(&self, shader: &Shader) {
+        shader.set_view(&self.view());
+        shader.set_projection(&self.projection());
+    }
+
+    // std140 layout: mat4 view, mat4 projection, mat4 view_projection
+    fn write_uniforms(&self, buffer: &UniformBuffer, offset: usize) {
+        let mut writer = Std140Writer::new();
+
+        writer.push_mat4(&self.view());
+        writer.push_mat4(&self.projection());
+        writer.push_mat4(&self.view_projection());
+
+        buffer.write(offset, &writer.into_bytes());
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct OrthoCamera {
+    position: Vector2,
+    zoom: f32,
+    rotation: f32,
+    width: f32,
+    height: f32,
+}
+
+impl OrthoCamera {
+    pub fn new(width: f32, height: f32) -> OrthoCamera {
+        OrthoCamera {
+            position: Vector2::make(width * 0.5, height * 0.5),
+            zoom: 1.0,
+            rotation: 0.0,
+            width,
+            height,
+        }
+    }
+
+    pub fn set_viewport_size(&mut self, width: f32, height: f32) {
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn set_position(&mut self, position: Vector2) {
+        self.position = position;
+    }
+
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        self.position = Vector2::make(self.position.x + dx / self.zoom, self.position.y + dy / self.zoom);
+    }
+
+    pub fn set_zoom(&mut self, zoom: f32) {
+        assert!(zoom > 0.0, "camera zoom must be positive");
+
+        self.zoom = zoom;
+    }
+
+    pub fn zoom_by(&mut self, factor: f32) {
+        self.set_zoom(self.zoom * factor);
+    }
+
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.rotation = rotation;
+    }
+
+    pub fn position(&self) -> Vector2 {
+        self.position
+    }
+
+    pub fn zoom(&self) -> f32 {
+        self.zoom
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.rotation
+    }
+
+    pub fn viewport_size(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+}
+
+impl Camera for OrthoCamera {
+    fn view(&self) -> Matrix4 {
+        let center = math::translation(self.width * 0.5, self.height * 0.5, 0.0);
+        let rotate = math::rotation(&Vector3::make(0.0, 0.0, 1.0), -self.rotation);
+        let zoom = math::scaling(self.zoom, self.zoom, 1.0);
+        let offset = math::translation(-self.position.x, -self.position.y, 0.0);
+
+        math::multiply(&center, &math::multiply(&rotate, &math::multiply(&zoom, &offset)))
+    }
+
+    fn projection(&self) -> Matrix4 {
+        math::ortho(0.0, self.width, self.height, 0.0, -1.0, 1.0)
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct PerspectiveCamera {
+    position: Vector3,
+    target: Vector3,
+    up: Vector3,
+    fov_y: f32,
+    aspect: f32,
+    near: f32,
+    far: f32,
+}
+
+impl PerspectiveCamera {
+    pub fn new(fov_y: f32, width: f32, height: f32, near: f32, far: f32) -> PerspectiveCamera {
+        PerspectiveCamera {
+            position: Vector3::make(0.0, 0.0, 1.0),
+            target: Vector3::new(),
+            up: Vector3::make(0.0, 1.0, 0.0),
+            fov_y,
+            aspect: width / height.max(1.0),
+            near,
+            far,
+        }
+    }
+
+    pub fn set_viewport_size(&mut self, width: f32, height: f32) {
+        self.aspect = width / height.max(1.0);
+    }
+
+    pub fn look_at(&mut self, position: Vector3, target: Vector3) {
+        self.position = position;
+        self.target = target;
+    }
+
+    pub fn set_fov(&mut self, fov_y: f32) {
+        self.fov_y = fov_y;
+    }
+
+    pub fn set_clip_planes(&mut self, near: f32, far: f32) {
+        self.near = near;
+        self.far = far;
+    }
+
+    pub fn pan(&mut self, dx: f32, dy: f32) {
+        let forward = math::normalize(math::sub(math::to_array(&self.target), math::to_array(&self.position)));
+        let right = math::normalize(math::cross(forward, math::to_array(&self.up)));
+        let up = math::cross(right, forward);
+        let delta = math::add(math::scale(right, dx), math::scale(up, dy));
+
+        self.position = math::to_vector(math::add(math::to_array(&self.position), delta));
+        self.target = math::to_vector(math::add(math::to_array(&self.target), delta));
+    }
+
+    pub fn zoom_by(&mut self, factor: f32) {
+        assert!(factor > 0.0, "camera zoom factor must be positive");
+
+        let target = math::to_array(&self.target);
+        let offset = math::sub(math::to_array(&self.position), target);
+        let distance = (math::length(offset) / factor).max(self.near);
+
+        self.position = math::to_vector(math::add(target, math::scale(math::normalize(offset), distance)));
+    }
+
+    pub fn orbit(&mut self, yaw: f32, pitch: f32) {
+        let target = math::to_array(&self.target);
+        let offset = math::sub(math::to_array(&self.position), target);
+        let distance = math::length(offset);
+
+        if distance <= 0.0 {
+            return;
+        }
+
+        let yaw = offset[0].atan2(offset[2]) + yaw;
+        let pitch = ((offset[1] / distance).asin() + pitch).max(-MAX_PITCH).min(MAX_PITCH);
+        let (sin_yaw, cos_yaw) = yaw.sin_cos();
+        let (sin_pitch, cos_pitch) = pitch.sin_cos();
+        let offset = [cos_pitch * sin_yaw, sin_pitch, cos_pitch * cos_yaw];
+
+        self.position = math::to_vector(math::add(target, math::scale(offset, distance)));
+    }
+
+    pub fn position(&self) -> Vector3 {
+        self.position
+    }
+
+    pub fn target(&self) -> Vector3 {
+        self.target
+    }
+
+    pub fn fov(&self) -> f32 {
+        self.fov_y
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+}
+
+impl Camera for PerspectiveCamera {
+    fn view(&self) -> Matrix4 {
+        math::look_at(&self.position, &self.target, &self.up)
+    }
+
+    fn projection(&self) -> Matrix4 {
+        math::perspective(self.fov_y, self.aspect, self.near, self.far)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ScaleMode {
+    // the largest uniform scale that fits, centered between bars
+    Letterbox,
+    // fills the framebuffer, distorting the aspect ratio
+    Stretch,
+    // whole-number scales only, for crisp pixel art; falls back to Letterbox when even 1x won't fit
+    IntegerScale,
+}
+
+// maps a fixed logical size onto whatever the framebuffer is; logical and screen coordinates both
+// have a top-left origin, screen ones in framebuffer pixels
+#[derive(Debug, Copy, Clone)]
+pub struct VirtualResolution {
+    width: f32,
+    height: f32,
+    mode: ScaleMode,
+    framebuffer: (u32, u32),
+    viewport: Viewport,
+}
+
+impl VirtualResolution {
+    pub fn new(width: f32, height: f32, mode: ScaleMode) -> VirtualResolution {
+        assert!(width > 0.0 && height > 0.0, "virtual resolution must be positive");
+
+        let framebuffer = (width as u32, height as u32);
+
+        VirtualResolution {
+            width,
+            height,
+            mode,
+            framebuffer,
+            viewport: Viewport {
+                x: 0,
+                y: 0,
+                width: framebuffer.0,
+                height: framebuffer.1,
+            },
+        }
+    }
+
+    // call on every framebuffer resize, before apply
+    pub fn resize(&mut self, framebuffer_width: u32, framebuffer_height: u32) {
+        self.framebuffer = (framebuffer_width, framebuffer_height);
+        self.viewport = self.compute_viewport();
+    }
+
+    fn compute_viewport(&self) -> Viewport {
+        let (fb_width, fb_height) = self.framebuffer;
+        let fit = (fb_width as f32 / self.width).min(fb_height as f32 / self.height);
+
+        let (width, height) = match self.mode {
+            ScaleMode::Stretch => (fb_width, fb_height),
+            ScaleMode::IntegerScale if fit >= 1.0 => {
+                let scale = fit.floor();
+
+                ((self.width * scale) as u32, (self.height * scale) as u32)
+            },
+            ScaleMode::Letterbox | ScaleMode::IntegerScale => {
+                let width = (self.width * fit).round() as u32;
+                let height = (self.height * fit).round() as u32;
+
+                (width.min(fb_width), height.min(fb_height))
+            },
+        };
+
+        // GL measures y from the bottom, which centers the same way
+        Viewport {
+            x: (fb_width - width) / 2,
+            y: (fb_height - height) / 2,
+            width,
+            height,
+        }
+    }
+
+    pub fn apply(&self) {
+        context::set_viewport(self.viewport.x, self.viewport.y, self.viewport.width, self.viewport.height);
+    }
+
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    pub fn mode(&self) -> ScaleMode {
+        self.mode
+    }
+
+    pub fn set_mode(&mut self, mode: ScaleMode) {
+        self.mode = mode;
+        self.viewport = self.compute_viewport();
+    }
+
+    pub fn logical_size(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+
+    pub fn framebuffer_size(&self) -> (u32, u32) {
+        self.framebuffer
+    }
+
+    // screen pixels per logical unit, per axis
+    pub fn scale(&self) -> (f32, f32) {
+        (self.viewport.width as f32 / self.width, self.viewport.height as f32 / self.height)
+    }
+
+    fn offset(&self) -> (f32, f32) {
+        let top = self.framebuffer.1 - self.viewport.y - self.viewport.height;
+
+        (self.viewport.x as f32, top as f32)
+    }
+
+    pub fn to_screen(&self, point: Vector2) -> Vector2 {
+        let (sx, sy) = self.scale();
+        let (ox, oy) = self.offset();
+
+        Vector2::make(ox + point.x * sx, oy + point.y * sy)
+    }
+
+    // for mouse input; points over the bars land outside 0..logical_size
+    pub fn to_logical(&self, point: Vector2) -> Vector2 {
+        let (sx, sy) = self.scale();
+        let (ox, oy) = self.offset();
+
+        Vector2::make((point.x - ox) / sx, (point.y - oy) / sy)
+    }
+
+    pub fn contains_screen_point(&self, point: Vector2) -> bool {
+        let logical = self.to_logical(point);
+
+        logical.x >= 0.0 && logical.y >= 0.0 && logical.x < self.width && logical.y < self.height
+    }
+
+    // logical to screen pixels, for placing full-framebuffer overlays over the logical area
+    pub fn transform(&self) -> Matrix4 {
+        let (sx, sy) = self.scale();
+        let (ox, oy) = self.offset();
+
+        math::multiply(&math::translation(ox, oy, 0.0), &math::scaling(sx, sy, 1.0))
+    }
+
+    // for drawing in logical units once apply has set the viewport; pairs with OrthoCamera::new(w, h)
+    pub fn projection(&self) -> Matrix4 {
+        math::ortho(0.0, self.width, self.height, 0.0, -1.0, 1.0)
+    }
+}
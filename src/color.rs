@@ -1,5 +1,48 @@
+use crate::error::{Error, Result};
+
+fn to_unit(value: u8) -> f32 {
+    value as f32 / 255.0
+}
+
+fn from_unit(value: f32) -> u8 {
+    (value.max(0.0).min(1.0) * 255.0).round() as u8
+}
+
+fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+// hue in degrees; chroma and the matching lightness offset are supplied by the caller
+fn from_hue(hue: f32, chroma: f32, offset: f32) -> [f32; 3] {
+    let h = hue.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+    let (r, g, b) = match h as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+
+    [r + offset, g + offset, b + offset]
+}
+
+// 8-bit sRGB-encoded color; the layout matches a normalized RGBA8 vertex attribute
 #[repr(C, packed)]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub struct Color {
     pub r: u8,
     pub g: u8,
@@ -8,16 +51,144 @@ pub struct Color {
 }
 
 impl Color {
+    pub const WHITE: Color = Color { r: 255, g: 255, b: 255, a: 255 };
+    pub const BLACK: Color = Color { r: 0, g: 0, b: 0, a: 255 };
+    pub const TRANSPARENT: Color = Color { r: 0, g: 0, b: 0, a: 0 };
+    pub const RED: Color = Color { r: 255, g: 0, b: 0, a: 255 };
+    pub const GREEN: Color = Color { r: 0, g: 255, b: 0, a: 255 };
+    pub const BLUE: Color = Color { r: 0, g: 0, b: 255, a: 255 };
+    pub const YELLOW: Color = Color { r: 255, g: 255, b: 0, a: 255 };
+    pub const CYAN: Color = Color { r: 0, g: 255, b: 255, a: 255 };
+    pub const MAGENTA: Color = Color { r: 255, g: 0, b: 255, a: 255 };
+    pub const GRAY: Color = Color { r: 128, g: 128, b: 128, a: 255 };
+
     pub fn new() -> Color {
-        Color {
-            r: 255,
-            g: 255,
-            b: 255,
-            a: 255,
-        }
+        Color::WHITE
     }
 
     pub fn make(r: u8, g: u8, b: u8, a: u8) -> Color {
         Color { r, g, b, a }
     }
+
+    // components are clamped to 0..1
+    pub fn from_f32(r: f32, g: f32, b: f32, a: f32) -> Color {
+        Color::make(from_unit(r), from_unit(g), from_unit(b), from_unit(a))
+    }
+
+    // accepts "rgb", "rgba", "rrggbb" and "rrggbbaa", with or without a leading '#'
+    pub fn from_hex(hex: &str) -> Result<Color> {
+        let digits = hex.trim().trim_start_matches('#');
+        let invalid = || Error::InvalidColor(format!("\"{}\" isn't a hex color", hex));
+
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let channel = |i: usize, width: usize| {
+            let value = u8::from_str_radix(&digits[i * width..(i + 1) * width], 16).unwrap();
+
+            if width == 1 { value * 17 } else { value }
+        };
+
+        match digits.len() {
+            3 => Ok(Color::make(channel(0, 1), channel(1, 1), channel(2, 1), 255)),
+            4 => Ok(Color::make(channel(0, 1), channel(1, 1), channel(2, 1), channel(3, 1))),
+            6 => Ok(Color::make(channel(0, 2), channel(1, 2), channel(2, 2), 255)),
+            8 => Ok(Color::make(channel(0, 2), channel(1, 2), channel(2, 2), channel(3, 2))),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn to_hex(&self) -> String {
+        let (r, g, b, a) = (self.r, self.g, self.b, self.a);
+
+        format!("#{:02x}{:02x}{:02x}{:02x}", r, g, b, a)
+    }
+
+    // hue in degrees, everything else in 0..1
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32, alpha: f32) -> Color {
+        let chroma = value * saturation;
+        let [r, g, b] = from_hue(hue, chroma, value - chroma);
+
+        Color::from_f32(r, g, b, alpha)
+    }
+
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32, alpha: f32) -> Color {
+        let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+        let [r, g, b] = from_hue(hue, chroma, lightness - chroma / 2.0);
+
+        Color::from_f32(r, g, b, alpha)
+    }
+
+    // the stored values normalized to 0..1, still sRGB-encoded
+    pub fn to_array_f32(&self) -> [f32; 4] {
+        [to_unit(self.r), to_unit(self.g), to_unit(self.b), to_unit(self.a)]
+    }
+
+    pub fn to_linear(&self) -> LinearColor {
+        let [r, g, b, a] = self.to_array_f32();
+
+        LinearColor::make(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+
+    // interpolates the encoded values; go through LinearColor for physically correct blends
+    pub fn lerp(&self, other: &Color, t: f32) -> Color {
+        let [r, g, b, a] = LinearColor::from_array(self.to_array_f32())
+            .lerp(&LinearColor::from_array(other.to_array_f32()), t)
+            .to_array();
+
+        Color::from_f32(r, g, b, a)
+    }
+
+    pub fn with_alpha(&self, a: u8) -> Color {
+        Color::make(self.r, self.g, self.b, a)
+    }
+}
+
+// unclamped f32 components, handed to GL as-is (clear color, blend color, HDR values)
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct LinearColor {
+    pub r: f32,
+    pub g: f32,
+    pub b: f32,
+    pub a: f32,
+}
+
+impl LinearColor {
+    pub const WHITE: LinearColor = LinearColor { r: 1.0, g: 1.0, b: 1.0, a: 1.0 };
+    pub const BLACK: LinearColor = LinearColor { r: 0.0, g: 0.0, b: 0.0, a: 1.0 };
+    pub const TRANSPARENT: LinearColor = LinearColor { r: 0.0, g: 0.0, b: 0.0, a: 0.0 };
+
+    pub fn new() -> LinearColor {
+        LinearColor::WHITE
+    }
+
+    pub fn make(r: f32, g: f32, b: f32, a: f32) -> LinearColor {
+        LinearColor { r, g, b, a }
+    }
+
+    pub fn from_array(values: [f32; 4]) -> LinearColor {
+        LinearColor::make(values[0], values[1], values[2], values[3])
+    }
+
+    pub fn to_array(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    pub fn to_srgb(&self) -> Color {
+        Color::from_f32(linear_to_srgb(self.r), linear_to_srgb(self.g), linear_to_srgb(self.b), self.a)
+    }
+
+    pub fn lerp(&self, other: &LinearColor, t: f32) -> LinearColor {
+        LinearColor::make(
+            self.r + (other.r - self.r) * t,
+            self.g + (other.g - self.g) * t,
+            self.b + (other.b - self.b) * t,
+            self.a + (other.a - self.a) * t,
+        )
+    }
+
+    pub fn premultiplied(&self) -> LinearColor {
+        LinearColor::make(self.r * self.a, self.g * self.a, self.b * self.a, self.a)
+    }
 }
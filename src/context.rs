@@ -1,14 +1,95 @@
 use crate::Result;
 use crate::Error;
 use crate::Color;
+use crate::LinearColor;
+use crate::CompressedFormat;
+use crate::Rect;
+use crate::fbo::DefaultFramebuffer;
+use crate::multi_context;
+use crate::query::{Query, QueryKind};
+use crate::texture;
 
 use flagset::{FlagSet, flags};
 use gl::types::*;
 use lazy_static::lazy_static;
-use std::collections::HashSet;
+use std::cell::Cell;
+use std::collections::{HashSet, VecDeque};
+use std::ffi::CStr;
 use std::fmt;
 use std::fmt::Display;
-use std::sync::Mutex;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::os::raw::c_char;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, ThreadId};
+use vex::Vector2;
+
+static INITIALIZED: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    static GL_THREAD: Cell<bool> = Cell::new(false);
+}
+
+// raw pointers are neither Send nor Sync, so anything holding one stays on its thread
+pub(crate) type ThreadMarker = PhantomData<*const ()>;
+
+// handed out by init as proof of being on the context thread; it is neither Send nor Sync, and neither
+// are the GL resources, so those can't be moved to another thread at compile time. Nothing takes it yet:
+// making the constructors and the free functions here require a &GlContext is a separate, breaking change.
+// Until then, calling them from the wrong thread is caught at runtime, by the NotInitialized error or the
+// init panic. current only hands one out on the thread init ran on, so it can't be minted anywhere else
+#[derive(Debug, Clone)]
+pub struct GlContext {
+    _thread: ThreadMarker,
+}
+
+impl GlContext {
+    pub fn current() -> Option<GlContext> {
+        if is_initialized() {
+            Some(GlContext { _thread: PhantomData })
+        } else {
+            None
+        }
+    }
+}
+
+// for objects adopted from foreign code: Borrowed wrappers leave deletion to whoever created the object
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Ownership {
+    Owned,
+    Borrowed,
+}
+
+// lets a lazy_static hold a GL resource, which is never Send or Sync itself
+pub struct ContextBound<T> {
+    value: T,
+    owner: ThreadId,
+}
+
+// sound for any T: the value is only reachable through Deref, which refuses every thread but the one
+// that created it, so no reference to it ever exists on two threads; lazy_static never drops it either
+unsafe impl<T> Sync for ContextBound<T> {}
+
+impl<T> ContextBound<T> {
+    pub(crate) fn new(value: T) -> ContextBound<T> {
+        ContextBound {
+            value,
+            owner: thread::current().id(),
+        }
+    }
+}
+
+impl<T> Deref for ContextBound<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        assert_initialized();
+        assert!(thread::current().id() == self.owner, "built-in GL objects can only be used on the thread that created them");
+
+        &self.value
+    }
+}
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
 pub enum Feature {
@@ -35,9 +116,46 @@ pub enum Feature {
     StencilTest,
     TextureCubeMapSeamless,
     ProgramPointSize,
+    // GL_CLIP_DISTANCE0 + n; the vertex shader has to write gl_ClipDistance[n]
+    ClipDistance(u32),
 }
 
 impl Feature {
+    // includes the 8 clip distances every driver has; higher ones are tracked once enabled
+    pub const ALL: [Feature; 31] = [
+        Feature::Blend,
+        Feature::ColorLogicOp,
+        Feature::CullFace,
+        Feature::DepthClamp,
+        Feature::DepthTest,
+        Feature::Dither,
+        Feature::FramebufferSrgb,
+        Feature::LineSmooth,
+        Feature::Multisample,
+        Feature::PolygonOffsetFill,
+        Feature::PolygonOffsetLine,
+        Feature::PolygonOffsetPoint,
+        Feature::PolygonSmooth,
+        Feature::RasterizerDiscard,
+        Feature::SampleAlphaToCoverage,
+        Feature::SampleAlphaToOne,
+        Feature::SampleCoverage,
+        Feature::SampleShading,
+        Feature::SampleMask,
+        Feature::ScissorTest,
+        Feature::StencilTest,
+        Feature::TextureCubeMapSeamless,
+        Feature::ProgramPointSize,
+        Feature::ClipDistance(0),
+        Feature::ClipDistance(1),
+        Feature::ClipDistance(2),
+        Feature::ClipDistance(3),
+        Feature::ClipDistance(4),
+        Feature::ClipDistance(5),
+        Feature::ClipDistance(6),
+        Feature::ClipDistance(7),
+    ];
+
     pub fn get_native(&self) -> GLenum {
         match self {
             Feature::Blend => gl::BLEND,
@@ -63,8 +181,24 @@ impl Feature {
             Feature::StencilTest => gl::STENCIL_TEST,
             Feature::TextureCubeMapSeamless => gl::TEXTURE_CUBE_MAP_SEAMLESS,
             Feature::ProgramPointSize => gl::PROGRAM_POINT_SIZE,
+            Feature::ClipDistance(n) => gl::CLIP_DISTANCE0 + n,
+        }
+    }
+}
+
+// ALL plus whatever else is in the given sets, i.e. clip distances past the guaranteed 8
+fn tracked_features(sets: &[&HashSet<Feature>]) -> Vec<Feature> {
+    let mut result = Feature::ALL.to_vec();
+
+    for set in sets.iter() {
+        for feature in set.iter() {
+            if !result.contains(feature) {
+                result.push(*feature);
+            }
         }
     }
+
+    result
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -82,6 +216,40 @@ impl FrontFace {
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Face {
+    Front,
+    Back,
+    FrontAndBack,
+}
+
+impl Face {
+    fn get_native(&self) -> GLenum {
+        match self {
+            Face::Front => gl::FRONT,
+            Face::Back => gl::BACK,
+            Face::FrontAndBack => gl::FRONT_AND_BACK,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PolygonMode {
+    Fill,
+    Line,
+    Point,
+}
+
+impl PolygonMode {
+    fn get_native(&self) -> GLenum {
+        match self {
+            PolygonMode::Fill => gl::FILL,
+            PolygonMode::Line => gl::LINE,
+            PolygonMode::Point => gl::POINT,
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum BlendComponent {
     Zero,
@@ -99,6 +267,10 @@ pub enum BlendComponent {
     OneMinusDstAlpha,
     OneMinusConstColor,
     OneMinusConstAlpha,
+    Src1Color,
+    Src1Alpha,
+    OneMinusSrc1Color,
+    OneMinusSrc1Alpha,
 }
 
 impl BlendComponent {
@@ -116,9 +288,94 @@ impl BlendComponent {
             BlendComponent::OneMinusSrcColor => gl::ONE_MINUS_SRC_COLOR,
             BlendComponent::OneMinusDstColor => gl::ONE_MINUS_DST_COLOR,
             BlendComponent::OneMinusSrcAlpha => gl::ONE_MINUS_SRC_ALPHA,
-            BlendComponent::OneMinusDstAlpha => gl::ONE_MINUS_DST_COLOR,
+            BlendComponent::OneMinusDstAlpha => gl::ONE_MINUS_DST_ALPHA,
             BlendComponent::OneMinusConstColor => gl::ONE_MINUS_CONSTANT_COLOR,
             BlendComponent::OneMinusConstAlpha => gl::ONE_MINUS_CONSTANT_ALPHA,
+            BlendComponent::Src1Color => gl::SRC1_COLOR,
+            BlendComponent::Src1Alpha => gl::SRC1_ALPHA,
+            BlendComponent::OneMinusSrc1Color => gl::ONE_MINUS_SRC1_COLOR,
+            BlendComponent::OneMinusSrc1Alpha => gl::ONE_MINUS_SRC1_ALPHA,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct BlendFunc {
+    pub src_rgb: BlendComponent,
+    pub dst_rgb: BlendComponent,
+    pub src_alpha: BlendComponent,
+    pub dst_alpha: BlendComponent,
+}
+
+impl BlendFunc {
+    pub const ALPHA: BlendFunc = BlendFunc::make(BlendComponent::SrcAlpha, BlendComponent::OneMinusSrcAlpha);
+    pub const PREMULTIPLIED: BlendFunc = BlendFunc::make(BlendComponent::One, BlendComponent::OneMinusSrcAlpha);
+    pub const ADDITIVE: BlendFunc = BlendFunc::make(BlendComponent::SrcAlpha, BlendComponent::One);
+    pub const MULTIPLY: BlendFunc = BlendFunc::make(BlendComponent::DstColor, BlendComponent::Zero);
+
+    pub const fn make(src: BlendComponent, dst: BlendComponent) -> BlendFunc {
+        BlendFunc {
+            src_rgb: src,
+            dst_rgb: dst,
+            src_alpha: src,
+            dst_alpha: dst,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompareFunc {
+    Never,
+    Less,
+    Equal,
+    LessEqual,
+    Greater,
+    NotEqual,
+    GreaterEqual,
+    Always,
+}
+
+impl CompareFunc {
+    pub fn get_native(&self) -> GLenum {
+        match self {
+            CompareFunc::Never => gl::NEVER,
+            CompareFunc::Less => gl::LESS,
+            CompareFunc::Equal => gl::EQUAL,
+            CompareFunc::LessEqual => gl::LEQUAL,
+            CompareFunc::Greater => gl::GREATER,
+            CompareFunc::NotEqual => gl::NOTEQUAL,
+            CompareFunc::GreaterEqual => gl::GEQUAL,
+            CompareFunc::Always => gl::ALWAYS,
+        }
+    }
+}
+
+pub type DepthFunc = CompareFunc;
+pub type StencilFunc = CompareFunc;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StencilOp {
+    Keep,
+    Zero,
+    Replace,
+    Increment,
+    IncrementWrap,
+    Decrement,
+    DecrementWrap,
+    Invert,
+}
+
+impl StencilOp {
+    pub fn get_native(&self) -> GLenum {
+        match self {
+            StencilOp::Keep => gl::KEEP,
+            StencilOp::Zero => gl::ZERO,
+            StencilOp::Replace => gl::REPLACE,
+            StencilOp::Increment => gl::INCR,
+            StencilOp::IncrementWrap => gl::INCR_WRAP,
+            StencilOp::Decrement => gl::DECR,
+            StencilOp::DecrementWrap => gl::DECR_WRAP,
+            StencilOp::Invert => gl::INVERT,
         }
     }
 }
@@ -139,10 +396,10 @@ impl Display for ClearFlag {
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Viewport {
-    x: u32,
-    y: u32,
-    width: u32,
-    height: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
 }
 
 impl Viewport {
@@ -156,52 +413,400 @@ impl Viewport {
     }
 }
 
-struct State {
-    initialized: bool,
+#[derive(Debug, Clone)]
+pub struct Capabilities {
+    pub version: (u32, u32),
+    pub glsl_version: (u32, u32),
+    pub vendor: String,
+    pub renderer: String,
+    pub max_texture_size: u32,
+    pub max_3d_texture_size: u32,
+    pub max_array_texture_layers: u32,
+    pub max_texture_units: u32,
+    pub max_combined_texture_units: u32,
+    pub max_vertex_attribs: u32,
+    pub max_uniform_block_size: u32,
+    pub max_uniform_buffer_bindings: u32,
+    pub uniform_buffer_alignment: u32,
+    pub max_color_attachments: u32,
+    pub max_draw_buffers: u32,
+    pub max_samples: u32,
+    pub max_viewports: u32,
+    pub max_clip_distances: u32,
+    // 1.0 when anisotropic filtering isn't available
+    pub max_anisotropy: f32,
+    pub compressed_formats: Vec<CompressedFormat>,
+    extensions: HashSet<String>,
+}
+
+impl Capabilities {
+    fn query() -> Capabilities {
+        let extensions = (0..get_integer(gl::NUM_EXTENSIONS))
+            .filter_map(|i| {
+                let raw = unsafe { gl::GetStringi(gl::EXTENSIONS, i) };
+
+                if raw.is_null() {
+                    None
+                } else {
+                    Some(unsafe { CStr::from_ptr(raw as *const c_char) }.to_string_lossy().into_owned())
+                }
+            })
+            .collect::<HashSet<String>>();
+
+        let count = get_integer(gl::NUM_COMPRESSED_TEXTURE_FORMATS);
+        let mut raw_formats = vec![0; count as usize];
+
+        if !raw_formats.is_empty() {
+            unsafe { gl::GetIntegerv(gl::COMPRESSED_TEXTURE_FORMATS, raw_formats.as_mut_ptr()) };
+        }
+
+        let compressed_formats = CompressedFormat::ALL
+            .iter()
+            .copied()
+            .filter(|format| {
+                raw_formats.contains(&(format.get_native() as GLint)) || extensions.contains(format.extension())
+            })
+            .collect();
+
+        let version = (get_integer(gl::MAJOR_VERSION), get_integer(gl::MINOR_VERSION));
+        let anisotropic = version >= (4, 6)
+            || extensions.contains("GL_EXT_texture_filter_anisotropic")
+            || extensions.contains("GL_ARB_texture_filter_anisotropic");
+
+        let mut max_anisotropy = 1.0;
+
+        if anisotropic {
+            unsafe { gl::GetFloatv(texture::MAX_TEXTURE_MAX_ANISOTROPY, &mut max_anisotropy) };
+        }
+
+        Capabilities {
+            version,
+            glsl_version: parse_version(&get_string(gl::SHADING_LANGUAGE_VERSION)),
+            vendor: get_string(gl::VENDOR),
+            renderer: get_string(gl::RENDERER),
+            max_texture_size: get_integer(gl::MAX_TEXTURE_SIZE),
+            max_3d_texture_size: get_integer(gl::MAX_3D_TEXTURE_SIZE),
+            max_array_texture_layers: get_integer(gl::MAX_ARRAY_TEXTURE_LAYERS),
+            max_texture_units: get_integer(gl::MAX_TEXTURE_IMAGE_UNITS),
+            max_combined_texture_units: get_integer(gl::MAX_COMBINED_TEXTURE_IMAGE_UNITS),
+            max_vertex_attribs: get_integer(gl::MAX_VERTEX_ATTRIBS),
+            max_uniform_block_size: get_integer(gl::MAX_UNIFORM_BLOCK_SIZE),
+            max_uniform_buffer_bindings: get_integer(gl::MAX_UNIFORM_BUFFER_BINDINGS),
+            uniform_buffer_alignment: get_integer(gl::UNIFORM_BUFFER_OFFSET_ALIGNMENT),
+            max_color_attachments: get_integer(gl::MAX_COLOR_ATTACHMENTS),
+            max_draw_buffers: get_integer(gl::MAX_DRAW_BUFFERS),
+            max_samples: get_integer(gl::MAX_SAMPLES),
+            max_viewports: if gl::ViewportArrayv::is_loaded() { get_integer(gl::MAX_VIEWPORTS) } else { 1 },
+            max_clip_distances: get_integer(gl::MAX_CLIP_DISTANCES),
+            max_anisotropy: max_anisotropy.max(1.0),
+            compressed_formats,
+            extensions,
+        }
+    }
+
+    pub fn supports_version(&self, major: u32, minor: u32) -> bool {
+        self.version >= (major, minor)
+    }
+
+    pub fn supports_glsl(&self, major: u32, minor: u32) -> bool {
+        self.glsl_version >= (major, minor)
+    }
+
+    pub fn supports_compressed(&self, format: CompressedFormat) -> bool {
+        self.compressed_formats.contains(&format)
+    }
+
+    pub fn has_extension(&self, name: &str) -> bool {
+        self.extensions.contains(name)
+    }
+
+    pub fn extensions(&self) -> impl Iterator<Item = &str> {
+        self.extensions.iter().map(|name| name.as_str())
+    }
+}
+
+fn gl_bool(value: bool) -> GLboolean {
+    if value { gl::TRUE } else { gl::FALSE }
+}
+
+fn get_integer(name: GLenum) -> u32 {
+    let mut value = 0;
+
+    unsafe { gl::GetIntegerv(name, &mut value) };
+
+    value.max(0) as u32
+}
+
+fn get_string(name: GLenum) -> String {
+    let raw = unsafe { gl::GetString(name) };
+
+    if raw.is_null() {
+        String::new()
+    } else {
+        unsafe { CStr::from_ptr(raw as *const c_char) }.to_string_lossy().into_owned()
+    }
+}
+
+// GLSL versions read like "4.60 NVIDIA" or "OpenGL ES GLSL ES 3.00"
+fn parse_version(text: &str) -> (u32, u32) {
+    let number = text
+        .split_whitespace()
+        .find(|word| word.chars().next().map_or(false, |c| c.is_ascii_digit()))
+        .unwrap_or("");
+
+    let mut parts = number.split('.');
+    let major = parts.next().and_then(|part| part.parse().ok()).unwrap_or(0);
+    let minor = parts
+        .next()
+        .map(|part| part.chars().take_while(|c| c.is_ascii_digit()).collect::<String>())
+        .and_then(|part| part.parse().ok())
+        .unwrap_or(0);
+
+    (major, minor)
+}
+
+pub(crate) struct State {
+    capabilities: Option<Arc<Capabilities>>,
     front: FrontFace,
-    blend_src: BlendComponent,
-    blend_dst: BlendComponent,
-    clear_color: Color,
+    cull_face: Face,
+    blend: BlendFunc,
+    blend_color: LinearColor,
+    clear_color: LinearColor,
     viewport: Viewport,
+    scissor: Viewport,
+    // set once indexed viewports/scissors diverge from index 0, which is all the cache tracks
+    viewport_array: bool,
+    scissor_array: bool,
+    // set once blending differs per draw buffer; features then only speaks for draw buffer 0
+    blend_indexed: bool,
+    depth_func: DepthFunc,
+    depth_mask: bool,
+    color_mask: [bool; 4],
+    polygon_mode: (PolygonMode, PolygonMode),
+    polygon_offset: (f32, f32),
+    depth_range: (f32, f32),
+    stencil_func: (StencilFunc, i32, u32),
+    stencil_op: (StencilOp, StencilOp, StencilOp),
+    stencil_mask: u32,
+    patch_vertices: u32,
+    line_width: f32,
+    point_size: f32,
     features: HashSet<Feature>,
+    clip_stack: Vec<Rect>,
+    // whether scissoring was on, and its box, before the first clip rect was pushed
+    clip_previous: Option<(bool, Viewport)>,
+    framebuffer: GLuint,
+    // the window's framebuffer size; GL has no query for it, so it's fed by notify_resize and set_viewport
+    drawable_size: (u32, u32),
+    active_unit: GLuint,
+    texture_units: Vec<TextureUnit>,
+    program: GLuint,
+    vertex_array: GLuint,
+    conditional_render: bool,
+    // dropped objects waiting for collect_garbage; per context, so they're only deleted where they live
+    garbage: Vec<Garbage>,
+    // counted per context, like the binds they count
+    stats: FrameStats,
+    frame_options: FrameOptions,
+    // GLsync is a raw pointer, which can't sit in a shared static
+    frame_fences: VecDeque<usize>,
 }
 
-lazy_static! {
-    static ref INTERNAL_STATE: Mutex<State> = {
-        Mutex::new(State {
-            initialized: false,
+impl State {
+    pub(crate) fn new() -> State {
+        State {
+            capabilities: None,
             front: FrontFace::CounterClockwise,
-            blend_src: BlendComponent::SrcAlpha,
-            blend_dst: BlendComponent::OneMinusSrcAlpha,
-            clear_color: Color::make(0, 0, 0, 0),
+            cull_face: Face::Back,
+            blend: BlendFunc::ALPHA,
+            blend_color: LinearColor::TRANSPARENT,
+            clear_color: LinearColor::TRANSPARENT,
             viewport: Viewport::new(),
+            scissor: Viewport::new(),
+            viewport_array: false,
+            scissor_array: false,
+            blend_indexed: false,
+            depth_func: DepthFunc::Less,
+            depth_mask: true,
+            color_mask: [true; 4],
+            polygon_mode: (PolygonMode::Fill, PolygonMode::Fill),
+            polygon_offset: (0.0, 0.0),
+            depth_range: (0.0, 1.0),
+            stencil_func: (StencilFunc::Always, 0, !0),
+            stencil_op: (StencilOp::Keep, StencilOp::Keep, StencilOp::Keep),
+            stencil_mask: !0,
+            patch_vertices: 3,
+            line_width: 1.0,
+            point_size: 1.0,
             features: HashSet::new(),
-        })
-    };
+            clip_stack: Vec::new(),
+            clip_previous: None,
+            framebuffer: 0,
+            drawable_size: (0, 0),
+            active_unit: 0,
+            texture_units: Vec::new(),
+            program: 0,
+            vertex_array: 0,
+            conditional_render: false,
+            garbage: Vec::new(),
+            stats: FrameStats::default(),
+            frame_options: FrameOptions::new(),
+            frame_fences: VecDeque::new(),
+        }
+    }
+
+    // for a parked context, which can't go through defer_delete
+    pub(crate) fn queue_garbage(&mut self, item: Garbage) {
+        self.garbage.push(item);
+    }
+}
+
+lazy_static! {
+    static ref INTERNAL_STATE: Mutex<State> = Mutex::new(State::new());
+}
+
+pub(crate) fn swap_state(state: State) -> State {
+    std::mem::replace(&mut *INTERNAL_STATE.lock().unwrap(), state)
+}
+
+// hands the shared objects a destroyed context still had queued, frame fences included, to the current
+// one; its container objects went with it
+pub(crate) fn adopt_garbage(state: State) {
+    let fences = state.frame_fences.into_iter().map(Garbage::Sync);
+
+    INTERNAL_STATE.lock().unwrap().garbage.extend(state.garbage.into_iter().filter(Garbage::is_shared).chain(fences));
+}
+
+pub fn init() -> Result<GlContext> {
+    if INITIALIZED.compare_exchange(false, true, Ordering::AcqRel, Ordering::Acquire).is_err() {
+        return Err(Error::AlreadyInitialized);
+    }
+
+    GL_THREAD.with(|flag| flag.set(true));
+    apply_defaults();
+
+    Ok(GlContext { _thread: PhantomData })
 }
 
-pub fn init() -> Result<()> {
+// brings a fresh GL context in line with a freshly created State
+pub(crate) fn apply_defaults() {
     let mut st = INTERNAL_STATE.lock().unwrap();
 
-    if st.initialized == false {
-        st.initialized = true;
+    unsafe {
+        // a fresh context's viewport covers the window, which is the best guess at its size until notify_resize
+        let mut viewport = [0; 4];
 
-        unsafe {
-            gl::FrontFace(gl::CCW);
-            gl::Viewport(0, 0, 0, 0);
-            gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+        gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr());
+        st.drawable_size = (viewport[2].max(0) as u32, viewport[3].max(0) as u32);
+
+        gl::FrontFace(gl::CCW);
+        gl::CullFace(gl::BACK);
+        gl::Viewport(0, 0, 0, 0);
+        gl::BlendFunc(gl::SRC_ALPHA, gl::ONE_MINUS_SRC_ALPHA);
+
+        let unit_count = get_integer(gl::MAX_TEXTURE_IMAGE_UNITS);
+
+        for i in 0..unit_count {
+            gl::ActiveTexture(gl::TEXTURE0 + i);
+            gl::BindTexture(gl::TEXTURE_1D, 0);
+            gl::BindTexture(gl::TEXTURE_2D, 0);
+            gl::BindTexture(gl::TEXTURE_2D_ARRAY, 0);
+            gl::BindTexture(gl::TEXTURE_3D, 0);
         }
 
+        gl::ActiveTexture(gl::TEXTURE0);
+        gl::UseProgram(0);
+        gl::BindVertexArray(0);
+
+        // some capabilities (dither, multisample) start out enabled, so the cache asks rather than assumes
+        st.features = Feature::ALL.iter().copied().filter(|feature| gl::IsEnabled(feature.get_native()) == gl::TRUE).collect();
+        st.active_unit = 0;
+        st.texture_units = vec![TextureUnit::new(0); unit_count as usize];
+        st.program = 0;
+        st.vertex_array = 0;
+    }
+}
+
+pub fn is_initialized() -> bool {
+    INITIALIZED.load(Ordering::Acquire) && GL_THREAD.with(|flag| flag.get())
+}
+
+// undoes init on this thread while its context is still current: pending deletions run, then the
+// latch, the thread flag and every cache go back to how they were before init. The built-in shaders
+// are lazy statics and can't be rebuilt, so they stay tied to the first context
+#[cfg(feature = "headless")]
+pub(crate) fn shutdown() {
+    if !is_initialized() {
+        return;
+    }
+
+    // the per-thread helpers own GL objects, which get queued for the collection below
+    crate::debug_draw::reset();
+    crate::overlay::reset();
+    crate::profiler::reset();
+    crate::renderer2d::reset();
+    crate::multi_context::reset();
+
+    collect_garbage();
+
+    for fence in INTERNAL_STATE.lock().unwrap().frame_fences.drain(..) {
+        unsafe { gl::DeleteSync(fence as GLsync) };
+    }
+
+    *INTERNAL_STATE.lock().unwrap() = State::new();
+
+    GL_THREAD.with(|flag| flag.set(false));
+    INITIALIZED.store(false, Ordering::Release);
+}
+
+pub(crate) fn check_initialized() -> Result<()> {
+    if is_initialized() {
         Ok(())
     } else {
-        Err(Error::AlreadyInitialized)
+        Err(Error::NotInitialized)
+    }
+}
+
+pub(crate) fn assert_initialized() {
+    if !is_initialized() {
+        panic!("gl_toolkit::init must be called on this thread first");
+    }
+}
+
+// queried once per context, then served from the cache
+pub fn capabilities() -> Arc<Capabilities> {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    st.capabilities
+        .get_or_insert_with(|| Arc::new(Capabilities::query()))
+        .clone()
+}
+
+pub fn has_extension(name: &str) -> bool {
+    capabilities().has_extension(name)
+}
+
+fn check_feature(feature: Feature) {
+    if let Feature::ClipDistance(n) = feature {
+        let max = capabilities().max_clip_distances;
+
+        if n >= max {
+            panic!("clip distance {} is beyond the {} the driver supports", n, max);
+        }
     }
 }
 
 pub fn enable(feature: Feature) -> bool {
-    let result = INTERNAL_STATE.lock().unwrap().features.insert(feature);
+    assert_initialized();
+    check_feature(feature);
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+    let indexed = feature == Feature::Blend && std::mem::replace(&mut st.blend_indexed, false);
+    let result = st.features.insert(feature);
 
-    if result {
+    if result || indexed {
         unsafe { gl::Enable(feature.get_native()) };
     }
 
@@ -209,9 +814,14 @@ pub fn enable(feature: Feature) -> bool {
 }
 
 pub fn disable(feature: Feature) -> bool {
-    let result = INTERNAL_STATE.lock().unwrap().features.remove(&feature);
+    assert_initialized();
+    check_feature(feature);
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+    let indexed = feature == Feature::Blend && std::mem::replace(&mut st.blend_indexed, false);
+    let result = st.features.remove(&feature);
 
-    if result {
+    if result || indexed {
         unsafe { gl::Disable(feature.get_native()) };
     }
 
@@ -219,29 +829,50 @@ pub fn disable(feature: Feature) -> bool {
 }
 
 pub fn clear(flags: FlagSet<ClearFlag>) {
+    assert_initialized();
+
     unsafe { gl::Clear(flags.bits()) };
 }
 
-pub fn set_clear_color(r: f32, g: f32, b: f32, a: f32) {
+// with an sRGB framebuffer bound, shader outputs are treated as linear and encoded on write
+pub fn set_srgb_conversion(enabled: bool) {
+    if enabled {
+        enable(Feature::FramebufferSrgb);
+    } else {
+        disable(Feature::FramebufferSrgb);
+    }
+}
+
+pub fn srgb_conversion() -> bool {
+    is_enabled(Feature::FramebufferSrgb)
+}
+
+// the normalized bytes are passed through without sRGB decoding, like any other 8-bit color
+pub fn set_clear_color(color: Color) {
+    set_clear_color_linear(LinearColor::from_array(color.to_array_f32()));
+}
+
+// the float form set_clear_color took before it moved to Color: existing (r, g, b, a) calls only need
+// the new name, and the values are cached as given, the same as set_clear_color_linear
+pub fn set_clear_color_rgba(r: f32, g: f32, b: f32, a: f32) {
+    set_clear_color_linear(LinearColor::make(r, g, b, a));
+}
+
+pub fn set_clear_color_linear(color: LinearColor) {
+    assert_initialized();
+
     let mut st = INTERNAL_STATE.lock().unwrap();
-    let sr = st.clear_color.r as f32 / 255.0;
-    let sg = st.clear_color.g as f32 / 255.0;
-    let sb = st.clear_color.b as f32 / 255.0;
-    let sa = st.clear_color.a as f32 / 255.0;
 
-    if sr != r || sg != g || sb != b || sa != a {
-        unsafe { gl::ClearColor(r, g, b, a) };
+    if st.clear_color != color {
+        unsafe { gl::ClearColor(color.r, color.g, color.b, color.a) };
 
-        st.clear_color = Color::make(
-            (r * 255.0) as u8,
-            (g * 255.0) as u8,
-            (b * 255.0) as u8,
-            (a * 255.0) as u8,
-        );
+        st.clear_color = color;
     }
 }
 
 pub fn set_front_face(target: FrontFace) {
+    assert_initialized();
+
     let mut st = INTERNAL_STATE.lock().unwrap();
 
     if st.front != target {
@@ -251,24 +882,1850 @@ pub fn set_front_face(target: FrontFace) {
     }
 }
 
+// which faces Feature::CullFace drops
+pub fn set_cull_face(face: Face) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.cull_face != face {
+        unsafe { gl::CullFace(face.get_native()) };
+
+        st.cull_face = face;
+    }
+}
+
 pub fn set_blend_func(src: BlendComponent, dst: BlendComponent) {
+    set_blend(BlendFunc::make(src, dst));
+}
+
+pub fn set_blend_func_separate(
+    src_rgb: BlendComponent,
+    dst_rgb: BlendComponent,
+    src_alpha: BlendComponent,
+    dst_alpha: BlendComponent,
+) {
+    set_blend(BlendFunc { src_rgb, dst_rgb, src_alpha, dst_alpha });
+}
+
+pub fn set_blend(func: BlendFunc) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.blend != func {
+        unsafe {
+            gl::BlendFuncSeparate(
+                func.src_rgb.get_native(),
+                func.dst_rgb.get_native(),
+                func.src_alpha.get_native(),
+                func.dst_alpha.get_native(),
+            );
+        }
+
+        st.blend = func;
+    }
+}
+
+// blending for one draw buffer of an MRT framebuffer, e.g. off for a G-buffer's normals; enable or
+// disable(Feature::Blend) sets all of them again
+pub fn set_blend_enabled_for(buffer: u32, enabled: bool) -> Result<()> {
+    check_initialized()?;
+
+    let max = capabilities().max_draw_buffers;
+
+    if buffer >= max {
+        return Err(Error::Unsupported(format!("draw buffer {} (the driver allows {})", buffer, max)));
+    }
+
+    if !gl::Enablei::is_loaded() || !gl::Disablei::is_loaded() {
+        return Err(Error::Unsupported("per-draw-buffer blending requires OpenGL 3.0".to_string()));
+    }
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    unsafe {
+        if enabled {
+            gl::Enablei(gl::BLEND, buffer);
+        } else {
+            gl::Disablei(gl::BLEND, buffer);
+        }
+    }
+
+    if buffer == 0 {
+        if enabled {
+            st.features.insert(Feature::Blend);
+        } else {
+            st.features.remove(&Feature::Blend);
+        }
+    }
+
+    st.blend_indexed = true;
+
+    Ok(())
+}
+
+pub fn set_blend_color(color: LinearColor) {
+    assert_initialized();
+
     let mut st = INTERNAL_STATE.lock().unwrap();
 
-    if st.blend_src != src || st.blend_dst != dst {
-        unsafe { gl::BlendFunc(src.get_native(), dst.get_native()) };
+    if st.blend_color != color {
+        unsafe { gl::BlendColor(color.r, color.g, color.b, color.a) };
 
-        st.blend_src = src;
-        st.blend_dst = dst;
+        st.blend_color = color;
     }
 }
 
 pub fn set_viewport(x: u32, y: u32, width: u32, height: u32) {
+    assert_initialized();
+
     let mut st = INTERNAL_STATE.lock().unwrap();
     let viewport = Viewport { x, y, width, height };
 
-    if st.viewport != viewport {
+    if st.viewport != viewport || st.viewport_array {
         unsafe { gl::Viewport(x as i32, y as i32, width as i32, height as i32) };
 
         st.viewport = viewport;
+        st.viewport_array = false;
+    }
+
+    // a viewport reaching past the known drawable size on the default framebuffer means the window grew;
+    // a smaller one says nothing about it shrinking, which only notify_resize can tell
+    if st.framebuffer == 0 {
+        let (width, height) = st.drawable_size;
+
+        st.drawable_size = (width.max(x.saturating_add(viewport.width)), height.max(y.saturating_add(viewport.height)));
+    }
+}
+
+// call from the window's resize handler with the framebuffer size in pixels (not points on HiDPI)
+pub fn notify_resize(width: u32, height: u32) {
+    assert_initialized();
+
+    INTERNAL_STATE.lock().unwrap().drawable_size = (width, height);
+}
+
+pub fn drawable_size() -> (u32, u32) {
+    INTERNAL_STATE.lock().unwrap().drawable_size
+}
+
+// window pixels have a top-left origin, as mouse positions do, while the viewport is placed from the
+// bottom-left of the drawable; points outside the viewport land outside -1..1
+pub fn pixel_to_ndc(x: f32, y: f32) -> Vector2 {
+    let st = INTERNAL_STATE.lock().unwrap();
+    let viewport = st.viewport;
+    let flipped = st.drawable_size.1 as f32 - y;
+
+    Vector2::make(
+        (x - viewport.x as f32) / viewport.width.max(1) as f32 * 2.0 - 1.0,
+        (flipped - viewport.y as f32) / viewport.height.max(1) as f32 * 2.0 - 1.0,
+    )
+}
+
+pub fn ndc_to_pixel(ndc: Vector2) -> Vector2 {
+    let st = INTERNAL_STATE.lock().unwrap();
+    let viewport = st.viewport;
+    let flipped = viewport.y as f32 + (ndc.y * 0.5 + 0.5) * viewport.height as f32;
+
+    Vector2::make(
+        viewport.x as f32 + (ndc.x * 0.5 + 0.5) * viewport.width as f32,
+        st.drawable_size.1 as f32 - flipped,
+    )
+}
+
+pub fn default_framebuffer() -> DefaultFramebuffer {
+    assert_initialized();
+
+    DefaultFramebuffer::new()
+}
+
+fn check_viewport_count(count: usize) -> Result<()> {
+    let max = capabilities().max_viewports as usize;
+
+    if count == 0 || count > max {
+        let msg = format!("{} viewports (the driver allows 1 to {})", count, max);
+
+        return Err(Error::Unsupported(msg));
+    }
+
+    Ok(())
+}
+
+// viewport i applies to primitives whose geometry shader writes gl_ViewportIndex = i
+pub fn set_viewports(viewports: &[Viewport]) -> Result<()> {
+    check_initialized()?;
+    check_viewport_count(viewports.len())?;
+
+    let values = viewports
+        .iter()
+        .flat_map(|viewport| vec![viewport.x as f32, viewport.y as f32, viewport.width as f32, viewport.height as f32])
+        .collect::<Vec<f32>>();
+
+    unsafe { gl::ViewportArrayv(0, viewports.len() as GLsizei, values.as_ptr()) };
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    st.viewport = viewports[0];
+    st.viewport_array = viewports.len() > 1;
+
+    Ok(())
+}
+
+// like set_viewports, but for the per-viewport scissor rectangles
+pub fn set_scissors(scissors: &[Viewport]) -> Result<()> {
+    check_initialized()?;
+    check_viewport_count(scissors.len())?;
+
+    let values = scissors
+        .iter()
+        .flat_map(|scissor| vec![scissor.x as GLint, scissor.y as GLint, scissor.width as GLint, scissor.height as GLint])
+        .collect::<Vec<GLint>>();
+
+    unsafe { gl::ScissorArrayv(0, scissors.len() as GLsizei, values.as_ptr()) };
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    st.scissor = scissors[0];
+    st.scissor_array = scissors.len() > 1;
+
+    Ok(())
+}
+
+pub fn set_depth_func(func: DepthFunc) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.depth_func != func {
+        unsafe { gl::DepthFunc(func.get_native()) };
+
+        st.depth_func = func;
+    }
+}
+
+pub fn set_depth_mask(enabled: bool) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.depth_mask != enabled {
+        unsafe { gl::DepthMask(if enabled { gl::TRUE } else { gl::FALSE }) };
+
+        st.depth_mask = enabled;
+    }
+}
+
+pub fn set_color_mask(r: bool, g: bool, b: bool, a: bool) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+    let mask = [r, g, b, a];
+
+    if st.color_mask != mask {
+        unsafe { gl::ColorMask(gl_bool(r), gl_bool(g), gl_bool(b), gl_bool(a)) };
+
+        st.color_mask = mask;
+    }
+}
+
+// core profiles only accept Face::FrontAndBack
+pub fn set_polygon_mode(face: Face, mode: PolygonMode) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+    let (front, back) = st.polygon_mode;
+    let target = match face {
+        Face::Front => (mode, back),
+        Face::Back => (front, mode),
+        Face::FrontAndBack => (mode, mode),
+    };
+
+    if st.polygon_mode != target {
+        unsafe { gl::PolygonMode(face.get_native(), mode.get_native()) };
+
+        st.polygon_mode = target;
+    }
+}
+
+// only takes effect while the matching Feature::PolygonOffset* is enabled
+pub fn set_polygon_offset(factor: f32, units: f32) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.polygon_offset != (factor, units) {
+        unsafe { gl::PolygonOffset(factor, units) };
+
+        st.polygon_offset = (factor, units);
+    }
+}
+
+pub fn set_depth_range(near: f32, far: f32) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.depth_range != (near, far) {
+        unsafe { gl::DepthRange(near as f64, far as f64) };
+
+        st.depth_range = (near, far);
+    }
+}
+
+pub fn set_stencil_func(func: StencilFunc, reference: i32, mask: u32) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+    let value = (func, reference, mask);
+
+    if st.stencil_func != value {
+        unsafe { gl::StencilFunc(func.get_native(), reference, mask) };
+
+        st.stencil_func = value;
+    }
+}
+
+pub fn set_stencil_op(stencil_fail: StencilOp, depth_fail: StencilOp, depth_pass: StencilOp) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+    let value = (stencil_fail, depth_fail, depth_pass);
+
+    if st.stencil_op != value {
+        unsafe {
+            gl::StencilOp(
+                stencil_fail.get_native(),
+                depth_fail.get_native(),
+                depth_pass.get_native(),
+            );
+        }
+
+        st.stencil_op = value;
+    }
+}
+
+pub fn set_stencil_mask(mask: u32) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.stencil_mask != mask {
+        unsafe { gl::StencilMask(mask) };
+
+        st.stencil_mask = mask;
+    }
+}
+
+pub fn set_patch_vertices(count: u32) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.patch_vertices != count {
+        unsafe { gl::PatchParameteri(gl::PATCH_VERTICES, count as GLint) };
+
+        st.patch_vertices = count;
+    }
+}
+
+pub fn set_line_width(width: f32) {
+    assert_initialized();
+    assert!(width > 0.0, "line width must be positive");
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.line_width != width {
+        unsafe { gl::LineWidth(width) };
+
+        st.line_width = width;
+    }
+}
+
+pub fn set_point_size(size: f32) {
+    assert_initialized();
+    assert!(size > 0.0, "point size must be positive");
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.point_size != size {
+        unsafe { gl::PointSize(size) };
+
+        st.point_size = size;
+    }
+}
+
+pub fn set_scissor(x: u32, y: u32, width: u32, height: u32) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+    let scissor = Viewport { x, y, width, height };
+
+    if st.scissor != scissor || st.scissor_array {
+        unsafe { gl::Scissor(x as i32, y as i32, width as i32, height as i32) };
+
+        st.scissor = scissor;
+        st.scissor_array = false;
+    }
+}
+
+#[must_use]
+pub struct ScissorGuard {
+    previous: Viewport,
+}
+
+impl Drop for ScissorGuard {
+    fn drop(&mut self) {
+        let prev = self.previous;
+
+        set_scissor(prev.x, prev.y, prev.width, prev.height);
+    }
+}
+
+pub fn set_scissor_scoped(x: u32, y: u32, width: u32, height: u32) -> ScissorGuard {
+    let previous = INTERNAL_STATE.lock().unwrap().scissor;
+
+    set_scissor(x, y, width, height);
+
+    ScissorGuard { previous }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ConditionalRenderMode {
+    // the GPU waits for the query result before deciding
+    Wait,
+    // draws go ahead if the result isn't ready yet
+    NoWait,
+    ByRegionWait,
+    ByRegionNoWait,
+}
+
+impl ConditionalRenderMode {
+    fn get_native(&self) -> GLenum {
+        match self {
+            ConditionalRenderMode::Wait => gl::QUERY_WAIT,
+            ConditionalRenderMode::NoWait => gl::QUERY_NO_WAIT,
+            ConditionalRenderMode::ByRegionWait => gl::QUERY_BY_REGION_WAIT,
+            ConditionalRenderMode::ByRegionNoWait => gl::QUERY_BY_REGION_NO_WAIT,
+        }
+    }
+}
+
+// until end_conditional_render, the GPU discards draws and clears when the query counted no samples;
+// the query has to have ended, but its result never has to reach the CPU
+pub fn begin_conditional_render(query: &Query, mode: ConditionalRenderMode) {
+    assert_initialized();
+
+    assert!(
+        query.kind() == QueryKind::SamplesPassed || query.kind() == QueryKind::AnySamplesPassed,
+        "conditional rendering requires an occlusion query",
+    );
+
+    assert!(query.is_issued() && !query.is_active(), "conditional rendering requires a query that has ended");
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    assert!(!st.conditional_render, "conditional rendering can't be nested");
+
+    unsafe { gl::BeginConditionalRender(query.handle(), mode.get_native()) };
+
+    st.conditional_render = true;
+}
+
+pub fn end_conditional_render() {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    assert!(st.conditional_render, "end_conditional_render called without begin_conditional_render");
+
+    unsafe { gl::EndConditionalRender() };
+
+    st.conditional_render = false;
+}
+
+pub fn is_conditional_render_active() -> bool {
+    INTERNAL_STATE.lock().unwrap().conditional_render
+}
+
+pub fn conditional_render_scoped(query: &Query, mode: ConditionalRenderMode) -> ConditionalRenderGuard {
+    begin_conditional_render(query, mode);
+
+    ConditionalRenderGuard { _thread: PhantomData }
+}
+
+#[must_use]
+pub struct ConditionalRenderGuard {
+    _thread: ThreadMarker,
+}
+
+impl Drop for ConditionalRenderGuard {
+    fn drop(&mut self) {
+        end_conditional_render();
+    }
+}
+
+pub fn viewport() -> Viewport {
+    INTERNAL_STATE.lock().unwrap().viewport
+}
+
+pub fn scissor() -> Viewport {
+    INTERNAL_STATE.lock().unwrap().scissor
+}
+
+pub fn clear_color() -> LinearColor {
+    INTERNAL_STATE.lock().unwrap().clear_color
+}
+
+pub fn blend_func() -> BlendFunc {
+    INTERNAL_STATE.lock().unwrap().blend
+}
+
+pub fn blend_color() -> LinearColor {
+    INTERNAL_STATE.lock().unwrap().blend_color
+}
+
+pub fn front_face() -> FrontFace {
+    INTERNAL_STATE.lock().unwrap().front
+}
+
+pub fn cull_face() -> Face {
+    INTERNAL_STATE.lock().unwrap().cull_face
+}
+
+pub fn depth_func() -> DepthFunc {
+    INTERNAL_STATE.lock().unwrap().depth_func
+}
+
+pub fn color_mask() -> [bool; 4] {
+    INTERNAL_STATE.lock().unwrap().color_mask
+}
+
+pub fn depth_mask() -> bool {
+    INTERNAL_STATE.lock().unwrap().depth_mask
+}
+
+pub fn polygon_mode(face: Face) -> PolygonMode {
+    let (front, back) = INTERNAL_STATE.lock().unwrap().polygon_mode;
+
+    match face {
+        Face::Back => back,
+        _ => front,
+    }
+}
+
+pub fn polygon_offset() -> (f32, f32) {
+    INTERNAL_STATE.lock().unwrap().polygon_offset
+}
+
+pub fn is_enabled(feature: Feature) -> bool {
+    INTERNAL_STATE.lock().unwrap().features.contains(&feature)
+}
+
+#[derive(Debug, Clone)]
+pub struct StateSnapshot {
+    front: FrontFace,
+    cull_face: Face,
+    blend: BlendFunc,
+    blend_color: LinearColor,
+    clear_color: LinearColor,
+    viewport: Viewport,
+    scissor: Viewport,
+    depth_func: DepthFunc,
+    depth_mask: bool,
+    color_mask: [bool; 4],
+    polygon_mode: (PolygonMode, PolygonMode),
+    polygon_offset: (f32, f32),
+    depth_range: (f32, f32),
+    stencil_func: (StencilFunc, i32, u32),
+    stencil_op: (StencilOp, StencilOp, StencilOp),
+    stencil_mask: u32,
+    patch_vertices: u32,
+    line_width: f32,
+    point_size: f32,
+    features: HashSet<Feature>,
+    framebuffer: GLuint,
+}
+
+impl StateSnapshot {
+    pub fn viewport(&self) -> Viewport {
+        self.viewport
+    }
+
+    pub fn clear_color(&self) -> LinearColor {
+        self.clear_color
+    }
+
+    pub fn blend_func(&self) -> BlendFunc {
+        self.blend
+    }
+
+    pub fn blend_color(&self) -> LinearColor {
+        self.blend_color
+    }
+
+    pub fn cull_face(&self) -> Face {
+        self.cull_face
+    }
+
+    pub fn is_enabled(&self, feature: Feature) -> bool {
+        self.features.contains(&feature)
+    }
+}
+
+pub fn snapshot() -> StateSnapshot {
+    assert_initialized();
+
+    let st = INTERNAL_STATE.lock().unwrap();
+
+    StateSnapshot {
+        front: st.front,
+        cull_face: st.cull_face,
+        blend: st.blend,
+        blend_color: st.blend_color,
+        clear_color: st.clear_color,
+        viewport: st.viewport,
+        scissor: st.scissor,
+        depth_func: st.depth_func,
+        depth_mask: st.depth_mask,
+        color_mask: st.color_mask,
+        polygon_mode: st.polygon_mode,
+        polygon_offset: st.polygon_offset,
+        depth_range: st.depth_range,
+        stencil_func: st.stencil_func,
+        stencil_op: st.stencil_op,
+        stencil_mask: st.stencil_mask,
+        patch_vertices: st.patch_vertices,
+        line_width: st.line_width,
+        point_size: st.point_size,
+        features: st.features.clone(),
+        framebuffer: st.framebuffer,
+    }
+}
+
+// reapplies everything unconditionally, since whoever ran in between may have bypassed the cache
+pub fn restore(snapshot: &StateSnapshot) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+    let blend = snapshot.blend;
+    let color = snapshot.clear_color;
+    let viewport = snapshot.viewport;
+    let scissor = snapshot.scissor;
+    let (stencil_func, reference, mask) = snapshot.stencil_func;
+    let (stencil_fail, depth_fail, depth_pass) = snapshot.stencil_op;
+
+    unsafe {
+        for feature in tracked_features(&[&snapshot.features, &st.features]).iter() {
+            if snapshot.features.contains(feature) {
+                gl::Enable(feature.get_native());
+            } else {
+                gl::Disable(feature.get_native());
+            }
+        }
+
+        gl::FrontFace(snapshot.front.get_native());
+        gl::CullFace(snapshot.cull_face.get_native());
+        gl::BlendFuncSeparate(
+            blend.src_rgb.get_native(),
+            blend.dst_rgb.get_native(),
+            blend.src_alpha.get_native(),
+            blend.dst_alpha.get_native(),
+        );
+        gl::BlendColor(
+            snapshot.blend_color.r,
+            snapshot.blend_color.g,
+            snapshot.blend_color.b,
+            snapshot.blend_color.a,
+        );
+        gl::ClearColor(color.r, color.g, color.b, color.a);
+        gl::Viewport(viewport.x as i32, viewport.y as i32, viewport.width as i32, viewport.height as i32);
+        gl::Scissor(scissor.x as i32, scissor.y as i32, scissor.width as i32, scissor.height as i32);
+        gl::DepthFunc(snapshot.depth_func.get_native());
+        gl::DepthMask(if snapshot.depth_mask { gl::TRUE } else { gl::FALSE });
+
+        let [r, g, b, a] = snapshot.color_mask;
+        gl::ColorMask(gl_bool(r), gl_bool(g), gl_bool(b), gl_bool(a));
+
+        match snapshot.polygon_mode {
+            (front, back) if front == back => gl::PolygonMode(gl::FRONT_AND_BACK, front.get_native()),
+            (front, back) => {
+                gl::PolygonMode(gl::FRONT, front.get_native());
+                gl::PolygonMode(gl::BACK, back.get_native());
+            },
+        }
+
+        gl::PolygonOffset(snapshot.polygon_offset.0, snapshot.polygon_offset.1);
+        gl::DepthRange(snapshot.depth_range.0 as f64, snapshot.depth_range.1 as f64);
+        gl::StencilFunc(stencil_func.get_native(), reference, mask);
+        gl::StencilOp(stencil_fail.get_native(), depth_fail.get_native(), depth_pass.get_native());
+        gl::StencilMask(snapshot.stencil_mask);
+
+        if gl::PatchParameteri::is_loaded() {
+            gl::PatchParameteri(gl::PATCH_VERTICES, snapshot.patch_vertices as GLint);
+        }
+
+        gl::LineWidth(snapshot.line_width);
+        gl::PointSize(snapshot.point_size);
+
+        if snapshot.framebuffer != UNKNOWN {
+            gl::BindFramebuffer(gl::FRAMEBUFFER, snapshot.framebuffer);
+        }
+    }
+
+    st.front = snapshot.front;
+    st.cull_face = snapshot.cull_face;
+    st.blend = snapshot.blend;
+    st.blend_color = snapshot.blend_color;
+    st.clear_color = snapshot.clear_color;
+    st.viewport = snapshot.viewport;
+    st.scissor = snapshot.scissor;
+    st.viewport_array = false;
+    st.scissor_array = false;
+    st.blend_indexed = false;
+    st.depth_func = snapshot.depth_func;
+    st.depth_mask = snapshot.depth_mask;
+    st.color_mask = snapshot.color_mask;
+    st.polygon_mode = snapshot.polygon_mode;
+    st.polygon_offset = snapshot.polygon_offset;
+    st.depth_range = snapshot.depth_range;
+    st.stencil_func = snapshot.stencil_func;
+    st.stencil_op = snapshot.stencil_op;
+    st.stencil_mask = snapshot.stencil_mask;
+    st.patch_vertices = snapshot.patch_vertices;
+    st.line_width = snapshot.line_width;
+    st.point_size = snapshot.point_size;
+    st.features = snapshot.features.clone();
+    st.framebuffer = snapshot.framebuffer;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Garbage {
+    Texture(GLuint),
+    Buffer(GLuint),
+    VertexArray(GLuint),
+    Program(GLuint),
+    ProgramPipeline(GLuint),
+    Shader(GLuint),
+    Framebuffer(GLuint),
+    Renderbuffer(GLuint),
+    Sampler(GLuint),
+    Query(GLuint),
+    // GLsync is a raw pointer, which can't sit in a shared static
+    Sync(usize),
+}
+
+impl Garbage {
+    // whether every context sharing objects with the one it was made in can delete it
+    pub(crate) fn is_shared(&self) -> bool {
+        !matches!(
+            self,
+            Garbage::VertexArray(_) | Garbage::ProgramPipeline(_) | Garbage::Framebuffer(_) | Garbage::Query(_)
+        )
+    }
+
+    fn is_null(&self) -> bool {
+        match *self {
+            Garbage::Texture(handle)
+            | Garbage::Buffer(handle)
+            | Garbage::VertexArray(handle)
+            | Garbage::Program(handle)
+            | Garbage::ProgramPipeline(handle)
+            | Garbage::Shader(handle)
+            | Garbage::Framebuffer(handle)
+            | Garbage::Renderbuffer(handle)
+            | Garbage::Sampler(handle)
+            | Garbage::Query(handle) => handle == 0,
+            Garbage::Sync(handle) => handle == 0,
+        }
+    }
+
+    unsafe fn delete(&self) {
+        match *self {
+            Garbage::Texture(handle) => gl::DeleteTextures(1, &handle),
+            Garbage::Buffer(handle) => gl::DeleteBuffers(1, &handle),
+            Garbage::VertexArray(handle) => gl::DeleteVertexArrays(1, &handle),
+            Garbage::Program(handle) => gl::DeleteProgram(handle),
+            Garbage::ProgramPipeline(handle) => gl::DeleteProgramPipelines(1, &handle),
+            Garbage::Shader(handle) => gl::DeleteShader(handle),
+            Garbage::Framebuffer(handle) => gl::DeleteFramebuffers(1, &handle),
+            Garbage::Renderbuffer(handle) => gl::DeleteRenderbuffers(1, &handle),
+            Garbage::Sampler(handle) => gl::DeleteSamplers(1, &handle),
+            Garbage::Query(handle) => gl::DeleteQueries(1, &handle),
+            Garbage::Sync(handle) => gl::DeleteSync(handle as GLsync),
+        }
+    }
+}
+
+// queued on the context current at the time; any context sharing objects with the one it was made in can delete it
+pub(crate) fn defer_delete(item: Garbage) {
+    if !item.is_null() {
+        INTERNAL_STATE.lock().unwrap().garbage.push(item);
+    }
+}
+
+// container objects (vertex arrays, framebuffers, pipelines, queries) aren't shared, so they're queued on
+// the context that made them, parked or not; owner is its multi_context id
+pub(crate) fn defer_delete_owned(owner: Option<u64>, item: Garbage) {
+    if !item.is_null() {
+        multi_context::defer_delete(owner, item);
+    }
+}
+
+// of the current context
+pub fn pending_garbage() -> usize {
+    INTERNAL_STATE.lock().unwrap().garbage.len()
+}
+
+// deletes what the current context has queued; other contexts keep theirs until made current
+pub fn collect_garbage() -> usize {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+    let items = std::mem::take(&mut st.garbage);
+
+    for item in items.iter() {
+        unsafe { item.delete() };
+
+        // GL unbinds deleted textures and vertex arrays, and their names may be handed out again
+        match *item {
+            Garbage::Texture(handle) => {
+                for unit in st.texture_units.iter_mut() {
+                    unit.forget(handle);
+                }
+            },
+            Garbage::VertexArray(handle) if st.vertex_array == handle => st.vertex_array = 0,
+            _ => (),
+        }
+    }
+
+    items.len()
+}
+
+// counters since the last end_frame; the skipped fields are binds the state caches turned into no-ops
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct FrameStats {
+    pub draw_calls: u32,
+    pub triangles: u64,
+    pub texture_binds: u32,
+    pub texture_binds_skipped: u32,
+    pub program_binds: u32,
+    pub program_binds_skipped: u32,
+    pub buffer_uploads: u32,
+    pub uploaded_bytes: u64,
+}
+
+pub(crate) fn count_draw(triangles: usize) {
+    let stats = &mut INTERNAL_STATE.lock().unwrap().stats;
+
+    stats.draw_calls += 1;
+    stats.triangles += triangles as u64;
+}
+
+pub(crate) fn count_texture_bind(issued: bool) {
+    let stats = &mut INTERNAL_STATE.lock().unwrap().stats;
+
+    match issued {
+        true => stats.texture_binds += 1,
+        false => stats.texture_binds_skipped += 1,
+    }
+}
+
+pub(crate) fn count_program_bind(issued: bool) {
+    let stats = &mut INTERNAL_STATE.lock().unwrap().stats;
+
+    match issued {
+        true => stats.program_binds += 1,
+        false => stats.program_binds_skipped += 1,
+    }
+}
+
+pub(crate) fn count_upload(bytes: usize) {
+    let stats = &mut INTERNAL_STATE.lock().unwrap().stats;
+
+    stats.buffer_uploads += 1;
+    stats.uploaded_bytes += bytes as u64;
+}
+
+pub fn frame_stats() -> FrameStats {
+    INTERNAL_STATE.lock().unwrap().stats
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FrameOptions {
+    // end_frame fences each frame, and begin_frame blocks until fewer than this many are still on the GPU
+    pub max_frames_in_flight: Option<usize>,
+    // debug builds only, and opt-in since code issuing raw GL calls would trip it: end_frame checks the
+    // state caches against glGet and panics on a mismatch
+    pub validate_state: bool,
+}
+
+impl FrameOptions {
+    pub fn new() -> FrameOptions {
+        FrameOptions {
+            max_frames_in_flight: None,
+            validate_state: false,
+        }
+    }
+}
+
+pub fn frame_options() -> FrameOptions {
+    INTERNAL_STATE.lock().unwrap().frame_options
+}
+
+pub fn set_frame_options(options: FrameOptions) {
+    INTERNAL_STATE.lock().unwrap().frame_options = options;
+}
+
+pub fn frames_in_flight() -> usize {
+    INTERNAL_STATE.lock().unwrap().frame_fences.len()
+}
+
+// drops the fences the GPU has passed; with wait, blocks on the oldest ones until at most keep remain
+fn retire_fences(keep: usize, wait: bool) {
+    let mut st = INTERNAL_STATE.lock().unwrap();
+    let fences = &mut st.frame_fences;
+
+    while let Some(fence) = fences.front().copied() {
+        let signaled = unsafe {
+            let status = gl::ClientWaitSync(fence as GLsync, 0, 0);
+
+            status == gl::ALREADY_SIGNALED || status == gl::CONDITION_SATISFIED
+        };
+
+        if !signaled && !(wait && fences.len() > keep) {
+            break;
+        }
+
+        unsafe {
+            if !signaled {
+                gl::ClientWaitSync(fence as GLsync, gl::SYNC_FLUSH_COMMANDS_BIT, gl::TIMEOUT_IGNORED);
+            }
+
+            gl::DeleteSync(fence as GLsync);
+        }
+
+        fences.pop_front();
+    }
+}
+
+// resets the counters, so work done between frames (loading, resizing) isn't charged to the next one,
+// and throttles the CPU per FrameOptions::max_frames_in_flight
+pub fn begin_frame() {
+    assert_initialized();
+
+    match frame_options().max_frames_in_flight {
+        Some(max) => retire_fences(max.saturating_sub(1), true),
+        None => retire_fences(0, false),
+    }
+
+    INTERNAL_STATE.lock().unwrap().stats = FrameStats::default();
+}
+
+// returns the finished frame's counters and starts a new frame from zero; objects dropped during
+// the frame are deleted here
+pub fn end_frame() -> FrameStats {
+    assert_initialized();
+
+    let options = frame_options();
+
+    #[cfg(debug_assertions)]
+    {
+        if options.validate_state {
+            if let Err(err) = verify_cached_state() {
+                panic!("{} (call invalidate_cached_state after raw GL calls)", err);
+            }
+        }
+    }
+
+    collect_garbage();
+
+    if options.max_frames_in_flight.is_some() {
+        let fence = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+
+        INTERNAL_STATE.lock().unwrap().frame_fences.push_back(fence as usize);
+    }
+
+    std::mem::take(&mut INTERNAL_STATE.lock().unwrap().stats)
+}
+
+// compares what the caches believe against glGet; bindings the cache marked unknown are skipped
+pub fn verify_cached_state() -> Result<()> {
+    check_initialized()?;
+
+    let st = INTERNAL_STATE.lock().unwrap();
+    let mut mismatches = Vec::new();
+
+    let mut check = |label: &str, cached: GLuint, name: GLenum| {
+        let actual = get_integer(name);
+
+        if cached != UNKNOWN && cached != actual {
+            mismatches.push(format!("{}: cached {}, GL reports {}", label, cached, actual));
+        }
+    };
+
+    check("program", st.program, gl::CURRENT_PROGRAM);
+    check("vertex array", st.vertex_array, gl::VERTEX_ARRAY_BINDING);
+    check("framebuffer", st.framebuffer, gl::DRAW_FRAMEBUFFER_BINDING);
+
+    if st.active_unit != UNKNOWN {
+        check("active texture unit", gl::TEXTURE0 + st.active_unit, gl::ACTIVE_TEXTURE);
+
+        // only the active unit can be queried without switching units
+        if let Some(unit) = st.texture_units.get(st.active_unit as usize) {
+            check("2D texture on the active unit", unit.d2_handle, gl::TEXTURE_BINDING_2D);
+        }
+    }
+
+    if !st.viewport_array {
+        let mut viewport = [0; 4];
+
+        unsafe { gl::GetIntegerv(gl::VIEWPORT, viewport.as_mut_ptr()) };
+
+        let cached = st.viewport;
+
+        if [cached.x as i32, cached.y as i32, cached.width as i32, cached.height as i32] != viewport {
+            mismatches.push(format!("viewport: cached {:?}, GL reports {:?}", cached, viewport));
+        }
+    }
+
+    let mut depth_mask = gl::FALSE;
+
+    unsafe { gl::GetBooleanv(gl::DEPTH_WRITEMASK, &mut depth_mask) };
+
+    if st.depth_mask != (depth_mask == gl::TRUE) {
+        mismatches.push(format!("depth mask: cached {}, GL reports {}", st.depth_mask, !st.depth_mask));
+    }
+
+    for feature in tracked_features(&[&st.features]).iter() {
+        let enabled = unsafe { gl::IsEnabled(feature.get_native()) == gl::TRUE };
+
+        if st.features.contains(feature) != enabled {
+            mismatches.push(format!("{:?}: cached {}, GL reports {}", feature, !enabled, enabled));
+        }
+    }
+
+    if mismatches.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::StateCacheMismatch(mismatches.join(", ")))
+    }
+}
+
+// stands in for whatever foreign GL code left bound; it's never a real object name, and binding it
+// (e.g. a scoped binding restoring it) leaves GL as is and the cache unknown
+const UNKNOWN: GLuint = GLuint::MAX;
+
+#[derive(Debug, Copy, Clone)]
+struct TextureUnit {
+    d1_handle: GLuint,
+    d2_handle: GLuint,
+    d2_array_handle: GLuint,
+    d3_handle: GLuint,
+}
+
+impl TextureUnit {
+    fn new(handle: GLuint) -> TextureUnit {
+        TextureUnit {
+            d1_handle: handle,
+            d2_handle: handle,
+            d2_array_handle: handle,
+            d3_handle: handle,
+        }
+    }
+
+    fn slot_mut(&mut self, target: GLenum) -> &mut GLuint {
+        match target {
+            gl::TEXTURE_1D => &mut self.d1_handle,
+            gl::TEXTURE_2D => &mut self.d2_handle,
+            gl::TEXTURE_2D_ARRAY => &mut self.d2_array_handle,
+            gl::TEXTURE_3D => &mut self.d3_handle,
+            _ => panic!("texture target {:#x} is not tracked by the unit cache", target),
+        }
+    }
+
+    fn forget(&mut self, handle: GLuint) {
+        for slot in [&mut self.d1_handle, &mut self.d2_handle, &mut self.d2_array_handle, &mut self.d3_handle] {
+            if *slot == handle {
+                *slot = 0;
+            }
+        }
+    }
+}
+
+// compares against the cache and only calls bind when the handle differs; returns whether it did
+fn bind_cached<F: FnOnce(GLuint)>(cached: &mut GLuint, handle: GLuint, bind: F) -> bool {
+    if handle == UNKNOWN {
+        *cached = UNKNOWN;
+        return false;
+    }
+
+    if *cached == handle {
+        return false;
+    }
+
+    bind(handle);
+    *cached = handle;
+    true
+}
+
+// call after foreign code (UI libraries, video decoders, ...) made raw GL calls; every texture, program,
+// vertex array and framebuffer bind afterwards reaches GL at least once
+pub fn invalidate_bindings() {
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    st.active_unit = UNKNOWN;
+    st.program = UNKNOWN;
+    st.vertex_array = UNKNOWN;
+    st.framebuffer = UNKNOWN;
+
+    for unit in st.texture_units.iter_mut() {
+        *unit = TextureUnit::new(UNKNOWN);
+    }
+}
+
+// also makes unit the active texture unit, so the caller can follow up with target-based calls
+pub(crate) fn bind_texture(unit: GLuint, target: GLenum, handle: GLuint) {
+    assert_initialized();
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if unit as usize >= st.texture_units.len() {
+        let count = st.texture_units.len();
+
+        // released first so the panic doesn't poison the state for whoever catches it
+        drop(st);
+        panic!("texture unit {} is beyond the {} units initialized by gl_toolkit::init", unit, count);
+    }
+
+    bind_cached(&mut st.active_unit, unit, |unit| unsafe { gl::ActiveTexture(gl::TEXTURE0 + unit) });
+
+    let slot = st.texture_units[unit as usize].slot_mut(target);
+    let issued = bind_cached(slot, handle, |handle| unsafe { gl::BindTexture(target, handle) });
+
+    drop(st);
+    count_texture_bind(issued);
+}
+
+pub(crate) fn bound_texture(unit: GLuint, target: GLenum) -> GLuint {
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    match st.texture_units.get_mut(unit as usize) {
+        Some(slots) => *slots.slot_mut(target),
+        None => 0,
+    }
+}
+
+pub(crate) fn bind_program(handle: GLuint) {
+    let issued = {
+        let mut st = INTERNAL_STATE.lock().unwrap();
+
+        bind_cached(&mut st.program, handle, |handle| unsafe { gl::UseProgram(handle) })
+    };
+
+    count_program_bind(issued);
+}
+
+pub(crate) fn bound_program() -> GLuint {
+    INTERNAL_STATE.lock().unwrap().program
+}
+
+pub(crate) fn bind_vertex_array(handle: GLuint) {
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    bind_cached(&mut st.vertex_array, handle, |handle| unsafe { gl::BindVertexArray(handle) });
+}
+
+pub(crate) fn bound_vertex_array() -> GLuint {
+    INTERNAL_STATE.lock().unwrap().vertex_array
+}
+
+// invalidate_bindings plus pushing every other cached value (blend, depth, viewport, features, ...)
+// back to GL, so state foreign code changed can't linger under a cache that thinks it's unchanged
+pub fn invalidate_cached_state() {
+    let snapshot = snapshot();
+
+    invalidate_bindings();
+    restore(&snapshot);
+}
+
+flags! {
+    // GL state beyond the caches that with_preserved_state reads back with glGet and rebinds afterwards
+    pub enum PreservedState: u16 {
+        Program = 0b0000_0001,
+        VertexArray = 0b0000_0010,
+        // GL_ARRAY_BUFFER; the element buffer belongs to the vertex array
+        ArrayBuffer = 0b0000_0100,
+        // the generic GL_UNIFORM_BUFFER binding, not the indexed ones
+        UniformBuffer = 0b0000_1000,
+        // draw, read and renderbuffer bindings
+        Framebuffers = 0b0001_0000,
+        // the active unit and every tracked target on each unit gl_toolkit::init set up
+        Textures = 0b0010_0000,
+        // pack and unpack alignment and row length
+        PixelStore = 0b0100_0000,
+    }
+}
+
+lazy_static! {
+    static ref PRESERVED_STATE: Mutex<FlagSet<PreservedState>> = Mutex::new(
+        PreservedState::Program
+            | PreservedState::VertexArray
+            | PreservedState::ArrayBuffer
+            | PreservedState::UniformBuffer
+            | PreservedState::Framebuffers
+            | PreservedState::Textures
+            | PreservedState::PixelStore
+    );
+}
+
+pub fn preserved_state() -> FlagSet<PreservedState> {
+    *PRESERVED_STATE.lock().unwrap()
+}
+
+// everything by default; dropping the texture units saves a few dozen glGets per call
+pub fn set_preserved_state(state: FlagSet<PreservedState>) {
+    *PRESERVED_STATE.lock().unwrap() = state;
+}
+
+const PIXEL_STORE: [GLenum; 4] = [gl::PACK_ALIGNMENT, gl::PACK_ROW_LENGTH, gl::UNPACK_ALIGNMENT, gl::UNPACK_ROW_LENGTH];
+
+struct SavedBindings {
+    program: Option<GLuint>,
+    vertex_array: Option<GLuint>,
+    array_buffer: Option<GLuint>,
+    uniform_buffer: Option<GLuint>,
+    // draw, read, renderbuffer
+    framebuffers: Option<(GLuint, GLuint, GLuint)>,
+    active_unit: Option<GLuint>,
+    texture_units: Vec<TextureUnit>,
+    pixel_store: Option<[GLuint; 4]>,
+}
+
+impl SavedBindings {
+    fn capture(state: FlagSet<PreservedState>) -> SavedBindings {
+        let query = |flag: PreservedState, name: GLenum| match state.contains(flag) {
+            true => Some(get_integer(name)),
+            false => None,
+        };
+
+        let mut result = SavedBindings {
+            program: query(PreservedState::Program, gl::CURRENT_PROGRAM),
+            vertex_array: query(PreservedState::VertexArray, gl::VERTEX_ARRAY_BINDING),
+            array_buffer: query(PreservedState::ArrayBuffer, gl::ARRAY_BUFFER_BINDING),
+            uniform_buffer: query(PreservedState::UniformBuffer, gl::UNIFORM_BUFFER_BINDING),
+            framebuffers: None,
+            active_unit: None,
+            texture_units: Vec::new(),
+            pixel_store: None,
+        };
+
+        if state.contains(PreservedState::Framebuffers) {
+            result.framebuffers = Some((
+                get_integer(gl::DRAW_FRAMEBUFFER_BINDING),
+                get_integer(gl::READ_FRAMEBUFFER_BINDING),
+                get_integer(gl::RENDERBUFFER_BINDING),
+            ));
+        }
+
+        if state.contains(PreservedState::Textures) {
+            let active = get_integer(gl::ACTIVE_TEXTURE) - gl::TEXTURE0;
+            let units = INTERNAL_STATE.lock().unwrap().texture_units.len() as GLuint;
+
+            for unit in 0..units {
+                unsafe { gl::ActiveTexture(gl::TEXTURE0 + unit) };
+
+                result.texture_units.push(TextureUnit {
+                    d1_handle: get_integer(gl::TEXTURE_BINDING_1D),
+                    d2_handle: get_integer(gl::TEXTURE_BINDING_2D),
+                    d2_array_handle: get_integer(gl::TEXTURE_BINDING_2D_ARRAY),
+                    d3_handle: get_integer(gl::TEXTURE_BINDING_3D),
+                });
+            }
+
+            unsafe { gl::ActiveTexture(gl::TEXTURE0 + active) };
+
+            result.active_unit = Some(active);
+        }
+
+        if state.contains(PreservedState::PixelStore) {
+            let mut values = [0; 4];
+
+            for (value, name) in values.iter_mut().zip(PIXEL_STORE.iter()) {
+                *value = get_integer(*name);
+            }
+
+            result.pixel_store = Some(values);
+        }
+
+        result
+    }
+
+    // rebinds what was captured and records it in the caches, which were invalidated beforehand
+    fn apply(&self) {
+        let mut st = INTERNAL_STATE.lock().unwrap();
+
+        unsafe {
+            if let Some(program) = self.program {
+                gl::UseProgram(program);
+                st.program = program;
+            }
+
+            if let Some(vertex_array) = self.vertex_array {
+                gl::BindVertexArray(vertex_array);
+                st.vertex_array = vertex_array;
+            }
+
+            if let Some(buffer) = self.array_buffer {
+                gl::BindBuffer(gl::ARRAY_BUFFER, buffer);
+            }
+
+            if let Some(buffer) = self.uniform_buffer {
+                gl::BindBuffer(gl::UNIFORM_BUFFER, buffer);
+            }
+
+            if let Some((draw, read, renderbuffer)) = self.framebuffers {
+                gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, draw);
+                gl::BindFramebuffer(gl::READ_FRAMEBUFFER, read);
+                gl::BindRenderbuffer(gl::RENDERBUFFER, renderbuffer);
+
+                // the cache only knows a single binding for both targets
+                st.framebuffer = if draw == read { draw } else { UNKNOWN };
+            }
+
+            for (unit, saved) in self.texture_units.iter().enumerate() {
+                gl::ActiveTexture(gl::TEXTURE0 + unit as GLuint);
+                gl::BindTexture(gl::TEXTURE_1D, saved.d1_handle);
+                gl::BindTexture(gl::TEXTURE_2D, saved.d2_handle);
+                gl::BindTexture(gl::TEXTURE_2D_ARRAY, saved.d2_array_handle);
+                gl::BindTexture(gl::TEXTURE_3D, saved.d3_handle);
+
+                st.texture_units[unit] = TextureUnit {
+                    d1_handle: saved.d1_handle,
+                    d2_handle: saved.d2_handle,
+                    d2_array_handle: saved.d2_array_handle,
+                    d3_handle: saved.d3_handle,
+                };
+            }
+
+            if let Some(active) = self.active_unit {
+                gl::ActiveTexture(gl::TEXTURE0 + active);
+                st.active_unit = active;
+            }
+
+            if let Some(values) = self.pixel_store {
+                for (value, name) in values.iter().zip(PIXEL_STORE.iter()) {
+                    gl::PixelStorei(*name, *value as GLint);
+                }
+            }
+        }
+    }
+}
+
+struct PreservedStateGuard {
+    snapshot: StateSnapshot,
+    saved: SavedBindings,
+}
+
+// restores on unwind too, so a host that catches a plugin's panic keeps a consistent context
+impl Drop for PreservedStateGuard {
+    fn drop(&mut self) {
+        invalidate_bindings();
+        restore(&self.snapshot);
+        self.saved.apply();
+    }
+}
+
+// for hosting plugins or scripts that issue raw GL: the cached state plus whatever preserved_state
+// selects is saved before f and put back after, and bindings that weren't saved are marked unknown
+// so the caches don't trust them
+pub fn with_preserved_state<R, F: FnOnce(&GlContext) -> R>(f: F) -> R {
+    assert_initialized();
+
+    let _guard = PreservedStateGuard {
+        snapshot: snapshot(),
+        saved: SavedBindings::capture(preserved_state()),
+    };
+
+    f(&GlContext { _thread: PhantomData })
+}
+
+pub(crate) fn bind_framebuffer(handle: GLuint) {
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    bind_cached(&mut st.framebuffer, handle, |handle| unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, handle) });
+}
+
+pub(crate) fn bound_framebuffer() -> GLuint {
+    INTERNAL_STATE.lock().unwrap().framebuffer
+}
+
+// for code that bound a framebuffer behind the cache's back and wants the cached one back
+pub(crate) fn rebind_framebuffer() {
+    let framebuffer = bound_framebuffer();
+
+    if framebuffer != UNKNOWN {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, framebuffer) };
+    }
+}
+
+pub(crate) fn forget_framebuffer(handle: GLuint) {
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if st.framebuffer == handle {
+        unsafe { gl::BindFramebuffer(gl::FRAMEBUFFER, 0) };
+
+        st.framebuffer = 0;
+    }
+}
+
+#[must_use]
+pub struct ClipGuard {
+    depth: usize,
+}
+
+impl Drop for ClipGuard {
+    fn drop(&mut self) {
+        let (remaining, previous) = {
+            let mut st = INTERNAL_STATE.lock().unwrap();
+
+            st.clip_stack.truncate(self.depth - 1);
+
+            match st.clip_stack.last() {
+                Some(rect) => (Some((*rect, st.viewport)), None),
+                None => (None, st.clip_previous.take()),
+            }
+        };
+
+        if let Some((rect, viewport)) = remaining {
+            apply_clip_rect(&rect, &viewport);
+        }
+
+        // the outermost pop puts scissoring back the way the first push found it
+        if let Some((enabled, scissor)) = previous {
+            set_scissor(scissor.x, scissor.y, scissor.width, scissor.height);
+
+            if enabled {
+                enable(Feature::ScissorTest);
+            } else {
+                disable(Feature::ScissorTest);
+            }
+        }
+    }
+}
+
+// the intersection with the parent clip rect, or with the viewport for the outermost one
+fn nested_clip_rect(parent: Option<&Rect>, viewport: &Viewport, rect: &Rect) -> Rect {
+    match parent {
+        Some(parent) => parent.intersect(rect),
+        None => Rect::make(0.0, 0.0, viewport.width as f32, viewport.height as f32).intersect(rect),
+    }
+}
+
+// rect has a top-left origin relative to the viewport; GL's scissor box is bottom-left in window pixels
+fn clip_scissor(rect: &Rect, viewport: &Viewport) -> Viewport {
+    let left = rect.x.round() as i32;
+    let top = rect.y.round() as i32;
+    let right = rect.right().round() as i32;
+    let bottom = rect.bottom().round() as i32;
+
+    Viewport {
+        x: (viewport.x as i32 + left).max(0) as u32,
+        y: (viewport.y as i32 + viewport.height as i32 - bottom).max(0) as u32,
+        width: (right - left).max(0) as u32,
+        height: (bottom - top).max(0) as u32,
+    }
+}
+
+fn apply_clip_rect(rect: &Rect, viewport: &Viewport) {
+    let scissor = clip_scissor(rect, viewport);
+
+    set_scissor(scissor.x, scissor.y, scissor.width, scissor.height);
+}
+
+pub fn push_clip_rect(rect: Rect) -> ClipGuard {
+    assert_initialized();
+
+    let (clipped, viewport, depth) = {
+        let mut st = INTERNAL_STATE.lock().unwrap();
+        let clipped = nested_clip_rect(st.clip_stack.last(), &st.viewport, &rect);
+
+        if st.clip_stack.is_empty() {
+            st.clip_previous = Some((st.features.contains(&Feature::ScissorTest), st.scissor));
+        }
+
+        st.clip_stack.push(clipped);
+
+        (clipped, st.viewport, st.clip_stack.len())
+    };
+
+    enable(Feature::ScissorTest);
+    apply_clip_rect(&clipped, &viewport);
+
+    ClipGuard { depth }
+}
+
+pub fn clip_rect() -> Option<Rect> {
+    INTERNAL_STATE.lock().unwrap().clip_stack.last().copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fbo::{DepthAttachment, Framebuffer};
+    use crate::texture::Texture;
+    use crate::vbo::{BufferMode, PrimitiveKind, VBO};
+    use crate::builtin::BasicVertex;
+
+    const NOT_INITIALIZED: &str = "gl_toolkit::init must be called on this thread first";
+
+    const BLEND_COMPONENTS: [(BlendComponent, GLenum); 19] = [
+        (BlendComponent::Zero, gl::ZERO),
+        (BlendComponent::One, gl::ONE),
+        (BlendComponent::SrcColor, gl::SRC_COLOR),
+        (BlendComponent::DstColor, gl::DST_COLOR),
+        (BlendComponent::SrcAlpha, gl::SRC_ALPHA),
+        (BlendComponent::DstAlpha, gl::DST_ALPHA),
+        (BlendComponent::ConstColor, gl::CONSTANT_COLOR),
+        (BlendComponent::ConstAlpha, gl::CONSTANT_ALPHA),
+        (BlendComponent::SrcAlphaSaturate, gl::SRC_ALPHA_SATURATE),
+        (BlendComponent::OneMinusSrcColor, gl::ONE_MINUS_SRC_COLOR),
+        (BlendComponent::OneMinusDstColor, gl::ONE_MINUS_DST_COLOR),
+        (BlendComponent::OneMinusSrcAlpha, gl::ONE_MINUS_SRC_ALPHA),
+        (BlendComponent::OneMinusDstAlpha, gl::ONE_MINUS_DST_ALPHA),
+        (BlendComponent::OneMinusConstColor, gl::ONE_MINUS_CONSTANT_COLOR),
+        (BlendComponent::OneMinusConstAlpha, gl::ONE_MINUS_CONSTANT_ALPHA),
+        (BlendComponent::Src1Color, gl::SRC1_COLOR),
+        (BlendComponent::Src1Alpha, gl::SRC1_ALPHA),
+        (BlendComponent::OneMinusSrc1Color, gl::ONE_MINUS_SRC1_COLOR),
+        (BlendComponent::OneMinusSrc1Alpha, gl::ONE_MINUS_SRC1_ALPHA),
+    ];
+
+    // no wildcard arm, so a new variant fails to compile until it's added to the table above
+    fn blend_component_index(component: BlendComponent) -> usize {
+        match component {
+            BlendComponent::Zero => 0,
+            BlendComponent::One => 1,
+            BlendComponent::SrcColor => 2,
+            BlendComponent::DstColor => 3,
+            BlendComponent::SrcAlpha => 4,
+            BlendComponent::DstAlpha => 5,
+            BlendComponent::ConstColor => 6,
+            BlendComponent::ConstAlpha => 7,
+            BlendComponent::SrcAlphaSaturate => 8,
+            BlendComponent::OneMinusSrcColor => 9,
+            BlendComponent::OneMinusDstColor => 10,
+            BlendComponent::OneMinusSrcAlpha => 11,
+            BlendComponent::OneMinusDstAlpha => 12,
+            BlendComponent::OneMinusConstColor => 13,
+            BlendComponent::OneMinusConstAlpha => 14,
+            BlendComponent::Src1Color => 15,
+            BlendComponent::Src1Alpha => 16,
+            BlendComponent::OneMinusSrc1Color => 17,
+            BlendComponent::OneMinusSrc1Alpha => 18,
+        }
+    }
+
+    #[test]
+    fn blend_components_map_to_their_gl_constants() {
+        for (i, (component, native)) in BLEND_COMPONENTS.iter().enumerate() {
+            assert_eq!(blend_component_index(*component), i, "{:?} is out of place in the table", component);
+            assert_eq!(component.get_native(), *native, "{:?} maps to the wrong constant", component);
+        }
+
+        let mut natives = BLEND_COMPONENTS.iter().map(|(_, native)| *native).collect::<Vec<_>>();
+
+        natives.sort();
+        natives.dedup();
+
+        assert_eq!(natives.len(), BLEND_COMPONENTS.len(), "two blend components share a constant");
+    }
+
+    // test threads never call init, so these hold even while the headless thread is initialized
+
+    #[test]
+    fn fallible_entry_points_return_not_initialized() {
+        assert!(!is_initialized());
+        assert!(matches!(check_initialized(), Err(Error::NotInitialized)));
+        assert!(matches!(Texture::make(&vec![0; 4], 1, 1, false), Err(Error::NotInitialized)));
+        assert!(matches!(Framebuffer::new(4, 4, 1, DepthAttachment::None), Err(Error::NotInitialized)));
+        assert!(matches!(set_blend_enabled_for(1, true), Err(Error::NotInitialized)));
+    }
+
+    #[test]
+    #[should_panic(expected = "gl_toolkit::init must be called on this thread first")]
+    fn vbo_panics_before_init() {
+        let _ = VBO::new(BufferMode::StaticDraw, PrimitiveKind::Triangles, &vec![BasicVertex::new(0.0, 0.0, 0.0)], None);
+    }
+
+    // a local one, since forcing a built-in shader's lazy_static here would poison it for the GL tests
+    #[test]
+    #[should_panic(expected = "gl_toolkit::init must be called on this thread first")]
+    fn context_bound_values_panic_before_init() {
+        let bound = ContextBound::new(0);
+
+        let _ = *bound;
+    }
+
+    #[test]
+    fn infallible_entry_points_panic_with_a_message() {
+        let calls: [fn(); 3] = [
+            || set_viewport(0, 0, 1, 1),
+            || { capabilities(); },
+            || { enable(Feature::Blend); },
+        ];
+
+        for call in calls.iter() {
+            let err = std::panic::catch_unwind(call).unwrap_err();
+            let message = err.downcast_ref::<&str>().copied().map(str::to_string)
+                .or_else(|| err.downcast_ref::<String>().cloned())
+                .unwrap();
+
+            assert_eq!(message, NOT_INITIALIZED);
+        }
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn shutdown_resets_the_init_latch() {
+        crate::headless::run(|| {
+            let viewport = super::viewport();
+
+            shutdown();
+
+            assert!(!is_initialized());
+            assert!(matches!(Texture::make(&vec![0; 4], 1, 1, false), Err(Error::NotInitialized)));
+
+            // the context is still current, so init can take it back over
+            crate::init().unwrap();
+            set_viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+
+            assert!(is_initialized());
+            assert!(Texture::make(&vec![0; 4], 1, 1, false).is_ok());
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn garbage_is_only_collected_by_its_own_context() {
+        crate::headless::run(|| {
+            let texture = Texture::make(&vec![0; 4], 1, 1, false).unwrap();
+            let handle = texture.handle();
+
+            drop(texture);
+
+            // what multi_context does when another context is made current
+            let primary = swap_state(State::new());
+
+            assert_eq!(pending_garbage(), 0);
+            assert_eq!(collect_garbage(), 0);
+            assert_eq!(unsafe { gl::IsTexture(handle) }, gl::TRUE);
+
+            swap_state(primary);
+
+            assert!(pending_garbage() >= 1);
+            assert!(collect_garbage() >= 1);
+            assert_eq!(unsafe { gl::IsTexture(handle) }, gl::FALSE);
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn frame_stats_follow_their_context() {
+        crate::headless::run(|| {
+            count_draw(1);
+
+            let primary = swap_state(State::new());
+
+            assert_eq!(frame_stats(), FrameStats::default());
+
+            count_draw(2);
+
+            let other = swap_state(primary);
+
+            assert_eq!((other.stats.draw_calls, other.stats.triangles), (1, 2));
+            assert!(frame_stats().draw_calls >= 1);
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn viewports_past_u32_max_saturate_the_drawable_size() {
+        crate::headless::run(|| {
+            let (viewport, size) = (super::viewport(), drawable_size());
+
+            set_viewport(u32::MAX, 1, 1, u32::MAX);
+
+            assert_eq!(drawable_size(), (u32::MAX, u32::MAX));
+
+            notify_resize(size.0, size.1);
+            set_viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+        });
+    }
+
+    #[test]
+    fn only_shared_garbage_outlives_its_context() {
+        let mut state = State::new();
+
+        state.garbage = vec![Garbage::Texture(1), Garbage::VertexArray(2), Garbage::Buffer(3), Garbage::Framebuffer(4)];
+
+        let shared = state.garbage.iter().filter(|item| item.is_shared()).collect::<Vec<_>>();
+
+        assert!(matches!(shared[..], [Garbage::Texture(1), Garbage::Buffer(3)]));
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn clear_colors_are_cached_without_rounding() {
+        crate::headless::run(|| {
+            let previous = clear_color();
+
+            set_clear_color_rgba(0.2, 0.3, 0.3, 1.0);
+
+            assert_eq!(clear_color(), LinearColor::make(0.2, 0.3, 0.3, 1.0));
+
+            set_clear_color(Color::make(255, 0, 51, 255));
+
+            assert_eq!(clear_color(), LinearColor::make(1.0, 0.0, 0.2, 1.0));
+
+            set_clear_color_linear(previous);
+        });
+    }
+
+    #[cfg(all(feature = "headless", debug_assertions))]
+    #[test]
+    fn end_frame_only_validates_when_asked_to() {
+        crate::headless::run(|| {
+            let options = frame_options();
+
+            assert!(!FrameOptions::new().validate_state);
+
+            // a raw call the caches never hear about
+            unsafe { gl::DepthMask(gl::FALSE) };
+
+            end_frame();
+
+            set_frame_options(FrameOptions {
+                validate_state: true,
+                ..options
+            });
+
+            let result = std::panic::catch_unwind(|| {
+                end_frame();
+            });
+
+            unsafe { gl::DepthMask(gl::TRUE) };
+            set_frame_options(options);
+
+            let message = result.unwrap_err();
+
+            assert!(message.downcast_ref::<String>().unwrap().contains("invalidate_cached_state"));
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    #[should_panic(expected = "is beyond the")]
+    fn texture_units_past_the_table_panic() {
+        crate::headless::run(|| bind_texture(100_000, gl::TEXTURE_2D, 0));
+    }
+
+    fn viewport(x: u32, y: u32, width: u32, height: u32) -> Viewport {
+        Viewport { x, y, width, height }
+    }
+
+    #[test]
+    fn nested_clip_rects_intersect() {
+        let vp = viewport(0, 0, 100, 100);
+        let outer = nested_clip_rect(None, &vp, &Rect::make(-10.0, 20.0, 50.0, 200.0));
+        let inner = nested_clip_rect(Some(&outer), &vp, &Rect::make(30.0, 10.0, 40.0, 40.0));
+
+        assert_eq!(outer, Rect::make(0.0, 20.0, 40.0, 80.0));
+        assert_eq!(inner, Rect::make(30.0, 20.0, 10.0, 30.0));
+    }
+
+    #[test]
+    fn disjoint_clip_rects_are_empty() {
+        let vp = viewport(0, 0, 100, 100);
+        let outer = nested_clip_rect(None, &vp, &Rect::make(0.0, 0.0, 10.0, 10.0));
+        let inner = nested_clip_rect(Some(&outer), &vp, &Rect::make(50.0, 50.0, 10.0, 10.0));
+
+        assert!(inner.is_empty());
+        assert_eq!(clip_scissor(&inner, &vp).width, 0);
+        assert_eq!(clip_scissor(&inner, &vp).height, 0);
+    }
+
+    #[test]
+    fn clip_scissor_flips_to_a_bottom_left_origin() {
+        let vp = viewport(10, 20, 200, 100);
+
+        assert_eq!(clip_scissor(&Rect::make(0.0, 0.0, 50.0, 10.0), &vp), viewport(10, 110, 50, 10));
+        assert_eq!(clip_scissor(&Rect::make(5.0, 90.0, 20.0, 10.0), &vp), viewport(15, 20, 20, 10));
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn clip_stack_scissors_outside_the_innermost_rect() {
+        use crate::readback;
+        use crate::texture::TextureFormat;
+
+        crate::headless::run(|| {
+            let snapshot = snapshot();
+
+            bind_framebuffer(0);
+            set_viewport(0, 0, 64, 64);
+            disable(Feature::ScissorTest);
+            set_clear_color_linear(LinearColor::BLACK);
+            clear(ClearFlag::Color.into());
+
+            {
+                let _outer = push_clip_rect(Rect::make(8.0, 8.0, 48.0, 48.0));
+                let _inner = push_clip_rect(Rect::make(32.0, 0.0, 64.0, 16.0));
+
+                assert_eq!(clip_rect(), Some(Rect::make(32.0, 8.0, 24.0, 8.0)));
+
+                set_clear_color_linear(LinearColor::WHITE);
+                clear(ClearFlag::Color.into());
+            }
+
+            assert!(!is_enabled(Feature::ScissorTest));
+            assert_eq!(clip_rect(), None);
+
+            // rows come back bottom-up
+            let pixels = readback::read_pixels(0, 0, 64, 64, TextureFormat::Rgba8);
+
+            for (i, pixel) in pixels.chunks(4).enumerate() {
+                let (x, y) = (i % 64, 63 - i / 64);
+                let inside = (32..56).contains(&x) && (8..16).contains(&y);
+                let expected = if inside { 255 } else { 0 };
+
+                assert_eq!(pixel[0], expected, "pixel ({}, {})", x, y);
+            }
+
+            restore(&snapshot);
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn clip_stack_restores_the_previous_scissor() {
+        crate::headless::run(|| {
+            let snapshot = snapshot();
+
+            set_viewport(0, 0, 64, 64);
+            set_scissor(1, 2, 3, 4);
+            enable(Feature::ScissorTest);
+
+            {
+                let _clip = push_clip_rect(Rect::make(0.0, 0.0, 16.0, 16.0));
+
+                assert_eq!(scissor(), viewport(0, 48, 16, 16));
+            }
+
+            assert!(is_enabled(Feature::ScissorTest));
+            assert_eq!(scissor(), viewport(1, 2, 3, 4));
+
+            restore(&snapshot);
+        });
     }
 }
@@ -0,0 +1,269 @@
+use crate::context;
+use crate::error::{Error, Result};
+
+use gl::types::*;
+use lazy_static::lazy_static;
+use std::ffi::{CStr, CString};
+use std::os::raw::c_void;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Mutex;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DebugSource {
+    Api,
+    WindowSystem,
+    ShaderCompiler,
+    ThirdParty,
+    Application,
+    Other,
+}
+
+impl DebugSource {
+    pub fn new(raw: GLenum) -> DebugSource {
+        match raw {
+            gl::DEBUG_SOURCE_API => DebugSource::Api,
+            gl::DEBUG_SOURCE_WINDOW_SYSTEM => DebugSource::WindowSystem,
+            gl::DEBUG_SOURCE_SHADER_COMPILER => DebugSource::ShaderCompiler,
+            gl::DEBUG_SOURCE_THIRD_PARTY => DebugSource::ThirdParty,
+            gl::DEBUG_SOURCE_APPLICATION => DebugSource::Application,
+            _ => DebugSource::Other,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DebugType {
+    Error,
+    DeprecatedBehavior,
+    UndefinedBehavior,
+    Portability,
+    Performance,
+    Marker,
+    PushGroup,
+    PopGroup,
+    Other,
+}
+
+impl DebugType {
+    pub fn new(raw: GLenum) -> DebugType {
+        match raw {
+            gl::DEBUG_TYPE_ERROR => DebugType::Error,
+            gl::DEBUG_TYPE_DEPRECATED_BEHAVIOR => DebugType::DeprecatedBehavior,
+            gl::DEBUG_TYPE_UNDEFINED_BEHAVIOR => DebugType::UndefinedBehavior,
+            gl::DEBUG_TYPE_PORTABILITY => DebugType::Portability,
+            gl::DEBUG_TYPE_PERFORMANCE => DebugType::Performance,
+            gl::DEBUG_TYPE_MARKER => DebugType::Marker,
+            gl::DEBUG_TYPE_PUSH_GROUP => DebugType::PushGroup,
+            gl::DEBUG_TYPE_POP_GROUP => DebugType::PopGroup,
+            _ => DebugType::Other,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DebugSeverity {
+    Notification,
+    Low,
+    Medium,
+    High,
+}
+
+impl DebugSeverity {
+    pub fn new(raw: GLenum) -> DebugSeverity {
+        match raw {
+            gl::DEBUG_SEVERITY_HIGH => DebugSeverity::High,
+            gl::DEBUG_SEVERITY_MEDIUM => DebugSeverity::Medium,
+            gl::DEBUG_SEVERITY_LOW => DebugSeverity::Low,
+            _ => DebugSeverity::Notification,
+        }
+    }
+}
+
+type DebugCallback = Box<dyn FnMut(DebugSource, DebugType, DebugSeverity, &str) + Send>;
+
+struct State {
+    min_severity: DebugSeverity,
+    callback: Option<DebugCallback>,
+    // bumped whenever the callback is set or cleared, so one replaced mid-call isn't put back
+    generation: u64,
+}
+
+lazy_static! {
+    static ref INTERNAL_STATE: Mutex<State> = {
+        Mutex::new(State {
+            min_severity: DebugSeverity::Notification,
+            callback: None,
+            generation: 0,
+        })
+    };
+}
+
+extern "system" fn debug_trampoline(
+    source: GLenum,
+    kind: GLenum,
+    _id: GLuint,
+    severity: GLenum,
+    _length: GLsizei,
+    message: *const GLchar,
+    _user_param: *mut c_void,
+) {
+    let severity = DebugSeverity::new(severity);
+
+    // the callback runs outside the lock, so it can change the settings and a panic can't poison them;
+    // while it's taken out, a GL error it triggers itself re-enters here and finds nothing to call
+    let (mut callback, generation) = {
+        let mut st = match INTERNAL_STATE.lock() {
+            Ok(st) => st,
+            Err(_) => return,
+        };
+
+        if severity < st.min_severity || message.is_null() {
+            return;
+        }
+
+        match st.callback.take() {
+            Some(callback) => (callback, st.generation),
+            None => return,
+        }
+    };
+
+    let text = unsafe { CStr::from_ptr(message) }.to_string_lossy();
+
+    // unwinding into the driver is undefined behavior, so a panic ends with the message
+    let _ = panic::catch_unwind(AssertUnwindSafe(|| {
+        callback(DebugSource::new(source), DebugType::new(kind), severity, &text);
+    }));
+
+    if let Ok(mut st) = INTERNAL_STATE.lock() {
+        if st.generation == generation && st.callback.is_none() {
+            st.callback = Some(callback);
+        }
+    }
+}
+
+pub fn is_debug_output_supported() -> bool {
+    gl::DebugMessageCallback::is_loaded()
+}
+
+pub fn set_debug_callback<F>(min_severity: DebugSeverity, callback: F) -> Result<()>
+where
+    F: FnMut(DebugSource, DebugType, DebugSeverity, &str) + Send + 'static,
+{
+    context::check_initialized()?;
+
+    if !is_debug_output_supported() {
+        return Err(Error::Unsupported("glDebugMessageCallback is not available".to_string()));
+    }
+
+    {
+        let mut st = INTERNAL_STATE.lock().unwrap();
+
+        st.min_severity = min_severity;
+        st.callback = Some(Box::new(callback));
+        st.generation += 1;
+    }
+
+    unsafe {
+        gl::Enable(gl::DEBUG_OUTPUT);
+        gl::Enable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+        gl::DebugMessageCallback(Some(debug_trampoline), ptr::null());
+    }
+
+    Ok(())
+}
+
+pub fn set_debug_severity(min_severity: DebugSeverity) {
+    INTERNAL_STATE.lock().unwrap().min_severity = min_severity;
+}
+
+pub fn clear_debug_callback() {
+    context::assert_initialized();
+
+    if is_debug_output_supported() {
+        unsafe {
+            gl::DebugMessageCallback(None, ptr::null());
+            gl::Disable(gl::DEBUG_OUTPUT_SYNCHRONOUS);
+            gl::Disable(gl::DEBUG_OUTPUT);
+        }
+    }
+
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    st.callback = None;
+    st.generation += 1;
+}
+
+// a no-op without KHR_debug, so callers can label unconditionally
+pub(crate) fn label_object(identifier: GLenum, handle: GLuint, label: &str) {
+    if handle == 0 || !gl::ObjectLabel::is_loaded() {
+        return;
+    }
+
+    let c_label = CString::new(label.replace('\0', "")).unwrap();
+
+    unsafe { gl::ObjectLabel(identifier, handle, -1, c_label.as_ptr()) };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn trigger(severity: GLenum) {
+        let message = CString::new("test message").unwrap();
+
+        debug_trampoline(gl::DEBUG_SOURCE_APPLICATION, gl::DEBUG_TYPE_OTHER, 0, severity, -1, message.as_ptr(), ptr::null_mut());
+    }
+
+    fn install(callback: DebugCallback) {
+        let mut st = INTERNAL_STATE.lock().unwrap();
+
+        st.min_severity = DebugSeverity::Notification;
+        st.callback = Some(callback);
+        st.generation += 1;
+    }
+
+    // one test, since the callback is process-wide
+    #[test]
+    fn callbacks_survive_panics_and_reentry() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+
+        install(Box::new(move |_, _, _, _| {
+            counter.fetch_add(1, Ordering::SeqCst);
+
+            // a nested message finds the callback taken out and is dropped
+            trigger(gl::DEBUG_SEVERITY_HIGH);
+
+            panic!("callback panic");
+        }));
+
+        trigger(gl::DEBUG_SEVERITY_HIGH);
+        trigger(gl::DEBUG_SEVERITY_HIGH);
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        assert!(!INTERNAL_STATE.is_poisoned());
+        assert!(INTERNAL_STATE.lock().unwrap().callback.is_some());
+
+        // replacing the callback from inside it keeps the replacement
+        let replaced = Arc::new(AtomicUsize::new(0));
+        let counter = replaced.clone();
+
+        install(Box::new(move |_, _, _, _| {
+            let counter = counter.clone();
+
+            install(Box::new(move |_, _, _, _| {
+                counter.fetch_add(1, Ordering::SeqCst);
+            }));
+        }));
+
+        trigger(gl::DEBUG_SEVERITY_HIGH);
+        trigger(gl::DEBUG_SEVERITY_HIGH);
+
+        assert_eq!(replaced.load(Ordering::SeqCst), 1);
+
+        INTERNAL_STATE.lock().unwrap().callback = None;
+    }
+}
@@ -0,0 +1,188 @@
+use crate::builtin::{self, ColorVertex};
+use crate::color::Color;
+use crate::math;
+use crate::shader::Shader;
+use crate::text::{Font, TextRenderer};
+use crate::vbo::{BufferMode, PrimitiveKind, Vertex, VBO};
+
+use std::cell::RefCell;
+use std::f32::consts::PI;
+use vex::{Matrix4, Vector3};
+
+const MAX_VERTICES: usize = 65536;
+const MAX_MARKER_GLYPHS: usize = 4096;
+
+struct Marker {
+    pos: [f32; 3],
+    text: String,
+    color: Color,
+}
+
+struct State {
+    shader: Shader,
+    vbo: VBO,
+    vertices: Vec<ColorVertex>,
+    markers: Vec<Marker>,
+    font: Option<Font>,
+    text: Option<TextRenderer>,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            shader: builtin::build_color_shader(),
+            vbo: VBO::new(
+                BufferMode::StreamDraw,
+                PrimitiveKind::Lines,
+                &vec![<ColorVertex as Vertex>::new(); MAX_VERTICES],
+                None,
+            ),
+            vertices: Vec::new(),
+            markers: Vec::new(),
+            font: None,
+            text: None,
+        }
+    }
+}
+
+thread_local! {
+    // the VBO and font texture belong to the GL thread, so the queue is per thread
+    static INTERNAL_STATE: RefCell<Option<State>> = RefCell::new(None);
+}
+
+fn with_state<F: FnOnce(&mut State)>(f: F) {
+    INTERNAL_STATE.with(|cell| {
+        let mut slot = cell.borrow_mut();
+
+        f(slot.get_or_insert_with(State::new));
+    });
+}
+
+// drops this thread's GL objects while the context is still current, for context::shutdown
+#[cfg(feature = "headless")]
+pub(crate) fn reset() {
+    let state = INTERNAL_STATE.with(|cell| cell.borrow_mut().take());
+
+    drop(state);
+}
+
+fn push_line(st: &mut State, from: [f32; 3], to: [f32; 3], color: Color) {
+    st.vertices.push(ColorVertex::from_parts(math::to_vector(from), color));
+    st.vertices.push(ColorVertex::from_parts(math::to_vector(to), color));
+}
+
+pub fn set_font(font: Font) {
+    with_state(|st| {
+        st.font = Some(font);
+
+        if st.text.is_none() {
+            st.text = Some(TextRenderer::new(MAX_MARKER_GLYPHS));
+        }
+    });
+}
+
+pub fn line(from: &Vector3, to: &Vector3, color: Color) {
+    with_state(|st| push_line(st, math::to_array(from), math::to_array(to), color));
+}
+
+pub fn wire_box(min: &Vector3, max: &Vector3, color: Color) {
+    let (a, b) = (math::to_array(min), math::to_array(max));
+    let corner = |i: usize| {
+        [
+            if i & 1 == 0 { a[0] } else { b[0] },
+            if i & 2 == 0 { a[1] } else { b[1] },
+            if i & 4 == 0 { a[2] } else { b[2] },
+        ]
+    };
+
+    with_state(|st| {
+        // connect every pair of corners that differ along exactly one axis
+        for i in 0..8 {
+            for bit in [1, 2, 4].iter() {
+                if i & bit == 0 {
+                    push_line(st, corner(i), corner(i | bit), color);
+                }
+            }
+        }
+    });
+}
+
+pub fn circle(center: &Vector3, radius: f32, color: Color) {
+    let c = math::to_array(center);
+    let segments = 32;
+    let point = |i: usize| {
+        let (sin, cos) = (2.0 * PI * i as f32 / segments as f32).sin_cos();
+
+        [c[0] + cos * radius, c[1] + sin * radius, c[2]]
+    };
+
+    with_state(|st| {
+        for i in 0..segments {
+            push_line(st, point(i), point(i + 1), color);
+        }
+    });
+}
+
+pub fn axes(transform: &Matrix4, size: f32) {
+    let m = transform.m;
+    let origin = [m[12], m[13], m[14]];
+    let axis = |col: usize| math::add(origin, math::scale(math::normalize([m[col * 4], m[col * 4 + 1], m[col * 4 + 2]]), size));
+
+    with_state(|st| {
+        push_line(st, origin, axis(0), Color::make(255, 0, 0, 255));
+        push_line(st, origin, axis(1), Color::make(0, 255, 0, 255));
+        push_line(st, origin, axis(2), Color::make(0, 0, 255, 255));
+    });
+}
+
+pub fn marker(pos: &Vector3, text: &str, color: Color) {
+    with_state(|st| {
+        st.markers.push(Marker {
+            pos: math::to_array(pos),
+            text: text.to_string(),
+            color,
+        });
+    });
+}
+
+pub fn clear() {
+    with_state(|st| {
+        st.vertices.clear();
+        st.markers.clear();
+    });
+}
+
+pub fn flush(view_projection: &Matrix4, screen_width: f32, screen_height: f32) {
+    with_state(|st| {
+        if !st.vertices.is_empty() {
+            let identity = math::identity();
+
+            st.shader.set_mvp(&identity, &identity, view_projection);
+
+            for chunk in st.vertices.chunks(MAX_VERTICES) {
+                st.vbo.write_vertices(chunk, 0).unwrap();
+                st.vbo.draw(0, chunk.len());
+            }
+        }
+
+        if let (Some(font), Some(text)) = (st.font.as_ref(), st.text.as_mut()) {
+            text.set_screen_projection(screen_width, screen_height);
+
+            for marker in st.markers.iter() {
+                let clip = math::transform(view_projection, marker.pos);
+
+                if clip[3] <= 0.0 {
+                    continue;
+                }
+
+                let x = (clip[0] / clip[3] * 0.5 + 0.5) * screen_width;
+                let y = (0.5 - clip[1] / clip[3] * 0.5) * screen_height;
+
+                text.draw(font, &marker.text, x, y, marker.color, None);
+            }
+        }
+
+        st.vertices.clear();
+        st.markers.clear();
+    });
+}
@@ -0,0 +1,349 @@
+use crate::builtin::{SRC_LIT_VERTEX, TEXTURE_WHITE};
+use crate::color::Color;
+use crate::context::{self, Feature, StateSnapshot};
+use crate::error::{Error, Result};
+use crate::fbo::{self, Framebuffer};
+use crate::math;
+use crate::post::{self, SRC_POST_VERTEX};
+use crate::shader::{Shader, Stage, StageKind};
+use crate::texture::{self, MagFilter, MinFilter, Texture, TextureFormat};
+use crate::vbo::VBO;
+
+use vex::{Matrix4, Vector3, Vector4};
+
+// must match the array size in SRC_LIGHTING_FRAGMENT
+pub const MAX_POINT_LIGHTS: usize = 32;
+
+// writes the G-buffer from NormalVertex geometry: albedo with the specular strength in alpha, and the
+// world-space normal; positions are rebuilt from depth in the lighting pass
+const SRC_GEOMETRY_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_tex;
+    uniform vec4 u_color;
+    uniform float u_specular;
+
+    in vec3 v_world_pos;
+    in vec3 v_normal;
+    in vec2 v_uv;
+
+    layout (location = 0) out vec4 out_albedo;
+    layout (location = 1) out vec4 out_normal;
+
+    void main() {
+        vec4 albedo = texture(u_tex, v_uv) * u_color;
+
+        // nothing blends into a G-buffer, so cutouts are alpha tested instead
+        if (albedo.a < 0.5) {
+            discard;
+        }
+
+        out_albedo = vec4(albedo.rgb, u_specular);
+        out_normal = vec4(normalize(v_normal), 0.0);
+    }
+"#;
+
+// the directional light uses the same uniforms and Blinn-Phong terms as SHADER_LIT
+const SRC_LIGHTING_FRAGMENT: &str = r#"
+    #version 330 core
+
+    const int MAX_POINT_LIGHTS = 32;
+
+    uniform sampler2D u_albedo;
+    uniform sampler2D u_normal;
+    uniform sampler2D u_depth;
+    uniform mat4 u_inverse_view_projection;
+    uniform vec3 u_camera_pos;
+    uniform vec3 u_light_dir;
+    uniform vec3 u_light_color;
+    uniform vec3 u_ambient;
+    uniform float u_shininess;
+    uniform int u_point_light_count;
+    uniform vec3 u_point_light_pos[MAX_POINT_LIGHTS];
+    uniform vec3 u_point_light_color[MAX_POINT_LIGHTS];
+    uniform float u_point_light_radius[MAX_POINT_LIGHTS];
+
+    in vec2 v_uv;
+
+    out vec4 out_color;
+
+    vec3 shade(vec3 n, vec3 v, vec3 l, vec3 color, vec4 albedo) {
+        vec3 h = normalize(l + v);
+        float diffuse = max(dot(n, l), 0.0);
+        float specular = diffuse > 0.0 ? pow(max(dot(n, h), 0.0), u_shininess) * albedo.a : 0.0;
+
+        return color * (albedo.rgb * diffuse + specular);
+    }
+
+    void main() {
+        float depth = texture(u_depth, v_uv).r;
+
+        // no geometry here, so whatever the target was cleared to shows through
+        if (depth >= 1.0) {
+            discard;
+        }
+
+        vec4 world = u_inverse_view_projection * vec4(v_uv * 2.0 - 1.0, depth * 2.0 - 1.0, 1.0);
+        vec3 pos = world.xyz / world.w;
+        vec4 albedo = texture(u_albedo, v_uv);
+        vec3 n = normalize(texture(u_normal, v_uv).xyz);
+        vec3 v = normalize(u_camera_pos - pos);
+        vec3 lit = albedo.rgb * u_ambient + shade(n, v, normalize(-u_light_dir), u_light_color, albedo);
+
+        for (int i = 0; i < u_point_light_count; i++) {
+            vec3 to_light = u_point_light_pos[i] - pos;
+            float distance = length(to_light);
+            float falloff = clamp(1.0 - distance / u_point_light_radius[i], 0.0, 1.0);
+
+            lit += shade(n, v, to_light / max(distance, 0.0001), u_point_light_color[i], albedo) * falloff * falloff;
+        }
+
+        out_color = vec4(lit, 1.0);
+    }
+"#;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PointLight {
+    pub position: Vector3,
+    pub color: Vector3,
+    // the light fades out completely at this distance
+    pub radius: f32,
+}
+
+impl PointLight {
+    pub fn new(position: Vector3, color: Vector3, radius: f32) -> PointLight {
+        PointLight { position, color, radius }
+    }
+}
+
+fn gbuffer_texture(format: TextureFormat, width: usize, height: usize) -> Result<Texture> {
+    let mut result = Texture::new_with_format(format, width, height);
+
+    result.set_min_filter(MinFilter::Nearest)?;
+    result.set_mag_filter(MagFilter::Nearest)?;
+
+    Ok(result)
+}
+
+// attachment 0 is albedo (RGBA8), 1 the normal (RGBA16F), plus a sampleable 24-bit depth texture
+fn build_gbuffer(width: usize, height: usize) -> Result<Framebuffer> {
+    let colors = vec![
+        gbuffer_texture(TextureFormat::Rgba8, width, height)?,
+        gbuffer_texture(TextureFormat::Rgba16F, width, height)?,
+    ];
+
+    Framebuffer::with_depth_texture(colors, gbuffer_texture(TextureFormat::Depth24, width, height)?)
+}
+
+// draws opaque NormalVertex geometry into a G-buffer, then lights every covered pixel once with a
+// directional light and up to MAX_POINT_LIGHTS point lights
+pub struct DeferredPipeline {
+    gbuffer: Framebuffer,
+    geometry_shader: Shader,
+    lighting_shader: Shader,
+    triangle: VBO,
+}
+
+impl DeferredPipeline {
+    pub fn new(width: usize, height: usize) -> Result<DeferredPipeline> {
+        context::check_initialized()?;
+
+        let gbuffer = build_gbuffer(width, height)?;
+        let geometry_shader = Shader::new(&vec![
+            Stage::with_name(StageKind::Vertex, "deferred geometry vertex", SRC_LIT_VERTEX)?,
+            Stage::with_name(StageKind::Fragment, "deferred geometry fragment", SRC_GEOMETRY_FRAGMENT)?,
+        ])?;
+
+        let lighting_shader = Shader::new(&vec![
+            Stage::with_name(StageKind::Vertex, "deferred lighting vertex", SRC_POST_VERTEX)?,
+            Stage::with_name(StageKind::Fragment, "deferred lighting fragment", SRC_LIGHTING_FRAGMENT)?,
+        ])?;
+
+        geometry_shader.set_uniform_mat4("u_model", &math::identity());
+        geometry_shader.set_uniform_i32("u_tex", 0);
+        geometry_shader.set_uniform_vec4("u_color", &Vector4::make(1.0, 1.0, 1.0, 1.0));
+        geometry_shader.set_uniform_f32("u_specular", 0.5);
+
+        lighting_shader.set_uniform_i32("u_albedo", 0);
+        lighting_shader.set_uniform_i32("u_normal", 1);
+        lighting_shader.set_uniform_i32("u_depth", 2);
+
+        let result = DeferredPipeline {
+            gbuffer,
+            geometry_shader,
+            lighting_shader,
+            triangle: post::fullscreen_triangle(),
+        };
+
+        // the same defaults as SHADER_LIT
+        result.set_directional_light(&Vector3::make(0.0, -0.7071, -0.7071), &Vector3::make(1.0, 1.0, 1.0));
+        result.set_ambient(&Vector3::make(0.1, 0.1, 0.1));
+        result.set_shininess(32.0);
+
+        Ok(result)
+    }
+
+    // rebuilds the G-buffer when the size changes, e.g. after context::notify_resize
+    pub fn resize(&mut self, width: usize, height: usize) -> Result<()> {
+        if self.gbuffer.width() != width || self.gbuffer.height() != height {
+            self.gbuffer = build_gbuffer(width, height)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_directional_light(&self, direction: &Vector3, color: &Vector3) {
+        self.lighting_shader.set_uniform_vec3("u_light_dir", direction);
+        self.lighting_shader.set_uniform_vec3("u_light_color", color);
+    }
+
+    pub fn set_ambient(&self, ambient: &Vector3) {
+        self.lighting_shader.set_uniform_vec3("u_ambient", ambient);
+    }
+
+    pub fn set_shininess(&self, shininess: f32) {
+        self.lighting_shader.set_uniform_f32("u_shininess", shininess);
+    }
+
+    // replaces the whole set; an empty slice leaves only the directional light
+    pub fn set_point_lights(&self, lights: &[PointLight]) -> Result<()> {
+        if lights.len() > MAX_POINT_LIGHTS {
+            let msg = format!("{} point lights (the deferred pipeline allows up to {})", lights.len(), MAX_POINT_LIGHTS);
+
+            return Err(Error::Unsupported(msg));
+        }
+
+        let shader = &self.lighting_shader;
+
+        for (i, light) in lights.iter().enumerate() {
+            shader.set_uniform_vec3(&format!("u_point_light_pos[{}]", i), &light.position);
+            shader.set_uniform_vec3(&format!("u_point_light_color[{}]", i), &light.color);
+            shader.set_uniform_f32(&format!("u_point_light_radius[{}]", i), light.radius.max(0.0001));
+        }
+
+        shader.set_uniform_i32("u_point_light_count", lights.len() as i32);
+
+        Ok(())
+    }
+
+    // clears the G-buffer and draws into it until the pass is dropped; unit 0 starts out white
+    pub fn begin_geometry_pass(&self, view: &Matrix4, projection: &Matrix4) -> GeometryPass<'_> {
+        let snapshot = context::snapshot();
+
+        self.gbuffer.bind();
+        context::set_viewport(0, 0, self.gbuffer.width() as u32, self.gbuffer.height() as u32);
+        context::enable(Feature::DepthTest);
+        context::disable(Feature::Blend);
+        context::set_depth_mask(true);
+
+        self.gbuffer.clear_color_attachment(0, Color::TRANSPARENT);
+        self.gbuffer.clear_color_attachment(1, Color::TRANSPARENT);
+        self.gbuffer.clear_depth(1.0);
+
+        texture::bind_handle(0, TEXTURE_WHITE.handle());
+
+        self.geometry_shader.set_view(view);
+        self.geometry_shader.set_projection(projection);
+        self.geometry_shader.bind();
+
+        GeometryPass {
+            pipeline: self,
+            snapshot,
+        }
+    }
+
+    // pass the view and projection the geometry pass used; None lights into the default framebuffer
+    pub fn render_lighting(&self, view: &Matrix4, projection: &Matrix4, target: Option<&Framebuffer>) {
+        let snapshot = context::snapshot();
+        let view_projection = math::multiply(projection, view);
+        let inverse_view_projection = math::inverse(&view_projection).unwrap_or_else(math::identity);
+        let camera_pos = match math::inverse(view) {
+            Some(inverse_view) => Vector3::make(inverse_view.m[12], inverse_view.m[13], inverse_view.m[14]),
+            None => Vector3::new(),
+        };
+
+        let (width, height) = fbo::target_size(target);
+
+        fbo::bind_target(target);
+        context::set_viewport(0, 0, width as u32, height as u32);
+        context::disable(Feature::DepthTest);
+        context::disable(Feature::Blend);
+
+        texture::bind_handle(0, self.albedo().handle());
+        texture::bind_handle(1, self.normal().handle());
+        texture::bind_handle(2, self.depth().handle());
+
+        self.lighting_shader.set_uniform_mat4("u_inverse_view_projection", &inverse_view_projection);
+        self.lighting_shader.set_uniform_vec3("u_camera_pos", &camera_pos);
+        self.lighting_shader.bind();
+        self.triangle.render();
+
+        context::restore(&snapshot);
+    }
+
+    pub fn gbuffer(&self) -> &Framebuffer {
+        &self.gbuffer
+    }
+
+    pub fn albedo(&self) -> &Texture {
+        self.gbuffer.color(0).unwrap()
+    }
+
+    pub fn normal(&self) -> &Texture {
+        self.gbuffer.color(1).unwrap()
+    }
+
+    pub fn depth(&self) -> &Texture {
+        self.gbuffer.depth_texture().unwrap()
+    }
+
+    pub fn geometry_shader(&self) -> &Shader {
+        &self.geometry_shader
+    }
+
+    pub fn lighting_shader(&self) -> &Shader {
+        &self.lighting_shader
+    }
+
+    pub fn width(&self) -> usize {
+        self.gbuffer.width()
+    }
+
+    pub fn height(&self) -> usize {
+        self.gbuffer.height()
+    }
+}
+
+#[must_use]
+pub struct GeometryPass<'a> {
+    pipeline: &'a DeferredPipeline,
+    snapshot: StateSnapshot,
+}
+
+impl GeometryPass<'_> {
+    pub fn set_model(&self, model: &Matrix4) {
+        self.pipeline.geometry_shader.set_model(model);
+    }
+
+    // multiplies the albedo texture on unit 0
+    pub fn set_color(&self, color: Color) {
+        let [r, g, b, a] = color.to_array_f32();
+
+        self.pipeline.geometry_shader.set_uniform_vec4("u_color", &Vector4::make(r, g, b, a));
+    }
+
+    // 0.0 to 1.0, stored in the albedo's alpha
+    pub fn set_specular(&self, specular: f32) {
+        self.pipeline.geometry_shader.set_uniform_f32("u_specular", specular);
+    }
+
+    pub fn shader(&self) -> &Shader {
+        &self.pipeline.geometry_shader
+    }
+}
+
+impl Drop for GeometryPass<'_> {
+    fn drop(&mut self) {
+        context::restore(&self.snapshot);
+    }
+}
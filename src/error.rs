@@ -1,4 +1,6 @@
 use gl::types::*;
+use std::error;
+use std::fmt;
 use std::result;
 
 pub type Result<T> = result::Result<T, Error>;
@@ -6,13 +8,86 @@ pub type Result<T> = result::Result<T, Error>;
 #[derive(Debug)]
 pub enum Error {
     NoMipmaps,
+    Unsupported(String),
+    NotInitialized,
     AlreadyInitialized,
+    ContextCreationFailed(String),
     InvalidTextureDimensions,
+    ImageLoadFailed(String),
+    ImageSaveFailed(String),
+    TextureParameterMismatch(String),
+    StateCacheMismatch(String),
+    InvalidTextureParameter(String),
+    AtlasFull(String),
+    AtlasEntryExists(String),
+    InvalidFont(String),
+    InvalidColor(String),
     CompileShaderStageFailed(String),
+    InvalidStageCombination(String),
     LinkShaderProgramFailed(String),
+    PreprocessShaderFailed(String),
+    UnknownUniformBlock(String),
+    VertexLayoutMismatch(String),
+    InvalidVertexData(String),
+    InvalidIndexData(String),
+    IncompleteFramebuffer(String),
+    InvalidAttachment(String),
+    InvalidHandle(String),
+    ModelLoadFailed(String),
+    Gl(GlError),
 }
 
-#[derive(Debug)]
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::NoMipmaps => write!(f, "texture has no mipmaps"),
+            Error::Unsupported(msg) => write!(f, "unsupported: {}", msg),
+            Error::NotInitialized => write!(f, "gl_toolkit is not initialized on this thread"),
+            Error::AlreadyInitialized => write!(f, "gl_toolkit is already initialized"),
+            Error::ContextCreationFailed(msg) => write!(f, "failed to create GL context: {}", msg),
+            Error::InvalidTextureDimensions => write!(f, "invalid texture dimensions"),
+            Error::ImageLoadFailed(msg) => write!(f, "failed to load image: {}", msg),
+            Error::ImageSaveFailed(msg) => write!(f, "failed to save image: {}", msg),
+            Error::TextureParameterMismatch(msg) => write!(f, "texture parameter mismatch: {}", msg),
+            Error::StateCacheMismatch(msg) => write!(f, "GL state doesn't match the cache: {}", msg),
+            Error::InvalidTextureParameter(msg) => write!(f, "invalid texture parameter: {}", msg),
+            Error::AtlasFull(msg) => write!(f, "texture atlas is full: {}", msg),
+            Error::AtlasEntryExists(name) => write!(f, "texture atlas already contains \"{}\"", name),
+            Error::InvalidFont(msg) => write!(f, "invalid font: {}", msg),
+            Error::InvalidColor(msg) => write!(f, "invalid color: {}", msg),
+            Error::CompileShaderStageFailed(msg) => write!(f, "failed to compile shader stage: {}", msg),
+            Error::InvalidStageCombination(msg) => write!(f, "invalid shader stage combination: {}", msg),
+            Error::LinkShaderProgramFailed(msg) => write!(f, "failed to link shader program: {}", msg),
+            Error::PreprocessShaderFailed(msg) => write!(f, "failed to preprocess shader: {}", msg),
+            Error::UnknownUniformBlock(name) => write!(f, "unknown uniform block \"{}\"", name),
+            Error::VertexLayoutMismatch(msg) => write!(f, "vertex layout mismatch: {}", msg),
+            Error::InvalidVertexData(msg) => write!(f, "invalid vertex data: {}", msg),
+            Error::InvalidIndexData(msg) => write!(f, "invalid index data: {}", msg),
+            Error::IncompleteFramebuffer(msg) => write!(f, "incomplete framebuffer: {}", msg),
+            Error::InvalidAttachment(msg) => write!(f, "invalid framebuffer attachment: {}", msg),
+            Error::InvalidHandle(msg) => write!(f, "invalid GL handle: {}", msg),
+            Error::ModelLoadFailed(msg) => write!(f, "failed to load model: {}", msg),
+            Error::Gl(err) => write!(f, "GL error: {}", err),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Gl(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<GlError> for Error {
+    fn from(err: GlError) -> Error {
+        Error::Gl(err)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum GlError {
     None,
     InvalidEnum,
@@ -55,8 +130,33 @@ impl GlError {
     }
 }
 
+impl fmt::Display for GlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GlError::None => write!(f, "no error"),
+            GlError::InvalidEnum => write!(f, "invalid enum"),
+            GlError::InvalidValue => write!(f, "invalid value"),
+            GlError::InvalidOperation => write!(f, "invalid operation"),
+            GlError::InvalidFramebufferOperation => write!(f, "invalid framebuffer operation"),
+            GlError::OutOfMemory => write!(f, "out of memory"),
+            GlError::StackUnderflow => write!(f, "stack underflow"),
+            GlError::StackOverflow => write!(f, "stack overflow"),
+            GlError::Unknown(n) => write!(f, "unknown error 0x{:X}", n),
+        }
+    }
+}
+
+impl error::Error for GlError {}
+
 pub fn get_error() -> GlError {
     let raw = unsafe { gl::GetError() };
 
     GlError::new(raw)
 }
+
+pub fn check_error() -> Result<()> {
+    match get_error() {
+        GlError::None => Ok(()),
+        err => Err(err.into()),
+    }
+}
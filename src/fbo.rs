@@ -0,0 +1,665 @@
+use crate::color::Color;
+use crate::context::{self, Garbage};
+use crate::debug;
+use crate::error::{Error, Result};
+use crate::multi_context;
+use crate::renderbuffer::Renderbuffer;
+use crate::texture::{MagFilter, Texture, TextureFormat};
+
+use gl::types::*;
+use std::fmt;
+use std::marker::PhantomData;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DepthAttachment {
+    None,
+    Depth,
+    DepthStencil,
+}
+
+impl DepthAttachment {
+    fn format(&self) -> Option<TextureFormat> {
+        match self {
+            DepthAttachment::None => None,
+            DepthAttachment::Depth => Some(TextureFormat::Depth24),
+            DepthAttachment::DepthStencil => Some(TextureFormat::Depth24Stencil8),
+        }
+    }
+
+    fn from_format(format: TextureFormat) -> DepthAttachment {
+        match format {
+            TextureFormat::Depth24Stencil8 => DepthAttachment::DepthStencil,
+            TextureFormat::Depth24 | TextureFormat::Depth32F => DepthAttachment::Depth,
+            _ => DepthAttachment::None,
+        }
+    }
+}
+
+fn status_name(status: GLenum) -> String {
+    let name = match status {
+        gl::FRAMEBUFFER_UNDEFINED => "FRAMEBUFFER_UNDEFINED",
+        gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "FRAMEBUFFER_INCOMPLETE_ATTACHMENT",
+        gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT",
+        gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER",
+        gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "FRAMEBUFFER_INCOMPLETE_READ_BUFFER",
+        gl::FRAMEBUFFER_UNSUPPORTED => "FRAMEBUFFER_UNSUPPORTED",
+        gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "FRAMEBUFFER_INCOMPLETE_MULTISAMPLE",
+        gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS",
+        _ => return format!("0x{:04X}", status),
+    };
+
+    name.to_string()
+}
+
+fn attach_depth(depth: DepthAttachment, width: usize, height: usize, samples: usize) -> Result<Option<Renderbuffer>> {
+    match depth.format() {
+        Some(format) => {
+            let buffer = Renderbuffer::new_multisampled(format, width, height, samples)?;
+
+            buffer.attach(buffer.attachment());
+            Ok(Some(buffer))
+        },
+        None => Ok(None),
+    }
+}
+
+fn check_color_count(count: usize) -> Result<()> {
+    let max = context::capabilities().max_color_attachments as usize;
+
+    if count > max {
+        return Err(Error::Unsupported(format!("{} color attachments (the driver allows up to {})", count, max)));
+    }
+
+    Ok(())
+}
+
+// the first max_draw_buffers attachments, so every output of an MRT shader lands somewhere by default
+fn default_draw_buffers(count: usize) -> Vec<usize> {
+    (0..count.min(context::capabilities().max_draw_buffers as usize)).collect()
+}
+
+// applies to whichever framebuffer is bound as the draw framebuffer
+fn apply_draw_buffers(attachments: &[usize]) {
+    let buffers = attachments
+        .iter()
+        .map(|attachment| gl::COLOR_ATTACHMENT0 + *attachment as GLenum)
+        .collect::<Vec<GLenum>>();
+
+    unsafe {
+        if buffers.is_empty() {
+            gl::DrawBuffer(gl::NONE);
+        } else {
+            gl::DrawBuffers(buffers.len() as GLsizei, buffers.as_ptr());
+        }
+    }
+}
+
+pub fn max_samples() -> usize {
+    context::assert_initialized();
+
+    let mut count = 0;
+
+    unsafe { gl::GetIntegerv(gl::MAX_SAMPLES, &mut count) };
+
+    count.max(0) as usize
+}
+
+#[must_use]
+pub struct FramebufferBinding<'a> {
+    previous: GLuint,
+    _framebuffer: PhantomData<&'a Framebuffer>,
+}
+
+impl Drop for FramebufferBinding<'_> {
+    fn drop(&mut self) {
+        context::bind_framebuffer(self.previous);
+    }
+}
+
+// the window's own framebuffer, sized by context::notify_resize; binding it is binding "no" Framebuffer
+#[derive(Debug, Copy, Clone)]
+pub struct DefaultFramebuffer {
+    _thread: context::ThreadMarker,
+}
+
+impl DefaultFramebuffer {
+    pub(crate) fn new() -> DefaultFramebuffer {
+        DefaultFramebuffer { _thread: PhantomData }
+    }
+
+    pub fn bind(&self) {
+        bind_target(None);
+    }
+
+    pub fn bind_scoped(&self) -> FramebufferBinding<'static> {
+        bind_target_scoped(None)
+    }
+
+    // None leaves that buffer alone; like glClear this honors the color/depth/stencil masks and scissor
+    pub fn clear(&self, color: Option<Color>, depth: Option<f32>, stencil: Option<i32>) {
+        let _binding = self.bind_scoped();
+
+        unsafe {
+            if let Some(color) = color {
+                gl::ClearBufferfv(gl::COLOR, 0, color.to_array_f32().as_ptr());
+            }
+
+            match (depth, stencil) {
+                (Some(depth), Some(stencil)) => gl::ClearBufferfi(gl::DEPTH_STENCIL, 0, depth, stencil),
+                (Some(depth), None) => gl::ClearBufferfv(gl::DEPTH, 0, &depth),
+                (None, Some(stencil)) => gl::ClearBufferiv(gl::STENCIL, 0, &stencil),
+                (None, None) => {},
+            }
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        context::drawable_size().0 as usize
+    }
+
+    pub fn height(&self) -> usize {
+        context::drawable_size().1 as usize
+    }
+}
+
+// None is the default framebuffer, so code can render to a target without caring which kind it is
+pub fn bind_target(target: Option<&Framebuffer>) {
+    context::assert_initialized();
+    context::bind_framebuffer(target.map_or(0, |framebuffer| framebuffer.handle));
+}
+
+pub fn bind_target_scoped(target: Option<&Framebuffer>) -> FramebufferBinding<'_> {
+    context::assert_initialized();
+
+    let previous = context::bound_framebuffer();
+
+    bind_target(target);
+
+    FramebufferBinding {
+        previous,
+        _framebuffer: PhantomData,
+    }
+}
+
+pub fn target_size(target: Option<&Framebuffer>) -> (usize, usize) {
+    match target {
+        Some(framebuffer) => (framebuffer.width, framebuffer.height),
+        None => {
+            let (width, height) = context::drawable_size();
+
+            (width as usize, height as usize)
+        },
+    }
+}
+
+pub struct Framebuffer {
+    handle: GLuint,
+    depth_buffer: Option<Renderbuffer>,
+    depth_texture: Option<Texture>,
+    depth: DepthAttachment,
+    colors: Vec<Texture>,
+    color_buffers: Vec<Renderbuffer>,
+    // attachment indices in fragment output order
+    draw_buffers: Vec<usize>,
+    samples: usize,
+    width: usize,
+    height: usize,
+    label: Option<String>,
+    // framebuffers aren't shared between contexts, so this is deleted in the one that made it
+    owner: Option<u64>,
+    _thread: context::ThreadMarker,
+}
+
+impl fmt::Debug for Framebuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Framebuffer")
+            .field("handle", &self.handle)
+            .field("label", &self.label)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("samples", &self.samples)
+            .finish()
+    }
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize, color_count: usize, depth: DepthAttachment) -> Result<Framebuffer> {
+        context::check_initialized()?;
+
+        let colors = (0..color_count).map(|_| Texture::new(width, height)).collect();
+
+        Framebuffer::from_textures(colors, width, height, depth)
+    }
+
+    // pair with context::set_srgb_conversion(true) so linear shader output is stored gamma-encoded
+    pub fn new_srgb(width: usize, height: usize, color_count: usize, depth: DepthAttachment) -> Result<Framebuffer> {
+        context::check_initialized()?;
+
+        let colors = (0..color_count)
+            .map(|_| Texture::new_with_format(TextureFormat::Srgb8Alpha8, width, height))
+            .collect();
+
+        Framebuffer::from_textures(colors, width, height, depth)
+    }
+
+    // half-float color attachments for values past 1.0, e.g. HDR scenes resolved by a tonemap pass
+    pub fn new_hdr(width: usize, height: usize, color_count: usize, depth: DepthAttachment) -> Result<Framebuffer> {
+        context::check_initialized()?;
+
+        let colors = (0..color_count)
+            .map(|_| Texture::new_with_format(TextureFormat::Rgba16F, width, height))
+            .collect();
+
+        Framebuffer::from_textures(colors, width, height, depth)
+    }
+
+    pub fn from_textures(colors: Vec<Texture>, width: usize, height: usize, depth: DepthAttachment) -> Result<Framebuffer> {
+        context::check_initialized()?;
+
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        let depth_buffer = match depth.format() {
+            Some(format) => Some(Renderbuffer::new(format, width, height)?),
+            None => None,
+        };
+
+        Framebuffer::attach_textures(colors, width, height, depth_buffer)
+    }
+
+    // the renderbuffer must hold a depth format and sets the framebuffer's size
+    pub fn with_depth_buffer(colors: Vec<Texture>, depth_buffer: Renderbuffer) -> Result<Framebuffer> {
+        context::check_initialized()?;
+
+        if !depth_buffer.format().is_depth() {
+            let msg = format!("{:?} isn't a depth format", depth_buffer.format());
+
+            return Err(Error::TextureParameterMismatch(msg));
+        }
+
+        if depth_buffer.is_multisampled() {
+            let msg = "multisampled depth buffers need multisampled color attachments".to_string();
+
+            return Err(Error::TextureParameterMismatch(msg));
+        }
+
+        let width = depth_buffer.width();
+        let height = depth_buffer.height();
+
+        Framebuffer::attach_textures(colors, width, height, Some(depth_buffer))
+    }
+
+    // a sampleable depth attachment, e.g. for shadow maps; colors may be empty
+    pub fn with_depth_texture(colors: Vec<Texture>, depth_texture: Texture) -> Result<Framebuffer> {
+        context::check_initialized()?;
+
+        let format = depth_texture.format();
+
+        if !format.is_depth() {
+            return Err(Error::TextureParameterMismatch(format!("{:?} isn't a depth format", format)));
+        }
+
+        let width = depth_texture.width();
+        let height = depth_texture.height();
+
+        Framebuffer::attach_all(colors, width, height, None, Some(depth_texture))
+    }
+
+    fn attach_textures(colors: Vec<Texture>, width: usize, height: usize, depth_buffer: Option<Renderbuffer>) -> Result<Framebuffer> {
+        Framebuffer::attach_all(colors, width, height, depth_buffer, None)
+    }
+
+    fn attach_all(
+        colors: Vec<Texture>,
+        width: usize,
+        height: usize,
+        depth_buffer: Option<Renderbuffer>,
+        depth_texture: Option<Texture>,
+    ) -> Result<Framebuffer> {
+        if colors.iter().any(|color| color.width() != width || color.height() != height) {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        check_color_count(colors.len())?;
+
+        let draw_buffers = default_draw_buffers(colors.len());
+        let previous = context::bound_framebuffer();
+        let mut handle = 0;
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut handle);
+        }
+
+        context::bind_framebuffer(handle);
+
+        unsafe {
+            for (i, color) in colors.iter().enumerate() {
+                gl::FramebufferTexture2D(
+                    gl::FRAMEBUFFER,
+                    gl::COLOR_ATTACHMENT0 + i as GLenum,
+                    gl::TEXTURE_2D,
+                    color.handle(),
+                    0,
+                );
+            }
+
+            if colors.is_empty() {
+                gl::ReadBuffer(gl::NONE);
+            }
+        }
+
+        apply_draw_buffers(&draw_buffers);
+
+        let mut depth = DepthAttachment::None;
+
+        if let Some(buffer) = depth_buffer.as_ref() {
+            buffer.attach(buffer.attachment());
+            depth = DepthAttachment::from_format(buffer.format());
+        }
+
+        if let Some(texture) = depth_texture.as_ref() {
+            let attachment = if texture.format().has_stencil() { gl::DEPTH_STENCIL_ATTACHMENT } else { gl::DEPTH_ATTACHMENT };
+
+            unsafe { gl::FramebufferTexture2D(gl::FRAMEBUFFER, attachment, gl::TEXTURE_2D, texture.handle(), 0) };
+            depth = DepthAttachment::from_format(texture.format());
+        }
+
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+
+        context::bind_framebuffer(previous);
+
+        let result = Framebuffer {
+            handle,
+            depth,
+            depth_buffer,
+            depth_texture,
+            colors,
+            color_buffers: Vec::new(),
+            draw_buffers,
+            samples: 0,
+            width,
+            height,
+            label: None,
+            owner: multi_context::current_id(),
+            _thread: PhantomData,
+        };
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(Error::IncompleteFramebuffer(status_name(status)));
+        }
+
+        Ok(result)
+    }
+
+    pub fn new_multisampled(width: usize, height: usize, color_count: usize, depth: DepthAttachment, samples: usize) -> Result<Framebuffer> {
+        context::check_initialized()?;
+
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        let max = max_samples();
+
+        if samples > max {
+            return Err(Error::Unsupported(format!("{} samples (the driver allows up to {})", samples, max)));
+        }
+
+        check_color_count(color_count)?;
+
+        let color_buffers = (0..color_count)
+            .map(|_| Renderbuffer::new_multisampled(TextureFormat::Rgba8, width, height, samples))
+            .collect::<Result<Vec<Renderbuffer>>>()?;
+
+        let previous = context::bound_framebuffer();
+        let mut handle = 0;
+
+        unsafe {
+            gl::GenFramebuffers(1, &mut handle);
+        }
+
+        context::bind_framebuffer(handle);
+
+        for (i, buffer) in color_buffers.iter().enumerate() {
+            buffer.attach(gl::COLOR_ATTACHMENT0 + i as GLenum);
+        }
+
+        let draw_buffers = default_draw_buffers(color_count);
+
+        apply_draw_buffers(&draw_buffers);
+
+        if color_count == 0 {
+            unsafe { gl::ReadBuffer(gl::NONE) };
+        }
+
+        let depth_buffer = match attach_depth(depth, width, height, samples) {
+            Ok(buffer) => buffer,
+            Err(err) => {
+                context::bind_framebuffer(previous);
+                unsafe { gl::DeleteFramebuffers(1, &handle) };
+
+                return Err(err);
+            },
+        };
+
+        let status = unsafe { gl::CheckFramebufferStatus(gl::FRAMEBUFFER) };
+
+        context::bind_framebuffer(previous);
+
+        let result = Framebuffer {
+            handle,
+            depth_buffer,
+            depth_texture: None,
+            depth,
+            colors: Vec::new(),
+            color_buffers,
+            draw_buffers,
+            samples,
+            width,
+            height,
+            label: None,
+            owner: multi_context::current_id(),
+            _thread: PhantomData,
+        };
+
+        if status != gl::FRAMEBUFFER_COMPLETE {
+            return Err(Error::IncompleteFramebuffer(status_name(status)));
+        }
+
+        Ok(result)
+    }
+
+    pub fn blit_to(&self, target: &Framebuffer, filter: MagFilter) {
+        context::assert_initialized();
+
+        let previous = context::bound_framebuffer();
+        let attachments = self.color_count().min(target.color_count());
+        let src = (self.width as GLint, self.height as GLint);
+        let dst = (target.width as GLint, target.height as GLint);
+
+        unsafe {
+            gl::BindFramebuffer(gl::READ_FRAMEBUFFER, self.handle);
+            gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, target.handle);
+
+            for i in 0..attachments {
+                let attachment = gl::COLOR_ATTACHMENT0 + i as GLenum;
+
+                gl::ReadBuffer(attachment);
+                gl::DrawBuffer(attachment);
+                gl::BlitFramebuffer(0, 0, src.0, src.1, 0, 0, dst.0, dst.1, gl::COLOR_BUFFER_BIT, filter.get_native());
+            }
+
+            // depth and stencil can only be blitted unscaled with nearest filtering
+            let mut mask = 0;
+
+            if self.depth != DepthAttachment::None && target.depth != DepthAttachment::None {
+                mask |= gl::DEPTH_BUFFER_BIT;
+            }
+
+            if self.depth == DepthAttachment::DepthStencil && target.depth == DepthAttachment::DepthStencil {
+                mask |= gl::STENCIL_BUFFER_BIT;
+            }
+
+            if mask != 0 && src == dst {
+                gl::BlitFramebuffer(0, 0, src.0, src.1, 0, 0, dst.0, dst.1, mask, gl::NEAREST);
+            }
+
+            if attachments > 0 {
+                gl::ReadBuffer(gl::COLOR_ATTACHMENT0);
+                apply_draw_buffers(&target.draw_buffers);
+            }
+
+            // restores both targets, which keeps the context cache truthful
+            gl::BindFramebuffer(gl::FRAMEBUFFER, previous);
+        }
+    }
+
+    pub fn bind(&self) {
+        bind_target(Some(self));
+    }
+
+    pub fn bind_scoped(&self) -> FramebufferBinding<'_> {
+        bind_target_scoped(Some(self))
+    }
+
+    pub fn unbind(&self) {
+        bind_target(None);
+    }
+
+    // each attachment gets its own value, unlike context::clear which uses the one global clear color
+    pub fn clear_color_attachment(&self, index: usize, color: Color) {
+        assert!(index < self.color_count(), "color attachment {} out of range", index);
+
+        let _binding = self.bind_scoped();
+        let value = color.to_array_f32();
+
+        // glClearBuffer addresses draw buffers rather than attachments, so an attachment that isn't
+        // drawn to is routed through draw buffer 0 for the call
+        match self.draw_buffers.iter().position(|attachment| *attachment == index) {
+            Some(slot) => unsafe { gl::ClearBufferfv(gl::COLOR, slot as GLint, value.as_ptr()) },
+            None => {
+                unsafe {
+                    gl::DrawBuffer(gl::COLOR_ATTACHMENT0 + index as GLenum);
+                    gl::ClearBufferfv(gl::COLOR, 0, value.as_ptr());
+                }
+
+                apply_draw_buffers(&self.draw_buffers);
+            },
+        }
+    }
+
+    // output location i of the fragment shader writes to attachments[i]; new framebuffers draw to
+    // all of their attachments in order
+    pub fn set_draw_buffers(&mut self, attachments: &[usize]) -> Result<()> {
+        context::check_initialized()?;
+
+        let max = context::capabilities().max_draw_buffers as usize;
+
+        if attachments.len() > max {
+            return Err(Error::Unsupported(format!("{} draw buffers (the driver allows up to {})", attachments.len(), max)));
+        }
+
+        for (i, attachment) in attachments.iter().enumerate() {
+            if *attachment >= self.color_count() {
+                let msg = format!("color attachment {} out of range (the framebuffer has {})", attachment, self.color_count());
+
+                return Err(Error::InvalidAttachment(msg));
+            }
+
+            if attachments[..i].contains(attachment) {
+                return Err(Error::InvalidAttachment(format!("color attachment {} listed twice", attachment)));
+            }
+        }
+
+        {
+            let _binding = self.bind_scoped();
+
+            apply_draw_buffers(attachments);
+        }
+
+        self.draw_buffers = attachments.to_vec();
+
+        Ok(())
+    }
+
+    pub fn draw_buffers(&self) -> &[usize] {
+        &self.draw_buffers
+    }
+
+    // depth and stencil clears honor the depth and stencil write masks, like glClear
+    pub fn clear_depth(&self, depth: f32) {
+        let _binding = self.bind_scoped();
+
+        unsafe { gl::ClearBufferfv(gl::DEPTH, 0, &depth) };
+    }
+
+    pub fn clear_stencil(&self, stencil: i32) {
+        let _binding = self.bind_scoped();
+
+        unsafe { gl::ClearBufferiv(gl::STENCIL, 0, &stencil) };
+    }
+
+    pub fn clear_depth_stencil(&self, depth: f32, stencil: i32) {
+        let _binding = self.bind_scoped();
+
+        unsafe { gl::ClearBufferfi(gl::DEPTH_STENCIL, 0, depth, stencil) };
+    }
+
+    pub fn color(&self, index: usize) -> Option<&Texture> {
+        self.colors.get(index)
+    }
+
+    pub fn color_count(&self) -> usize {
+        self.colors.len().max(self.color_buffers.len())
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    pub fn is_multisampled(&self) -> bool {
+        self.samples > 0
+    }
+
+    pub fn depth(&self) -> DepthAttachment {
+        self.depth
+    }
+
+    pub fn depth_texture(&self) -> Option<&Texture> {
+        self.depth_texture.as_ref()
+    }
+
+    pub fn depth_buffer(&self) -> Option<&Renderbuffer> {
+        self.depth_buffer.as_ref()
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        debug::label_object(gl::FRAMEBUFFER, self.handle, label);
+        self.label = Some(label.to_string());
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.handle
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        context::forget_framebuffer(self.handle);
+        context::defer_delete_owned(self.owner, Garbage::Framebuffer(self.handle));
+
+        self.handle = 0;
+    }
+}
@@ -0,0 +1,401 @@
+use crate::builtin::TangentVertex;
+use crate::context::{self, Feature};
+use crate::error::{Error, Result};
+use crate::math;
+use crate::shader::Shader;
+use crate::texture::{ClampMode, MagFilter, MinFilter, Texture, TextureFormat, WrapCoord};
+use crate::vbo::{BufferMode, PrimitiveKind, VBO};
+
+use ::gltf::image::Format;
+use ::gltf::mesh::Mode;
+use ::gltf::texture::{self as gltf_texture, WrappingMode};
+use std::collections::HashMap;
+use std::path::Path;
+use vex::{Matrix4, Vector2, Vector3, Vector4};
+
+#[derive(Debug, Clone)]
+pub struct GltfMaterial {
+    pub name: Option<String>,
+    pub base_color_factor: [f32; 4],
+    pub metallic_factor: f32,
+    pub roughness_factor: f32,
+    // indices into GltfModel::textures
+    pub base_color_texture: Option<usize>,
+    pub normal_texture: Option<usize>,
+    pub double_sided: bool,
+}
+
+impl GltfMaterial {
+    // what the spec prescribes for primitives without a material
+    pub fn new() -> GltfMaterial {
+        GltfMaterial {
+            name: None,
+            base_color_factor: [1.0; 4],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            base_color_texture: None,
+            normal_texture: None,
+            double_sided: false,
+        }
+    }
+}
+
+pub struct GltfPrimitive {
+    pub vbo: VBO,
+    pub material: Option<usize>,
+}
+
+pub struct GltfMesh {
+    pub name: Option<String>,
+    pub primitives: Vec<GltfPrimitive>,
+}
+
+// one placement of a mesh in the scene, with the node hierarchy already folded into the transform
+#[derive(Debug, Copy, Clone)]
+pub struct GltfNode {
+    pub mesh: usize,
+    pub transform: Matrix4,
+}
+
+// static meshes only: skins, morph targets and animations are ignored
+pub struct GltfModel {
+    pub meshes: Vec<GltfMesh>,
+    pub materials: Vec<GltfMaterial>,
+    pub textures: Vec<Texture>,
+    pub nodes: Vec<GltfNode>,
+}
+
+fn load_error(err: ::gltf::Error) -> Error {
+    Error::ModelLoadFailed(err.to_string())
+}
+
+fn primitive_kind(mode: Mode) -> PrimitiveKind {
+    match mode {
+        Mode::Points => PrimitiveKind::Points,
+        Mode::Lines => PrimitiveKind::Lines,
+        Mode::LineLoop => PrimitiveKind::LineLoop,
+        Mode::LineStrip => PrimitiveKind::LineStrip,
+        Mode::Triangles => PrimitiveKind::Triangles,
+        Mode::TriangleStrip => PrimitiveKind::TriangleStrip,
+        Mode::TriangleFan => PrimitiveKind::TriangleFan,
+    }
+}
+
+fn clamp_mode(mode: WrappingMode) -> ClampMode {
+    match mode {
+        WrappingMode::ClampToEdge => ClampMode::Edge,
+        WrappingMode::MirroredRepeat => ClampMode::RepeatMirrored,
+        WrappingMode::Repeat => ClampMode::Repeat,
+    }
+}
+
+fn min_filter(filter: Option<gltf_texture::MinFilter>) -> MinFilter {
+    match filter {
+        Some(gltf_texture::MinFilter::Nearest) => MinFilter::Nearest,
+        Some(gltf_texture::MinFilter::Linear) => MinFilter::Linear,
+        Some(gltf_texture::MinFilter::NearestMipmapNearest) => MinFilter::NearestMipmapNearest,
+        Some(gltf_texture::MinFilter::LinearMipmapNearest) => MinFilter::LinearMipmapNearest,
+        Some(gltf_texture::MinFilter::NearestMipmapLinear) => MinFilter::NearestMipmapLinear,
+        Some(gltf_texture::MinFilter::LinearMipmapLinear) | None => MinFilter::LinearMipmapLinear,
+    }
+}
+
+fn mag_filter(filter: Option<gltf_texture::MagFilter>) -> MagFilter {
+    match filter {
+        Some(gltf_texture::MagFilter::Nearest) => MagFilter::Nearest,
+        _ => MagFilter::Linear,
+    }
+}
+
+fn rgba_pixels(image: &::gltf::image::Data) -> Result<Vec<u8>> {
+    let pixels = &image.pixels;
+
+    match image.format {
+        Format::R8G8B8A8 => Ok(pixels.clone()),
+        Format::R8G8B8 => Ok(pixels.chunks(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect()),
+        Format::R8G8 => Ok(pixels.chunks(2).flat_map(|p| [p[0], p[1], 0, 255]).collect()),
+        Format::R8 => Ok(pixels.iter().flat_map(|p| [*p, *p, *p, 255]).collect()),
+        format => Err(Error::ModelLoadFailed(format!("{:?} images aren't supported", format))),
+    }
+}
+
+// per-triangle tangents from the UV gradients, accumulated per vertex; for files that leave them out
+fn generate_tangents(vertices: &mut [TangentVertex], indices: &[u32]) {
+    let mut tangents = vec![[0.0f32; 3]; vertices.len()];
+
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+        let (pa, pb, pc) = (vertices[a].pos, vertices[b].pos, vertices[c].pos);
+        let (ua, ub, uc) = (vertices[a].uv, vertices[b].uv, vertices[c].uv);
+        let e1 = math::sub(math::to_array(&pb), math::to_array(&pa));
+        let e2 = math::sub(math::to_array(&pc), math::to_array(&pa));
+        let (du1, dv1) = (ub.x - ua.x, ub.y - ua.y);
+        let (du2, dv2) = (uc.x - ua.x, uc.y - ua.y);
+        let det = du1 * dv2 - du2 * dv1;
+
+        if det.abs() < f32::EPSILON {
+            continue;
+        }
+
+        let tangent = math::scale(math::sub(math::scale(e1, dv2), math::scale(e2, dv1)), 1.0 / det);
+
+        for i in [a, b, c].iter() {
+            tangents[*i] = math::add(tangents[*i], tangent);
+        }
+    }
+
+    for (vertex, tangent) in vertices.iter_mut().zip(tangents.iter()) {
+        let normal = vertex.normal;
+        let normal = math::to_array(&normal);
+        let orthogonal = math::sub(*tangent, math::scale(normal, math::dot(normal, *tangent)));
+
+        // any vector perpendicular to the normal keeps the basis valid when the UVs are degenerate
+        vertex.tangent = if math::length(orthogonal) > f32::EPSILON {
+            math::to_vector(math::normalize(orthogonal))
+        } else if normal[0].abs() < 0.9 {
+            math::to_vector(math::normalize(math::cross(normal, [1.0, 0.0, 0.0])))
+        } else {
+            math::to_vector(math::normalize(math::cross(normal, [0.0, 1.0, 0.0])))
+        };
+    }
+}
+
+fn flatten(matrix: [[f32; 4]; 4]) -> Matrix4 {
+    let mut m = [0.0; 16];
+
+    for (col, values) in matrix.iter().enumerate() {
+        m[col * 4..col * 4 + 4].copy_from_slice(values);
+    }
+
+    Matrix4 { m }
+}
+
+fn collect_nodes(node: ::gltf::Node, parent: &Matrix4, nodes: &mut Vec<GltfNode>) {
+    let transform = math::multiply(parent, &flatten(node.transform().matrix()));
+
+    if let Some(mesh) = node.mesh() {
+        nodes.push(GltfNode {
+            mesh: mesh.index(),
+            transform,
+        });
+    }
+
+    for child in node.children() {
+        collect_nodes(child, &transform, nodes);
+    }
+}
+
+struct TextureCache<'a> {
+    images: &'a [::gltf::image::Data],
+    textures: Vec<Texture>,
+    // keyed by glTF texture index and whether the texels hold color, which is sRGB-encoded
+    indices: HashMap<(usize, bool), usize>,
+}
+
+impl TextureCache<'_> {
+    fn get(&mut self, texture: gltf_texture::Texture, srgb: bool) -> Result<usize> {
+        if let Some(index) = self.indices.get(&(texture.index(), srgb)) {
+            return Ok(*index);
+        }
+
+        let image = &self.images[texture.source().index()];
+        let format = if srgb { TextureFormat::Srgb8Alpha8 } else { TextureFormat::Rgba8 };
+        let pixels = rgba_pixels(image)?;
+        let (width, height) = (image.width as usize, image.height as usize);
+        let mut result = Texture::make_with_format(format, &pixels, width, height, true)?;
+        let sampler = texture.sampler();
+
+        result.set_clamp(WrapCoord::S, clamp_mode(sampler.wrap_s()))?;
+        result.set_clamp(WrapCoord::T, clamp_mode(sampler.wrap_t()))?;
+        result.set_min_filter(min_filter(sampler.min_filter()))?;
+        result.set_mag_filter(mag_filter(sampler.mag_filter()))?;
+
+        self.textures.push(result);
+        self.indices.insert((texture.index(), srgb), self.textures.len() - 1);
+
+        Ok(self.textures.len() - 1)
+    }
+}
+
+impl GltfModel {
+    // accepts both .gltf (with external or embedded buffers) and binary .glb files
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<GltfModel> {
+        context::check_initialized()?;
+
+        let (document, buffers, images) = ::gltf::import(path).map_err(load_error)?;
+
+        GltfModel::build(&document, &buffers, &images)
+    }
+
+    // only buffers and images embedded in the data itself can be resolved
+    pub fn from_slice(bytes: &[u8]) -> Result<GltfModel> {
+        context::check_initialized()?;
+
+        let (document, buffers, images) = ::gltf::import_slice(bytes).map_err(load_error)?;
+
+        GltfModel::build(&document, &buffers, &images)
+    }
+
+    fn build(
+        document: &::gltf::Document,
+        buffers: &[::gltf::buffer::Data],
+        images: &[::gltf::image::Data],
+    ) -> Result<GltfModel> {
+        let mut cache = TextureCache {
+            images,
+            textures: Vec::new(),
+            indices: HashMap::new(),
+        };
+
+        let mut materials = Vec::new();
+
+        for material in document.materials() {
+            let pbr = material.pbr_metallic_roughness();
+            let base_color_texture = match pbr.base_color_texture() {
+                Some(info) => Some(cache.get(info.texture(), true)?),
+                None => None,
+            };
+
+            let normal_texture = match material.normal_texture() {
+                Some(normal) => Some(cache.get(normal.texture(), false)?),
+                None => None,
+            };
+
+            materials.push(GltfMaterial {
+                name: material.name().map(|name| name.to_string()),
+                base_color_factor: pbr.base_color_factor(),
+                metallic_factor: pbr.metallic_factor(),
+                roughness_factor: pbr.roughness_factor(),
+                base_color_texture,
+                normal_texture,
+                double_sided: material.double_sided(),
+            });
+        }
+
+        let mut meshes = Vec::new();
+
+        for mesh in document.meshes() {
+            let mut primitives = Vec::new();
+
+            for primitive in mesh.primitives() {
+                let reader = primitive.reader(|buffer| Some(&*buffers[buffer.index()]));
+                let positions = match reader.read_positions() {
+                    Some(positions) => positions.collect::<Vec<[f32; 3]>>(),
+                    None => continue,
+                };
+
+                let normals = reader.read_normals().map(|normals| normals.collect::<Vec<[f32; 3]>>());
+                let tangents = reader.read_tangents().map(|tangents| tangents.collect::<Vec<[f32; 4]>>());
+                let uvs = reader.read_tex_coords(0).map(|uvs| uvs.into_f32().collect::<Vec<[f32; 2]>>());
+                let indices = match reader.read_indices() {
+                    Some(indices) => indices.into_u32().collect::<Vec<u32>>(),
+                    None => (0..positions.len() as u32).collect(),
+                };
+
+                let mut vertices = positions
+                    .iter()
+                    .enumerate()
+                    .map(|(i, pos)| {
+                        let normal = normals.as_ref().map_or([0.0, 0.0, 1.0], |normals| normals[i]);
+                        let tangent = tangents.as_ref().map_or([1.0, 0.0, 0.0, 1.0], |tangents| tangents[i]);
+                        let uv = uvs.as_ref().map_or([0.0, 0.0], |uvs| uvs[i]);
+
+                        TangentVertex::from_parts(
+                            math::to_vector(*pos),
+                            math::to_vector(normal),
+                            Vector3::make(tangent[0], tangent[1], tangent[2]),
+                            Vector2::make(uv[0], uv[1]),
+                        )
+                    })
+                    .collect::<Vec<TangentVertex>>();
+
+                if tangents.is_none() && primitive.mode() == Mode::Triangles {
+                    generate_tangents(&mut vertices, &indices);
+                }
+
+                let kind = primitive_kind(primitive.mode());
+
+                primitives.push(GltfPrimitive {
+                    vbo: VBO::new_indexed(BufferMode::StaticDraw, kind, &vertices, &indices),
+                    material: primitive.material().index(),
+                });
+            }
+
+            meshes.push(GltfMesh {
+                name: mesh.name().map(|name| name.to_string()),
+                primitives,
+            });
+        }
+
+        let mut nodes = Vec::new();
+
+        match document.default_scene().or_else(|| document.scenes().next()) {
+            Some(scene) => {
+                for node in scene.nodes() {
+                    collect_nodes(node, &math::identity(), &mut nodes);
+                }
+            },
+            None => {
+                for mesh in 0..meshes.len() {
+                    nodes.push(GltfNode {
+                        mesh,
+                        transform: math::identity(),
+                    });
+                }
+            },
+        }
+
+        Ok(GltfModel {
+            meshes,
+            materials,
+            textures: cache.textures,
+            nodes,
+        })
+    }
+
+    fn apply_material(&self, shader: &Shader, material: &GltfMaterial) {
+        let [r, g, b, a] = material.base_color_factor;
+        let base_color_texture = material.base_color_texture.map(|index| &self.textures[index]);
+        let normal_texture = material.normal_texture.map(|index| &self.textures[index]);
+
+        shader.set_uniform_vec4("u_base_color", &Vector4::make(r, g, b, a));
+        shader.set_uniform_f32("u_metallic", material.metallic_factor);
+        shader.set_uniform_f32("u_roughness", material.roughness_factor);
+        shader.set_uniform_i32("u_has_tex", base_color_texture.is_some() as i32);
+        shader.set_uniform_i32("u_has_normal_map", normal_texture.is_some() as i32);
+
+        if let Some(texture) = base_color_texture {
+            texture.bind(0);
+        }
+
+        if let Some(texture) = normal_texture {
+            texture.bind(1);
+        }
+    }
+
+    // meant for SHADER_PBR, or any shader with its uniforms; view, projection and lighting are left to
+    // the caller, u_model and the material uniforms are set per draw
+    pub fn draw(&self, shader: &Shader) {
+        let default_material = GltfMaterial::new();
+        let culling = context::is_enabled(Feature::CullFace);
+
+        for node in self.nodes.iter() {
+            shader.set_model(&node.transform);
+
+            for primitive in self.meshes[node.mesh].primitives.iter() {
+                let material = primitive.material.map_or(&default_material, |index| &self.materials[index]);
+
+                if culling && material.double_sided {
+                    context::disable(Feature::CullFace);
+                }
+
+                self.apply_material(shader, material);
+                primitive.vbo.render();
+
+                if culling && material.double_sided {
+                    context::enable(Feature::CullFace);
+                }
+            }
+        }
+    }
+}
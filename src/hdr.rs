@@ -0,0 +1,103 @@
+use crate::context::{self, StateSnapshot};
+use crate::error::Result;
+use crate::fbo::{DepthAttachment, Framebuffer};
+use crate::post::{PostPass, PostProcess, TonemapOperator};
+use crate::texture::Texture;
+
+// renders the scene into a half-float framebuffer, then tonemaps it down to a displayable target
+pub struct HdrPipeline {
+    framebuffer: Framebuffer,
+    tonemap: PostPass,
+    post: PostProcess,
+    operator: TonemapOperator,
+    exposure: f32,
+}
+
+impl HdrPipeline {
+    pub fn new(width: usize, height: usize) -> Result<HdrPipeline> {
+        context::check_initialized()?;
+
+        Ok(HdrPipeline {
+            framebuffer: Framebuffer::new_hdr(width, height, 1, DepthAttachment::Depth)?,
+            tonemap: PostPass::tonemap(TonemapOperator::Aces, 1.0)?,
+            post: PostProcess::new(),
+            operator: TonemapOperator::Aces,
+            exposure: 1.0,
+        })
+    }
+
+    // rebuilds the framebuffer when the size changes, e.g. after context::notify_resize
+    pub fn resize(&mut self, width: usize, height: usize) -> Result<()> {
+        if self.framebuffer.width() != width || self.framebuffer.height() != height {
+            self.framebuffer = Framebuffer::new_hdr(width, height, 1, DepthAttachment::Depth)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn set_operator(&mut self, operator: TonemapOperator) {
+        self.operator = operator;
+        self.tonemap.set_tonemap(operator, self.exposure);
+    }
+
+    pub fn operator(&self) -> TonemapOperator {
+        self.operator
+    }
+
+    // a linear multiplier applied before tonemapping
+    pub fn set_exposure(&mut self, exposure: f32) {
+        self.exposure = exposure.max(0.0);
+        self.tonemap.set_tonemap(self.operator, self.exposure);
+    }
+
+    pub fn exposure(&self) -> f32 {
+        self.exposure
+    }
+
+    // 2.2 by default; use 1.0 when resolving into an sRGB target with sRGB conversion enabled
+    pub fn set_gamma(&self, gamma: f32) {
+        self.tonemap.shader().set_uniform_f32("u_gamma", gamma);
+    }
+
+    // binds the HDR framebuffer and its viewport until the pass is dropped; clearing is up to the caller
+    pub fn begin_scene(&self) -> HdrPass<'_> {
+        let snapshot = context::snapshot();
+
+        self.framebuffer.bind();
+        context::set_viewport(0, 0, self.framebuffer.width() as u32, self.framebuffer.height() as u32);
+
+        HdrPass {
+            _pipeline: self,
+            snapshot,
+        }
+    }
+
+    // None resolves into the default framebuffer using the current viewport
+    pub fn resolve(&self, target: Option<&Framebuffer>) {
+        self.post.apply(&self.tonemap, self.texture(), target);
+    }
+
+    pub fn framebuffer(&self) -> &Framebuffer {
+        &self.framebuffer
+    }
+
+    pub fn texture(&self) -> &Texture {
+        self.framebuffer.color(0).unwrap()
+    }
+
+    pub fn tonemap_pass(&self) -> &PostPass {
+        &self.tonemap
+    }
+}
+
+#[must_use]
+pub struct HdrPass<'a> {
+    _pipeline: &'a HdrPipeline,
+    snapshot: StateSnapshot,
+}
+
+impl Drop for HdrPass<'_> {
+    fn drop(&mut self) {
+        context::restore(&self.snapshot);
+    }
+}
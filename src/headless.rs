@@ -0,0 +1,386 @@
+use crate::context::{self, GlContext, ThreadMarker};
+use crate::error::{Error, Result};
+
+use lazy_static::lazy_static;
+use std::ffi::{CStr, CString};
+use std::marker::PhantomData;
+use std::os::raw::{c_char, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::sync::Mutex;
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+type EGLBoolean = u32;
+type EGLenum = u32;
+type EGLint = i32;
+type EGLDisplay = *mut c_void;
+type EGLConfig = *mut c_void;
+type EGLContext = *mut c_void;
+type EGLSurface = *mut c_void;
+type GetPlatformDisplayFn = unsafe extern "C" fn(EGLenum, *mut c_void, *const EGLint) -> EGLDisplay;
+
+const EGL_FALSE: EGLBoolean = 0;
+const EGL_NONE: EGLint = 0x3038;
+const EGL_EXTENSIONS: EGLint = 0x3055;
+const EGL_OPENGL_API: EGLenum = 0x30A2;
+const EGL_SURFACE_TYPE: EGLint = 0x3033;
+const EGL_PBUFFER_BIT: EGLint = 0x0001;
+const EGL_RENDERABLE_TYPE: EGLint = 0x3040;
+const EGL_OPENGL_BIT: EGLint = 0x0008;
+const EGL_RED_SIZE: EGLint = 0x3024;
+const EGL_GREEN_SIZE: EGLint = 0x3023;
+const EGL_BLUE_SIZE: EGLint = 0x3022;
+const EGL_ALPHA_SIZE: EGLint = 0x3021;
+const EGL_DEPTH_SIZE: EGLint = 0x3025;
+const EGL_STENCIL_SIZE: EGLint = 0x3026;
+const EGL_WIDTH: EGLint = 0x3057;
+const EGL_HEIGHT: EGLint = 0x3056;
+const EGL_CONTEXT_MAJOR_VERSION: EGLint = 0x3098;
+const EGL_CONTEXT_MINOR_VERSION: EGLint = 0x30FB;
+const EGL_CONTEXT_OPENGL_PROFILE_MASK: EGLint = 0x30FD;
+const EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT: EGLint = 0x0001;
+const EGL_PLATFORM_SURFACELESS_MESA: EGLenum = 0x31DD;
+
+// loaded at runtime rather than linked, so the crate still builds where there's no libEGL (macOS,
+// Windows) and HeadlessContext::new fails there instead
+struct Egl {
+    get_display: unsafe extern "C" fn(*mut c_void) -> EGLDisplay,
+    initialize: unsafe extern "C" fn(EGLDisplay, *mut EGLint, *mut EGLint) -> EGLBoolean,
+    terminate: unsafe extern "C" fn(EGLDisplay) -> EGLBoolean,
+    query_string: unsafe extern "C" fn(EGLDisplay, EGLint) -> *const c_char,
+    bind_api: unsafe extern "C" fn(EGLenum) -> EGLBoolean,
+    choose_config: unsafe extern "C" fn(EGLDisplay, *const EGLint, *mut EGLConfig, EGLint, *mut EGLint) -> EGLBoolean,
+    create_context: unsafe extern "C" fn(EGLDisplay, EGLConfig, EGLContext, *const EGLint) -> EGLContext,
+    destroy_context: unsafe extern "C" fn(EGLDisplay, EGLContext) -> EGLBoolean,
+    create_pbuffer_surface: unsafe extern "C" fn(EGLDisplay, EGLConfig, *const EGLint) -> EGLSurface,
+    destroy_surface: unsafe extern "C" fn(EGLDisplay, EGLSurface) -> EGLBoolean,
+    make_current: unsafe extern "C" fn(EGLDisplay, EGLSurface, EGLSurface, EGLContext) -> EGLBoolean,
+    get_proc_address: unsafe extern "C" fn(*const c_char) -> *const c_void,
+    get_error: unsafe extern "C" fn() -> EGLint,
+}
+
+#[cfg(unix)]
+mod dl {
+    use std::os::raw::{c_char, c_int, c_void};
+
+    pub const RTLD_NOW: c_int = 2;
+
+    extern "C" {
+        pub fn dlopen(filename: *const c_char, flags: c_int) -> *mut c_void;
+        pub fn dlsym(handle: *mut c_void, symbol: *const c_char) -> *mut c_void;
+    }
+}
+
+#[cfg(unix)]
+unsafe fn open_library() -> *mut c_void {
+    ["libEGL.so.1", "libEGL.so", "libEGL.dylib"]
+        .iter()
+        .map(|name| CString::new(*name).unwrap())
+        .map(|name| dl::dlopen(name.as_ptr(), dl::RTLD_NOW))
+        .find(|library| !library.is_null())
+        .unwrap_or(ptr::null_mut())
+}
+
+#[cfg(not(unix))]
+unsafe fn open_library() -> *mut c_void {
+    ptr::null_mut()
+}
+
+#[cfg(unix)]
+unsafe fn symbol(library: *mut c_void, name: &str) -> Option<*mut c_void> {
+    let name = CString::new(name).unwrap();
+    let address = dl::dlsym(library, name.as_ptr());
+
+    if address.is_null() {
+        None
+    } else {
+        Some(address)
+    }
+}
+
+#[cfg(not(unix))]
+unsafe fn symbol(_library: *mut c_void, _name: &str) -> Option<*mut c_void> {
+    None
+}
+
+// F is always one of the fn pointer types in Egl
+unsafe fn load_symbol<F>(library: *mut c_void, name: &str) -> Option<F> {
+    assert_eq!(std::mem::size_of::<F>(), std::mem::size_of::<*mut c_void>());
+
+    symbol(library, name).map(|address| std::mem::transmute_copy(&address))
+}
+
+impl Egl {
+    // the library is never closed, so the pointers stay valid for the life of the process
+    unsafe fn load() -> Option<Egl> {
+        let library = open_library();
+
+        if library.is_null() {
+            return None;
+        }
+
+        Some(Egl {
+            get_display: load_symbol(library, "eglGetDisplay")?,
+            initialize: load_symbol(library, "eglInitialize")?,
+            terminate: load_symbol(library, "eglTerminate")?,
+            query_string: load_symbol(library, "eglQueryString")?,
+            bind_api: load_symbol(library, "eglBindAPI")?,
+            choose_config: load_symbol(library, "eglChooseConfig")?,
+            create_context: load_symbol(library, "eglCreateContext")?,
+            destroy_context: load_symbol(library, "eglDestroyContext")?,
+            create_pbuffer_surface: load_symbol(library, "eglCreatePbufferSurface")?,
+            destroy_surface: load_symbol(library, "eglDestroySurface")?,
+            make_current: load_symbol(library, "eglMakeCurrent")?,
+            get_proc_address: load_symbol(library, "eglGetProcAddress")?,
+            get_error: load_symbol(library, "eglGetError")?,
+        })
+    }
+
+    fn error(&self, action: &str) -> Error {
+        let code = unsafe { (self.get_error)() };
+
+        Error::ContextCreationFailed(format!("{} failed (EGL error 0x{:X})", action, code))
+    }
+
+    fn proc_address(&self, name: &str) -> *const c_void {
+        let name = CString::new(name).unwrap();
+
+        unsafe { (self.get_proc_address)(name.as_ptr()) }
+    }
+
+    // prefers Mesa's surfaceless platform so no display server is needed at all
+    unsafe fn open_display(&self) -> EGLDisplay {
+        let extensions = (self.query_string)(ptr::null_mut(), EGL_EXTENSIONS);
+        let surfaceless = !extensions.is_null()
+            && CStr::from_ptr(extensions)
+                .to_string_lossy()
+                .split(' ')
+                .any(|name| name == "EGL_MESA_platform_surfaceless");
+
+        let get_platform_display = self.proc_address("eglGetPlatformDisplayEXT");
+
+        if surfaceless && !get_platform_display.is_null() {
+            let get_platform_display: GetPlatformDisplayFn = std::mem::transmute(get_platform_display);
+            let display = get_platform_display(EGL_PLATFORM_SURFACELESS_MESA, ptr::null_mut(), ptr::null());
+
+            if !display.is_null() {
+                return display;
+            }
+        }
+
+        (self.get_display)(ptr::null_mut())
+    }
+}
+
+lazy_static! {
+    static ref EGL: Option<Egl> = unsafe { Egl::load() };
+}
+
+pub struct HeadlessContext {
+    display: EGLDisplay,
+    surface: EGLSurface,
+    context: EGLContext,
+    egl: &'static Egl,
+    width: u32,
+    height: u32,
+    gl: Option<GlContext>,
+    _thread: ThreadMarker,
+}
+
+impl HeadlessContext {
+    // creates an offscreen 3.3 core context, makes it current, and initializes the crate on this thread
+    pub fn new(width: u32, height: u32) -> Result<HeadlessContext> {
+        if context::is_initialized() {
+            return Err(Error::AlreadyInitialized);
+        }
+
+        let egl = match EGL.as_ref() {
+            Some(egl) => egl,
+            None => return Err(Error::ContextCreationFailed("libEGL could not be loaded".to_string())),
+        };
+
+        unsafe {
+            let display = egl.open_display();
+
+            if display.is_null() || (egl.initialize)(display, ptr::null_mut(), ptr::null_mut()) == EGL_FALSE {
+                return Err(egl.error("eglInitialize"));
+            }
+
+            let mut result = HeadlessContext {
+                display,
+                surface: ptr::null_mut(),
+                context: ptr::null_mut(),
+                egl,
+                width,
+                height,
+                gl: None,
+                _thread: PhantomData,
+            };
+
+            if (egl.bind_api)(EGL_OPENGL_API) == EGL_FALSE {
+                return Err(egl.error("eglBindAPI"));
+            }
+
+            let config_attribs = [
+                EGL_SURFACE_TYPE, EGL_PBUFFER_BIT,
+                EGL_RENDERABLE_TYPE, EGL_OPENGL_BIT,
+                EGL_RED_SIZE, 8,
+                EGL_GREEN_SIZE, 8,
+                EGL_BLUE_SIZE, 8,
+                EGL_ALPHA_SIZE, 8,
+                EGL_DEPTH_SIZE, 24,
+                EGL_STENCIL_SIZE, 8,
+                EGL_NONE,
+            ];
+
+            let mut config = ptr::null_mut();
+            let mut count = 0;
+
+            if (egl.choose_config)(display, config_attribs.as_ptr(), &mut config, 1, &mut count) == EGL_FALSE || count == 0 {
+                return Err(egl.error("eglChooseConfig"));
+            }
+
+            let surface_attribs = [
+                EGL_WIDTH, width.max(1) as EGLint,
+                EGL_HEIGHT, height.max(1) as EGLint,
+                EGL_NONE,
+            ];
+
+            result.surface = (egl.create_pbuffer_surface)(display, config, surface_attribs.as_ptr());
+            if result.surface.is_null() {
+                return Err(egl.error("eglCreatePbufferSurface"));
+            }
+
+            let context_attribs = [
+                EGL_CONTEXT_MAJOR_VERSION, 3,
+                EGL_CONTEXT_MINOR_VERSION, 3,
+                EGL_CONTEXT_OPENGL_PROFILE_MASK, EGL_CONTEXT_OPENGL_CORE_PROFILE_BIT,
+                EGL_NONE,
+            ];
+
+            result.context = (egl.create_context)(display, config, ptr::null_mut(), context_attribs.as_ptr());
+            if result.context.is_null() {
+                return Err(egl.error("eglCreateContext"));
+            }
+
+            if (egl.make_current)(display, result.surface, result.surface, result.context) == EGL_FALSE {
+                return Err(egl.error("eglMakeCurrent"));
+            }
+
+            gl::load_with(|name| egl.proc_address(name));
+
+            result.gl = Some(crate::init()?);
+            context::set_viewport(0, 0, width, height);
+
+            Ok(result)
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn gl_context(&self) -> &GlContext {
+        self.gl.as_ref().unwrap()
+    }
+}
+
+impl Drop for HeadlessContext {
+    fn drop(&mut self) {
+        // while the context is still current, so its objects are deleted in it
+        if self.gl.take().is_some() {
+            context::shutdown();
+        }
+
+        let egl = self.egl;
+
+        unsafe {
+            (egl.make_current)(self.display, ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+
+            if !self.context.is_null() {
+                (egl.destroy_context)(self.display, self.context);
+            }
+
+            if !self.surface.is_null() {
+                (egl.destroy_surface)(self.display, self.surface);
+            }
+
+            (egl.terminate)(self.display);
+        }
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+const SHARED_WIDTH: u32 = 256;
+const SHARED_HEIGHT: u32 = 256;
+
+lazy_static! {
+    static ref SHARED_THREAD: Mutex<Option<Sender<Job>>> = Mutex::new(None);
+}
+
+fn spawn_shared_thread() -> Result<Sender<Job>> {
+    let (job_tx, job_rx) = mpsc::channel::<Job>();
+    let (ready_tx, ready_rx) = mpsc::channel();
+
+    thread::Builder::new()
+        .name("gl_toolkit-headless".to_string())
+        .spawn(move || {
+            let _context = match HeadlessContext::new(SHARED_WIDTH, SHARED_HEIGHT) {
+                Ok(context) => {
+                    ready_tx.send(Ok(())).unwrap();
+                    context
+                },
+                Err(err) => {
+                    ready_tx.send(Err(err)).unwrap();
+                    return;
+                },
+            };
+
+            for job in job_rx {
+                job();
+                context::collect_garbage();
+            }
+        })
+        .map_err(|err| Error::ContextCreationFailed(err.to_string()))?;
+
+    ready_rx.recv().unwrap()?;
+
+    Ok(job_tx)
+}
+
+// runs f on a single process-wide headless GL thread; calls are serialized, so parallel tests are safe
+pub fn run<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R + Send + 'static,
+    R: Send + 'static,
+{
+    let sender = {
+        let mut shared = SHARED_THREAD.lock().unwrap();
+
+        if shared.is_none() {
+            *shared = Some(spawn_shared_thread().expect("failed to create headless GL context"));
+        }
+
+        shared.as_ref().unwrap().clone()
+    };
+
+    let (result_tx, result_rx) = mpsc::channel();
+
+    sender
+        .send(Box::new(move || {
+            let result = panic::catch_unwind(AssertUnwindSafe(f));
+            result_tx.send(result).unwrap();
+        }))
+        .expect("headless GL thread has exited");
+
+    match result_rx.recv().unwrap() {
+        Ok(value) => value,
+        Err(payload) => panic::resume_unwind(payload),
+    }
+}
@@ -0,0 +1,141 @@
+use crate::context::{self, Garbage};
+use crate::error::{Error, Result};
+use crate::vbo::{as_bytes, BufferMode};
+
+use gl::types::*;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::raw::c_void;
+use std::ptr;
+
+// the layout glDrawArraysIndirect reads, for VBOs without an index buffer
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct DrawArraysCommand {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first: u32,
+    pub base_instance: u32,
+}
+
+// the layout glDrawElementsIndirect reads; first_index counts indices, not bytes
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub struct DrawElementsCommand {
+    pub count: u32,
+    pub instance_count: u32,
+    pub first_index: u32,
+    pub base_vertex: i32,
+    pub base_instance: u32,
+}
+
+pub trait IndirectCommand: Copy {}
+
+impl IndirectCommand for DrawArraysCommand {}
+impl IndirectCommand for DrawElementsCommand {}
+
+pub(crate) fn check_indirect_support(multi: bool) -> Result<()> {
+    context::check_initialized()?;
+
+    if !gl::DrawArraysIndirect::is_loaded() || !gl::DrawElementsIndirect::is_loaded() {
+        return Err(Error::Unsupported("indirect draws require OpenGL 4.0".to_string()));
+    }
+
+    if multi && (!gl::MultiDrawArraysIndirect::is_loaded() || !gl::MultiDrawElementsIndirect::is_loaded()) {
+        return Err(Error::Unsupported("multi-draw indirect requires OpenGL 4.3".to_string()));
+    }
+
+    Ok(())
+}
+
+// draw commands living on the GPU, filled from the CPU or by a compute shader through bind_storage
+pub struct DrawIndirectBuffer {
+    handle: GLuint,
+    size: usize,
+    _thread: context::ThreadMarker,
+}
+
+impl DrawIndirectBuffer {
+    pub fn new(size: usize, mode: BufferMode) -> DrawIndirectBuffer {
+        context::assert_initialized();
+
+        let mut handle = 0;
+
+        unsafe {
+            gl::GenBuffers(1, &mut handle);
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, handle);
+            gl::BufferData(gl::DRAW_INDIRECT_BUFFER, size as GLsizeiptr, ptr::null(), mode.to_raw_enum());
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+        }
+
+        DrawIndirectBuffer {
+            handle,
+            size,
+            _thread: PhantomData,
+        }
+    }
+
+    pub fn from_commands<T: IndirectCommand>(commands: &[T], mode: BufferMode) -> DrawIndirectBuffer {
+        let result = DrawIndirectBuffer::new(commands.len() * mem::size_of::<T>(), mode);
+
+        result.write_commands(0, commands);
+        result
+    }
+
+    // offset is in bytes
+    pub fn write(&self, offset: usize, bytes: &[u8]) {
+        context::assert_initialized();
+
+        if bytes.is_empty() {
+            return;
+        }
+
+        assert!(offset + bytes.len() <= self.size, "indirect buffer write out of bounds");
+
+        unsafe {
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, self.handle);
+            gl::BufferSubData(
+                gl::DRAW_INDIRECT_BUFFER,
+                offset as GLintptr,
+                bytes.len() as GLsizeiptr,
+                bytes.as_ptr() as *const c_void,
+            );
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+        }
+
+        context::count_upload(bytes.len());
+    }
+
+    // index is in commands of type T
+    pub fn write_commands<T: IndirectCommand>(&self, index: usize, commands: &[T]) {
+        self.write(index * mem::size_of::<T>(), as_bytes(commands));
+    }
+
+    // exposes the commands as `buffer { ... }` to compute shaders; needs OpenGL 4.3
+    pub fn bind_storage(&self, binding: GLuint) -> Result<()> {
+        context::check_initialized()?;
+
+        if !context::capabilities().supports_version(4, 3) {
+            return Err(Error::Unsupported("shader storage buffers require OpenGL 4.3".to_string()));
+        }
+
+        unsafe { gl::BindBufferBase(gl::SHADER_STORAGE_BUFFER, binding, self.handle) };
+
+        Ok(())
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.handle
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for DrawIndirectBuffer {
+    fn drop(&mut self) {
+        context::defer_delete(Garbage::Buffer(self.handle));
+        self.handle = 0;
+    }
+}
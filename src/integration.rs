@@ -0,0 +1,3 @@
+// renderers for third-party UI libraries, each behind a feature named after the library
+#[cfg(feature = "egui")]
+pub mod egui;
@@ -0,0 +1,281 @@
+use crate::context::{self, BlendComponent, Feature};
+use crate::error::Result;
+use crate::shader::{Shader, Stage, StageKind};
+use crate::texture::{self, ClampMode, MagFilter, MinFilter, Texture, WrapCoord};
+use crate::vbo::{AttributeKind, BufferMode, PrimitiveKind, UpdateStrategy, Vertex, VBO};
+
+use ::egui::epaint::{ClippedPrimitive, Color32, ImageData, ImageDelta, Mesh, Pos2, Primitive, TexturesDelta};
+use ::egui::{Rect, TextureId};
+use std::collections::HashMap;
+use vex::Vector2;
+
+const SRC_VERTEX: &str = r#"
+    #version 330 core
+
+    uniform vec2 u_screen_size;
+
+    layout (location = 0) in vec2 a_pos;
+    layout (location = 1) in vec2 a_coord;
+    layout (location = 2) in vec4 a_color;
+
+    out vec2 v_coord;
+    out vec4 v_color;
+
+    void main() {
+        v_coord = a_coord;
+        v_color = a_color;
+        gl_Position = vec4(2.0 * a_pos.x / u_screen_size.x - 1.0, 1.0 - 2.0 * a_pos.y / u_screen_size.y, 0.0, 1.0);
+    }
+"#;
+
+const SRC_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_tex;
+
+    in vec2 v_coord;
+    in vec4 v_color;
+
+    out vec4 frag_color;
+
+    void main() {
+        frag_color = v_color * texture(u_tex, v_coord);
+    }
+"#;
+
+const INITIAL_VERTICES: usize = 4096;
+const INITIAL_INDICES: usize = 8192;
+
+// egui's vertex is repr(C) pos/uv/color, which maps straight onto an interleaved layout
+impl Vertex for ::egui::epaint::Vertex {
+    fn attrs() -> Vec<(bool, usize, AttributeKind)> {
+        vec![
+            (false, 2, AttributeKind::Float),
+            (false, 2, AttributeKind::Float),
+            (true, 4, AttributeKind::UnsignedByte),
+        ]
+    }
+
+    fn new() -> ::egui::epaint::Vertex {
+        ::egui::epaint::Vertex {
+            pos: Pos2::ZERO,
+            uv: Pos2::ZERO,
+            color: Color32::TRANSPARENT,
+        }
+    }
+}
+
+fn build_vbo(vertex_capacity: usize, index_capacity: usize) -> VBO {
+    let vertices = vec![<::egui::epaint::Vertex as Vertex>::new(); vertex_capacity];
+    let mut result = VBO::new_indexed(BufferMode::StreamDraw, PrimitiveKind::Triangles, &vertices, &vec![0u32; index_capacity]);
+
+    result.set_update_strategy(UpdateStrategy::Orphan).unwrap();
+    result
+}
+
+// egui hands out premultiplied sRGBA; font coverage becomes premultiplied white
+fn image_pixels(image: &ImageData) -> (Vec<u8>, usize, usize) {
+    match image {
+        ImageData::Color(image) => {
+            let pixels = image.pixels.iter().flat_map(|color| color.to_array()).collect();
+
+            (pixels, image.size[0], image.size[1])
+        },
+        ImageData::Font(image) => {
+            let pixels = image
+                .pixels
+                .iter()
+                .flat_map(|coverage| {
+                    let value = (coverage.max(0.0).min(1.0) * 255.0).round() as u8;
+
+                    [value; 4]
+                })
+                .collect();
+
+            (pixels, image.size[0], image.size[1])
+        },
+    }
+}
+
+fn build_texture(pixels: &Vec<u8>, width: usize, height: usize) -> Result<Texture> {
+    let mut result = Texture::make(pixels, width, height, false)?;
+
+    result.set_min_filter(MinFilter::Linear)?;
+    result.set_mag_filter(MagFilter::Linear)?;
+    result.set_clamp(WrapCoord::S, ClampMode::Edge)?;
+    result.set_clamp(WrapCoord::T, ClampMode::Edge)?;
+
+    Ok(result)
+}
+
+// draws egui's tessellated output with the crate's own Shader/Texture/VBO; GL state, including the
+// bound program, texture and vertex array, is put back the way it was after every paint
+pub struct EguiRenderer {
+    shader: Shader,
+    vbo: VBO,
+    vertex_capacity: usize,
+    index_capacity: usize,
+    textures: HashMap<TextureId, Texture>,
+    next_user_id: u64,
+}
+
+impl EguiRenderer {
+    pub fn new() -> Result<EguiRenderer> {
+        context::check_initialized()?;
+
+        let shader = Shader::new(&vec![
+            Stage::with_name(StageKind::Vertex, "egui.vert", SRC_VERTEX)?,
+            Stage::with_name(StageKind::Fragment, "egui.frag", SRC_FRAGMENT)?,
+        ])?;
+
+        shader.set_uniform_i32("u_tex", 0);
+
+        Ok(EguiRenderer {
+            shader,
+            vbo: build_vbo(INITIAL_VERTICES, INITIAL_INDICES),
+            vertex_capacity: INITIAL_VERTICES,
+            index_capacity: INITIAL_INDICES,
+            textures: HashMap::new(),
+            next_user_id: 0,
+        })
+    }
+
+    // makes a texture of the application's drawable with egui::Image and friends
+    pub fn register_texture(&mut self, texture: Texture) -> TextureId {
+        let id = TextureId::User(self.next_user_id);
+
+        self.next_user_id += 1;
+        self.textures.insert(id, texture);
+        id
+    }
+
+    pub fn unregister_texture(&mut self, id: TextureId) -> Option<Texture> {
+        self.textures.remove(&id)
+    }
+
+    pub fn texture(&self, id: TextureId) -> Option<&Texture> {
+        self.textures.get(&id)
+    }
+
+    pub fn set_texture(&mut self, id: TextureId, delta: &ImageDelta) -> Result<()> {
+        let (pixels, width, height) = image_pixels(&delta.image);
+
+        match (delta.pos, self.textures.get(&id)) {
+            (Some([x, y]), Some(texture)) => texture.write(&pixels, x, y, width, height),
+            _ => {
+                self.textures.insert(id, build_texture(&pixels, width, height)?);
+            },
+        }
+
+        Ok(())
+    }
+
+    pub fn free_texture(&mut self, id: TextureId) {
+        self.textures.remove(&id);
+    }
+
+    // uploads the delta's new textures, draws, then frees what egui released, in the order egui expects;
+    // screen_size is the framebuffer size in pixels
+    pub fn paint(
+        &mut self,
+        screen_size: [u32; 2],
+        pixels_per_point: f32,
+        primitives: &[ClippedPrimitive],
+        textures_delta: &TexturesDelta,
+    ) -> Result<()> {
+        for (id, delta) in textures_delta.set.iter() {
+            self.set_texture(*id, delta)?;
+        }
+
+        self.paint_primitives(screen_size, pixels_per_point, primitives);
+
+        for id in textures_delta.free.iter() {
+            self.free_texture(*id);
+        }
+
+        Ok(())
+    }
+
+    pub fn paint_primitives(&mut self, screen_size: [u32; 2], pixels_per_point: f32, primitives: &[ClippedPrimitive]) {
+        context::assert_initialized();
+
+        let [width, height] = screen_size;
+
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        let snapshot = context::snapshot();
+        let previous_program = context::bound_program();
+        let previous_texture = texture::bound_handle(0, gl::TEXTURE_2D);
+        let previous_vertex_array = context::bound_vertex_array();
+
+        context::set_viewport(0, 0, width, height);
+        context::enable(Feature::Blend);
+        context::enable(Feature::ScissorTest);
+        context::disable(Feature::DepthTest);
+        context::disable(Feature::CullFace);
+        context::set_blend_func(BlendComponent::One, BlendComponent::OneMinusSrcAlpha);
+
+        let screen_points = Vector2::make(width as f32 / pixels_per_point, height as f32 / pixels_per_point);
+
+        self.shader.set_uniform_vec2("u_screen_size", &screen_points);
+
+        for primitive in primitives.iter() {
+            match &primitive.primitive {
+                Primitive::Mesh(mesh) => {
+                    if set_clip_rect(&primitive.clip_rect, screen_size, pixels_per_point) {
+                        self.draw_mesh(mesh);
+                    }
+                },
+                // paint callbacks target a specific backend; there's nothing portable to run here
+                Primitive::Callback(_) => (),
+            }
+        }
+
+        context::restore(&snapshot);
+        context::bind_program(previous_program);
+        texture::bind_handle(0, previous_texture);
+        context::bind_vertex_array(previous_vertex_array);
+    }
+
+    fn draw_mesh(&mut self, mesh: &Mesh) {
+        let texture = match self.textures.get(&mesh.texture_id) {
+            Some(texture) => texture,
+            None => return,
+        };
+
+        if mesh.indices.is_empty() {
+            return;
+        }
+
+        if mesh.vertices.len() > self.vertex_capacity || mesh.indices.len() > self.index_capacity {
+            self.vertex_capacity = self.vertex_capacity.max(mesh.vertices.len().next_power_of_two());
+            self.index_capacity = self.index_capacity.max(mesh.indices.len().next_power_of_two());
+            self.vbo = build_vbo(self.vertex_capacity, self.index_capacity);
+        }
+
+        self.vbo.write_vertices(&mesh.vertices, 0).unwrap();
+        self.vbo.write_indices(&mesh.indices, 0).unwrap();
+
+        self.shader.bind();
+        texture.bind(0);
+        self.vbo.render_range(0, mesh.indices.len());
+    }
+}
+
+// clip rects are in points with a top-left origin; returns false when nothing would be visible
+fn set_clip_rect(clip_rect: &Rect, screen_size: [u32; 2], pixels_per_point: f32) -> bool {
+    let [width, height] = screen_size;
+    let min_x = (clip_rect.min.x * pixels_per_point).round().max(0.0).min(width as f32) as u32;
+    let min_y = (clip_rect.min.y * pixels_per_point).round().max(0.0).min(height as f32) as u32;
+    let max_x = (clip_rect.max.x * pixels_per_point).round().max(0.0).min(width as f32) as u32;
+    let max_y = (clip_rect.max.y * pixels_per_point).round().max(0.0).min(height as f32) as u32;
+
+    if max_x <= min_x || max_y <= min_y {
+        return false;
+    }
+
+    context::set_scissor(min_x, height - max_y, max_x - min_x, max_y - min_y);
+    true
+}
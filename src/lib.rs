@@ -1,23 +1,107 @@
+mod atlas;
 mod builtin;
+mod camera;
 mod color;
+mod debug;
+mod deferred;
 mod error;
+mod fbo;
+#[cfg(feature = "gltf")]
+mod gltf_loader;
+mod hdr;
+mod indirect;
 mod shader;
+mod shader_cache;
+mod shader_preprocessor;
+mod sprite;
 mod context;
+mod loader;
+mod material;
+mod math;
+mod matrix_stack;
+mod mesh;
+mod multi_context;
+mod nine_slice;
+mod obj_loader;
+mod pipeline;
+mod post;
+mod query;
+mod readback;
+mod renderbuffer;
+mod rect;
+mod sampler;
+#[cfg(feature = "image")]
+mod screenshot;
+mod shadow;
+mod text;
+mod text_layout;
 mod texture;
+mod texture3d;
+mod texture_array;
+mod ubo;
+mod uniforms;
 mod vbo;
+mod vbo_builder;
 
+pub mod debug_draw;
+pub mod integration;
+pub mod overlay;
+pub mod particles;
+pub mod profiler;
+pub mod renderer2d;
+#[cfg(feature = "headless")]
+pub mod headless;
+pub mod shapes;
+#[cfg(feature = "testing")]
+pub mod testing;
+
+pub use atlas::*;
 pub use builtin::*;
+pub use camera::*;
 pub use context::*;
 pub use color::*;
+pub use debug::*;
+pub use deferred::*;
 pub use error::*;
+pub use fbo::*;
+#[cfg(feature = "gltf")]
+pub use gltf_loader::*;
+pub use hdr::*;
+pub use indirect::*;
+pub use loader::*;
+pub use material::*;
+pub use matrix_stack::*;
+pub use mesh::*;
+pub use multi_context::*;
+pub use nine_slice::*;
+pub use obj_loader::*;
+pub use pipeline::*;
+pub use post::*;
+pub use query::*;
+pub use readback::*;
+pub use renderbuffer::*;
+pub use rect::*;
+pub use sampler::*;
+#[cfg(feature = "image")]
+pub use screenshot::*;
 pub use shader::*;
+pub use shader_cache::*;
+pub use shader_preprocessor::*;
+pub use shadow::*;
+pub use sprite::*;
+pub use text::*;
+pub use text_layout::*;
 pub use texture::*;
+pub use texture3d::*;
+pub use texture_array::*;
+pub use ubo::*;
+pub use uniforms::*;
 pub use vbo::*;
+pub use vbo_builder::*;
 
-pub fn init() -> Result<()> {
-    context::init()?;
-    texture::init();
-    shader::init();
+#[cfg(feature = "derive")]
+pub use gl_toolkit_derive::Uniforms;
 
-    Ok(())
+pub fn init() -> Result<GlContext> {
+    context::init()
 }
@@ -0,0 +1,306 @@
+use crate::error::Result;
+use crate::texture::{ClampMode, MagFilter, MinFilter, Texture, WrapCoord};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Copy, Clone)]
+pub struct TextureParams {
+    pub mipmaps: bool,
+    pub clamp: ClampMode,
+    pub min_filter: MinFilter,
+    pub mag_filter: MagFilter,
+}
+
+impl TextureParams {
+    pub fn new() -> TextureParams {
+        TextureParams {
+            mipmaps: false,
+            clamp: ClampMode::Edge,
+            min_filter: MinFilter::Nearest,
+            mag_filter: MagFilter::Nearest,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub enum LoadBudget {
+    Time(Duration),
+    Bytes(usize),
+    Unlimited,
+}
+
+#[derive(Debug, Clone)]
+pub struct TextureTicket {
+    id: u64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl TextureTicket {
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
+
+struct Request {
+    ticket: TextureTicket,
+    name: String,
+    width: usize,
+    height: usize,
+    buf: Vec<u8>,
+    params: TextureParams,
+}
+
+#[derive(Clone)]
+pub struct TextureQueue {
+    sender: Sender<Request>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl TextureQueue {
+    pub fn queue(&self, name: &str, width: usize, height: usize, buf: Vec<u8>, params: TextureParams) -> TextureTicket {
+        let ticket = TextureTicket {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            cancelled: Arc::new(AtomicBool::new(false)),
+        };
+
+        let request = Request {
+            ticket: ticket.clone(),
+            name: name.to_string(),
+            width,
+            height,
+            buf,
+            params,
+        };
+
+        // the loader may already be gone; the ticket then simply never resolves
+        let _ = self.sender.send(request);
+
+        ticket
+    }
+}
+
+pub struct TextureLoader {
+    queue: TextureQueue,
+    receiver: Receiver<Request>,
+    pending: VecDeque<Request>,
+    names: HashMap<String, u64>,
+    results: HashMap<u64, Result<Texture>>,
+    placeholder: Option<Texture>,
+}
+
+impl TextureLoader {
+    pub fn new() -> TextureLoader {
+        let (sender, receiver) = mpsc::channel();
+
+        TextureLoader {
+            queue: TextureQueue {
+                sender,
+                next_id: Arc::new(AtomicU64::new(1)),
+            },
+            receiver,
+            pending: VecDeque::new(),
+            names: HashMap::new(),
+            results: HashMap::new(),
+            placeholder: None,
+        }
+    }
+
+    pub fn set_placeholder(&mut self, texture: Texture) {
+        self.placeholder = Some(texture);
+    }
+
+    pub fn queue_handle(&self) -> TextureQueue {
+        self.queue.clone()
+    }
+
+    pub fn queue(&self, name: &str, width: usize, height: usize, buf: Vec<u8>, params: TextureParams) -> TextureTicket {
+        self.queue.queue(name, width, height, buf, params)
+    }
+
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn pump(&mut self, budget: LoadBudget) -> usize {
+        let start = Instant::now();
+        let mut bytes = 0;
+        let mut uploaded = 0;
+
+        self.pending.extend(self.receiver.try_iter());
+
+        while let Some(request) = self.pending.pop_front() {
+            if request.ticket.is_cancelled() {
+                continue;
+            }
+
+            let exhausted = match budget {
+                LoadBudget::Time(limit) => start.elapsed() >= limit,
+                LoadBudget::Bytes(limit) => bytes + request.buf.len() > limit,
+                LoadBudget::Unlimited => false,
+            };
+
+            // always make progress, even if a single upload exceeds the budget
+            if exhausted && uploaded > 0 {
+                self.pending.push_front(request);
+                break;
+            }
+
+            bytes += request.buf.len();
+            uploaded += 1;
+
+            let result = TextureLoader::upload(&request);
+
+            self.names.insert(request.name, request.ticket.id);
+            self.results.insert(request.ticket.id, result);
+        }
+
+        uploaded
+    }
+
+    fn upload(request: &Request) -> Result<Texture> {
+        let params = &request.params;
+        let mut texture = Texture::make(&request.buf, request.width, request.height, params.mipmaps)?;
+
+        texture.set_clamp(WrapCoord::S, params.clamp)?;
+        texture.set_clamp(WrapCoord::T, params.clamp)?;
+        texture.set_min_filter(params.min_filter)?;
+        texture.set_mag_filter(params.mag_filter)?;
+
+        Ok(texture)
+    }
+
+    pub fn is_ready(&self, ticket: &TextureTicket) -> bool {
+        self.results.contains_key(&ticket.id)
+    }
+
+    pub fn get(&self, ticket: &TextureTicket) -> Option<&Texture> {
+        match self.results.get(&ticket.id) {
+            Some(Ok(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+
+    pub fn get_or_placeholder(&self, ticket: &TextureTicket) -> Option<&Texture> {
+        self.get(ticket).or(self.placeholder.as_ref())
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Texture> {
+        match self.names.get(name).and_then(|id| self.results.get(id)) {
+            Some(Ok(texture)) => Some(texture),
+            _ => None,
+        }
+    }
+
+    pub fn take(&mut self, ticket: &TextureTicket) -> Option<Result<Texture>> {
+        self.names.retain(|_, id| *id != ticket.id);
+        self.results.remove(&ticket.id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::texture::generate;
+
+    use std::thread;
+
+    // "decodes" on a worker thread, the way an image loader would, and hands the buffers over
+    fn queue_from_worker(queue: TextureQueue, count: usize) -> Vec<TextureTicket> {
+        thread::spawn(move || {
+            (0..count)
+                .map(|i| {
+                    let buf = generate::checkerboard(16, 16, 4, Color::BLACK, Color::WHITE);
+
+                    queue.queue(&format!("checker{}", i), 16, 16, buf, TextureParams::new())
+                })
+                .collect()
+        })
+        .join()
+        .unwrap()
+    }
+
+    #[test]
+    fn tickets_are_unique_and_share_cancellation() {
+        let loader = TextureLoader::new();
+        let tickets = queue_from_worker(loader.queue_handle(), 3);
+        let copy = tickets[1].clone();
+
+        assert_eq!(tickets.iter().map(|ticket| ticket.id()).collect::<Vec<_>>(), vec![1, 2, 3]);
+        assert!(tickets.iter().all(|ticket| !loader.is_ready(ticket)));
+
+        copy.cancel();
+
+        assert!(tickets[1].is_cancelled());
+        assert!(!tickets[0].is_cancelled());
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn textures_only_materialize_through_pump() {
+        crate::headless::run(|| {
+            let mut loader = TextureLoader::new();
+            let tickets = queue_from_worker(loader.queue_handle(), 3);
+
+            assert!(tickets.iter().all(|ticket| loader.get(ticket).is_none()));
+            assert_eq!(loader.pump(LoadBudget::Unlimited), 3);
+
+            for (i, ticket) in tickets.iter().enumerate() {
+                let texture = loader.get(ticket).expect("uploaded by pump");
+
+                assert_eq!((texture.width(), texture.height()), (16, 16));
+                assert!(loader.find(&format!("checker{}", i)).is_some());
+            }
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn pump_respects_the_budget() {
+        crate::headless::run(|| {
+            let mut loader = TextureLoader::new();
+            let tickets = queue_from_worker(loader.queue_handle(), 5);
+
+            // 1024 bytes per texture
+            assert_eq!(loader.pump(LoadBudget::Bytes(2048)), 2);
+            assert_eq!(loader.pending_count(), 3);
+            assert!(loader.is_ready(&tickets[1]) && !loader.is_ready(&tickets[2]));
+
+            // an exhausted budget still uploads one, so the queue always drains eventually
+            assert_eq!(loader.pump(LoadBudget::Time(Duration::from_secs(0))), 1);
+            assert_eq!(loader.pump(LoadBudget::Bytes(0)), 1);
+            assert_eq!(loader.pump(LoadBudget::Unlimited), 1);
+            assert_eq!(loader.pending_count(), 0);
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn cancelled_requests_are_never_uploaded() {
+        crate::headless::run(|| {
+            let mut loader = TextureLoader::new();
+            let tickets = queue_from_worker(loader.queue_handle(), 2);
+
+            loader.set_placeholder(Texture::solid(Color::MAGENTA).unwrap());
+            tickets[0].cancel();
+
+            assert_eq!(loader.pump(LoadBudget::Unlimited), 1);
+            assert!(!loader.is_ready(&tickets[0]));
+            assert!(loader.get(&tickets[0]).is_none());
+            assert!(loader.get_or_placeholder(&tickets[0]).is_some());
+            assert!(loader.is_ready(&tickets[1]));
+        });
+    }
+}
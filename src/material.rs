@@ -0,0 +1,174 @@
+use crate::context::{self, BlendFunc, DepthFunc, Face, Feature};
+use crate::mesh::{TextureSlot, UniformValue};
+use crate::shader::Shader;
+use crate::texture::Texture;
+use crate::vbo::VBO;
+
+use gl::types::*;
+
+// the fixed-function state a material draws with; every change goes through the context caches, so
+// applying the same state twice costs nothing
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RenderState {
+    // None disables blending
+    pub blend: Option<BlendFunc>,
+    pub depth_test: bool,
+    pub depth_write: bool,
+    pub depth_func: DepthFunc,
+    // None disables culling
+    pub cull: Option<Face>,
+}
+
+impl RenderState {
+    pub fn new() -> RenderState {
+        RenderState {
+            blend: None,
+            depth_test: true,
+            depth_write: true,
+            depth_func: DepthFunc::Less,
+            cull: Some(Face::Back),
+        }
+    }
+
+    // alpha blended, depth tested but not written, so transparent surfaces don't hide each other
+    pub fn transparent() -> RenderState {
+        RenderState {
+            blend: Some(BlendFunc::ALPHA),
+            depth_write: false,
+            ..RenderState::new()
+        }
+    }
+
+    pub fn apply(&self) {
+        match self.blend {
+            Some(func) => {
+                context::enable(Feature::Blend);
+                context::set_blend(func);
+            },
+            None => {
+                context::disable(Feature::Blend);
+            },
+        }
+
+        if self.depth_test {
+            context::enable(Feature::DepthTest);
+        } else {
+            context::disable(Feature::DepthTest);
+        }
+
+        context::set_depth_mask(self.depth_write);
+        context::set_depth_func(self.depth_func);
+
+        match self.cull {
+            Some(face) => {
+                context::enable(Feature::CullFace);
+                context::set_cull_face(face);
+            },
+            None => {
+                context::disable(Feature::CullFace);
+            },
+        }
+    }
+}
+
+// everything a draw needs besides its geometry; bind() applies all of it, so state can't leak in
+// from whatever was drawn before
+pub struct Material<'a> {
+    shader: &'a Shader,
+    uniforms: Vec<(String, UniformValue)>,
+    textures: Vec<TextureSlot<'a>>,
+    state: RenderState,
+}
+
+impl<'a> Material<'a> {
+    pub fn new(shader: &'a Shader) -> Material<'a> {
+        Material {
+            shader,
+            uniforms: Vec::new(),
+            textures: Vec::new(),
+            state: RenderState::new(),
+        }
+    }
+
+    pub fn set_uniform(&mut self, name: &str, value: UniformValue) {
+        match self.uniforms.iter_mut().find(|(key, _)| key == name) {
+            Some(entry) => entry.1 = value,
+            None => self.uniforms.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<UniformValue> {
+        self.uniforms.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn remove_uniform(&mut self, name: &str) {
+        self.uniforms.retain(|(key, _)| key != name);
+    }
+
+    pub fn set_texture(&mut self, name: &str, texture: &'a Texture, unit: GLuint) {
+        self.textures.retain(|binding| binding.name != name && binding.unit != unit);
+        self.textures.push(TextureSlot {
+            name: name.to_string(),
+            texture,
+            unit,
+        });
+    }
+
+    pub fn remove_texture(&mut self, name: &str) {
+        self.textures.retain(|binding| binding.name != name);
+    }
+
+    pub fn set_shader(&mut self, shader: &'a Shader) {
+        self.shader = shader;
+    }
+
+    pub fn shader(&self) -> &Shader {
+        self.shader
+    }
+
+    pub fn set_state(&mut self, state: RenderState) {
+        self.state = state;
+    }
+
+    pub fn state(&self) -> &RenderState {
+        &self.state
+    }
+
+    pub fn state_mut(&mut self) -> &mut RenderState {
+        &mut self.state
+    }
+
+    pub fn is_transparent(&self) -> bool {
+        self.state.blend.is_some()
+    }
+
+    // orders opaque materials before blended ones, then groups by shader and first texture so sorting a
+    // batch by this key minimizes program and texture switches
+    pub fn sort_key(&self) -> u64 {
+        let blended = (self.is_transparent() as u64) << 63;
+        let shader = (self.shader.handle() as u64 & 0x7FFF_FFFF) << 32;
+        let texture = self.textures.first().map_or(0, |binding| binding.texture.handle() as u64);
+
+        blended | shader | texture
+    }
+
+    pub fn bind(&self) {
+        self.state.apply();
+        self.shader.bind();
+
+        for binding in self.textures.iter() {
+            binding.bind(self.shader);
+        }
+
+        for (name, value) in self.uniforms.iter() {
+            value.upload(self.shader, name);
+        }
+    }
+
+    pub fn draw(&self, vbo: &VBO) {
+        self.bind();
+        vbo.render();
+    }
+}
@@ -0,0 +1,200 @@
+use vex::{Matrix4, Vector3};
+
+pub(crate) fn identity() -> Matrix4 {
+    Matrix4 {
+        m: [
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ],
+    }
+}
+
+pub(crate) fn ortho(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Matrix4 {
+    Matrix4 {
+        m: [
+            2.0 / (right - left), 0.0, 0.0, 0.0,
+            0.0, 2.0 / (top - bottom), 0.0, 0.0,
+            0.0, 0.0, -2.0 / (far - near), 0.0,
+            -(right + left) / (right - left),
+            -(top + bottom) / (top - bottom),
+            -(far + near) / (far - near),
+            1.0,
+        ],
+    }
+}
+
+pub(crate) fn perspective(fov_y: f32, aspect: f32, near: f32, far: f32) -> Matrix4 {
+    let f = 1.0 / (fov_y * 0.5).tan();
+
+    Matrix4 {
+        m: [
+            f / aspect, 0.0, 0.0, 0.0,
+            0.0, f, 0.0, 0.0,
+            0.0, 0.0, (far + near) / (near - far), -1.0,
+            0.0, 0.0, 2.0 * far * near / (near - far), 0.0,
+        ],
+    }
+}
+
+pub(crate) fn look_at(eye: &Vector3, target: &Vector3, up: &Vector3) -> Matrix4 {
+    let f = normalize(sub(to_array(target), to_array(eye)));
+    let s = normalize(cross(f, to_array(up)));
+    let u = cross(s, f);
+    let e = to_array(eye);
+
+    Matrix4 {
+        m: [
+            s[0], u[0], -f[0], 0.0,
+            s[1], u[1], -f[1], 0.0,
+            s[2], u[2], -f[2], 0.0,
+            -dot(s, e), -dot(u, e), dot(f, e), 1.0,
+        ],
+    }
+}
+
+pub(crate) fn multiply(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+    let (a, b) = (a.m, b.m);
+    let mut m = [0.0; 16];
+
+    for col in 0..4 {
+        for row in 0..4 {
+            m[col * 4 + row] = (0..4).map(|k| a[k * 4 + row] * b[col * 4 + k]).sum();
+        }
+    }
+
+    Matrix4 { m }
+}
+
+pub(crate) fn translation(x: f32, y: f32, z: f32) -> Matrix4 {
+    let mut result = identity();
+
+    result.m[12] = x;
+    result.m[13] = y;
+    result.m[14] = z;
+    result
+}
+
+pub(crate) fn scaling(x: f32, y: f32, z: f32) -> Matrix4 {
+    let mut result = identity();
+
+    result.m[0] = x;
+    result.m[5] = y;
+    result.m[10] = z;
+    result
+}
+
+pub(crate) fn rotation(axis: &Vector3, angle: f32) -> Matrix4 {
+    let [x, y, z] = normalize(to_array(axis));
+    let (s, c) = angle.sin_cos();
+    let t = 1.0 - c;
+
+    Matrix4 {
+        m: [
+            t * x * x + c, t * x * y + s * z, t * x * z - s * y, 0.0,
+            t * x * y - s * z, t * y * y + c, t * y * z + s * x, 0.0,
+            t * x * z + s * y, t * y * z - s * x, t * z * z + c, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ],
+    }
+}
+
+pub(crate) fn transform(m: &Matrix4, p: [f32; 3]) -> [f32; 4] {
+    let m = m.m;
+    let mut result = [0.0; 4];
+
+    for (row, value) in result.iter_mut().enumerate() {
+        *value = m[row] * p[0] + m[4 + row] * p[1] + m[8 + row] * p[2] + m[12 + row];
+    }
+
+    result
+}
+
+pub(crate) fn to_array(v: &Vector3) -> [f32; 3] {
+    [v.x, v.y, v.z]
+}
+
+pub(crate) fn to_vector(v: [f32; 3]) -> Vector3 {
+    Vector3::make(v[0], v[1], v[2])
+}
+
+pub(crate) fn add(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+pub(crate) fn sub(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn scale(a: [f32; 3], s: f32) -> [f32; 3] {
+    [a[0] * s, a[1] * s, a[2] * s]
+}
+
+pub(crate) fn dot(a: [f32; 3], b: [f32; 3]) -> f32 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn cross(a: [f32; 3], b: [f32; 3]) -> [f32; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+pub(crate) fn length(a: [f32; 3]) -> f32 {
+    dot(a, a).sqrt()
+}
+
+pub(crate) fn normalize(a: [f32; 3]) -> [f32; 3] {
+    let len = length(a);
+
+    if len > 0.0 { scale(a, 1.0 / len) } else { a }
+}
+
+// None for singular matrices; expands along 2x2 sub-determinants of the top and bottom halves
+pub(crate) fn inverse(m: &Matrix4) -> Option<Matrix4> {
+    let a = |row: usize, col: usize| m.m[row * 4 + col];
+
+    let s0 = a(0, 0) * a(1, 1) - a(1, 0) * a(0, 1);
+    let s1 = a(0, 0) * a(1, 2) - a(1, 0) * a(0, 2);
+    let s2 = a(0, 0) * a(1, 3) - a(1, 0) * a(0, 3);
+    let s3 = a(0, 1) * a(1, 2) - a(1, 1) * a(0, 2);
+    let s4 = a(0, 1) * a(1, 3) - a(1, 1) * a(0, 3);
+    let s5 = a(0, 2) * a(1, 3) - a(1, 2) * a(0, 3);
+    let c5 = a(2, 2) * a(3, 3) - a(3, 2) * a(2, 3);
+    let c4 = a(2, 1) * a(3, 3) - a(3, 1) * a(2, 3);
+    let c3 = a(2, 1) * a(3, 2) - a(3, 1) * a(2, 2);
+    let c2 = a(2, 0) * a(3, 3) - a(3, 0) * a(2, 3);
+    let c1 = a(2, 0) * a(3, 2) - a(3, 0) * a(2, 2);
+    let c0 = a(2, 0) * a(3, 1) - a(3, 0) * a(2, 1);
+    let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+
+    if det == 0.0 || !det.is_finite() {
+        return None;
+    }
+
+    let d = 1.0 / det;
+
+    Some(Matrix4 {
+        m: [
+            (a(1, 1) * c5 - a(1, 2) * c4 + a(1, 3) * c3) * d,
+            (-a(0, 1) * c5 + a(0, 2) * c4 - a(0, 3) * c3) * d,
+            (a(3, 1) * s5 - a(3, 2) * s4 + a(3, 3) * s3) * d,
+            (-a(2, 1) * s5 + a(2, 2) * s4 - a(2, 3) * s3) * d,
+            (-a(1, 0) * c5 + a(1, 2) * c2 - a(1, 3) * c1) * d,
+            (a(0, 0) * c5 - a(0, 2) * c2 + a(0, 3) * c1) * d,
+            (-a(3, 0) * s5 + a(3, 2) * s2 - a(3, 3) * s1) * d,
+            (a(2, 0) * s5 - a(2, 2) * s2 + a(2, 3) * s1) * d,
+            (a(1, 0) * c4 - a(1, 1) * c2 + a(1, 3) * c0) * d,
+            (-a(0, 0) * c4 + a(0, 1) * c2 - a(0, 3) * c0) * d,
+            (a(3, 0) * s4 - a(3, 1) * s2 + a(3, 3) * s0) * d,
+            (-a(2, 0) * s4 + a(2, 1) * s2 - a(2, 3) * s0) * d,
+            (-a(1, 0) * c3 + a(1, 1) * c1 - a(1, 2) * c0) * d,
+            (a(0, 0) * c3 - a(0, 1) * c1 + a(0, 2) * c0) * d,
+            (-a(3, 0) * s3 + a(3, 1) * s1 - a(3, 2) * s0) * d,
+            (a(2, 0) * s3 - a(2, 1) * s1 + a(2, 2) * s0) * d,
+        ],
+    })
+}
@@ -0,0 +1,76 @@
+use crate::math;
+use crate::shader::Shader;
+
+use vex::{Matrix4, Vector3};
+
+pub struct MatrixStack {
+    stack: Vec<Matrix4>,
+}
+
+impl MatrixStack {
+    pub fn new() -> MatrixStack {
+        MatrixStack {
+            stack: vec![math::identity()],
+        }
+    }
+
+    pub fn push(&mut self) {
+        let top = self.current();
+
+        self.stack.push(top);
+    }
+
+    pub fn pop(&mut self) {
+        assert!(self.stack.len() > 1, "MatrixStack::pop called without a matching push");
+
+        self.stack.pop();
+    }
+
+    pub fn current(&self) -> Matrix4 {
+        self.stack[self.stack.len() - 1]
+    }
+
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn load_identity(&mut self) {
+        self.set(math::identity());
+    }
+
+    pub fn set(&mut self, matrix: Matrix4) {
+        let last = self.stack.len() - 1;
+
+        self.stack[last] = matrix;
+    }
+
+    pub fn multiply(&mut self, matrix: &Matrix4) {
+        let result = math::multiply(&self.current(), matrix);
+
+        self.set(result);
+    }
+
+    pub fn translate(&mut self, x: f32, y: f32, z: f32) {
+        self.multiply(&math::translation(x, y, z));
+    }
+
+    pub fn rotate(&mut self, axis: &Vector3, angle: f32) {
+        self.multiply(&math::rotation(axis, angle));
+    }
+
+    pub fn rotate_z(&mut self, angle: f32) {
+        self.rotate(&Vector3::make(0.0, 0.0, 1.0), angle);
+    }
+
+    pub fn scale(&mut self, x: f32, y: f32, z: f32) {
+        self.multiply(&math::scaling(x, y, z));
+    }
+
+    pub fn upload(&self, shader: &Shader, name: &str) {
+        shader.set_uniform_mat4(name, &self.current());
+    }
+
+    pub fn upload_model(&self, shader: &Shader) {
+        shader.set_model(&self.current());
+    }
+}
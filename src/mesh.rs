@@ -0,0 +1,123 @@
+use crate::shader::Shader;
+use crate::texture::Texture;
+use crate::vbo::VBO;
+
+use gl::types::*;
+use vex::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum UniformValue {
+    F32(f32),
+    I32(i32),
+    Vec2(Vector2),
+    Vec3(Vector3),
+    Vec4(Vector4),
+    Mat3(Matrix3),
+    Mat4(Matrix4),
+}
+
+impl UniformValue {
+    pub fn upload(&self, shader: &Shader, name: &str) {
+        match self {
+            UniformValue::F32(value) => shader.set_uniform_f32(name, *value),
+            UniformValue::I32(value) => shader.set_uniform_i32(name, *value),
+            UniformValue::Vec2(value) => shader.set_uniform_vec2(name, value),
+            UniformValue::Vec3(value) => shader.set_uniform_vec3(name, value),
+            UniformValue::Vec4(value) => shader.set_uniform_vec4(name, value),
+            UniformValue::Mat3(value) => shader.set_uniform_mat3(name, value),
+            UniformValue::Mat4(value) => shader.set_uniform_mat4(name, value),
+        }
+    }
+}
+
+pub(crate) struct TextureSlot<'a> {
+    pub(crate) name: String,
+    pub(crate) texture: &'a Texture,
+    pub(crate) unit: GLuint,
+}
+
+impl TextureSlot<'_> {
+    pub(crate) fn bind(&self, shader: &Shader) {
+        self.texture.bind(self.unit);
+
+        shader.set_uniform_i32(&self.name, self.unit as i32);
+    }
+}
+
+pub struct Mesh<'a> {
+    vbo: VBO,
+    shader: &'a Shader,
+    uniforms: Vec<(String, UniformValue)>,
+    textures: Vec<TextureSlot<'a>>,
+}
+
+impl<'a> Mesh<'a> {
+    pub fn new(vbo: VBO, shader: &'a Shader) -> Mesh<'a> {
+        Mesh {
+            vbo,
+            shader,
+            uniforms: Vec::new(),
+            textures: Vec::new(),
+        }
+    }
+
+    pub fn set_uniform(&mut self, name: &str, value: UniformValue) {
+        match self.uniforms.iter_mut().find(|(key, _)| key == name) {
+            Some(entry) => entry.1 = value,
+            None => self.uniforms.push((name.to_string(), value)),
+        }
+    }
+
+    pub fn uniform(&self, name: &str) -> Option<UniformValue> {
+        self.uniforms.iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| *value)
+    }
+
+    pub fn remove_uniform(&mut self, name: &str) {
+        self.uniforms.retain(|(key, _)| key != name);
+    }
+
+    pub fn set_texture(&mut self, name: &str, texture: &'a Texture, unit: GLuint) {
+        self.textures.retain(|binding| binding.name != name && binding.unit != unit);
+        self.textures.push(TextureSlot {
+            name: name.to_string(),
+            texture,
+            unit,
+        });
+    }
+
+    pub fn remove_texture(&mut self, name: &str) {
+        self.textures.retain(|binding| binding.name != name);
+    }
+
+    pub fn set_shader(&mut self, shader: &'a Shader) {
+        self.shader = shader;
+    }
+
+    pub fn shader(&self) -> &Shader {
+        self.shader
+    }
+
+    pub fn vbo(&self) -> &VBO {
+        &self.vbo
+    }
+
+    pub fn vbo_mut(&mut self) -> &mut VBO {
+        &mut self.vbo
+    }
+
+    pub fn draw(&self) {
+        self.shader.bind();
+
+        for binding in self.textures.iter() {
+            binding.bind(self.shader);
+        }
+
+        for (name, value) in self.uniforms.iter() {
+            value.upload(self.shader, name);
+        }
+
+        self.vbo.render();
+    }
+}
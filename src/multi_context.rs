@@ -0,0 +1,210 @@
+use crate::context::{self, Garbage, ThreadMarker};
+use crate::error::Result;
+
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::sync::Mutex;
+
+// the context gl_toolkit::init was called with
+const PRIMARY: u64 = 0;
+
+struct Snapshot {
+    context: context::State,
+}
+
+impl Snapshot {
+    fn new() -> Snapshot {
+        Snapshot {
+            context: context::State::new(),
+        }
+    }
+
+    // installs this snapshot as the live state and returns what was live before
+    fn swap_in(self) -> Snapshot {
+        Snapshot {
+            context: context::swap_state(self.context),
+        }
+    }
+}
+
+struct State {
+    next_id: u64,
+    current: Option<u64>,
+    parked: HashMap<u64, Snapshot>,
+}
+
+lazy_static! {
+    static ref INTERNAL_STATE: Mutex<State> = {
+        Mutex::new(State {
+            next_id: PRIMARY + 1,
+            current: Some(PRIMARY),
+            parked: HashMap::new(),
+        })
+    };
+}
+
+// Each GL context keeps its own copy of the state caches. Only the current one lives in the
+// module statics; the rest are parked here until made current again. Resources, including
+// the builtin shaders, are only usable across contexts that share objects.
+pub struct Context {
+    id: u64,
+    _thread: ThreadMarker,
+}
+
+impl Context {
+    pub fn primary() -> Context {
+        Context {
+            id: PRIMARY,
+            _thread: PhantomData,
+        }
+    }
+
+    // the new GL context must already be current on this thread
+    pub fn new() -> Result<Context> {
+        context::check_initialized()?;
+
+        let id = {
+            let mut st = INTERNAL_STATE.lock().unwrap();
+            let id = st.next_id;
+            let previous = Snapshot::new().swap_in();
+
+            match st.current {
+                Some(current) => {
+                    st.parked.insert(current, previous);
+                }
+                // the last current context was dropped while current
+                None => context::adopt_garbage(previous.context),
+            }
+
+            st.next_id += 1;
+            st.current = Some(id);
+            id
+        };
+
+        context::apply_defaults();
+
+        Ok(Context {
+            id,
+            _thread: PhantomData,
+        })
+    }
+
+    // call right after making the matching GL context current with the windowing library
+    pub fn make_current(&self) {
+        context::assert_initialized();
+
+        let mut st = INTERNAL_STATE.lock().unwrap();
+
+        if st.current == Some(self.id) {
+            return;
+        }
+
+        let snapshot = st.parked.remove(&self.id).unwrap_or_else(Snapshot::new);
+        let previous = snapshot.swap_in();
+
+        match st.current {
+            Some(current) => {
+                st.parked.insert(current, previous);
+            }
+            None => context::adopt_garbage(previous.context),
+        }
+
+        st.current = Some(self.id);
+    }
+
+    pub fn is_current(&self) -> bool {
+        INTERNAL_STATE.lock().unwrap().current == Some(self.id)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+}
+
+// None while the last current context has been dropped and no other was made current
+pub(crate) fn current_id() -> Option<u64> {
+    INTERNAL_STATE.lock().unwrap().current
+}
+
+// for context::defer_delete_owned; the objects of a context that's gone went with it
+pub(crate) fn defer_delete(owner: Option<u64>, item: Garbage) {
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    if owner.is_none() || owner == st.current {
+        context::defer_delete(item);
+    } else if let Some(snapshot) = owner.and_then(|id| st.parked.get_mut(&id)) {
+        snapshot.context.queue_garbage(item);
+    }
+}
+
+// forgets every context but the primary one, for context::shutdown
+#[cfg(feature = "headless")]
+pub(crate) fn reset() {
+    let mut st = INTERNAL_STATE.lock().unwrap();
+
+    st.parked.clear();
+    st.current = Some(PRIMARY);
+}
+
+impl Drop for Context {
+    fn drop(&mut self) {
+        // the primary context outlives every handle to it
+        if self.id == PRIMARY {
+            return;
+        }
+
+        let mut st = INTERNAL_STATE.lock().unwrap();
+
+        if let Some(snapshot) = st.parked.remove(&self.id) {
+            context::adopt_garbage(snapshot.context);
+        }
+
+        if st.current == Some(self.id) {
+            st.current = None;
+        }
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod tests {
+    use super::*;
+    use crate::query::{Query, QueryKind};
+
+    #[test]
+    fn container_garbage_goes_to_the_context_that_made_it() {
+        crate::headless::run(|| {
+            let primary = Context::primary();
+            let query = Query::new(QueryKind::SamplesPassed);
+
+            context::collect_garbage();
+
+            // what Context::new does, minus apply_defaults: the GL context stays the same, so the primary
+            // one's caches have to keep matching it
+            let other = {
+                let mut st = INTERNAL_STATE.lock().unwrap();
+                let id = st.next_id;
+
+                st.parked.insert(PRIMARY, Snapshot::new().swap_in());
+                st.next_id += 1;
+                st.current = Some(id);
+
+                Context {
+                    id,
+                    _thread: PhantomData,
+                }
+            };
+
+            drop(query);
+
+            assert_eq!(context::pending_garbage(), 0);
+
+            primary.make_current();
+
+            assert_eq!(context::pending_garbage(), 1);
+            assert_eq!(context::collect_garbage(), 1);
+
+            drop(other);
+        });
+    }
+}
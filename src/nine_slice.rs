@@ -0,0 +1,178 @@
+use crate::builtin::SpriteVertex;
+use crate::color::Color;
+use crate::error::Result;
+use crate::rect::Rect;
+use crate::sprite::SpriteBatch;
+use crate::texture::Texture;
+use crate::vbo::{BufferMode, PrimitiveKind, VBO};
+
+use vex::{Vector2, Vector3};
+
+// a 4x4 grid of corners shared between the 9 quads
+const VERTEX_COUNT: usize = 16;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Insets {
+    pub left: f32,
+    pub top: f32,
+    pub right: f32,
+    pub bottom: f32,
+}
+
+impl Insets {
+    pub fn new() -> Insets {
+        Insets::make(0.0, 0.0, 0.0, 0.0)
+    }
+
+    pub fn make(left: f32, top: f32, right: f32, bottom: f32) -> Insets {
+        Insets { left, top, right, bottom }
+    }
+
+    pub fn uniform(value: f32) -> Insets {
+        Insets::make(value, value, value, value)
+    }
+}
+
+// corners keep their size, edges stretch along one axis and the center along both; insets are in
+// texels and map 1:1 to destination units unless the destination is too small to fit them
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct NineSlice {
+    src_rect: Rect,
+    insets: Insets,
+    texture_width: f32,
+    texture_height: f32,
+}
+
+// shrinks both borders by the same factor when they'd overlap
+fn fit(start: f32, end: f32, size: f32) -> (f32, f32) {
+    let total = start + end;
+
+    if total > size && total > 0.0 {
+        let scale = size.max(0.0) / total;
+
+        (start * scale, end * scale)
+    } else {
+        (start, end)
+    }
+}
+
+impl NineSlice {
+    pub fn new(texture: &Texture, insets: Insets) -> NineSlice {
+        let src_rect = Rect::make(0.0, 0.0, texture.width() as f32, texture.height() as f32);
+
+        NineSlice::from_region(texture, src_rect, insets)
+    }
+
+    // for panels packed into an atlas; src_rect is in texels
+    pub fn from_region(texture: &Texture, src_rect: Rect, insets: Insets) -> NineSlice {
+        NineSlice {
+            src_rect,
+            insets,
+            texture_width: texture.width() as f32,
+            texture_height: texture.height() as f32,
+        }
+    }
+
+    pub fn src_rect(&self) -> Rect {
+        self.src_rect
+    }
+
+    pub fn insets(&self) -> Insets {
+        self.insets
+    }
+
+    pub fn set_insets(&mut self, insets: Insets) {
+        self.insets = insets;
+    }
+
+    // grid lines as (source, destination) pairs per axis, left to right and top to bottom
+    fn grid(&self, dst_rect: &Rect) -> ([(f32, f32); 4], [(f32, f32); 4]) {
+        let src = &self.src_rect;
+        let (left, right) = fit(self.insets.left, self.insets.right, dst_rect.width);
+        let (top, bottom) = fit(self.insets.top, self.insets.bottom, dst_rect.height);
+
+        let columns = [
+            (src.x, dst_rect.x),
+            (src.x + self.insets.left, dst_rect.x + left),
+            (src.right() - self.insets.right, dst_rect.right() - right),
+            (src.right(), dst_rect.right()),
+        ];
+
+        let rows = [
+            (src.y, dst_rect.y),
+            (src.y + self.insets.top, dst_rect.y + top),
+            (src.bottom() - self.insets.bottom, dst_rect.bottom() - bottom),
+            (src.bottom(), dst_rect.bottom()),
+        ];
+
+        (columns, rows)
+    }
+
+    // row-major over the 4x4 grid; pair with indices() for a Triangles VBO
+    pub fn vertices(&self, dst_rect: Rect, color: Color) -> Vec<SpriteVertex> {
+        let (columns, rows) = self.grid(&dst_rect);
+        let mut result = Vec::with_capacity(VERTEX_COUNT);
+
+        for (v, y) in rows.iter() {
+            for (u, x) in columns.iter() {
+                result.push(SpriteVertex::from_parts(
+                    Vector3::make(*x, *y, 0.0),
+                    Vector2::make(u / self.texture_width, v / self.texture_height),
+                    color,
+                ));
+            }
+        }
+
+        result
+    }
+
+    pub fn indices() -> Vec<u16> {
+        let mut result = Vec::with_capacity(54);
+
+        for row in 0..3u16 {
+            for column in 0..3u16 {
+                let base = row * 4 + column;
+
+                result.extend_from_slice(&[base, base + 1, base + 5, base + 5, base + 4, base]);
+            }
+        }
+
+        result
+    }
+
+    // draw with SHADER_SPRITE (or anything taking SpriteVertex) and the texture bound to unit 0
+    pub fn make_vbo(&self, dst_rect: Rect, color: Color) -> VBO {
+        VBO::new(
+            BufferMode::DynamicDraw,
+            PrimitiveKind::Triangles,
+            &self.vertices(dst_rect, color),
+            Some(&NineSlice::indices()),
+        )
+    }
+
+    // for a VBO from make_vbo, when the panel moves, resizes or changes color
+    pub fn update_vbo(&self, vbo: &VBO, dst_rect: Rect, color: Color) -> Result<()> {
+        vbo.write_vertices(&self.vertices(dst_rect, color), 0)
+    }
+
+    // queues the non-empty slices as individual sprites between SpriteBatch::begin and end
+    pub fn draw(&self, batch: &mut SpriteBatch, texture: &Texture, dst_rect: Rect, color: Color) {
+        let (columns, rows) = self.grid(&dst_rect);
+
+        for row in 0..3 {
+            for column in 0..3 {
+                let (u0, x0) = columns[column];
+                let (u1, x1) = columns[column + 1];
+                let (v0, y0) = rows[row];
+                let (v1, y1) = rows[row + 1];
+                let dst = Rect::make(x0, y0, x1 - x0, y1 - y0);
+
+                if dst.is_empty() {
+                    continue;
+                }
+
+                batch.draw(texture, Rect::make(u0, v0, u1 - u0, v1 - v0), dst, color, 0.0);
+            }
+        }
+    }
+}
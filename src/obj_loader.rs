@@ -0,0 +1,395 @@
+use crate::builtin::NormalVertex;
+use crate::error::{Error, Result};
+use crate::math;
+use crate::shader::Shader;
+use crate::texture::Texture;
+use crate::vbo::{BufferMode, PrimitiveKind, VBO};
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use vex::Vector2;
+
+#[derive(Debug, Clone)]
+pub struct ObjMaterial {
+    pub name: String,
+    pub ambient: [f32; 3],
+    pub diffuse: [f32; 3],
+    pub specular: [f32; 3],
+    pub shininess: f32,
+    pub alpha: f32,
+    // resolved against the MTL file's directory
+    pub diffuse_map: Option<PathBuf>,
+    // index into ObjModel::textures; only filled in with the image feature
+    pub diffuse_texture: Option<usize>,
+}
+
+impl ObjMaterial {
+    pub fn new(name: &str) -> ObjMaterial {
+        ObjMaterial {
+            name: name.to_string(),
+            ambient: [0.0; 3],
+            diffuse: [1.0; 3],
+            specular: [0.0; 3],
+            shininess: 0.0,
+            alpha: 1.0,
+            diffuse_map: None,
+            diffuse_texture: None,
+        }
+    }
+}
+
+// every face using the same material ends up in one indexed draw
+pub struct ObjGroup {
+    pub vbo: VBO,
+    pub material: Option<usize>,
+}
+
+pub struct ObjModel {
+    pub groups: Vec<ObjGroup>,
+    pub materials: Vec<ObjMaterial>,
+    pub textures: Vec<Texture>,
+}
+
+fn parse_error(name: &str, line: usize, msg: &str) -> Error {
+    Error::ModelLoadFailed(format!("{}:{}: {}", name, line + 1, msg))
+}
+
+fn parse_floats<const N: usize>(args: &[&str], defaults: [f32; N]) -> Option<[f32; N]> {
+    if args.is_empty() {
+        return None;
+    }
+
+    let mut result = defaults;
+
+    for (i, arg) in args.iter().take(N).enumerate() {
+        result[i] = arg.parse().ok()?;
+    }
+
+    Some(result)
+}
+
+// OBJ indices are 1-based, and negative ones count back from the most recent element
+fn resolve_index(value: &str, len: usize) -> Option<usize> {
+    let index = value.parse::<i64>().ok()?;
+    let resolved = if index < 0 { len as i64 + index } else { index - 1 };
+
+    if resolved >= 0 && (resolved as usize) < len {
+        Some(resolved as usize)
+    } else {
+        None
+    }
+}
+
+// v, v/vt, v//vn or v/vt/vn
+type Corner = (usize, Option<usize>, Option<usize>);
+
+fn parse_corner(value: &str, positions: usize, uvs: usize, normals: usize) -> Option<Corner> {
+    let mut parts = value.split('/');
+    let position = resolve_index(parts.next()?, positions)?;
+    let uv = match parts.next() {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, uvs)?),
+        _ => None,
+    };
+
+    let normal = match parts.next() {
+        Some(part) if !part.is_empty() => Some(resolve_index(part, normals)?),
+        _ => None,
+    };
+
+    Some((position, uv, normal))
+}
+
+// map_Kd may carry options like -s or -bm ahead of the file name, which always comes last
+fn map_path(args: &[&str], dir: &Path) -> Option<PathBuf> {
+    args.last().map(|name| dir.join(name))
+}
+
+fn parse_mtl(source: &str, name: &str, dir: &Path, materials: &mut Vec<ObjMaterial>) -> Result<()> {
+    for (line, text) in source.lines().enumerate() {
+        let tokens = text.split_whitespace().collect::<Vec<&str>>();
+        let (keyword, args) = match tokens.split_first() {
+            Some((keyword, args)) if !keyword.starts_with('#') => (*keyword, args),
+            _ => continue,
+        };
+
+        if keyword == "newmtl" {
+            materials.push(ObjMaterial::new(args.join(" ").as_str()));
+            continue;
+        }
+
+        let material = match materials.last_mut() {
+            Some(material) => material,
+            None => return Err(parse_error(name, line, &format!("\"{}\" before any newmtl", keyword))),
+        };
+
+        let invalid = || parse_error(name, line, &format!("invalid \"{}\" statement", keyword));
+
+        match keyword {
+            "Ka" => material.ambient = parse_floats(args, [0.0; 3]).ok_or_else(invalid)?,
+            "Kd" => material.diffuse = parse_floats(args, [0.0; 3]).ok_or_else(invalid)?,
+            "Ks" => material.specular = parse_floats(args, [0.0; 3]).ok_or_else(invalid)?,
+            "Ns" => material.shininess = parse_floats(args, [0.0]).ok_or_else(invalid)?[0],
+            "d" => material.alpha = parse_floats(args, [1.0]).ok_or_else(invalid)?[0],
+            "Tr" => material.alpha = 1.0 - parse_floats(args, [0.0]).ok_or_else(invalid)?[0],
+            "map_Kd" => material.diffuse_map = map_path(args, dir),
+            // everything else (illum, Ke, Ni, bump maps, ...) has nowhere to go
+            _ => (),
+        }
+    }
+
+    Ok(())
+}
+
+struct Group {
+    vertices: Vec<NormalVertex>,
+    indices: Vec<u32>,
+    // corners without a normal get a smoothed face normal once the group is complete
+    generated_normals: Vec<bool>,
+    lookup: HashMap<Corner, u32>,
+}
+
+impl Group {
+    fn new() -> Group {
+        Group {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+            generated_normals: Vec::new(),
+            lookup: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, corner: Corner, data: &ObjData) -> u32 {
+        if let Some(index) = self.lookup.get(&corner) {
+            return *index;
+        }
+
+        let (position, uv, normal) = corner;
+        let uv = uv.map_or([0.0, 0.0], |uv| data.uvs[uv]);
+        let index = self.vertices.len() as u32;
+
+        self.vertices.push(NormalVertex::from_parts(
+            math::to_vector(data.positions[position]),
+            math::to_vector(normal.map_or([0.0; 3], |normal| data.normals[normal])),
+            Vector2::make(uv[0], uv[1]),
+        ));
+
+        self.generated_normals.push(normal.is_none());
+        self.lookup.insert(corner, index);
+        index
+    }
+
+    fn generate_normals(&mut self) {
+        if !self.generated_normals.iter().any(|generated| *generated) {
+            return;
+        }
+
+        let mut normals = vec![[0.0f32; 3]; self.vertices.len()];
+
+        for triangle in self.indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0] as usize, triangle[1] as usize, triangle[2] as usize];
+            let (pa, pb, pc) = (self.vertices[a].pos, self.vertices[b].pos, self.vertices[c].pos);
+            let pa = math::to_array(&pa);
+            let e1 = math::sub(math::to_array(&pb), pa);
+            let e2 = math::sub(math::to_array(&pc), pa);
+
+            // left unnormalized so larger faces weigh more
+            let normal = math::cross(e1, e2);
+
+            for i in [a, b, c].iter() {
+                normals[*i] = math::add(normals[*i], normal);
+            }
+        }
+
+        for (i, normal) in normals.iter().enumerate() {
+            if self.generated_normals[i] && math::length(*normal) > f32::EPSILON {
+                self.vertices[i].normal = math::to_vector(math::normalize(*normal));
+            }
+        }
+    }
+}
+
+struct ObjData {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    normals: Vec<[f32; 3]>,
+}
+
+impl ObjModel {
+    // mtllib files are resolved relative to the OBJ, and their diffuse maps relative to themselves
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ObjModel> {
+        let path = path.as_ref();
+        let source = fs::read_to_string(path)
+            .map_err(|err| Error::ModelLoadFailed(format!("{}: {}", path.display(), err)))?;
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let name = path.display().to_string();
+
+        ObjModel::parse(&source, &name, Some(dir))
+    }
+
+    // mtllib is ignored; usemtl still splits the groups, with default materials
+    pub fn from_str(source: &str) -> Result<ObjModel> {
+        ObjModel::parse(source, "<obj>", None)
+    }
+
+    fn parse(source: &str, name: &str, dir: Option<&Path>) -> Result<ObjModel> {
+        let mut data = ObjData {
+            positions: Vec::new(),
+            uvs: Vec::new(),
+            normals: Vec::new(),
+        };
+
+        let mut materials = Vec::new();
+        let mut groups: Vec<(Option<usize>, Group)> = Vec::new();
+        let mut current = None;
+
+        for (line, text) in source.lines().enumerate() {
+            let tokens = text.split_whitespace().collect::<Vec<&str>>();
+            let (keyword, args) = match tokens.split_first() {
+                Some((keyword, args)) if !keyword.starts_with('#') => (*keyword, args),
+                _ => continue,
+            };
+
+            let invalid = || parse_error(name, line, &format!("invalid \"{}\" statement", keyword));
+
+            match keyword {
+                "v" => data.positions.push(parse_floats(args, [0.0; 3]).ok_or_else(invalid)?),
+                "vt" => data.uvs.push(parse_floats(args, [0.0; 2]).ok_or_else(invalid)?),
+                "vn" => data.normals.push(parse_floats(args, [0.0; 3]).ok_or_else(invalid)?),
+                "f" => {
+                    if args.len() < 3 {
+                        return Err(invalid());
+                    }
+
+                    let material = current;
+                    let group = match groups.iter().position(|(key, _)| *key == material) {
+                        Some(index) => &mut groups[index].1,
+                        None => {
+                            groups.push((material, Group::new()));
+                            &mut groups.last_mut().unwrap().1
+                        },
+                    };
+
+                    let mut corners = Vec::with_capacity(args.len());
+
+                    for arg in args.iter() {
+                        let corner = parse_corner(arg, data.positions.len(), data.uvs.len(), data.normals.len())
+                            .ok_or_else(|| parse_error(name, line, &format!("invalid face vertex \"{}\"", arg)))?;
+
+                        corners.push(group.push(corner, &data));
+                    }
+
+                    // polygons are fanned out from their first corner
+                    for i in 1..corners.len() - 1 {
+                        group.indices.extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+                    }
+                },
+                "usemtl" => {
+                    let material_name = args.join(" ");
+
+                    current = match materials.iter().position(|material: &ObjMaterial| material.name == material_name) {
+                        Some(index) => Some(index),
+                        None => {
+                            materials.push(ObjMaterial::new(&material_name));
+                            Some(materials.len() - 1)
+                        },
+                    };
+                },
+                "mtllib" => {
+                    if let Some(dir) = dir {
+                        for file in args.iter() {
+                            let path = dir.join(file);
+                            let source = fs::read_to_string(&path)
+                                .map_err(|err| Error::ModelLoadFailed(format!("{}: {}", path.display(), err)))?;
+
+                            let mtl_dir = path.parent().unwrap_or(dir);
+
+                            parse_mtl(&source, &path.display().to_string(), mtl_dir, &mut materials)?;
+                        }
+                    }
+                },
+                // lines, points, smoothing groups and object/group names don't affect the output
+                _ => (),
+            }
+        }
+
+        let groups = groups
+            .into_iter()
+            .filter(|(_, group)| !group.indices.is_empty())
+            .map(|(material, mut group)| {
+                group.generate_normals();
+
+                ObjGroup {
+                    vbo: VBO::new_indexed(BufferMode::StaticDraw, PrimitiveKind::Triangles, &group.vertices, &group.indices),
+                    material,
+                }
+            })
+            .collect();
+
+        let textures = load_textures(&mut materials)?;
+
+        Ok(ObjModel {
+            groups,
+            materials,
+            textures,
+        })
+    }
+
+    // meant for SHADER_LIT; binds each group's diffuse texture to unit 0, everything else is up to the
+    // caller
+    pub fn draw(&self, shader: &Shader) {
+        shader.bind();
+
+        for group in self.groups.iter() {
+            let texture = group
+                .material
+                .and_then(|index| self.materials[index].diffuse_texture)
+                .map(|index| &self.textures[index]);
+
+            if let Some(texture) = texture {
+                texture.bind(0);
+            }
+
+            group.vbo.render();
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+fn load_textures(materials: &mut [ObjMaterial]) -> Result<Vec<Texture>> {
+    use crate::texture::ImageOptions;
+
+    let mut textures = Vec::new();
+    let mut paths: Vec<PathBuf> = Vec::new();
+
+    // OBJ texture coordinates start at the bottom, images at the top
+    let options = ImageOptions {
+        flip_vertically: true,
+        mipmaps: true,
+        srgb: true,
+    };
+
+    for material in materials.iter_mut() {
+        let path = match &material.diffuse_map {
+            Some(path) => path,
+            None => continue,
+        };
+
+        material.diffuse_texture = match paths.iter().position(|loaded| loaded == path) {
+            Some(index) => Some(index),
+            None => {
+                textures.push(Texture::from_file_with_options(path, options)?);
+                paths.push(path.clone());
+                Some(textures.len() - 1)
+            },
+        };
+    }
+
+    Ok(textures)
+}
+
+#[cfg(not(feature = "image"))]
+fn load_textures(_materials: &mut [ObjMaterial]) -> Result<Vec<Texture>> {
+    Ok(Vec::new())
+}
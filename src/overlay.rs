@@ -0,0 +1,241 @@
+use crate::builtin::TEXTURE_WHITE;
+use crate::color::Color;
+use crate::context::{self, BlendFunc, Feature, FrameStats};
+use crate::math;
+use crate::profiler;
+use crate::rect::Rect;
+use crate::sprite::{SpriteBatch, SpriteSort};
+use crate::text::Font;
+use crate::text_layout::{LayoutOptions, TextLayout};
+
+use std::cell::RefCell;
+use std::time::Instant;
+
+// frame times kept for the graph, one bar each
+const HISTORY: usize = 120;
+// frames averaged for the FPS readout, so it stays readable
+const FPS_WINDOW: usize = 30;
+const BAR_WIDTH: f32 = 2.0;
+const GRAPH_HEIGHT: f32 = 48.0;
+// the top of the graph; longer frames are clamped to it
+const GRAPH_MAX_MS: f32 = 50.0;
+const PADDING: f32 = 6.0;
+
+struct State {
+    enabled: bool,
+    x: f32,
+    y: f32,
+    font: Option<Font>,
+    batch: Option<SpriteBatch>,
+    last: Option<Instant>,
+    times: Vec<f32>,
+    next: usize,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            enabled: false,
+            x: 8.0,
+            y: 8.0,
+            font: None,
+            batch: None,
+            last: None,
+            times: Vec::with_capacity(HISTORY),
+            next: 0,
+        }
+    }
+
+    fn push_time(&mut self, milliseconds: f32) {
+        if self.times.len() < HISTORY {
+            self.times.push(milliseconds);
+        } else {
+            self.times[self.next] = milliseconds;
+        }
+
+        self.next = (self.next + 1) % HISTORY;
+    }
+
+    // oldest first
+    fn history(&self) -> impl Iterator<Item = f32> + '_ {
+        let (newer, older) = self.times.split_at(self.next.min(self.times.len()));
+
+        older.iter().chain(newer.iter()).copied()
+    }
+
+    fn average_ms(&self) -> f32 {
+        let count = self.times.len().min(FPS_WINDOW);
+
+        match count {
+            0 => 0.0,
+            _ => self.history().skip(self.times.len() - count).sum::<f32>() / count as f32,
+        }
+    }
+}
+
+thread_local! {
+    // the batch and font texture belong to the GL thread, so the overlay is per thread
+    static INTERNAL_STATE: RefCell<Option<State>> = RefCell::new(None);
+}
+
+fn with_state<R, F: FnOnce(&mut State) -> R>(f: F) -> R {
+    INTERNAL_STATE.with(|cell| {
+        let mut slot = cell.borrow_mut();
+
+        f(slot.get_or_insert_with(State::new))
+    })
+}
+
+// drops this thread's GL objects while the context is still current, for context::shutdown
+#[cfg(feature = "headless")]
+pub(crate) fn reset() {
+    let state = INTERNAL_STATE.with(|cell| cell.borrow_mut().take());
+
+    drop(state);
+}
+
+fn bar_color(milliseconds: f32) -> Color {
+    if milliseconds <= 1000.0 / 60.0 {
+        Color::make(64, 224, 96, 255)
+    } else if milliseconds <= 1000.0 / 30.0 {
+        Color::make(240, 200, 48, 255)
+    } else {
+        Color::make(240, 64, 48, 255)
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    match bytes {
+        0..=1023 => format!("{} B", bytes),
+        1024..=1_048_575 => format!("{:.1} KB", bytes as f64 / 1024.0),
+        _ => format!("{:.1} MB", bytes as f64 / 1_048_576.0),
+    }
+}
+
+fn stats_text(st: &State, stats: &FrameStats) -> String {
+    let average = st.average_ms();
+    let fps = if average > 0.0 { 1000.0 / average } else { 0.0 };
+    let mut text = format!("{:.0} fps  {:.2} ms", fps, average);
+
+    if let Some(timings) = profiler::latest() {
+        text.push_str(&format!("  gpu {:.2} ms", timings.total_milliseconds()));
+    }
+
+    text.push_str(&format!(
+        "\ndraws {}  tris {}\ntextures {} ({} skipped)\nprograms {} ({} skipped)\nuploads {} ({})",
+        stats.draw_calls,
+        stats.triangles,
+        stats.texture_binds,
+        stats.texture_binds_skipped,
+        stats.program_binds,
+        stats.program_binds_skipped,
+        stats.buffer_uploads,
+        format_bytes(stats.uploaded_bytes),
+    ));
+
+    text
+}
+
+pub fn set_enabled(enabled: bool) {
+    with_state(|st| st.enabled = enabled);
+}
+
+pub fn is_enabled() -> bool {
+    with_state(|st| st.enabled)
+}
+
+pub fn toggle() {
+    with_state(|st| st.enabled = !st.enabled);
+}
+
+// without a font only the frame time graph is drawn
+pub fn set_font(font: Font) {
+    with_state(|st| st.font = Some(font));
+}
+
+// the top-left corner in pixels from the viewport's top-left
+pub fn set_position(x: f32, y: f32) {
+    with_state(|st| {
+        st.x = x;
+        st.y = y;
+    });
+}
+
+// call once per frame, after the scene and before context::end_frame, so the counters cover the whole
+// frame but not the overlay itself; frame times are measured between calls, even while disabled
+pub fn render() {
+    context::assert_initialized();
+
+    with_state(|st| {
+        let now = Instant::now();
+
+        if let Some(last) = st.last.replace(now) {
+            st.push_time(now.duration_since(last).as_secs_f32() * 1000.0);
+        }
+
+        if !st.enabled {
+            return;
+        }
+
+        let stats = context::frame_stats();
+        let viewport = context::viewport();
+        let layout = st.font.as_ref().map(|font| {
+            TextLayout::plain(font, &stats_text(st, &stats), Color::make(255, 255, 255, 255), &LayoutOptions::new())
+        });
+
+        let text_size = layout.as_ref().map_or((0.0, 0.0), |layout| layout.size());
+        let graph_width = HISTORY as f32 * BAR_WIDTH;
+        let panel = Rect::make(
+            st.x,
+            st.y,
+            graph_width.max(text_size.0) + PADDING * 2.0,
+            text_size.1 + GRAPH_HEIGHT + PADDING * if layout.is_some() { 3.0 } else { 2.0 },
+        );
+
+        let graph_bottom = panel.bottom() - PADDING;
+        let white = &*TEXTURE_WHITE;
+        let texel = Rect::make(0.0, 0.0, 1.0, 1.0);
+        let snapshot = context::snapshot();
+
+        context::disable(Feature::DepthTest);
+        context::disable(Feature::CullFace);
+        context::enable(Feature::Blend);
+        context::set_blend(BlendFunc::ALPHA);
+
+        let mut batch = st.batch.take().unwrap_or_else(|| {
+            let mut batch = SpriteBatch::new();
+
+            batch.set_sort(SpriteSort::Submission);
+            batch
+        });
+
+        batch.set_projection(math::ortho(0.0, viewport.width as f32, viewport.height as f32, 0.0, -1.0, 1.0));
+        batch.begin();
+        batch.draw(white, texel, panel, Color::make(0, 0, 0, 160), 0.0);
+
+        // 60 and 30 fps reference lines
+        for target in [1000.0 / 60.0, 1000.0 / 30.0].iter() {
+            let y = graph_bottom - target / GRAPH_MAX_MS * GRAPH_HEIGHT;
+
+            batch.draw(white, texel, Rect::make(panel.x + PADDING, y, graph_width, 1.0), Color::make(255, 255, 255, 64), 0.0);
+        }
+
+        // newest bar at the right edge
+        let offset = (HISTORY - st.times.len()) as f32 * BAR_WIDTH;
+
+        for (i, milliseconds) in st.history().enumerate() {
+            let height = (milliseconds / GRAPH_MAX_MS).min(1.0) * GRAPH_HEIGHT;
+            let x = panel.x + PADDING + offset + i as f32 * BAR_WIDTH;
+
+            batch.draw(white, texel, Rect::make(x, graph_bottom - height, BAR_WIDTH, height), bar_color(milliseconds), 0.0);
+        }
+
+        if let (Some(font), Some(layout)) = (st.font.as_ref(), layout.as_ref()) {
+            layout.draw(&mut batch, font, panel.x + PADDING, panel.y + PADDING);
+        }
+
+        batch.end();
+        st.batch = Some(batch);
+        context::restore(&snapshot);
+    });
+}
@@ -0,0 +1,531 @@
+use crate::builtin::{SpriteVertex, SHADER_SPRITE, TEXTURE_WHITE};
+use crate::color::Color;
+use crate::context::{self, BlendFunc, ContextBound, Feature};
+use crate::math;
+use crate::shader::{Shader, Stage, StageKind};
+use crate::texture::Texture;
+use crate::vbo::{AttributeKind, BufferMode, PrimitiveKind, UpdateStrategy, Vertex, VBO};
+
+use lazy_static::lazy_static;
+use vex::{Matrix4, Vector2, Vector3};
+
+const SRC_INSTANCED_VERTEX: &str = r#"
+    #version 330 core
+
+    uniform mat4 u_projection;
+    uniform vec3 u_right;
+    uniform vec3 u_up;
+
+    layout (location = 0) in vec3 a_pos;
+    layout (location = 1) in vec2 a_params;
+    layout (location = 2) in vec4 a_color;
+
+    out vec2 v_coord;
+    out vec4 v_color;
+
+    const vec2 CORNERS[4] = vec2[](vec2(-0.5, -0.5), vec2(0.5, -0.5), vec2(-0.5, 0.5), vec2(0.5, 0.5));
+
+    void main() {
+        vec2 corner = CORNERS[gl_VertexID];
+        float s = sin(a_params.y);
+        float c = cos(a_params.y);
+        vec2 offset = vec2(corner.x * c - corner.y * s, corner.x * s + corner.y * c) * a_params.x;
+
+        v_coord = vec2(corner.x + 0.5, 0.5 - corner.y);
+        v_color = a_color;
+        gl_Position = u_projection * vec4(a_pos + u_right * offset.x + u_up * offset.y, 1.0);
+    }
+"#;
+
+const SRC_INSTANCED_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_tex;
+
+    in vec2 v_coord;
+    in vec4 v_color;
+
+    out vec4 out_color;
+
+    void main() {
+        out_color = texture(u_tex, v_coord) * v_color;
+    }
+"#;
+
+lazy_static! {
+    static ref SHADER_INSTANCED: ContextBound<Shader> = {
+        context::assert_initialized();
+
+        let result = Shader::new(&vec![
+            Stage::with_name(StageKind::Vertex, "particles.vert", SRC_INSTANCED_VERTEX).unwrap(),
+            Stage::with_name(StageKind::Fragment, "particles.frag", SRC_INSTANCED_FRAGMENT).unwrap(),
+        ])
+        .unwrap();
+
+        result.set_uniform_i32("u_tex", 0);
+        ContextBound::new(result)
+    };
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ParticleBlend {
+    Alpha,
+    Additive,
+}
+
+impl ParticleBlend {
+    fn to_blend_func(&self) -> BlendFunc {
+        match self {
+            ParticleBlend::Alpha => BlendFunc::ALPHA,
+            ParticleBlend::Additive => BlendFunc::ADDITIVE,
+        }
+    }
+}
+
+// piecewise linear over a particle's normalized age (0 at spawn, 1 at death)
+#[derive(Debug, Clone, PartialEq)]
+pub struct Curve {
+    keys: Vec<(f32, f32)>,
+}
+
+impl Curve {
+    pub fn constant(value: f32) -> Curve {
+        Curve { keys: vec![(0.0, value)] }
+    }
+
+    pub fn linear(from: f32, to: f32) -> Curve {
+        Curve { keys: vec![(0.0, from), (1.0, to)] }
+    }
+
+    // keys may come in any order; they're sorted by time
+    pub fn from_keys(keys: &[(f32, f32)]) -> Curve {
+        let mut keys = keys.to_vec();
+
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Curve { keys }
+    }
+
+    pub fn evaluate(&self, t: f32) -> f32 {
+        evaluate(&self.keys, t, 1.0, |a, b, t| a + (b - a) * t)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorGradient {
+    keys: Vec<(f32, Color)>,
+}
+
+impl ColorGradient {
+    pub fn constant(color: Color) -> ColorGradient {
+        ColorGradient { keys: vec![(0.0, color)] }
+    }
+
+    pub fn linear(from: Color, to: Color) -> ColorGradient {
+        ColorGradient { keys: vec![(0.0, from), (1.0, to)] }
+    }
+
+    pub fn from_keys(keys: &[(f32, Color)]) -> ColorGradient {
+        let mut keys = keys.to_vec();
+
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+        ColorGradient { keys }
+    }
+
+    pub fn evaluate(&self, t: f32) -> Color {
+        evaluate(&self.keys, t, Color::WHITE, |a, b, t| a.lerp(&b, t))
+    }
+}
+
+fn evaluate<T: Copy, F: Fn(T, T, f32) -> T>(keys: &[(f32, T)], t: f32, fallback: T, lerp: F) -> T {
+    let (first, last) = match (keys.first(), keys.last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return fallback,
+    };
+
+    if t <= first.0 {
+        return first.1;
+    }
+
+    if t >= last.0 {
+        return last.1;
+    }
+
+    // nothing found means a NaN, in t or among the keys
+    let next = match keys.iter().position(|key| key.0 > t) {
+        Some(next) => next,
+        None => return last.1,
+    };
+    let (t0, a) = keys[next - 1];
+    let (t1, b) = keys[next];
+
+    lerp(a, b, (t - t0) / (t1 - t0))
+}
+
+#[derive(Debug, Clone)]
+pub struct EmitterConfig {
+    // particles per second while the emitter is running; bursts go through ParticleEmitter::burst
+    pub spawn_rate: f32,
+    pub max_particles: usize,
+    // seconds, picked uniformly between the two
+    pub lifetime: (f32, f32),
+    pub velocity: Vector3,
+    // each axis is randomized by up to +/- this much
+    pub velocity_spread: Vector3,
+    pub acceleration: Vector3,
+    // radians per second
+    pub rotation_speed: (f32, f32),
+    // scales the particle's own velocity, before acceleration is added
+    pub speed_over_life: Curve,
+    pub size_over_life: Curve,
+    pub color_over_life: ColorGradient,
+    pub blend: ParticleBlend,
+    // one instanced draw instead of four streamed vertices per particle
+    pub instanced: bool,
+}
+
+impl EmitterConfig {
+    pub fn new() -> EmitterConfig {
+        EmitterConfig {
+            spawn_rate: 10.0,
+            max_particles: 1000,
+            lifetime: (1.0, 1.0),
+            velocity: Vector3::make(0.0, 1.0, 0.0),
+            velocity_spread: Vector3::new(),
+            acceleration: Vector3::new(),
+            rotation_speed: (0.0, 0.0),
+            speed_over_life: Curve::constant(1.0),
+            size_over_life: Curve::constant(1.0),
+            color_over_life: ColorGradient::constant(Color::WHITE),
+            blend: ParticleBlend::Alpha,
+            instanced: false,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Particle {
+    pos: [f32; 3],
+    velocity: [f32; 3],
+    drift: [f32; 3],
+    age: f32,
+    lifetime: f32,
+    rotation: f32,
+    rotation_speed: f32,
+}
+
+#[repr(C, packed)]
+#[derive(Copy, Clone, Debug)]
+struct ParticleInstance {
+    pos: Vector3,
+    // size, rotation
+    params: Vector2,
+    color: Color,
+}
+
+impl Vertex for ParticleInstance {
+    fn attrs() -> Vec<(bool, usize, AttributeKind)> {
+        vec![
+            (false, 3, AttributeKind::Float),
+            (false, 2, AttributeKind::Float),
+            (true, 4, AttributeKind::UnsignedByte),
+        ]
+    }
+
+    fn new() -> ParticleInstance {
+        ParticleInstance {
+            pos: Vector3::new(),
+            params: Vector2::new(),
+            color: Color::new(),
+        }
+    }
+}
+
+// xorshift32; particles only need something cheap that doesn't repeat visibly
+struct Random(u32);
+
+impl Random {
+    fn next(&mut self) -> f32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+
+        (self.0 >> 8) as f32 / (1 << 24) as f32
+    }
+
+    fn range(&mut self, range: (f32, f32)) -> f32 {
+        range.0 + (range.1 - range.0) * self.next()
+    }
+
+    fn spread(&mut self, amount: f32) -> f32 {
+        (self.next() * 2.0 - 1.0) * amount
+    }
+}
+
+fn build_vbo(config: &EmitterConfig) -> VBO {
+    let capacity = config.max_particles.max(1);
+    let mut result = if config.instanced {
+        let instances = vec![<ParticleInstance as Vertex>::new(); capacity];
+        let result = VBO::new(BufferMode::StreamDraw, PrimitiveKind::TriangleStrip, &instances, None);
+
+        result.set_instanced(true);
+        result
+    } else {
+        let vertices = vec![<SpriteVertex as Vertex>::new(); capacity * 4];
+        let indices = (0..capacity as u32)
+            .flat_map(|i| {
+                let base = i * 4;
+
+                vec![base, base + 1, base + 2, base + 2, base + 3, base]
+            })
+            .collect::<Vec<u32>>();
+
+        VBO::new_indexed(BufferMode::StreamDraw, PrimitiveKind::Triangles, &vertices, &indices)
+    };
+
+    result.set_update_strategy(UpdateStrategy::Orphan).unwrap();
+    result
+}
+
+// simulated on the CPU and streamed to the GPU each render; particles live in world space, so moving
+// the emitter leaves existing ones behind
+pub struct ParticleEmitter {
+    config: EmitterConfig,
+    vbo: VBO,
+    particles: Vec<Particle>,
+    position: Vector3,
+    emitting: bool,
+    spawn_accumulator: f32,
+    random: Random,
+}
+
+impl ParticleEmitter {
+    pub fn new(config: EmitterConfig) -> ParticleEmitter {
+        context::assert_initialized();
+
+        ParticleEmitter {
+            vbo: build_vbo(&config),
+            particles: Vec::with_capacity(config.max_particles),
+            config,
+            position: Vector3::new(),
+            emitting: true,
+            spawn_accumulator: 0.0,
+            random: Random(0x2545_f491),
+        }
+    }
+
+    pub fn config(&self) -> &EmitterConfig {
+        &self.config
+    }
+
+    // changing max_particles or instanced rebuilds the buffer; live particles past the new limit die
+    pub fn set_config(&mut self, config: EmitterConfig) {
+        if config.max_particles != self.config.max_particles || config.instanced != self.config.instanced {
+            self.vbo = build_vbo(&config);
+            self.particles.truncate(config.max_particles);
+        }
+
+        self.config = config;
+    }
+
+    pub fn position(&self) -> Vector3 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: Vector3) {
+        self.position = position;
+    }
+
+    pub fn set_seed(&mut self, seed: u32) {
+        self.random = Random(seed.max(1));
+    }
+
+    // stopping lets the live particles finish their lifetimes
+    pub fn start(&mut self) {
+        self.emitting = true;
+    }
+
+    pub fn stop(&mut self) {
+        self.emitting = false;
+        self.spawn_accumulator = 0.0;
+    }
+
+    pub fn is_emitting(&self) -> bool {
+        self.emitting
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+    }
+
+    pub fn burst(&mut self, count: usize) {
+        for _ in 0..count {
+            if self.particles.len() >= self.config.max_particles {
+                break;
+            }
+
+            self.spawn();
+        }
+    }
+
+    fn spawn(&mut self) {
+        let config = &self.config;
+        let random = &mut self.random;
+        let velocity = [
+            config.velocity.x + random.spread(config.velocity_spread.x),
+            config.velocity.y + random.spread(config.velocity_spread.y),
+            config.velocity.z + random.spread(config.velocity_spread.z),
+        ];
+
+        let particle = Particle {
+            pos: math::to_array(&self.position),
+            velocity,
+            drift: [0.0; 3],
+            age: 0.0,
+            lifetime: random.range(config.lifetime).max(f32::EPSILON),
+            rotation: 0.0,
+            rotation_speed: random.range(config.rotation_speed),
+        };
+
+        self.particles.push(particle);
+    }
+
+    pub fn update(&mut self, elapsed_time: f32) {
+        let config = &self.config;
+        let acceleration = math::scale(math::to_array(&config.acceleration), elapsed_time);
+
+        for particle in self.particles.iter_mut() {
+            particle.age += elapsed_time;
+
+            let t = particle.age / particle.lifetime;
+            let speed = config.speed_over_life.evaluate(t);
+
+            particle.drift = math::add(particle.drift, acceleration);
+            particle.pos = math::add(particle.pos, math::scale(math::add(math::scale(particle.velocity, speed), particle.drift), elapsed_time));
+            particle.rotation += particle.rotation_speed * elapsed_time;
+        }
+
+        self.particles.retain(|particle| particle.age < particle.lifetime);
+
+        if !self.emitting {
+            return;
+        }
+
+        self.spawn_accumulator += self.config.spawn_rate * elapsed_time;
+
+        let count = self.spawn_accumulator.floor();
+
+        self.spawn_accumulator -= count;
+        self.burst(count as usize);
+    }
+
+    // billboards face the camera using the view's right and up axes; texture None draws plain quads.
+    // depth writes are off while drawing so particles don't cut holes in each other
+    pub fn render(&self, texture: Option<&Texture>, view: &Matrix4, projection: &Matrix4) {
+        context::assert_initialized();
+
+        if self.particles.is_empty() {
+            return;
+        }
+
+        let snapshot = context::snapshot();
+        let view_projection = math::multiply(projection, view);
+        let m = view.m;
+        let right = [m[0], m[4], m[8]];
+        let up = [m[1], m[5], m[9]];
+
+        context::enable(Feature::Blend);
+        context::set_blend(self.config.blend.to_blend_func());
+        context::set_depth_mask(false);
+
+        match texture {
+            Some(texture) => texture.bind(0),
+            None => TEXTURE_WHITE.bind(0),
+        }
+
+        if self.config.instanced {
+            self.render_instanced(&view_projection, right, up);
+        } else {
+            self.render_streamed(&view_projection, right, up);
+        }
+
+        context::restore(&snapshot);
+    }
+
+    fn render_instanced(&self, view_projection: &Matrix4, right: [f32; 3], up: [f32; 3]) {
+        let instances = self
+            .particles
+            .iter()
+            .map(|particle| {
+                let t = particle.age / particle.lifetime;
+
+                ParticleInstance {
+                    pos: math::to_vector(particle.pos),
+                    params: Vector2::make(self.config.size_over_life.evaluate(t), particle.rotation),
+                    color: self.config.color_over_life.evaluate(t),
+                }
+            })
+            .collect::<Vec<ParticleInstance>>();
+
+        self.vbo.write_vertices(&instances, 0).unwrap();
+
+        SHADER_INSTANCED.bind();
+        SHADER_INSTANCED.set_uniform_mat4("u_projection", view_projection);
+        SHADER_INSTANCED.set_uniform_vec3("u_right", &math::to_vector(right));
+        SHADER_INSTANCED.set_uniform_vec3("u_up", &math::to_vector(up));
+
+        self.vbo.render_instanced(4, instances.len());
+    }
+
+    fn render_streamed(&self, view_projection: &Matrix4, right: [f32; 3], up: [f32; 3]) {
+        let mut vertices = Vec::with_capacity(self.particles.len() * 4);
+
+        for particle in self.particles.iter() {
+            let t = particle.age / particle.lifetime;
+            let half = self.config.size_over_life.evaluate(t) * 0.5;
+            let color = self.config.color_over_life.evaluate(t);
+            let (sin, cos) = particle.rotation.sin_cos();
+
+            // the quad's own axes, rotated within the camera plane
+            let x = math::scale(math::add(math::scale(right, cos), math::scale(up, sin)), half);
+            let y = math::scale(math::sub(math::scale(up, cos), math::scale(right, sin)), half);
+
+            let corner = |sx: f32, sy: f32, u: f32, v: f32| {
+                let pos = math::add(particle.pos, math::add(math::scale(x, sx), math::scale(y, sy)));
+
+                SpriteVertex::from_parts(math::to_vector(pos), Vector2::make(u, v), color)
+            };
+
+            vertices.push(corner(-1.0, 1.0, 0.0, 0.0));
+            vertices.push(corner(1.0, 1.0, 1.0, 0.0));
+            vertices.push(corner(1.0, -1.0, 1.0, 1.0));
+            vertices.push(corner(-1.0, -1.0, 0.0, 1.0));
+        }
+
+        self.vbo.write_vertices(&vertices, 0).unwrap();
+
+        SHADER_SPRITE.bind();
+        SHADER_SPRITE.set_uniform_mat4("u_projection", view_projection);
+        SHADER_SPRITE.set_uniform_i32("u_tex", 0);
+
+        self.vbo.render_range(0, self.particles.len() * 6);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curves_tolerate_nan() {
+        // positive NaN sorts after every key
+        let curve = Curve::from_keys(&[(1.0, 2.0), (f32::NAN, 5.0), (0.0, 1.0)]);
+
+        assert_eq!(curve.evaluate(0.5), 1.5);
+        assert_eq!(curve.evaluate(f32::NAN), 5.0);
+        assert_eq!(Curve::linear(0.0, 1.0).evaluate(f32::NAN), 1.0);
+    }
+}
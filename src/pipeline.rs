@@ -0,0 +1,115 @@
+use crate::context::{self, Garbage};
+use crate::error::{Error, Result};
+use crate::multi_context;
+use crate::shader::{self, Shader, StageKind};
+
+use gl::types::*;
+use std::marker::PhantomData;
+use std::ptr;
+
+fn pipeline_log(handle: GLuint) -> String {
+    let mut len = 0;
+
+    unsafe { gl::GetProgramPipelineiv(handle, gl::INFO_LOG_LENGTH, &mut len) };
+
+    let mut log = vec![0u8; len.max(1) as usize];
+
+    unsafe {
+        gl::GetProgramPipelineInfoLog(handle, log.len() as GLsizei, ptr::null_mut(), log.as_mut_ptr() as *mut GLchar)
+    };
+
+    String::from_utf8_lossy(&log).trim_end_matches('\0').trim_end().to_string()
+}
+
+// combines stages from separable programs without linking every combination
+pub struct Pipeline {
+    handle: GLuint,
+    // pipelines aren't shared between contexts, so this is deleted in the one that made it
+    owner: Option<u64>,
+    _thread: context::ThreadMarker,
+}
+
+impl Pipeline {
+    pub fn new() -> Result<Pipeline> {
+        context::check_initialized()?;
+
+        if !gl::GenProgramPipelines::is_loaded() {
+            return Err(Error::Unsupported("program pipelines (GL 4.1 or ARB_separate_shader_objects)".to_string()));
+        }
+
+        let mut handle = 0;
+
+        unsafe { gl::GenProgramPipelines(1, &mut handle) };
+
+        Ok(Pipeline {
+            handle,
+            owner: multi_context::current_id(),
+            _thread: PhantomData,
+        })
+    }
+
+    // stages the program doesn't contain are left empty in the pipeline
+    pub fn set_stages(&mut self, kinds: &[StageKind], shader: &Shader) -> Result<()> {
+        context::check_initialized()?;
+
+        if !shader.is_separable() {
+            let msg = "only programs created with Shader::new_separable can be used in a Pipeline".to_string();
+
+            return Err(Error::InvalidStageCombination(msg));
+        }
+
+        let bits = kinds.iter().fold(0, |acc, kind| acc | kind.pipeline_bit());
+
+        unsafe { gl::UseProgramStages(self.handle, bits, shader.handle()) };
+
+        Ok(())
+    }
+
+    pub fn set_stage(&mut self, kind: StageKind, shader: &Shader) -> Result<()> {
+        self.set_stages(&[kind], shader)
+    }
+
+    pub fn clear_stage(&mut self, kind: StageKind) {
+        context::assert_initialized();
+
+        unsafe { gl::UseProgramStages(self.handle, kind.pipeline_bit(), 0) };
+    }
+
+    // catches mismatched interfaces between stages; worth calling once after assembling a new combination
+    pub fn validate(&self) -> Result<()> {
+        context::check_initialized()?;
+
+        let mut status = gl::FALSE as GLint;
+
+        unsafe {
+            gl::ValidateProgramPipeline(self.handle);
+            gl::GetProgramPipelineiv(self.handle, gl::VALIDATE_STATUS, &mut status);
+        }
+
+        if status != gl::TRUE as GLint {
+            return Err(Error::InvalidStageCombination(pipeline_log(self.handle)));
+        }
+
+        Ok(())
+    }
+
+    // a bound program takes precedence over the pipeline, and Shader's uniform setters bind their program,
+    // so set uniforms before binding the pipeline
+    pub fn bind(&self) {
+        context::assert_initialized();
+        shader::use_program(0);
+
+        unsafe { gl::BindProgramPipeline(self.handle) };
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.handle
+    }
+}
+
+impl Drop for Pipeline {
+    fn drop(&mut self) {
+        context::defer_delete_owned(self.owner, Garbage::ProgramPipeline(self.handle));
+        self.handle = 0;
+    }
+}
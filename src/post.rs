@@ -0,0 +1,543 @@
+use crate::builtin::BasicVertex;
+use crate::context::{self, Feature};
+use crate::error::Result;
+use crate::fbo::{DepthAttachment, Framebuffer};
+use crate::shader::{Shader, Stage, StageKind};
+use crate::texture::{self, Texture, TextureFormat};
+use crate::vbo::{BufferMode, PrimitiveKind, VBO};
+
+use vex::Vector2;
+
+pub(crate) const SRC_POST_VERTEX: &str = r#"
+    #version 330 core
+
+    layout (location = 0) in vec3 a_pos;
+
+    out vec2 v_uv;
+
+    void main() {
+        v_uv = a_pos.xy * 0.5 + 0.5;
+        gl_Position = vec4(a_pos.xy, 0.0, 1.0);
+    }
+"#;
+
+const SRC_COPY_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_input;
+
+    in vec2 v_uv;
+
+    out vec4 out_color;
+
+    void main() {
+        out_color = texture(u_input, v_uv);
+    }
+"#;
+
+const SRC_GAMMA_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_input;
+    uniform float u_gamma;
+
+    in vec2 v_uv;
+
+    out vec4 out_color;
+
+    void main() {
+        vec4 color = texture(u_input, v_uv);
+
+        out_color = vec4(pow(color.rgb, vec3(1.0 / u_gamma)), color.a);
+    }
+"#;
+
+// u_operator picks the curve (see TonemapOperator); the result is gamma-encoded for a non-sRGB target
+const SRC_TONEMAP_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_input;
+    uniform float u_exposure;
+    uniform float u_gamma;
+    uniform int u_operator;
+
+    in vec2 v_uv;
+
+    out vec4 out_color;
+
+    // Narkowicz's fit of the ACES filmic curve
+    vec3 aces(vec3 x) {
+        return clamp((x * (2.51 * x + 0.03)) / (x * (2.43 * x + 0.59) + 0.14), 0.0, 1.0);
+    }
+
+    void main() {
+        vec4 color = texture(u_input, v_uv);
+        vec3 hdr = color.rgb * u_exposure;
+        vec3 mapped = u_operator == 1 ? aces(hdr) : hdr / (hdr + 1.0);
+
+        out_color = vec4(pow(mapped, vec3(1.0 / u_gamma)), color.a);
+    }
+"#;
+
+// 9-tap gaussian folded into 5 linearly filtered fetches
+const SRC_BLUR_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_input;
+    uniform vec2 u_texel_size;
+    uniform vec2 u_direction;
+
+    in vec2 v_uv;
+
+    out vec4 out_color;
+
+    const float offsets[3] = float[](0.0, 1.3846153846, 3.2307692308);
+    const float weights[3] = float[](0.2270270270, 0.3162162162, 0.0702702703);
+
+    void main() {
+        vec2 step = u_direction * u_texel_size;
+        vec4 color = texture(u_input, v_uv) * weights[0];
+
+        for (int i = 1; i < 3; i++) {
+            color += texture(u_input, v_uv + step * offsets[i]) * weights[i];
+            color += texture(u_input, v_uv - step * offsets[i]) * weights[i];
+        }
+
+        out_color = color;
+    }
+"#;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TonemapOperator {
+    Reinhard,
+    Aces,
+}
+
+impl TonemapOperator {
+    fn to_uniform(&self) -> i32 {
+        match self {
+            TonemapOperator::Reinhard => 0,
+            TonemapOperator::Aces => 1,
+        }
+    }
+}
+
+// radius taps either side along u_direction, weighted by a gaussian with u_sigma
+const SRC_GAUSSIAN_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_input;
+    uniform vec2 u_texel_size;
+    uniform vec2 u_direction;
+    uniform int u_radius;
+    uniform float u_sigma;
+
+    in vec2 v_uv;
+
+    out vec4 out_color;
+
+    void main() {
+        vec2 step = u_direction * u_texel_size;
+        vec4 color = vec4(0.0);
+        float total = 0.0;
+
+        for (int i = -u_radius; i <= u_radius; i++) {
+            float weight = exp(-float(i * i) / (2.0 * u_sigma * u_sigma));
+
+            color += texture(u_input, v_uv + step * float(i)) * weight;
+            total += weight;
+        }
+
+        out_color = color / total;
+    }
+"#;
+
+// keeps what's brighter than u_threshold, fading in over u_knee so the cutoff doesn't show
+const SRC_THRESHOLD_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_input;
+    uniform float u_threshold;
+    uniform float u_knee;
+
+    in vec2 v_uv;
+
+    out vec4 out_color;
+
+    void main() {
+        vec3 color = texture(u_input, v_uv).rgb;
+        float brightness = max(color.r, max(color.g, color.b));
+        float soft = clamp(brightness - u_threshold + u_knee, 0.0, 2.0 * u_knee);
+
+        soft = soft * soft / (4.0 * u_knee + 0.0001);
+
+        float contribution = max(soft, brightness - u_threshold) / max(brightness, 0.0001);
+
+        out_color = vec4(color * contribution, 1.0);
+    }
+"#;
+
+const SRC_COMPOSITE_FRAGMENT: &str = r#"
+    #version 330 core
+
+    uniform sampler2D u_input;
+    uniform sampler2D u_bloom;
+    uniform float u_intensity;
+
+    in vec2 v_uv;
+
+    out vec4 out_color;
+
+    void main() {
+        vec4 color = texture(u_input, v_uv);
+
+        out_color = vec4(color.rgb + texture(u_bloom, v_uv).rgb * u_intensity, color.a);
+    }
+"#;
+
+// fragment shaders receive the source as u_input on unit 0, its texel size as u_texel_size, and v_uv
+pub struct PostPass {
+    shader: Shader,
+}
+
+impl PostPass {
+    pub fn new(fragment: &str) -> Result<PostPass> {
+        let shader = Shader::new(&vec![
+            Stage::with_name(StageKind::Vertex, "post vertex", SRC_POST_VERTEX)?,
+            Stage::new(StageKind::Fragment, fragment)?,
+        ])?;
+
+        shader.set_uniform_i32("u_input", 0);
+
+        Ok(PostPass { shader })
+    }
+
+    pub fn copy() -> Result<PostPass> {
+        PostPass::new(SRC_COPY_FRAGMENT)
+    }
+
+    pub fn gamma(gamma: f32) -> Result<PostPass> {
+        let result = PostPass::new(SRC_GAMMA_FRAGMENT)?;
+
+        result.shader.set_uniform_f32("u_gamma", gamma);
+        Ok(result)
+    }
+
+    // exposure scales the linear input before the curve; render into an sRGB target with a gamma of 1.0
+    pub fn tonemap(operator: TonemapOperator, exposure: f32) -> Result<PostPass> {
+        let result = PostPass::new(SRC_TONEMAP_FRAGMENT)?;
+
+        result.shader.set_uniform_f32("u_gamma", 2.2);
+        result.set_tonemap(operator, exposure);
+        Ok(result)
+    }
+
+    // only meaningful for passes made by tonemap
+    pub fn set_tonemap(&self, operator: TonemapOperator, exposure: f32) {
+        self.shader.set_uniform_i32("u_operator", operator.to_uniform());
+        self.shader.set_uniform_f32("u_exposure", exposure);
+    }
+
+    pub fn blur_horizontal() -> Result<PostPass> {
+        let result = PostPass::new(SRC_BLUR_FRAGMENT)?;
+
+        result.shader.set_uniform_vec2("u_direction", &Vector2::make(1.0, 0.0));
+        Ok(result)
+    }
+
+    pub fn blur_vertical() -> Result<PostPass> {
+        let result = PostPass::new(SRC_BLUR_FRAGMENT)?;
+
+        result.shader.set_uniform_vec2("u_direction", &Vector2::make(0.0, 1.0));
+        Ok(result)
+    }
+
+    pub fn shader(&self) -> &Shader {
+        &self.shader
+    }
+}
+
+// one oversized triangle covers the screen without the diagonal seam of a quad; pairs with SRC_POST_VERTEX
+pub(crate) fn fullscreen_triangle() -> VBO {
+    let vertices = vec![
+        BasicVertex::new(-1.0, -1.0, 0.0),
+        BasicVertex::new(3.0, -1.0, 0.0),
+        BasicVertex::new(-1.0, 3.0, 0.0),
+    ];
+
+    VBO::new(BufferMode::StaticDraw, PrimitiveKind::Triangles, &vertices, None)
+}
+
+pub struct PostProcess {
+    triangle: VBO,
+    targets: Vec<Framebuffer>,
+}
+
+impl PostProcess {
+    pub fn new() -> PostProcess {
+        PostProcess {
+            triangle: fullscreen_triangle(),
+            targets: Vec::new(),
+        }
+    }
+
+    // None draws into the default framebuffer using the current viewport
+    pub fn apply(&self, pass: &PostPass, input: &Texture, target: Option<&Framebuffer>) {
+        context::assert_initialized();
+
+        let previous_framebuffer = context::bound_framebuffer();
+        let viewport = context::viewport();
+        let depth_test = context::disable(Feature::DepthTest);
+        let blend = context::disable(Feature::Blend);
+
+        match target {
+            Some(framebuffer) => {
+                context::bind_framebuffer(framebuffer.handle());
+                context::set_viewport(0, 0, framebuffer.width() as u32, framebuffer.height() as u32);
+            },
+            None => context::bind_framebuffer(0),
+        }
+
+        {
+            let _texture = input.bind_scoped(0);
+            let texel_size = Vector2::make(1.0 / input.width() as f32, 1.0 / input.height() as f32);
+
+            pass.shader.set_uniform_vec2("u_texel_size", &texel_size);
+            pass.shader.bind();
+            self.triangle.render();
+        }
+
+        context::bind_framebuffer(previous_framebuffer);
+        context::set_viewport(viewport.x, viewport.y, viewport.width, viewport.height);
+
+        if depth_test {
+            context::enable(Feature::DepthTest);
+        }
+
+        if blend {
+            context::enable(Feature::Blend);
+        }
+    }
+
+    // intermediate results ping-pong between two framebuffers sized to the input
+    pub fn chain(&mut self, passes: &[&PostPass], input: &Texture, target: Option<&Framebuffer>) -> Result<()> {
+        if passes.is_empty() {
+            return Ok(());
+        }
+
+        self.ensure_targets(input.width(), input.height())?;
+
+        let (last, rest) = passes.split_last().unwrap();
+        let mut source = input;
+
+        for (i, pass) in rest.iter().enumerate() {
+            let framebuffer = &self.targets[i % 2];
+
+            self.apply(pass, source, Some(framebuffer));
+            source = framebuffer.color(0).unwrap();
+        }
+
+        self.apply(last, source, target);
+
+        Ok(())
+    }
+
+    fn ensure_targets(&mut self, width: usize, height: usize) -> Result<()> {
+        let matches = self.targets
+            .first()
+            .map_or(false, |framebuffer| framebuffer.width() == width && framebuffer.height() == height);
+
+        if !matches {
+            self.targets = vec![
+                Framebuffer::new(width, height, 1, DepthAttachment::None)?,
+                Framebuffer::new(width, height, 1, DepthAttachment::None)?,
+            ];
+        }
+
+        Ok(())
+    }
+}
+
+fn is_float_format(format: TextureFormat) -> bool {
+    format == TextureFormat::Rgba16F || format == TextureFormat::Rgba32F
+}
+
+// recreates target when its size or precision no longer fits; float inputs keep float intermediates
+fn ensure_target(target: &mut Option<Framebuffer>, width: usize, height: usize, hdr: bool) -> Result<()> {
+    let matches = target.as_ref().map_or(false, |framebuffer| {
+        let format = framebuffer.color(0).unwrap().format();
+
+        framebuffer.width() == width && framebuffer.height() == height && is_float_format(format) == hdr
+    });
+
+    if !matches {
+        *target = Some(if hdr {
+            Framebuffer::new_hdr(width, height, 1, DepthAttachment::None)?
+        } else {
+            Framebuffer::new(width, height, 1, DepthAttachment::None)?
+        });
+    }
+
+    Ok(())
+}
+
+// separable blur: each iteration is a horizontal then a vertical pass between two internal targets
+pub struct GaussianBlur {
+    horizontal: PostPass,
+    vertical: PostPass,
+    post: PostProcess,
+    targets: [Option<Framebuffer>; 2],
+    radius: usize,
+    iterations: usize,
+}
+
+impl GaussianBlur {
+    // radius is in texels either side of the center; more iterations widen the blur further
+    pub fn new(radius: usize, iterations: usize) -> Result<GaussianBlur> {
+        let horizontal = PostPass::new(SRC_GAUSSIAN_FRAGMENT)?;
+        let vertical = PostPass::new(SRC_GAUSSIAN_FRAGMENT)?;
+
+        horizontal.shader.set_uniform_vec2("u_direction", &Vector2::make(1.0, 0.0));
+        vertical.shader.set_uniform_vec2("u_direction", &Vector2::make(0.0, 1.0));
+
+        let mut result = GaussianBlur {
+            horizontal,
+            vertical,
+            post: PostProcess::new(),
+            targets: [None, None],
+            radius: 0,
+            iterations: 1,
+        };
+
+        result.set_radius(radius);
+        result.set_iterations(iterations);
+        Ok(result)
+    }
+
+    pub fn set_radius(&mut self, radius: usize) {
+        // most of the curve fits within the radius at this sigma
+        let sigma = (radius as f32 / 2.0).max(0.5);
+
+        for pass in [&self.horizontal, &self.vertical].iter() {
+            pass.shader.set_uniform_i32("u_radius", radius as i32);
+            pass.shader.set_uniform_f32("u_sigma", sigma);
+        }
+
+        self.radius = radius;
+    }
+
+    pub fn radius(&self) -> usize {
+        self.radius
+    }
+
+    pub fn set_iterations(&mut self, iterations: usize) {
+        self.iterations = iterations.max(1);
+    }
+
+    pub fn iterations(&self) -> usize {
+        self.iterations
+    }
+
+    // the result lives in an internal target and stays valid until the next apply
+    pub fn apply(&mut self, input: &Texture) -> Result<&Texture> {
+        let hdr = is_float_format(input.format());
+
+        for target in self.targets.iter_mut() {
+            ensure_target(target, input.width(), input.height(), hdr)?;
+        }
+
+        let (first, second) = match &self.targets {
+            [Some(first), Some(second)] => (first, second),
+            _ => unreachable!(),
+        };
+
+        let mut source = input;
+
+        for _ in 0..self.iterations {
+            self.post.apply(&self.horizontal, source, Some(first));
+            self.post.apply(&self.vertical, first.color(0).unwrap(), Some(second));
+            source = second.color(0).unwrap();
+        }
+
+        // iterations is at least 1, so this is always the last vertical pass
+        Ok(second.color(0).unwrap())
+    }
+}
+
+// extracts the bright parts of a scene at half resolution, blurs them and adds them back on top
+pub struct Bloom {
+    threshold_pass: PostPass,
+    composite: PostPass,
+    blur: GaussianBlur,
+    post: PostProcess,
+    bright: Option<Framebuffer>,
+    threshold: f32,
+    intensity: f32,
+}
+
+impl Bloom {
+    pub fn new() -> Result<Bloom> {
+        let threshold_pass = PostPass::new(SRC_THRESHOLD_FRAGMENT)?;
+        let composite = PostPass::new(SRC_COMPOSITE_FRAGMENT)?;
+
+        composite.shader.set_uniform_i32("u_bloom", 1);
+
+        let mut result = Bloom {
+            threshold_pass,
+            composite,
+            blur: GaussianBlur::new(8, 2)?,
+            post: PostProcess::new(),
+            bright: None,
+            threshold: 0.0,
+            intensity: 0.0,
+        };
+
+        result.set_threshold(1.0);
+        result.set_intensity(0.8);
+        Ok(result)
+    }
+
+    // in linear units: 1.0 blooms only what an HDR scene pushes past white
+    pub fn set_threshold(&mut self, threshold: f32) {
+        self.threshold = threshold.max(0.0);
+        self.threshold_pass.shader.set_uniform_f32("u_threshold", self.threshold);
+        self.threshold_pass.shader.set_uniform_f32("u_knee", self.threshold * 0.5);
+    }
+
+    pub fn threshold(&self) -> f32 {
+        self.threshold
+    }
+
+    pub fn set_intensity(&mut self, intensity: f32) {
+        self.intensity = intensity.max(0.0);
+        self.composite.shader.set_uniform_f32("u_intensity", self.intensity);
+    }
+
+    pub fn intensity(&self) -> f32 {
+        self.intensity
+    }
+
+    // radius and iterations of the blur spreading the glow
+    pub fn blur_mut(&mut self) -> &mut GaussianBlur {
+        &mut self.blur
+    }
+
+    // None composites into the default framebuffer using the current viewport; uses texture unit 1
+    pub fn apply(&mut self, input: &Texture, target: Option<&Framebuffer>) -> Result<()> {
+        let width = (input.width() / 2).max(1);
+        let height = (input.height() / 2).max(1);
+
+        ensure_target(&mut self.bright, width, height, is_float_format(input.format()))?;
+
+        let bright = self.bright.as_ref().unwrap();
+
+        self.post.apply(&self.threshold_pass, input, Some(bright));
+
+        let glow = self.blur.apply(bright.color(0).unwrap())?;
+
+        texture::bind_handle(1, glow.handle());
+        self.post.apply(&self.composite, input, target);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+use crate::context;
+use crate::query::{Query, QueryKind};
+
+use std::cell::RefCell;
+use std::ffi::CString;
+use std::marker::PhantomData;
+
+// results are read back this many frames late so the CPU never waits on the GPU
+const FRAME_LATENCY: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct ScopeTiming {
+    pub name: String,
+    pub depth: usize,
+    pub milliseconds: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameTimings {
+    pub frame: u64,
+    pub scopes: Vec<ScopeTiming>,
+}
+
+impl FrameTimings {
+    // sums the outermost scopes only, since nested ones are already included in their parents
+    pub fn total_milliseconds(&self) -> f64 {
+        self.scopes
+            .iter()
+            .filter(|scope| scope.depth == 0)
+            .map(|scope| scope.milliseconds)
+            .sum()
+    }
+
+    pub fn find(&self, name: &str) -> Option<&ScopeTiming> {
+        self.scopes.iter().find(|scope| scope.name == name)
+    }
+}
+
+struct Record {
+    name: String,
+    depth: usize,
+    start: Query,
+    end: Option<Query>,
+}
+
+struct Frame {
+    number: u64,
+    records: Vec<Record>,
+}
+
+struct State {
+    frames: Vec<Frame>,
+    current: usize,
+    frame: u64,
+    depth: usize,
+    spare: Vec<Query>,
+    latest: Option<FrameTimings>,
+}
+
+impl State {
+    fn new() -> State {
+        State {
+            frames: (0..FRAME_LATENCY).map(|_| Frame { number: 0, records: Vec::new() }).collect(),
+            current: 0,
+            frame: 0,
+            depth: 0,
+            spare: Vec::new(),
+            latest: None,
+        }
+    }
+
+    fn query(&mut self) -> Query {
+        self.spare.pop().unwrap_or_else(|| Query::new(QueryKind::Timestamp))
+    }
+
+    fn resolve(&mut self, index: usize) {
+        let records = std::mem::take(&mut self.frames[index].records);
+        let ready = records.iter().all(|record| {
+            record.end.as_ref().map_or(false, |end| end.is_available()) && record.start.is_available()
+        });
+
+        if ready && !records.is_empty() {
+            let scopes = records
+                .iter()
+                .map(|record| {
+                    let start = record.start.result().unwrap();
+                    let end = record.end.as_ref().and_then(|end| end.result()).unwrap();
+
+                    ScopeTiming {
+                        name: record.name.clone(),
+                        depth: record.depth,
+                        milliseconds: end.saturating_sub(start) as f64 / 1_000_000.0,
+                    }
+                })
+                .collect();
+
+            self.latest = Some(FrameTimings {
+                frame: self.frames[index].number,
+                scopes,
+            });
+        }
+
+        for record in records {
+            self.spare.push(record.start);
+            self.spare.extend(record.end);
+        }
+    }
+}
+
+thread_local! {
+    // queries belong to the GL thread, so profiling state is per thread
+    static INTERNAL_STATE: RefCell<Option<State>> = RefCell::new(None);
+}
+
+fn with_state<R, F: FnOnce(&mut State) -> R>(f: F) -> R {
+    INTERNAL_STATE.with(|cell| {
+        let mut slot = cell.borrow_mut();
+
+        f(slot.get_or_insert_with(State::new))
+    })
+}
+
+// drops this thread's GL objects while the context is still current, for context::shutdown
+#[cfg(feature = "headless")]
+pub(crate) fn reset() {
+    let state = INTERNAL_STATE.with(|cell| cell.borrow_mut().take());
+
+    drop(state);
+}
+
+// call once per frame, before any scopes; collects the results from FRAME_LATENCY frames ago
+pub fn begin_frame() {
+    context::assert_initialized();
+
+    with_state(|st| {
+        st.frame += 1;
+        st.current = (st.current + 1) % FRAME_LATENCY;
+        st.depth = 0;
+
+        let current = st.current;
+
+        st.resolve(current);
+        st.frames[current].number = st.frame;
+    });
+}
+
+pub fn latest() -> Option<FrameTimings> {
+    with_state(|st| st.latest.clone())
+}
+
+#[must_use]
+pub struct ProfileScope {
+    frame: usize,
+    index: usize,
+    _thread: context::ThreadMarker,
+}
+
+impl Drop for ProfileScope {
+    fn drop(&mut self) {
+        with_state(|st| {
+            let mut end = st.query();
+
+            end.record_timestamp();
+            st.depth = st.depth.saturating_sub(1);
+
+            if let Some(record) = st.frames[self.frame].records.get_mut(self.index) {
+                record.end = Some(end);
+            }
+        });
+
+        if gl::PopDebugGroup::is_loaded() {
+            unsafe { gl::PopDebugGroup() };
+        }
+    }
+}
+
+// times the GPU work issued until the guard drops, and labels it as a debug group in captures
+pub fn scope(name: &str) -> ProfileScope {
+    context::assert_initialized();
+
+    if gl::PushDebugGroup::is_loaded() {
+        let label = CString::new(name).unwrap();
+
+        unsafe { gl::PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, 0, -1, label.as_ptr()) };
+    }
+
+    with_state(|st| {
+        let mut start = st.query();
+        let depth = st.depth;
+        let frame = st.current;
+
+        start.record_timestamp();
+        st.depth += 1;
+        st.frames[frame].records.push(Record {
+            name: name.to_string(),
+            depth,
+            start,
+            end: None,
+        });
+
+        ProfileScope {
+            frame,
+            index: st.frames[frame].records.len() - 1,
+            _thread: PhantomData,
+        }
+    })
+}
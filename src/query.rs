@@ -0,0 +1,170 @@
+use crate::context::{self, Garbage};
+use crate::multi_context;
+
+use gl::types::*;
+use std::marker::PhantomData;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum QueryKind {
+    SamplesPassed,
+    AnySamplesPassed,
+    PrimitivesGenerated,
+    TimeElapsed,
+    Timestamp,
+}
+
+impl QueryKind {
+    pub fn get_native(&self) -> GLenum {
+        match self {
+            QueryKind::SamplesPassed => gl::SAMPLES_PASSED,
+            QueryKind::AnySamplesPassed => gl::ANY_SAMPLES_PASSED,
+            QueryKind::PrimitivesGenerated => gl::PRIMITIVES_GENERATED,
+            QueryKind::TimeElapsed => gl::TIME_ELAPSED,
+            QueryKind::Timestamp => gl::TIMESTAMP,
+        }
+    }
+}
+
+pub struct Query {
+    kind: QueryKind,
+    handle: GLuint,
+    active: bool,
+    issued: bool,
+    // queries aren't shared between contexts, so this is deleted in the one that made it
+    owner: Option<u64>,
+    _thread: context::ThreadMarker,
+}
+
+impl Query {
+    pub fn new(kind: QueryKind) -> Query {
+        context::assert_initialized();
+
+        let mut handle = 0;
+
+        unsafe { gl::GenQueries(1, &mut handle) };
+
+        Query {
+            kind,
+            handle,
+            active: false,
+            issued: false,
+            owner: multi_context::current_id(),
+            _thread: PhantomData,
+        }
+    }
+
+    pub fn begin(&mut self) {
+        context::assert_initialized();
+
+        assert!(self.kind != QueryKind::Timestamp, "timestamp queries are recorded, not begun");
+
+        assert!(!self.active, "Query::begin called on a query that is already active");
+
+        unsafe { gl::BeginQuery(self.kind.get_native(), self.handle) };
+
+        self.active = true;
+    }
+
+    pub fn end(&mut self) {
+        context::assert_initialized();
+
+        assert!(self.active, "Query::end called on a query that was never begun");
+
+        unsafe { gl::EndQuery(self.kind.get_native()) };
+
+        self.active = false;
+        self.issued = true;
+    }
+
+    // captures the GPU clock once every previously issued command has completed
+    pub fn record_timestamp(&mut self) {
+        context::assert_initialized();
+
+        assert!(self.kind == QueryKind::Timestamp, "Query::record_timestamp requires a Timestamp query");
+
+        unsafe { gl::QueryCounter(self.handle, gl::TIMESTAMP) };
+
+        self.issued = true;
+    }
+
+    pub fn scope(&mut self) -> QueryScope<'_> {
+        self.begin();
+
+        QueryScope { query: self }
+    }
+
+    pub fn is_available(&self) -> bool {
+        context::assert_initialized();
+
+        if !self.issued || self.active {
+            return false;
+        }
+
+        let mut available = 0;
+
+        unsafe { gl::GetQueryObjectuiv(self.handle, gl::QUERY_RESULT_AVAILABLE, &mut available) };
+
+        available != 0
+    }
+
+    pub fn try_result(&self) -> Option<u64> {
+        if self.is_available() {
+            Some(self.fetch())
+        } else {
+            None
+        }
+    }
+
+    pub fn result(&self) -> Option<u64> {
+        context::assert_initialized();
+
+        if !self.issued || self.active {
+            return None;
+        }
+
+        Some(self.fetch())
+    }
+
+    fn fetch(&self) -> u64 {
+        let mut value = 0;
+
+        unsafe { gl::GetQueryObjectui64v(self.handle, gl::QUERY_RESULT, &mut value) };
+
+        value
+    }
+
+    pub fn kind(&self) -> QueryKind {
+        self.kind
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active
+    }
+
+    // whether the query has been ended or recorded at least once
+    pub fn is_issued(&self) -> bool {
+        self.issued
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.handle
+    }
+}
+
+impl Drop for Query {
+    fn drop(&mut self) {
+        context::defer_delete_owned(self.owner, Garbage::Query(self.handle));
+        self.handle = 0;
+    }
+}
+
+#[must_use]
+pub struct QueryScope<'a> {
+    query: &'a mut Query,
+}
+
+impl<'a> Drop for QueryScope<'a> {
+    fn drop(&mut self) {
+        self.query.end();
+    }
+}
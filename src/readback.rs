@@ -0,0 +1,145 @@
+use crate::context::{self, Garbage};
+use crate::texture::TextureFormat;
+
+use gl::types::*;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::ptr;
+use std::slice;
+
+pub fn read_pixels(x: i32, y: i32, width: usize, height: usize, format: TextureFormat) -> Vec<u8> {
+    context::assert_initialized();
+
+    let mut buf = vec![0u8; width * height * format.bytes_per_pixel()];
+
+    if buf.is_empty() {
+        return buf;
+    }
+
+    unsafe {
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            x,
+            y,
+            width as GLsizei,
+            height as GLsizei,
+            format.pixel_format(),
+            format.pixel_type(),
+            buf.as_mut_ptr() as *mut c_void,
+        );
+    }
+
+    buf
+}
+
+pub fn read_pixels_async(x: i32, y: i32, width: usize, height: usize, format: TextureFormat) -> PendingReadback {
+    context::assert_initialized();
+
+    let size = width * height * format.bytes_per_pixel();
+    let mut handle = 0;
+
+    let fence = unsafe {
+        gl::GenBuffers(1, &mut handle);
+        gl::BindBuffer(gl::PIXEL_PACK_BUFFER, handle);
+        gl::BufferData(gl::PIXEL_PACK_BUFFER, size as GLsizeiptr, ptr::null(), gl::STREAM_READ);
+        gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+        gl::ReadPixels(
+            x,
+            y,
+            width as GLsizei,
+            height as GLsizei,
+            format.pixel_format(),
+            format.pixel_type(),
+            ptr::null_mut(),
+        );
+        gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+
+        gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)
+    };
+
+    PendingReadback {
+        handle,
+        fence,
+        size,
+        width,
+        height,
+        _thread: PhantomData,
+    }
+}
+
+pub struct PendingReadback {
+    handle: GLuint,
+    fence: GLsync,
+    size: usize,
+    width: usize,
+    height: usize,
+    _thread: context::ThreadMarker,
+}
+
+impl PendingReadback {
+    pub fn is_ready(&self) -> bool {
+        context::assert_initialized();
+
+        let status = unsafe { gl::ClientWaitSync(self.fence, 0, 0) };
+
+        status == gl::ALREADY_SIGNALED || status == gl::CONDITION_SATISFIED
+    }
+
+    pub fn try_take(self) -> Result<Vec<u8>, PendingReadback> {
+        if self.is_ready() {
+            Ok(self.download())
+        } else {
+            Err(self)
+        }
+    }
+
+    pub fn wait(self) -> Vec<u8> {
+        context::assert_initialized();
+
+        unsafe {
+            gl::ClientWaitSync(self.fence, gl::SYNC_FLUSH_COMMANDS_BIT, gl::TIMEOUT_IGNORED);
+        }
+
+        self.download()
+    }
+
+    fn download(&self) -> Vec<u8> {
+        let mut buf = vec![0u8; self.size];
+
+        if self.size == 0 {
+            return buf;
+        }
+
+        unsafe {
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, self.handle);
+
+            let src = gl::MapBufferRange(gl::PIXEL_PACK_BUFFER, 0, self.size as GLsizeiptr, gl::MAP_READ_BIT);
+
+            if !src.is_null() {
+                buf.copy_from_slice(slice::from_raw_parts(src as *const u8, self.size));
+                gl::UnmapBuffer(gl::PIXEL_PACK_BUFFER);
+            }
+
+            gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+        }
+
+        buf
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+}
+
+impl Drop for PendingReadback {
+    fn drop(&mut self) {
+        context::defer_delete(Garbage::Sync(self.fence as usize));
+        context::defer_delete(Garbage::Buffer(self.handle));
+
+        self.handle = 0;
+    }
+}
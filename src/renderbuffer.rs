@@ -0,0 +1,153 @@
+use crate::context::{self, Garbage};
+use crate::debug;
+use crate::error::{Error, Result};
+use crate::fbo;
+use crate::texture::TextureFormat;
+
+use gl::types::*;
+use std::fmt;
+use std::marker::PhantomData;
+
+pub struct Renderbuffer {
+    handle: GLuint,
+    format: TextureFormat,
+    samples: usize,
+    width: usize,
+    height: usize,
+    label: Option<String>,
+    _thread: context::ThreadMarker,
+}
+
+impl fmt::Debug for Renderbuffer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Renderbuffer")
+            .field("handle", &self.handle)
+            .field("label", &self.label)
+            .field("format", &self.format)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .finish()
+    }
+}
+
+impl Renderbuffer {
+    pub fn new(format: TextureFormat, width: usize, height: usize) -> Result<Renderbuffer> {
+        Renderbuffer::new_multisampled(format, width, height, 0)
+    }
+
+    pub fn new_multisampled(format: TextureFormat, width: usize, height: usize, samples: usize) -> Result<Renderbuffer> {
+        context::check_initialized()?;
+
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        let max = fbo::max_samples();
+
+        if samples > max {
+            return Err(Error::Unsupported(format!("{} samples (the driver allows up to {})", samples, max)));
+        }
+
+        let mut handle = 0;
+
+        unsafe { gl::GenRenderbuffers(1, &mut handle) };
+
+        let result = Renderbuffer {
+            handle,
+            format,
+            samples,
+            width,
+            height,
+            label: None,
+            _thread: PhantomData,
+        };
+
+        result.allocate();
+
+        Ok(result)
+    }
+
+    fn allocate(&self) {
+        unsafe {
+            gl::BindRenderbuffer(gl::RENDERBUFFER, self.handle);
+            gl::RenderbufferStorageMultisample(
+                gl::RENDERBUFFER,
+                self.samples as GLsizei,
+                self.format.get_native(),
+                self.width as GLsizei,
+                self.height as GLsizei,
+            );
+            gl::BindRenderbuffer(gl::RENDERBUFFER, 0);
+        }
+    }
+
+    // the contents are lost; framebuffers it's attached to pick up the new storage automatically
+    pub fn resize(&mut self, width: usize, height: usize) -> Result<()> {
+        context::check_initialized()?;
+
+        if width == 0 || height == 0 {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        if width != self.width || height != self.height {
+            self.width = width;
+            self.height = height;
+            self.allocate();
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn attachment(&self) -> GLenum {
+        match self.format {
+            TextureFormat::Depth24Stencil8 => gl::DEPTH_STENCIL_ATTACHMENT,
+            TextureFormat::Depth24 | TextureFormat::Depth32F => gl::DEPTH_ATTACHMENT,
+            _ => gl::COLOR_ATTACHMENT0,
+        }
+    }
+
+    // expects the target framebuffer to be bound
+    pub(crate) fn attach(&self, attachment: GLenum) {
+        unsafe { gl::FramebufferRenderbuffer(gl::FRAMEBUFFER, attachment, gl::RENDERBUFFER, self.handle) };
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    pub fn is_multisampled(&self) -> bool {
+        self.samples > 0
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        debug::label_object(gl::RENDERBUFFER, self.handle, label);
+        self.label = Some(label.to_string());
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.handle
+    }
+}
+
+impl Drop for Renderbuffer {
+    fn drop(&mut self) {
+        context::defer_delete(Garbage::Renderbuffer(self.handle));
+        self.handle = 0;
+    }
+}
@@ -0,0 +1,168 @@
+use crate::builtin::TEXTURE_WHITE;
+use crate::color::Color;
+use crate::context;
+use crate::math;
+use crate::rect::Rect;
+use crate::sprite::{SpriteBatch, SpriteSort};
+use crate::texture::Texture;
+
+use std::cell::RefCell;
+use vex::{Matrix4, Vector2};
+
+struct State {
+    batch: SpriteBatch,
+    // None follows the viewport, in pixels with a top-left origin
+    projection: Option<Matrix4>,
+    drawing: bool,
+    pixel_snap: bool,
+    // the batch's projection, its inverse and the viewport size, set by begin for snapping
+    current: Matrix4,
+    inverse: Option<Matrix4>,
+    screen: (f32, f32),
+}
+
+impl State {
+    fn new() -> State {
+        let mut batch = SpriteBatch::new();
+
+        // draw calls come out in the order they were made, merging runs that share a texture
+        batch.set_sort(SpriteSort::Submission);
+
+        State {
+            batch,
+            projection: None,
+            drawing: false,
+            pixel_snap: false,
+            current: math::identity(),
+            inverse: None,
+            screen: (1.0, 1.0),
+        }
+    }
+
+    fn begin(&mut self) {
+        if self.drawing {
+            return;
+        }
+
+        let viewport = context::viewport();
+        let projection = self.projection.unwrap_or_else(|| {
+            math::ortho(0.0, viewport.width as f32, viewport.height as f32, 0.0, -1.0, 1.0)
+        });
+
+        self.current = projection;
+        self.inverse = math::inverse(&projection);
+        self.screen = (viewport.width.max(1) as f32, viewport.height.max(1) as f32);
+        self.batch.set_projection(projection);
+        self.batch.begin();
+        self.drawing = true;
+    }
+
+    // moves the rect so its top-left corner lands on a whole pixel, keeping its size, so sprites at
+    // fractional positions don't shimmer as they move
+    fn snap(&self, rect: Rect) -> Rect {
+        let inverse = match (self.pixel_snap, self.inverse) {
+            (true, Some(inverse)) => inverse,
+            _ => return rect,
+        };
+
+        let (width, height) = self.screen;
+        let clip = math::transform(&self.current, [rect.x, rect.y, 0.0]);
+        let x = ((clip[0] / clip[3] * 0.5 + 0.5) * width).round() / width * 2.0 - 1.0;
+        let y = ((clip[1] / clip[3] * 0.5 + 0.5) * height).round() / height * 2.0 - 1.0;
+        let p = math::transform(&inverse, [x, y, clip[2] / clip[3]]);
+
+        Rect::make(p[0] / p[3], p[1] / p[3], rect.width, rect.height)
+    }
+
+    fn end(&mut self) {
+        if self.drawing {
+            self.batch.end();
+            self.drawing = false;
+        }
+    }
+}
+
+thread_local! {
+    // the batch's VBO belongs to the GL thread, so the queue is per thread
+    static INTERNAL_STATE: RefCell<Option<State>> = RefCell::new(None);
+}
+
+fn with_state<R, F: FnOnce(&mut State) -> R>(f: F) -> R {
+    context::assert_initialized();
+
+    INTERNAL_STATE.with(|cell| {
+        let mut slot = cell.borrow_mut();
+
+        f(slot.get_or_insert_with(State::new))
+    })
+}
+
+// drops this thread's GL objects while the context is still current, for context::shutdown
+#[cfg(feature = "headless")]
+pub(crate) fn reset() {
+    let state = INTERNAL_STATE.with(|cell| cell.borrow_mut().take());
+
+    drop(state);
+}
+
+// applies from the next batch on; reset_projection goes back to tracking the viewport
+pub fn set_projection(projection: Matrix4) {
+    with_state(|st| st.projection = Some(projection));
+}
+
+pub fn reset_projection() {
+    with_state(|st| st.projection = None);
+}
+
+// rounds quad positions to whole viewport pixels through the projection, so it also holds for zoomed
+// or panned cameras; rotated quads snap their unrotated top-left corner
+pub fn set_pixel_snap(enabled: bool) {
+    with_state(|st| st.pixel_snap = enabled);
+}
+
+pub fn pixel_snap() -> bool {
+    with_state(|st| st.pixel_snap)
+}
+
+// higher layers draw on top of lower ones regardless of call order; goes back to 0 after each flush
+pub fn set_layer(layer: i32) {
+    with_state(|st| {
+        st.begin();
+        st.batch.set_layer(layer);
+    });
+}
+
+// position is the top-left corner before rotation, which turns the quad about its center; uv_rect is
+// normalized, None samples the whole texture
+pub fn draw_quad(texture: &Texture, position: Vector2, size: Vector2, rotation: f32, color: Color, uv_rect: Option<Rect>) {
+    let (width, height) = (texture.width() as f32, texture.height() as f32);
+    let src_rect = match uv_rect {
+        Some(uv) => Rect::make(uv.x * width, uv.y * height, uv.width * width, uv.height * height),
+        None => Rect::make(0.0, 0.0, width, height),
+    };
+
+    let dst_rect = Rect::make(position.x, position.y, size.x, size.y);
+
+    with_state(|st| {
+        st.begin();
+        let dst_rect = st.snap(dst_rect);
+
+        st.batch.draw(texture, src_rect, dst_rect, color, rotation);
+    });
+}
+
+pub fn draw_rect(color: Color, rect: Rect) {
+    let texture = &*TEXTURE_WHITE;
+
+    with_state(|st| {
+        st.begin();
+        let rect = st.snap(rect);
+
+        st.batch.draw(texture, Rect::make(0.0, 0.0, 1.0, 1.0), rect, color, 0.0);
+    });
+}
+
+// draws everything queued since the last flush, in submission order; blending is left to the caller
+pub fn flush() {
+    with_state(|st| st.end());
+}
@@ -0,0 +1,120 @@
+use crate::color::Color;
+use crate::context::{self, Garbage};
+use crate::texture::{ClampMode, MagFilter, MinFilter, WrapCoord};
+
+use gl::types::*;
+use std::marker::PhantomData;
+
+pub struct Sampler {
+    handle: GLuint,
+    s_clamp: ClampMode,
+    t_clamp: ClampMode,
+    r_clamp: ClampMode,
+    min_filter: MinFilter,
+    mag_filter: MagFilter,
+    border_color: Color,
+    _thread: context::ThreadMarker,
+}
+
+impl Sampler {
+    pub fn new() -> Sampler {
+        context::assert_initialized();
+
+        let mut handle = 0 as GLuint;
+        let clamp = ClampMode::Edge;
+        let min_filter = MinFilter::Nearest;
+        let mag_filter = MagFilter::Nearest;
+
+        unsafe {
+            gl::GenSamplers(1, &mut handle);
+            gl::SamplerParameteri(handle, gl::TEXTURE_WRAP_S, clamp.get_native() as i32);
+            gl::SamplerParameteri(handle, gl::TEXTURE_WRAP_T, clamp.get_native() as i32);
+            gl::SamplerParameteri(handle, gl::TEXTURE_WRAP_R, clamp.get_native() as i32);
+            gl::SamplerParameteri(handle, gl::TEXTURE_MIN_FILTER, min_filter.get_native() as i32);
+            gl::SamplerParameteri(handle, gl::TEXTURE_MAG_FILTER, mag_filter.get_native() as i32);
+        }
+
+        Sampler {
+            handle,
+            s_clamp: clamp,
+            t_clamp: clamp,
+            r_clamp: clamp,
+            min_filter,
+            mag_filter,
+            border_color: Color::TRANSPARENT,
+            _thread: PhantomData,
+        }
+    }
+
+    pub fn bind(&self, unit: GLuint) {
+        context::assert_initialized();
+
+        unsafe { gl::BindSampler(unit, self.handle) };
+    }
+
+    pub fn unbind(unit: GLuint) {
+        context::assert_initialized();
+
+        unsafe { gl::BindSampler(unit, 0) };
+    }
+
+    pub fn set_clamp(&mut self, coord: WrapCoord, mode: ClampMode) {
+        unsafe { gl::SamplerParameteri(self.handle, coord.get_native(), mode.get_native() as i32) };
+
+        match coord {
+            WrapCoord::S => self.s_clamp = mode,
+            WrapCoord::T => self.t_clamp = mode,
+            WrapCoord::R => self.r_clamp = mode,
+        }
+    }
+
+    pub fn set_min_filter(&mut self, filter: MinFilter) {
+        unsafe { gl::SamplerParameteri(self.handle, gl::TEXTURE_MIN_FILTER, filter.get_native() as i32) };
+
+        self.min_filter = filter;
+    }
+
+    pub fn set_mag_filter(&mut self, filter: MagFilter) {
+        unsafe { gl::SamplerParameteri(self.handle, gl::TEXTURE_MAG_FILTER, filter.get_native() as i32) };
+
+        self.mag_filter = filter;
+    }
+
+    pub fn set_border_color(&mut self, color: Color) {
+        let values = color.to_array_f32();
+
+        unsafe { gl::SamplerParameterfv(self.handle, gl::TEXTURE_BORDER_COLOR, values.as_ptr()) };
+
+        self.border_color = color;
+    }
+
+    pub fn clamp(&self, coord: WrapCoord) -> ClampMode {
+        match coord {
+            WrapCoord::S => self.s_clamp,
+            WrapCoord::T => self.t_clamp,
+            WrapCoord::R => self.r_clamp,
+        }
+    }
+
+    pub fn min_filter(&self) -> MinFilter {
+        self.min_filter
+    }
+
+    pub fn mag_filter(&self) -> MagFilter {
+        self.mag_filter
+    }
+
+    pub fn border_color(&self) -> Color {
+        self.border_color
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.handle
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        context::defer_delete(Garbage::Sampler(self.handle));
+    }
+}
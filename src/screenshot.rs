@@ -0,0 +1,120 @@
+use crate::context;
+use crate::error::{Error, Result};
+use crate::readback::{self, PendingReadback};
+use crate::texture::TextureFormat;
+
+use ::image::{ColorType, ImageFormat};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+
+// GL rows start at the bottom; images expect the top row first
+pub(crate) fn flip_rows(buf: &mut [u8], row_size: usize) {
+    let height = if row_size == 0 { 0 } else { buf.len() / row_size };
+
+    for y in 0..height / 2 {
+        let (top, bottom) = buf.split_at_mut((height - 1 - y) * row_size);
+
+        top[y * row_size..(y + 1) * row_size].swap_with_slice(&mut bottom[..row_size]);
+    }
+}
+
+// expects top-down RGBA8 rows
+pub(crate) fn save_png(path: &Path, pixels: &[u8], width: usize, height: usize) -> Result<()> {
+    ::image::save_buffer_with_format(path, pixels, width as u32, height as u32, ColorType::Rgba8, ImageFormat::Png)
+        .map_err(|err| Error::ImageSaveFailed(format!("{}: {}", path.display(), err)))
+}
+
+// captures the current viewport of the default framebuffer; the GPU copy goes through a PBO so the
+// calling frame doesn't stall, and the PNG is encoded off the GL thread
+pub fn capture_screenshot<P: AsRef<Path>>(path: P) -> Screenshot {
+    context::assert_initialized();
+
+    let viewport = context::viewport();
+    let previous = context::bound_framebuffer();
+
+    context::bind_framebuffer(0);
+
+    let readback = readback::read_pixels_async(
+        viewport.x as i32,
+        viewport.y as i32,
+        viewport.width as usize,
+        viewport.height as usize,
+        TextureFormat::Rgba8,
+    );
+
+    context::bind_framebuffer(previous);
+
+    Screenshot {
+        path: path.as_ref().to_path_buf(),
+        readback: Some(readback),
+        encoder: None,
+    }
+}
+
+#[must_use]
+pub struct Screenshot {
+    path: PathBuf,
+    readback: Option<PendingReadback>,
+    encoder: Option<Receiver<Result<PathBuf>>>,
+}
+
+impl Screenshot {
+    fn encode(&mut self, mut pixels: Vec<u8>, width: usize, height: usize) {
+        let (sender, receiver) = mpsc::channel();
+        let path = self.path.clone();
+
+        thread::spawn(move || {
+            flip_rows(&mut pixels, width * 4);
+
+            let result = save_png(&path, &pixels, width, height).map(|_| path);
+            let _ = sender.send(result);
+        });
+
+        self.encoder = Some(receiver);
+    }
+
+    // call once a frame on the GL thread; returns the written path once the file is on disk
+    pub fn poll(&mut self) -> Option<Result<PathBuf>> {
+        if let Some(readback) = self.readback.take() {
+            let (width, height) = (readback.width(), readback.height());
+
+            match readback.try_take() {
+                Ok(pixels) => self.encode(pixels, width, height),
+                Err(pending) => {
+                    self.readback = Some(pending);
+                    return None;
+                },
+            }
+        }
+
+        match self.encoder.as_ref()?.try_recv() {
+            Ok(result) => {
+                self.encoder = None;
+                Some(result)
+            },
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.encoder = None;
+                Some(Err(Error::ImageSaveFailed(format!("{}: encoder thread panicked", self.path.display()))))
+            },
+        }
+    }
+
+    pub fn wait(mut self) -> Result<PathBuf> {
+        if let Some(readback) = self.readback.take() {
+            let (width, height) = (readback.width(), readback.height());
+
+            self.encode(readback.wait(), width, height);
+        }
+
+        match self.encoder.take().map(|encoder| encoder.recv()) {
+            Some(Ok(result)) => result,
+            _ => Err(Error::ImageSaveFailed(format!("{}: encoder thread panicked", self.path.display()))),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
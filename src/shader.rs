@@ -1,150 +1,710 @@
+use crate::context::{self, Garbage, Ownership};
+use crate::debug;
 use crate::{Error, Result};
 use crate::Texture;
 
 use gl::types::*;
-use lazy_static::lazy_static;
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::ffi::CString;
+use std::fmt;
+use std::marker::PhantomData;
 use std::ptr;
-use std::str;
-use std::sync::Mutex;
-use vex::Matrix4;
-
-fn to_native(s: &str) -> *const GLchar {
-    let c_str = CString::new(s).unwrap();
-    let result = c_str.as_ptr() as *const GLchar;
-
-    result
-}
+use vex::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum StageKind {
     Vertex,
+    TessControl,
+    TessEvaluation,
     Geometry,
     Fragment,
 }
 
 impl StageKind {
+    pub(crate) fn pipeline_bit(&self) -> GLbitfield {
+        match self {
+            StageKind::Vertex => gl::VERTEX_SHADER_BIT,
+            StageKind::TessControl => gl::TESS_CONTROL_SHADER_BIT,
+            StageKind::TessEvaluation => gl::TESS_EVALUATION_SHADER_BIT,
+            StageKind::Geometry => gl::GEOMETRY_SHADER_BIT,
+            StageKind::Fragment => gl::FRAGMENT_SHADER_BIT,
+        }
+    }
+
     fn get_native(&self) -> GLenum {
         match self {
             StageKind::Vertex => gl::VERTEX_SHADER,
+            StageKind::TessControl => gl::TESS_CONTROL_SHADER,
+            StageKind::TessEvaluation => gl::TESS_EVALUATION_SHADER,
             StageKind::Geometry => gl::GEOMETRY_SHADER,
             StageKind::Fragment => gl::FRAGMENT_SHADER,
         }
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum GlslType {
+    Float,
+    Vec2,
+    Vec3,
+    Vec4,
+    Double,
+    Int,
+    IVec2,
+    IVec3,
+    IVec4,
+    UInt,
+    UVec2,
+    UVec3,
+    UVec4,
+    Bool,
+    Mat2,
+    Mat3,
+    Mat4,
+    Sampler1D,
+    Sampler2D,
+    Sampler3D,
+    SamplerCube,
+    Sampler2DArray,
+    Sampler2DShadow,
+    Other(GLenum),
+}
+
+impl GlslType {
+    pub fn new(raw: GLenum) -> GlslType {
+        match raw {
+            gl::FLOAT => GlslType::Float,
+            gl::FLOAT_VEC2 => GlslType::Vec2,
+            gl::FLOAT_VEC3 => GlslType::Vec3,
+            gl::FLOAT_VEC4 => GlslType::Vec4,
+            gl::DOUBLE => GlslType::Double,
+            gl::INT => GlslType::Int,
+            gl::INT_VEC2 => GlslType::IVec2,
+            gl::INT_VEC3 => GlslType::IVec3,
+            gl::INT_VEC4 => GlslType::IVec4,
+            gl::UNSIGNED_INT => GlslType::UInt,
+            gl::UNSIGNED_INT_VEC2 => GlslType::UVec2,
+            gl::UNSIGNED_INT_VEC3 => GlslType::UVec3,
+            gl::UNSIGNED_INT_VEC4 => GlslType::UVec4,
+            gl::BOOL => GlslType::Bool,
+            gl::FLOAT_MAT2 => GlslType::Mat2,
+            gl::FLOAT_MAT3 => GlslType::Mat3,
+            gl::FLOAT_MAT4 => GlslType::Mat4,
+            gl::SAMPLER_1D => GlslType::Sampler1D,
+            gl::SAMPLER_2D => GlslType::Sampler2D,
+            gl::SAMPLER_3D => GlslType::Sampler3D,
+            gl::SAMPLER_CUBE => GlslType::SamplerCube,
+            gl::SAMPLER_2D_ARRAY => GlslType::Sampler2DArray,
+            gl::SAMPLER_2D_SHADOW => GlslType::Sampler2DShadow,
+            n => GlslType::Other(n),
+        }
+    }
+
+    // scalar component count for vector types, 0 for everything else
+    pub fn components(&self) -> usize {
+        match self {
+            GlslType::Float | GlslType::Double | GlslType::Int | GlslType::UInt | GlslType::Bool => 1,
+            GlslType::Vec2 | GlslType::IVec2 | GlslType::UVec2 => 2,
+            GlslType::Vec3 | GlslType::IVec3 | GlslType::UVec3 => 3,
+            GlslType::Vec4 | GlslType::IVec4 | GlslType::UVec4 => 4,
+            _ => 0,
+        }
+    }
+
+    pub fn is_integer(&self) -> bool {
+        match self {
+            GlslType::Int
+            | GlslType::IVec2
+            | GlslType::IVec3
+            | GlslType::IVec4
+            | GlslType::UInt
+            | GlslType::UVec2
+            | GlslType::UVec3
+            | GlslType::UVec4 => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_sampler(&self) -> bool {
+        match self {
+            GlslType::Sampler1D
+            | GlslType::Sampler2D
+            | GlslType::Sampler3D
+            | GlslType::SamplerCube
+            | GlslType::Sampler2DArray
+            | GlslType::Sampler2DShadow => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ActiveVariable {
+    pub name: String,
+    pub location: GLint,
+    pub kind: GlslType,
+    pub size: usize,
+}
+
+fn validate_stages(stages: &[Stage], separable: bool) -> Result<()> {
+    let count = |kind: StageKind| stages.iter().filter(|stage| stage.kind == kind).count();
+    let kinds = [
+        StageKind::Vertex,
+        StageKind::TessControl,
+        StageKind::TessEvaluation,
+        StageKind::Geometry,
+        StageKind::Fragment,
+    ];
+
+    for kind in kinds.iter() {
+        if count(*kind) > 1 {
+            let msg = format!("more than one {:?} stage was supplied", kind);
+
+            return Err(Error::InvalidStageCombination(msg));
+        }
+    }
+
+    // separable programs may supply any subset and get combined in a Pipeline
+    if !separable && count(StageKind::Vertex) == 0 {
+        let msg = "a program requires a Vertex stage".to_string();
+
+        return Err(Error::InvalidStageCombination(msg));
+    }
+
+    if count(StageKind::TessControl) > 0 && count(StageKind::TessEvaluation) == 0 {
+        let msg = "a TessControl stage requires a matching TessEvaluation stage".to_string();
+
+        return Err(Error::InvalidStageCombination(msg));
+    }
+
+    Ok(())
+}
+
+fn stage_log(handle: GLuint) -> String {
+    let mut len = 0;
+
+    unsafe { gl::GetShaderiv(handle, gl::INFO_LOG_LENGTH, &mut len) };
+
+    let mut log = vec![0u8; len.max(1) as usize];
+
+    unsafe { gl::GetShaderInfoLog(handle, log.len() as GLsizei, ptr::null_mut(), log.as_mut_ptr() as *mut GLchar) };
+
+    String::from_utf8_lossy(&log).trim_end_matches('\0').trim_end().to_string()
+}
+
+fn program_log(handle: GLuint) -> String {
+    let mut len = 0;
+
+    unsafe { gl::GetProgramiv(handle, gl::INFO_LOG_LENGTH, &mut len) };
+
+    let mut log = vec![0u8; len.max(1) as usize];
+
+    unsafe { gl::GetProgramInfoLog(handle, log.len() as GLsizei, ptr::null_mut(), log.as_mut_ptr() as *mut GLchar) };
+
+    String::from_utf8_lossy(&log).trim_end_matches('\0').trim_end().to_string()
+}
+
+// drivers prefix messages with "0:12(5)" (Mesa), "0(12)" (NVIDIA) or "ERROR: 0:12:" (AMD, Intel)
+fn log_line_number(message: &str) -> Option<usize> {
+    let bytes = message.as_bytes();
+
+    for start in 0..bytes.len() {
+        if !bytes[start].is_ascii_digit() || (start > 0 && bytes[start - 1].is_ascii_alphanumeric()) {
+            continue;
+        }
+
+        let mut i = start;
+        while i < bytes.len() && bytes[i].is_ascii_digit() {
+            i += 1;
+        }
+
+        if i >= bytes.len() || (bytes[i] != b':' && bytes[i] != b'(') {
+            continue;
+        }
+
+        let digits = message[i + 1..].chars().take_while(|c| c.is_ascii_digit()).collect::<String>();
+
+        if let Ok(line) = digits.parse() {
+            return Some(line);
+        }
+    }
+
+    None
+}
+
+fn describe_compile_error(name: &str, kind: StageKind, src: &str, log: &str) -> String {
+    let lines = src.lines().collect::<Vec<&str>>();
+    let mut result = format!("{} ({:?} stage)", name, kind);
+
+    for message in log.lines().filter(|message| !message.trim().is_empty()) {
+        result.push_str("\n  ");
+        result.push_str(message.trim());
+
+        if let Some(line) = log_line_number(message) {
+            if let Some(text) = line.checked_sub(1).and_then(|index| lines.get(index)) {
+                result.push_str(&format!("\n    {:>4} | {}", line, text));
+            }
+        }
+    }
+
+    result
+}
+
 pub struct Stage {
+    kind: StageKind,
     handle: GLuint,
+    _thread: context::ThreadMarker,
 }
 
 impl Stage {
     pub fn new(kind: StageKind, src: &str) -> Result<Stage> {
+        Stage::with_name(kind, "<unnamed>", src)
+    }
+
+    // the name (usually a file path) is only used to label compile errors
+    pub fn with_name(kind: StageKind, name: &str, src: &str) -> Result<Stage> {
+        context::check_initialized()?;
+
         unsafe {
             let mut success = gl::FALSE as GLint;
-            let src = CString::new(src.as_bytes()).unwrap();
+            let c_src = CString::new(src.as_bytes()).unwrap();
             let handle: GLuint = gl::CreateShader(kind.get_native());
 
-            gl::ShaderSource(handle, 1, &src.as_ptr(), ptr::null());
+            gl::ShaderSource(handle, 1, &c_src.as_ptr(), ptr::null());
             gl::CompileShader(handle);
             gl::GetShaderiv(handle, gl::COMPILE_STATUS, &mut success);
 
             if success != gl::TRUE as GLint {
-                let mut log = Vec::with_capacity(512);
-                log.set_len(511);
-
-                let log_ptr = log.as_mut_ptr() as *mut GLchar;
-                gl::GetShaderInfoLog(handle, 512, ptr::null_mut(), log_ptr);
+                let err = describe_compile_error(name, kind, src, &stage_log(handle));
 
-                let err = str::from_utf8(&log).unwrap().into();
+                gl::DeleteShader(handle);
                 Err(Error::CompileShaderStageFailed(err))
             } else {
-                Ok(Stage { handle })
+                Ok(Stage { kind, handle, _thread: PhantomData })
             }
         }
     }
+
+    pub fn kind(&self) -> StageKind {
+        self.kind
+    }
 }
 
 impl Drop for Stage {
     fn drop(&mut self) {
-        unsafe { gl::DeleteShader(self.handle) };
+        context::defer_delete(Garbage::Shader(self.handle));
         self.handle = 0;
     }
 }
 
 pub struct Shader {
     handle: GLuint,
+    locations: RefCell<HashMap<String, GLint>>,
+    separable: bool,
+    ownership: Ownership,
+    label: Option<String>,
+    _thread: context::ThreadMarker,
+}
+
+impl fmt::Debug for Shader {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Shader")
+            .field("handle", &self.handle)
+            .field("label", &self.label)
+            .field("separable", &self.separable)
+            .field("ownership", &self.ownership)
+            .finish()
+    }
 }
 
 impl Shader {
     pub fn new(stages: &Vec<Stage>) -> Result<Shader> {
+        Shader::link(stages, false)
+    }
+
+    pub fn new_separable(stages: &Vec<Stage>) -> Result<Shader> {
+        if !gl::GenProgramPipelines::is_loaded() {
+            return Err(Error::Unsupported("separable programs (GL 4.1 or ARB_separate_shader_objects)".to_string()));
+        }
+
+        Shader::link(stages, true)
+    }
+
+    fn link(stages: &[Stage], separable: bool) -> Result<Shader> {
+        context::check_initialized()?;
+        validate_stages(stages, separable)?;
+
         unsafe {
             let handle = gl::CreateProgram();
             for stage in stages {
                 gl::AttachShader(handle, stage.handle);
             }
 
+            if separable {
+                gl::ProgramParameteri(handle, gl::PROGRAM_SEPARABLE, gl::TRUE as GLint);
+            }
+
+            // some drivers only keep the binary around when asked before linking
+            if gl::ProgramParameteri::is_loaded() {
+                gl::ProgramParameteri(handle, gl::PROGRAM_BINARY_RETRIEVABLE_HINT, gl::TRUE as GLint);
+            }
+
             gl::LinkProgram(handle);
 
-            let mut success = gl::FALSE as GLint;
-            gl::GetProgramiv(handle, gl::LINK_STATUS, &mut success);
+            Shader::check_link(handle)
+        }
+    }
 
-            if success != gl::TRUE as GLint {
-                let mut log = Vec::with_capacity(512);
-                log.set_len(511);
+    // binaries are driver-specific; expect this to fail after a driver update and fall back to compiling
+    pub fn from_binary(format: GLenum, binary: &[u8]) -> Result<Shader> {
+        context::check_initialized()?;
 
-                let log_ptr = log.as_mut_ptr() as *mut GLchar;
-                gl::GetShaderInfoLog(handle, 512, ptr::null_mut(), log_ptr);
+        if !gl::ProgramBinary::is_loaded() {
+            return Err(Error::Unsupported("program binaries (GL 4.1 or ARB_get_program_binary)".to_string()));
+        }
 
-                let err = str::from_utf8(&log).unwrap().into();
-                Err(Error::LinkShaderProgramFailed(err))
-            } else {
-                Ok(Shader { handle })
+        unsafe {
+            let handle = gl::CreateProgram();
+
+            gl::ProgramBinary(handle, format, binary.as_ptr() as *const _, binary.len() as GLsizei);
+
+            Shader::check_link(handle)
+        }
+    }
+
+    // wraps a linked program created by foreign code; uniform locations are looked up lazily as usual
+    pub fn from_raw_handle(handle: GLuint, ownership: Ownership) -> Result<Shader> {
+        context::check_initialized()?;
+
+        let mut linked = gl::FALSE as GLint;
+        let mut separable = gl::FALSE as GLint;
+
+        unsafe {
+            if gl::IsProgram(handle) != gl::TRUE {
+                return Err(Error::InvalidHandle(format!("{} is not a program object", handle)));
             }
+
+            gl::GetProgramiv(handle, gl::LINK_STATUS, &mut linked);
+
+            if gl::GenProgramPipelines::is_loaded() {
+                gl::GetProgramiv(handle, gl::PROGRAM_SEPARABLE, &mut separable);
+            }
+        }
+
+        if linked != gl::TRUE as GLint {
+            return Err(Error::InvalidHandle(format!("program {} isn't linked", handle)));
+        }
+
+        Ok(Shader {
+            handle,
+            locations: RefCell::new(HashMap::new()),
+            separable: separable == gl::TRUE as GLint,
+            ownership,
+            label: None,
+            _thread: PhantomData,
+        })
+    }
+
+    unsafe fn check_link(handle: GLuint) -> Result<Shader> {
+        let mut success = gl::FALSE as GLint;
+        gl::GetProgramiv(handle, gl::LINK_STATUS, &mut success);
+
+        if success != gl::TRUE as GLint {
+            let err = program_log(handle);
+
+            gl::DeleteProgram(handle);
+            Err(Error::LinkShaderProgramFailed(err))
+        } else {
+            let mut separable = gl::FALSE as GLint;
+
+            // binaries keep the flag they were linked with, so ask rather than assume
+            if gl::GenProgramPipelines::is_loaded() {
+                gl::GetProgramiv(handle, gl::PROGRAM_SEPARABLE, &mut separable);
+            }
+
+            Ok(Shader {
+                handle,
+                locations: RefCell::new(HashMap::new()),
+                separable: separable == gl::TRUE as GLint,
+                ownership: Ownership::Owned,
+                label: None,
+                _thread: PhantomData,
+            })
+        }
+    }
+
+    pub fn is_separable(&self) -> bool {
+        self.separable
+    }
+
+    pub fn program_binary(&self) -> Result<(GLenum, Vec<u8>)> {
+        context::check_initialized()?;
+
+        let mut formats = 0;
+
+        if gl::GetProgramBinary::is_loaded() {
+            unsafe { gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut formats) };
+        }
+
+        if formats <= 0 {
+            return Err(Error::Unsupported("program binaries (GL 4.1 or ARB_get_program_binary)".to_string()));
+        }
+
+        unsafe {
+            let mut length = 0;
+            gl::GetProgramiv(self.handle, gl::PROGRAM_BINARY_LENGTH, &mut length);
+
+            if length <= 0 {
+                return Err(Error::Unsupported("the driver didn't retain a binary for this program".to_string()));
+            }
+
+            let mut format = 0;
+            let mut written = 0;
+            let mut binary = vec![0u8; length as usize];
+
+            gl::GetProgramBinary(self.handle, length, &mut written, &mut format, binary.as_mut_ptr() as *mut _);
+            binary.truncate(written.max(0) as usize);
+
+            Ok((format, binary))
         }
     }
 
     pub fn bind(&self) {
-        let mut st = INTERNAL_STATE.lock().unwrap();
+        context::assert_initialized();
+        use_program(self.handle);
+    }
+
+    pub fn bind_scoped(&self) -> ShaderBinding<'_> {
+        let previous = context::bound_program();
+
+        self.bind();
 
-        if st.active_program != self.handle {
-            unsafe { gl::UseProgram(self.handle) };
+        ShaderBinding {
+            previous,
+            _shader: PhantomData,
+        }
+    }
 
-            st.active_program = self.handle;
+    pub fn uniform_location(&self, name: &str) -> GLint {
+        let mut locations = self.locations.borrow_mut();
+
+        if let Some(loc) = locations.get(name) {
+            return *loc;
         }
+
+        let c_name = CString::new(name).unwrap();
+        let loc = unsafe { gl::GetUniformLocation(self.handle, c_name.as_ptr()) };
+
+        locations.insert(name.to_string(), loc);
+        loc
     }
 
     pub fn upload_texture(&self, name: &str, texture: &Texture, unit: GLenum) {
         texture.bind(unit);
 
-        unsafe {
-            let loc = gl::GetUniformLocation(self.handle, to_native(name));
+        self.set_uniform_i32(name, unit as i32);
+    }
 
-            gl::Uniform1i(loc, unit as i32);
+    pub fn set_uniform_f32(&self, name: &str, value: f32) {
+        self.bind();
+
+        unsafe { gl::Uniform1f(self.uniform_location(name), value) };
+    }
+
+    pub fn set_uniform_i32(&self, name: &str, value: i32) {
+        self.bind();
+
+        unsafe { gl::Uniform1i(self.uniform_location(name), value) };
+    }
+
+    pub fn set_uniform_vec2(&self, name: &str, value: &Vector2) {
+        self.bind();
+
+        unsafe { gl::Uniform2f(self.uniform_location(name), value.x, value.y) };
+    }
+
+    pub fn set_uniform_vec3(&self, name: &str, value: &Vector3) {
+        self.bind();
+
+        unsafe { gl::Uniform3f(self.uniform_location(name), value.x, value.y, value.z) };
+    }
+
+    pub fn set_uniform_vec4(&self, name: &str, value: &Vector4) {
+        self.bind();
+
+        unsafe { gl::Uniform4f(self.uniform_location(name), value.x, value.y, value.z, value.w) };
+    }
+
+    pub fn set_uniform_mat3(&self, name: &str, value: &Matrix3) {
+        let m = value.m;
+
+        self.bind();
+
+        unsafe { gl::UniformMatrix3fv(self.uniform_location(name), 1, gl::FALSE, m.as_ptr()) };
+    }
+
+    pub fn set_uniform_mat4(&self, name: &str, value: &Matrix4) {
+        let m = value.m;
+
+        self.bind();
+
+        unsafe { gl::UniformMatrix4fv(self.uniform_location(name), 1, gl::FALSE, m.as_ptr()) };
+    }
+
+    pub fn set_model(&self, model: &Matrix4) {
+        self.set_uniform_mat4("u_model", model);
+    }
+
+    pub fn set_view(&self, view: &Matrix4) {
+        self.set_uniform_mat4("u_view", view);
+    }
+
+    pub fn set_projection(&self, projection: &Matrix4) {
+        self.set_uniform_mat4("u_projection", projection);
+    }
+
+    // the world-space plane (normal in xyz, offset in w) the builtin shaders measure gl_ClipDistance[0]
+    // against; geometry on the negative side is dropped once Feature::ClipDistance(0) is enabled, and
+    // the all-zero default never clips
+    pub fn set_clip_plane(&self, plane: &Vector4) {
+        self.set_uniform_vec4("u_clip_plane", plane);
+    }
+
+    pub fn set_mvp(&self, model: &Matrix4, view: &Matrix4, projection: &Matrix4) {
+        self.set_model(model);
+        self.set_view(view);
+        self.set_projection(projection);
+    }
+
+    pub fn bind_uniform_block(&self, name: &str, binding: GLuint) -> Result<()> {
+        context::check_initialized()?;
+
+        let c_name = CString::new(name).unwrap();
+        let index = unsafe { gl::GetUniformBlockIndex(self.handle, c_name.as_ptr()) };
+
+        if index == gl::INVALID_INDEX {
+            return Err(Error::UnknownUniformBlock(name.to_string()));
         }
+
+        unsafe { gl::UniformBlockBinding(self.handle, index, binding) };
+
+        Ok(())
     }
 
-    pub fn upload_mat4(&self) {
+    // built-in gl_* inputs are skipped; the rest are sorted by location
+    pub fn attributes(&self) -> Vec<ActiveVariable> {
+        context::assert_initialized();
+
+        let mut result = self.reflect(gl::ACTIVE_ATTRIBUTES, gl::ACTIVE_ATTRIBUTE_MAX_LENGTH, |handle, i, len, buf| unsafe {
+            let mut size = 0;
+            let mut kind = 0;
+            let mut written = 0;
+
+            gl::GetActiveAttrib(handle, i, len, &mut written, &mut size, &mut kind, buf.as_mut_ptr() as *mut GLchar);
+            (written, size, kind)
+        });
+
+        for variable in result.iter_mut() {
+            let c_name = CString::new(variable.name.as_str()).unwrap();
+
+            variable.location = unsafe { gl::GetAttribLocation(self.handle, c_name.as_ptr()) };
+        }
+
+        result.sort_by_key(|variable| variable.location);
+        result
+    }
+
+    // members of uniform blocks report a location of -1
+    pub fn uniforms(&self) -> Vec<ActiveVariable> {
+        context::assert_initialized();
+
+        let mut result = self.reflect(gl::ACTIVE_UNIFORMS, gl::ACTIVE_UNIFORM_MAX_LENGTH, |handle, i, len, buf| unsafe {
+            let mut size = 0;
+            let mut kind = 0;
+            let mut written = 0;
+
+            gl::GetActiveUniform(handle, i, len, &mut written, &mut size, &mut kind, buf.as_mut_ptr() as *mut GLchar);
+            (written, size, kind)
+        });
+
+        for variable in result.iter_mut() {
+            variable.location = self.uniform_location(&variable.name);
+        }
+
+        result
+    }
+
+    fn reflect<F>(&self, count_name: GLenum, length_name: GLenum, query: F) -> Vec<ActiveVariable>
+    where
+        F: Fn(GLuint, GLuint, GLsizei, &mut Vec<u8>) -> (GLsizei, GLint, GLenum),
+    {
+        let mut count = 0;
+        let mut max_length = 0;
+
+        unsafe {
+            gl::GetProgramiv(self.handle, count_name, &mut count);
+            gl::GetProgramiv(self.handle, length_name, &mut max_length);
+        }
+
+        let mut buf = vec![0u8; max_length.max(1) as usize];
+
+        (0..count.max(0) as GLuint)
+            .filter_map(|i| {
+                let (written, size, kind) = query(self.handle, i, buf.len() as GLsizei, &mut buf);
+                let raw_name = String::from_utf8_lossy(&buf[..written.max(0) as usize]);
+                let name = raw_name.trim_end_matches("[0]").to_string();
+
+                if name.starts_with("gl_") {
+                    None
+                } else {
+                    Some(ActiveVariable {
+                        name,
+                        location: -1,
+                        kind: GlslType::new(kind),
+                        size: size.max(0) as usize,
+                    })
+                }
+            })
+            .collect()
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        debug::label_object(gl::PROGRAM, self.handle, label);
+        self.label = Some(label.to_string());
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.handle
     }
 }
 
 impl Drop for Shader {
     fn drop(&mut self) {
-        unsafe { gl::DeleteProgram(self.handle) };
+        if self.ownership == Ownership::Owned {
+            context::defer_delete(Garbage::Program(self.handle));
+        }
+
         self.handle = 0;
     }
 }
 
-struct State {
-    active_program: GLuint,
+#[must_use]
+pub struct ShaderBinding<'a> {
+    previous: GLuint,
+    _shader: PhantomData<&'a Shader>,
 }
 
-lazy_static! {
-    static ref INTERNAL_STATE: Mutex<State> = {
-        Mutex::new(State {
-            active_program: 0,
-        })
-    };
+impl Drop for ShaderBinding<'_> {
+    fn drop(&mut self) {
+        use_program(self.previous);
+    }
 }
 
-pub fn init() {
-    unsafe { gl::UseProgram(0) };
+pub(crate) fn use_program(handle: GLuint) {
+    context::bind_program(handle);
 }
@@ -0,0 +1,107 @@
+use crate::context;
+use crate::error::Result;
+use crate::shader::{Shader, Stage, StageKind};
+
+use gl::types::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// FNV-1a, so keys stay stable across Rust releases (std's hasher makes no such promise)
+fn hash(state: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(state, |acc, byte| (acc ^ *byte as u64).wrapping_mul(0x0100_0000_01b3))
+}
+
+pub struct ShaderCache {
+    dir: PathBuf,
+}
+
+impl ShaderCache {
+    pub fn new<P: AsRef<Path>>(dir: P) -> ShaderCache {
+        ShaderCache {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // the driver identity is part of the key so an update or GPU swap never loads a stale binary
+    fn key(&self, sources: &[(StageKind, &str)]) -> u64 {
+        let caps = context::capabilities();
+        let mut result = 0xcbf2_9ce4_8422_2325;
+
+        result = hash(result, caps.vendor.as_bytes());
+        result = hash(result, caps.renderer.as_bytes());
+        result = hash(result, format!("{}.{}", caps.version.0, caps.version.1).as_bytes());
+
+        for (kind, src) in sources {
+            result = hash(result, format!("{:?}", kind).as_bytes());
+            result = hash(result, &(src.len() as u64).to_le_bytes());
+            result = hash(result, src.as_bytes());
+        }
+
+        result
+    }
+
+    fn path(&self, key: u64) -> PathBuf {
+        self.dir.join(format!("{:016x}.bin", key))
+    }
+
+    fn load(&self, path: &Path) -> Option<Shader> {
+        let bytes = fs::read(path).ok()?;
+
+        if bytes.len() <= 4 {
+            return None;
+        }
+
+        let mut format = [0u8; 4];
+        format.copy_from_slice(&bytes[..4]);
+
+        Shader::from_binary(GLenum::from_le_bytes(format), &bytes[4..]).ok()
+    }
+
+    fn store(&self, path: &Path, shader: &Shader) {
+        if let Ok((format, binary)) = shader.program_binary() {
+            let mut bytes = format.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&binary);
+
+            // the cache is best-effort; a failed write only costs a compile next time
+            let _ = fs::create_dir_all(&self.dir).and_then(|_| fs::write(path, bytes));
+        }
+    }
+
+    // compiles and caches on a miss; corrupt or rejected entries are silently replaced
+    pub fn load_or_compile(&self, sources: &[(StageKind, &str)]) -> Result<Shader> {
+        let path = self.path(self.key(sources));
+
+        if let Some(shader) = self.load(&path) {
+            return Ok(shader);
+        }
+
+        let stages = sources
+            .iter()
+            .map(|(kind, src)| Stage::new(*kind, src))
+            .collect::<Result<Vec<Stage>>>()?;
+
+        let shader = Shader::new(&stages)?;
+
+        self.store(&path, &shader);
+
+        Ok(shader)
+    }
+
+    pub fn clear(&self) -> std::io::Result<()> {
+        if self.dir.exists() {
+            for entry in fs::read_dir(&self.dir)? {
+                let path = entry?.path();
+
+                if path.extension().map_or(false, |ext| ext == "bin") {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,167 @@
+use crate::error::{Error, Result};
+use crate::shader::{Stage, StageKind};
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+
+type Provider = Box<dyn Fn(&str) -> Option<String>>;
+
+fn directive<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let rest = line.trim_start().strip_prefix('#')?.trim_start().strip_prefix(name)?;
+
+    if rest.is_empty() || rest.starts_with(char::is_whitespace) {
+        Some(rest.trim())
+    } else {
+        None
+    }
+}
+
+fn include_name(args: &str) -> Option<&str> {
+    let args = args.strip_prefix('"')?;
+    let end = args.find('"')?;
+
+    Some(&args[..end])
+}
+
+pub struct ShaderPreprocessor {
+    version: String,
+    defines: BTreeMap<String, String>,
+    sources: HashMap<String, String>,
+    provider: Option<Provider>,
+}
+
+impl ShaderPreprocessor {
+    pub fn new() -> ShaderPreprocessor {
+        ShaderPreprocessor {
+            version: "330 core".to_string(),
+            defines: BTreeMap::new(),
+            sources: HashMap::new(),
+            provider: None,
+        }
+    }
+
+    // used when the root source doesn't declare its own #version
+    pub fn set_version(&mut self, version: &str) {
+        self.version = version.to_string();
+    }
+
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    pub fn define(&mut self, name: &str, value: &str) {
+        self.defines.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn define_all(&mut self, defines: &HashMap<String, String>) {
+        for (name, value) in defines {
+            self.define(name, value);
+        }
+    }
+
+    pub fn undefine(&mut self, name: &str) {
+        self.defines.remove(name);
+    }
+
+    // registered sources take precedence over the provider
+    pub fn add_source(&mut self, name: &str, src: &str) {
+        self.sources.insert(name.to_string(), src.to_string());
+    }
+
+    pub fn set_provider<F>(&mut self, provider: F)
+    where
+        F: Fn(&str) -> Option<String> + 'static,
+    {
+        self.provider = Some(Box::new(provider));
+    }
+
+    pub fn set_directory<P: Into<PathBuf>>(&mut self, dir: P) {
+        let dir = dir.into();
+
+        self.set_provider(move |name| fs::read_to_string(dir.join(name)).ok());
+    }
+
+    fn resolve(&self, name: &str) -> Option<String> {
+        match self.sources.get(name) {
+            Some(src) => Some(src.clone()),
+            None => self.provider.as_ref().and_then(|provider| provider(name)),
+        }
+    }
+
+    // each file is included at most once; cycles and missing files are errors
+    fn expand(
+        &self,
+        name: &str,
+        src: &str,
+        stack: &mut Vec<String>,
+        seen: &mut HashSet<String>,
+        out: &mut String,
+    ) -> Result<Option<String>> {
+        let mut version = None;
+
+        stack.push(name.to_string());
+
+        for (i, line) in src.lines().enumerate() {
+            if let Some(args) = directive(line, "version") {
+                version.get_or_insert_with(|| args.to_string());
+            } else if let Some(args) = directive(line, "include") {
+                let include = include_name(args).ok_or_else(|| {
+                    Error::PreprocessShaderFailed(format!("{}:{}: malformed #include {}", name, i + 1, args))
+                })?;
+
+                if stack.iter().any(|entry| entry == include) {
+                    let msg = format!("{}:{}: circular #include \"{}\" ({})", name, i + 1, include, stack.join(" -> "));
+
+                    return Err(Error::PreprocessShaderFailed(msg));
+                }
+
+                if seen.insert(include.to_string()) {
+                    let included = self.resolve(include).ok_or_else(|| {
+                        Error::PreprocessShaderFailed(format!("{}:{}: can't find \"{}\"", name, i + 1, include))
+                    })?;
+
+                    self.expand(include, &included, stack, seen, out)?;
+                }
+            } else {
+                out.push_str(line);
+                out.push('\n');
+            }
+        }
+
+        stack.pop();
+
+        Ok(version)
+    }
+
+    // the result always starts with a single #version line followed by the injected #defines
+    pub fn process(&self, name: &str, src: &str) -> Result<String> {
+        let mut body = String::new();
+        let version = self.expand(name, src, &mut Vec::new(), &mut HashSet::new(), &mut body)?;
+        let mut result = format!("#version {}\n", version.as_deref().unwrap_or(&self.version));
+
+        for (define, value) in &self.defines {
+            result.push_str(&format!("#define {} {}\n", define, value));
+        }
+
+        result.push_str(&body);
+
+        Ok(result)
+    }
+
+    pub fn process_file(&self, name: &str) -> Result<String> {
+        let src = self
+            .resolve(name)
+            .ok_or_else(|| Error::PreprocessShaderFailed(format!("can't find \"{}\"", name)))?;
+
+        self.process(name, &src)
+    }
+
+    pub fn stage(&self, kind: StageKind, name: &str, src: &str) -> Result<Stage> {
+        Stage::with_name(kind, name, &self.process(name, src)?)
+    }
+
+    pub fn stage_from_file(&self, kind: StageKind, name: &str) -> Result<Stage> {
+        Stage::with_name(kind, name, &self.process_file(name)?)
+    }
+}
@@ -0,0 +1,196 @@
+use crate::color::Color;
+use crate::context::{self, ClearFlag, Feature, StateSnapshot};
+use crate::error::Result;
+use crate::fbo::Framebuffer;
+use crate::math;
+use crate::shader::{Shader, Stage, StageKind};
+use crate::texture::{self, ClampMode, MagFilter, MinFilter, Texture, TextureFormat, WrapCoord};
+
+use gl::types::*;
+use vex::{Matrix4, Vector3};
+
+const SRC_DEPTH_VERTEX: &str = r#"
+    #version 330 core
+
+    uniform mat4 u_light_space;
+    uniform mat4 u_model;
+
+    layout (location = 0) in vec3 a_pos;
+
+    void main() {
+        gl_Position = u_light_space * u_model * vec4(a_pos, 1.0);
+    }
+"#;
+
+const SRC_DEPTH_FRAGMENT: &str = r#"
+    #version 330 core
+
+    void main() {
+    }
+"#;
+
+// paste below #version in a lit fragment shader; ShadowMap::bind_for_sampling fills in the uniforms
+pub const SHADOW_PCF_GLSL: &str = r#"
+    uniform sampler2D u_shadow_map;
+    uniform mat4 u_light_space;
+    uniform float u_shadow_bias;
+
+    float shadow_factor(vec3 world_pos) {
+        vec4 light_pos = u_light_space * vec4(world_pos, 1.0);
+        vec3 proj = light_pos.xyz / light_pos.w * 0.5 + 0.5;
+
+        if (proj.z > 1.0 || any(lessThan(proj.xy, vec2(0.0))) || any(greaterThan(proj.xy, vec2(1.0)))) {
+            return 1.0;
+        }
+
+        vec2 texel = 1.0 / vec2(textureSize(u_shadow_map, 0));
+        float lit = 0.0;
+
+        for (int x = -1; x <= 1; x++) {
+            for (int y = -1; y <= 1; y++) {
+                float depth = texture(u_shadow_map, proj.xy + vec2(x, y) * texel).r;
+
+                lit += proj.z - u_shadow_bias > depth ? 0.0 : 1.0;
+            }
+        }
+
+        return lit / 9.0;
+    }
+"#;
+
+pub struct ShadowMap {
+    framebuffer: Framebuffer,
+    shader: Shader,
+    light_space: Matrix4,
+    bias: f32,
+    size: usize,
+}
+
+impl ShadowMap {
+    pub fn new(size: usize) -> Result<ShadowMap> {
+        let mut depth = Texture::new_with_format(TextureFormat::Depth24, size, size);
+
+        // a depth of 1.0 beyond the edges keeps everything outside the light's frustum lit
+        depth.set_clamp(WrapCoord::S, ClampMode::Border)?;
+        depth.set_clamp(WrapCoord::T, ClampMode::Border)?;
+        depth.set_border_color(Color::WHITE)?;
+        depth.set_min_filter(MinFilter::Nearest)?;
+        depth.set_mag_filter(MagFilter::Nearest)?;
+
+        let framebuffer = Framebuffer::with_depth_texture(Vec::new(), depth)?;
+        let shader = Shader::new(&vec![
+            Stage::with_name(StageKind::Vertex, "shadow depth vertex", SRC_DEPTH_VERTEX)?,
+            Stage::with_name(StageKind::Fragment, "shadow depth fragment", SRC_DEPTH_FRAGMENT)?,
+        ])?;
+
+        let identity = math::identity();
+
+        shader.set_uniform_mat4("u_model", &identity);
+
+        Ok(ShadowMap {
+            framebuffer,
+            shader,
+            light_space: identity,
+            bias: 0.005,
+            size,
+        })
+    }
+
+    // an orthographic light covering a sphere of the given radius around center
+    pub fn directional(direction: &Vector3, center: &Vector3, radius: f32) -> Matrix4 {
+        let d = math::normalize(math::to_array(direction));
+        let eye = math::to_vector(math::sub(math::to_array(center), math::scale(d, radius * 2.0)));
+        let up = if d[1].abs() > 0.99 { Vector3::make(0.0, 0.0, 1.0) } else { Vector3::make(0.0, 1.0, 0.0) };
+        let view = math::look_at(&eye, center, &up);
+        let projection = math::ortho(-radius, radius, -radius, radius, radius, radius * 3.0);
+
+        math::multiply(&projection, &view)
+    }
+
+    pub fn spot(position: &Vector3, target: &Vector3, fov_y: f32, near: f32, far: f32) -> Matrix4 {
+        let d = math::normalize(math::sub(math::to_array(target), math::to_array(position)));
+        let up = if d[1].abs() > 0.99 { Vector3::make(0.0, 0.0, 1.0) } else { Vector3::make(0.0, 1.0, 0.0) };
+        let view = math::look_at(position, target, &up);
+        let projection = math::perspective(fov_y, 1.0, near, far);
+
+        math::multiply(&projection, &view)
+    }
+
+    pub fn set_light_space(&mut self, light_space: Matrix4) {
+        self.light_space = light_space;
+    }
+
+    pub fn light_space(&self) -> &Matrix4 {
+        &self.light_space
+    }
+
+    pub fn set_bias(&mut self, bias: f32) {
+        self.bias = bias;
+    }
+
+    pub fn bias(&self) -> f32 {
+        self.bias
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn texture(&self) -> &Texture {
+        self.framebuffer.depth_texture().unwrap()
+    }
+
+    pub fn depth_shader(&self) -> &Shader {
+        &self.shader
+    }
+
+    // renders depth only from the light until the pass is dropped
+    pub fn begin_pass(&self) -> ShadowPass<'_> {
+        let snapshot = context::snapshot();
+
+        self.framebuffer.bind();
+        context::set_viewport(0, 0, self.size as u32, self.size as u32);
+        context::enable(Feature::DepthTest);
+        context::set_depth_mask(true);
+        context::clear(ClearFlag::Depth.into());
+
+        self.shader.set_uniform_mat4("u_light_space", &self.light_space);
+        self.shader.bind();
+
+        ShadowPass {
+            map: self,
+            snapshot,
+        }
+    }
+
+    // binds the depth texture to unit and sets the SHADOW_PCF_GLSL uniforms on shader
+    pub fn bind_for_sampling(&self, shader: &Shader, unit: GLuint) {
+        texture::bind_handle(unit, self.texture().handle());
+
+        shader.set_uniform_i32("u_shadow_map", unit as i32);
+        shader.set_uniform_mat4("u_light_space", &self.light_space);
+        shader.set_uniform_f32("u_shadow_bias", self.bias);
+    }
+}
+
+#[must_use]
+pub struct ShadowPass<'a> {
+    map: &'a ShadowMap,
+    snapshot: StateSnapshot,
+}
+
+impl ShadowPass<'_> {
+    pub fn set_model(&self, model: &Matrix4) {
+        self.map.shader.set_model(model);
+    }
+
+    pub fn shader(&self) -> &Shader {
+        &self.map.shader
+    }
+}
+
+impl Drop for ShadowPass<'_> {
+    fn drop(&mut self) {
+        context::restore(&self.snapshot);
+    }
+}
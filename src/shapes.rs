@@ -0,0 +1,289 @@
+use crate::builtin::{BasicVertex, ColorVertex, NormalVertex, SpriteVertex, TextureVertex};
+use crate::color::Color;
+use crate::vbo::{BufferMode, PrimitiveKind, Vertex, VBO};
+
+use std::f32::consts::PI;
+use vex::{Vector2, Vector3};
+
+pub trait ShapeVertex: Vertex {
+    fn from_shape(pos: Vector3, normal: Vector3, coord: Vector2) -> Self;
+}
+
+impl ShapeVertex for BasicVertex {
+    fn from_shape(pos: Vector3, _normal: Vector3, _coord: Vector2) -> BasicVertex {
+        BasicVertex::from_parts(pos)
+    }
+}
+
+impl ShapeVertex for ColorVertex {
+    fn from_shape(pos: Vector3, _normal: Vector3, _coord: Vector2) -> ColorVertex {
+        ColorVertex::from_parts(pos, Color::make(255, 255, 255, 255))
+    }
+}
+
+impl ShapeVertex for TextureVertex {
+    fn from_shape(pos: Vector3, _normal: Vector3, coord: Vector2) -> TextureVertex {
+        TextureVertex::from_parts(pos, coord)
+    }
+}
+
+impl ShapeVertex for SpriteVertex {
+    fn from_shape(pos: Vector3, _normal: Vector3, coord: Vector2) -> SpriteVertex {
+        SpriteVertex::from_parts(pos, coord, Color::make(255, 255, 255, 255))
+    }
+}
+
+impl ShapeVertex for NormalVertex {
+    fn from_shape(pos: Vector3, normal: Vector3, coord: Vector2) -> NormalVertex {
+        NormalVertex::from_parts(pos, normal, coord)
+    }
+}
+
+pub struct Shape<T: ShapeVertex> {
+    pub vertices: Vec<T>,
+    pub indices: Vec<u16>,
+}
+
+impl<T: ShapeVertex> Shape<T> {
+    fn new() -> Shape<T> {
+        Shape {
+            vertices: Vec::new(),
+            indices: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, pos: [f32; 3], normal: [f32; 3], coord: [f32; 2]) -> u16 {
+        assert!(self.vertices.len() <= u16::MAX as usize, "shape exceeds the 16-bit index range");
+
+        self.vertices.push(T::from_shape(
+            Vector3::make(pos[0], pos[1], pos[2]),
+            Vector3::make(normal[0], normal[1], normal[2]),
+            Vector2::make(coord[0], coord[1]),
+        ));
+
+        (self.vertices.len() - 1) as u16
+    }
+
+    fn triangle(&mut self, a: u16, b: u16, c: u16) {
+        self.indices.extend_from_slice(&[a, b, c]);
+    }
+
+    // a-b-c-d run counter-clockwise around the quad when seen from the front
+    fn quad(&mut self, a: u16, b: u16, c: u16, d: u16) {
+        self.indices.extend_from_slice(&[a, b, c, c, d, a]);
+    }
+
+    pub fn into_vbo(self, mode: BufferMode) -> VBO {
+        VBO::new(mode, PrimitiveKind::Triangles, &self.vertices, Some(&self.indices))
+    }
+}
+
+pub fn quad<T: ShapeVertex>(width: f32, height: f32) -> Shape<T> {
+    let mut result = Shape::new();
+    let hw = width * 0.5;
+    let hh = height * 0.5;
+    let normal = [0.0, 0.0, 1.0];
+
+    let a = result.push([-hw, -hh, 0.0], normal, [0.0, 0.0]);
+    let b = result.push([hw, -hh, 0.0], normal, [1.0, 0.0]);
+    let c = result.push([hw, hh, 0.0], normal, [1.0, 1.0]);
+    let d = result.push([-hw, hh, 0.0], normal, [0.0, 1.0]);
+
+    result.quad(a, b, c, d);
+    result
+}
+
+pub fn circle<T: ShapeVertex>(radius: f32, segments: usize) -> Shape<T> {
+    let segments = segments.max(3);
+    let mut result = Shape::new();
+    let normal = [0.0, 0.0, 1.0];
+    let center = result.push([0.0, 0.0, 0.0], normal, [0.5, 0.5]);
+
+    for i in 0..segments {
+        let (sin, cos) = (2.0 * PI * i as f32 / segments as f32).sin_cos();
+
+        result.push([cos * radius, sin * radius, 0.0], normal, [0.5 + cos * 0.5, 0.5 + sin * 0.5]);
+    }
+
+    for i in 0..segments {
+        result.triangle(center, center + 1 + i as u16, center + 1 + ((i + 1) % segments) as u16);
+    }
+
+    result
+}
+
+pub fn rounded_rect<T: ShapeVertex>(width: f32, height: f32, radius: f32, corner_segments: usize) -> Shape<T> {
+    let corner_segments = corner_segments.max(1);
+    let radius = radius.max(0.0).min(width.min(height) * 0.5);
+    let hw = width * 0.5;
+    let hh = height * 0.5;
+    let corners = [
+        (hw - radius, hh - radius),
+        (radius - hw, hh - radius),
+        (radius - hw, radius - hh),
+        (hw - radius, radius - hh),
+    ];
+
+    let mut result = Shape::new();
+    let normal = [0.0, 0.0, 1.0];
+    let center = result.push([0.0, 0.0, 0.0], normal, [0.5, 0.5]);
+
+    for (corner, (cx, cy)) in corners.iter().enumerate() {
+        for i in 0..=corner_segments {
+            let angle = (corner as f32 + i as f32 / corner_segments as f32) * PI * 0.5;
+            let (sin, cos) = angle.sin_cos();
+            let x = cx + cos * radius;
+            let y = cy + sin * radius;
+
+            result.push([x, y, 0.0], normal, [x / width + 0.5, y / height + 0.5]);
+        }
+    }
+
+    let ring = (result.vertices.len() - 1) as u16;
+
+    for i in 0..ring {
+        result.triangle(center, center + 1 + i, center + 1 + (i + 1) % ring);
+    }
+
+    result
+}
+
+pub fn cube<T: ShapeVertex>(size: f32) -> Shape<T> {
+    // each face is (normal, u axis, v axis) with u x v == normal
+    let faces = [
+        ([1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]),
+        ([-1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]),
+        ([0.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, -1.0]),
+        ([0.0, -1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 1.0]),
+        ([0.0, 0.0, 1.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+        ([0.0, 0.0, -1.0], [-1.0, 0.0, 0.0], [0.0, 1.0, 0.0]),
+    ];
+
+    let mut result = Shape::new();
+    let h = size * 0.5;
+
+    for (n, u, v) in faces.iter() {
+        let corner = |su: f32, sv: f32| {
+            [
+                (n[0] + u[0] * su + v[0] * sv) * h,
+                (n[1] + u[1] * su + v[1] * sv) * h,
+                (n[2] + u[2] * su + v[2] * sv) * h,
+            ]
+        };
+
+        let a = result.push(corner(-1.0, -1.0), *n, [0.0, 0.0]);
+        let b = result.push(corner(1.0, -1.0), *n, [1.0, 0.0]);
+        let c = result.push(corner(1.0, 1.0), *n, [1.0, 1.0]);
+        let d = result.push(corner(-1.0, 1.0), *n, [0.0, 1.0]);
+
+        result.quad(a, b, c, d);
+    }
+
+    result
+}
+
+pub fn uv_sphere<T: ShapeVertex>(radius: f32, rings: usize, segments: usize) -> Shape<T> {
+    let rings = rings.max(2);
+    let segments = segments.max(3);
+    let mut result = Shape::new();
+
+    for r in 0..=rings {
+        let (sin_phi, cos_phi) = (PI * r as f32 / rings as f32).sin_cos();
+
+        for s in 0..=segments {
+            let (sin_theta, cos_theta) = (2.0 * PI * s as f32 / segments as f32).sin_cos();
+            let normal = [sin_phi * cos_theta, cos_phi, -sin_phi * sin_theta];
+            let pos = [normal[0] * radius, normal[1] * radius, normal[2] * radius];
+
+            result.push(pos, normal, [s as f32 / segments as f32, 1.0 - r as f32 / rings as f32]);
+        }
+    }
+
+    let stride = (segments + 1) as u16;
+
+    for r in 0..rings as u16 {
+        for s in 0..segments as u16 {
+            let a = r * stride + s;
+            let b = a + stride;
+
+            result.quad(a, b, b + 1, a + 1);
+        }
+    }
+
+    result
+}
+
+pub fn cylinder<T: ShapeVertex>(radius: f32, height: f32, segments: usize) -> Shape<T> {
+    let segments = segments.max(3);
+    let hh = height * 0.5;
+    let mut result = Shape::new();
+
+    for s in 0..=segments {
+        let u = s as f32 / segments as f32;
+        let (sin, cos) = (2.0 * PI * u).sin_cos();
+        let normal = [cos, 0.0, -sin];
+
+        result.push([cos * radius, hh, -sin * radius], normal, [u, 1.0]);
+        result.push([cos * radius, -hh, -sin * radius], normal, [u, 0.0]);
+    }
+
+    for s in 0..segments as u16 {
+        let top = s * 2;
+
+        result.quad(top, top + 1, top + 3, top + 2);
+    }
+
+    for &(y, facing) in [(hh, 1.0), (-hh, -1.0)].iter() {
+        let normal = [0.0, facing, 0.0];
+        let center = result.push([0.0, y, 0.0], normal, [0.5, 0.5]);
+
+        for s in 0..segments {
+            let (sin, cos) = (2.0 * PI * s as f32 / segments as f32).sin_cos();
+
+            result.push([cos * radius, y, -sin * radius], normal, [0.5 + cos * 0.5, 0.5 + sin * 0.5]);
+        }
+
+        for s in 0..segments {
+            let current = center + 1 + s as u16;
+            let next = center + 1 + ((s + 1) % segments) as u16;
+
+            if facing > 0.0 {
+                result.triangle(center, current, next);
+            } else {
+                result.triangle(center, next, current);
+            }
+        }
+    }
+
+    result
+}
+
+pub fn plane_grid<T: ShapeVertex>(width: f32, depth: f32, x_divisions: usize, z_divisions: usize) -> Shape<T> {
+    let x_divisions = x_divisions.max(1);
+    let z_divisions = z_divisions.max(1);
+    let normal = [0.0, 1.0, 0.0];
+    let mut result = Shape::new();
+
+    for iz in 0..=z_divisions {
+        let v = iz as f32 / z_divisions as f32;
+
+        for ix in 0..=x_divisions {
+            let u = ix as f32 / x_divisions as f32;
+
+            result.push([(u - 0.5) * width, 0.0, (v - 0.5) * depth], normal, [u, v]);
+        }
+    }
+
+    let stride = (x_divisions + 1) as u16;
+
+    for iz in 0..z_divisions as u16 {
+        for ix in 0..x_divisions as u16 {
+            let a = iz * stride + ix;
+            let b = a + stride;
+
+            result.quad(a, b, b + 1, a + 1);
+        }
+    }
+
+    result
+}
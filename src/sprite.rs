@@ -1,29 +1,221 @@
+use crate::builtin::{SpriteVertex, SHADER_SPRITE};
+use crate::color::Color;
+use crate::math;
+use crate::rect::Rect;
+use crate::texture::{self, Texture};
+use crate::vbo::{BufferMode, PrimitiveKind, Vertex, VBO};
+
 use gl::types::*;
-use std::mem;
-use std::ptr;
+use std::cmp::Ordering;
+use vex::{Matrix4, Vector2, Vector3};
+
+const MAX_SPRITES: usize = 2048;
+
+// how sprites within one layer are ordered; layers themselves always draw lowest first, and sprites
+// that compare equal keep their submission order
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SpriteSort {
+    // fewest draw calls, but overlapping sprites with different textures may draw in any order
+    Texture,
+    Submission,
+    // by the bottom edge of the destination rect, so sprites lower on screen draw in front (top-down games)
+    Y,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Sprite {
+    texture: GLuint,
+    layer: i32,
+    sort_y: f32,
+    vertices: [SpriteVertex; 4],
+}
+
+pub struct SpriteBatch {
+    vbo: VBO,
+    projection: Matrix4,
+    sprites: Vec<Sprite>,
+    vertices: Vec<SpriteVertex>,
+    layer: i32,
+    sort: SpriteSort,
+    drawing: bool,
+    draw_calls: usize,
+}
+
+impl SpriteBatch {
+    pub fn new() -> SpriteBatch {
+        let vertices = vec![SpriteVertex::new(); MAX_SPRITES * 4];
+        let indices: Vec<u16> = (0..MAX_SPRITES as u16)
+            .flat_map(|i| {
+                let base = i * 4;
+
+                vec![base, base + 1, base + 2, base + 2, base + 3, base]
+            })
+            .collect();
+
+        SpriteBatch {
+            vbo: VBO::new(BufferMode::DynamicDraw, PrimitiveKind::Triangles, &vertices, Some(&indices)),
+            projection: math::identity(),
+            sprites: Vec::new(),
+            vertices: Vec::with_capacity(MAX_SPRITES * 4),
+            layer: 0,
+            sort: SpriteSort::Texture,
+            drawing: false,
+            draw_calls: 0,
+        }
+    }
+
+    pub fn set_projection(&mut self, projection: Matrix4) {
+        self.projection = projection;
+    }
+
+    pub fn set_screen_projection(&mut self, width: f32, height: f32) {
+        self.projection = math::ortho(0.0, width, height, 0.0, -1.0, 1.0);
+    }
+
+    pub fn set_sort(&mut self, sort: SpriteSort) {
+        self.sort = sort;
+    }
+
+    pub fn sort(&self) -> SpriteSort {
+        self.sort
+    }
+
+    // applies to the sprites drawn after it; begin resets it to 0
+    pub fn set_layer(&mut self, layer: i32) {
+        self.layer = layer;
+    }
+
+    pub fn layer(&self) -> i32 {
+        self.layer
+    }
+
+    pub fn begin(&mut self) {
+        assert!(!self.drawing, "SpriteBatch::begin called twice without end");
+
+        self.drawing = true;
+        self.draw_calls = 0;
+        self.layer = 0;
+        self.sprites.clear();
+    }
+
+    pub fn draw(&mut self, texture: &Texture, src_rect: Rect, dst_rect: Rect, color: Color, rotation: f32) {
+        assert!(self.drawing, "SpriteBatch::draw called outside of begin/end");
+
+        // flush only has the handle, so mips and anisotropy are brought up to date here
+        texture.flush_pending();
+
+        let tex_width = texture.width() as f32;
+        let tex_height = texture.height() as f32;
+        let u0 = src_rect.x / tex_width;
+        let v0 = src_rect.y / tex_height;
+        let u1 = src_rect.right() / tex_width;
+        let v1 = src_rect.bottom() / tex_height;
+
+        let cx = dst_rect.x + dst_rect.width * 0.5;
+        let cy = dst_rect.y + dst_rect.height * 0.5;
+        let hw = dst_rect.width * 0.5;
+        let hh = dst_rect.height * 0.5;
+        let (sin, cos) = rotation.sin_cos();
+
+        let corner = |dx: f32, dy: f32, u: f32, v: f32| {
+            let x = cx + dx * cos - dy * sin;
+            let y = cy + dx * sin + dy * cos;
+
+            SpriteVertex::from_parts(Vector3::make(x, y, 0.0), Vector2::make(u, v), color)
+        };
+
+        self.sprites.push(Sprite {
+            texture: texture.handle(),
+            layer: self.layer,
+            sort_y: dst_rect.bottom(),
+            vertices: [
+                corner(-hw, -hh, u0, v0),
+                corner(hw, -hh, u1, v0),
+                corner(hw, hh, u1, v1),
+                corner(-hw, hh, u0, v1),
+            ],
+        });
+    }
+
+    pub fn end(&mut self) {
+        assert!(self.drawing, "SpriteBatch::end called without begin");
+
+        self.drawing = false;
+
+        if self.sprites.is_empty() {
+            return;
+        }
+
+        // stable sorts, so ties keep submission order
+        match self.sort {
+            SpriteSort::Texture => self.sprites.sort_by_key(|sprite| (sprite.layer, sprite.texture)),
+            SpriteSort::Submission => self.sprites.sort_by_key(|sprite| sprite.layer),
+            SpriteSort::Y => self.sprites.sort_by(|a, b| {
+                a.layer.cmp(&b.layer).then(a.sort_y.partial_cmp(&b.sort_y).unwrap_or(Ordering::Equal))
+            }),
+        }
 
-pub fn make() {
-    const vertices: [f32; 8] = [-1.0, 1.0, 1.0, 1.0, 1.0, -1.0, -1.0, -1.0];
+        SHADER_SPRITE.bind();
+        SHADER_SPRITE.set_uniform_mat4("u_projection", &self.projection);
+        SHADER_SPRITE.set_uniform_i32("u_tex", 0);
 
-    let (mut vbo, mut vao) = (0, 0);
-    let vert_ptr = &vertices[0] as *const f32 as *const c_void;
-    let buffer_size = (vertices.len() * mem::size_of::<GLfloat>()) as GLsizeiptr;
-    let pos_size = 3 * mem::size_of::<GLfloat>() as GLsizei;
+        let mut sprites = std::mem::take(&mut self.sprites);
+        let mut start = 0;
+
+        while start < sprites.len() {
+            let texture = sprites[start].texture;
+            let mut end = start + 1;
+
+            while end < sprites.len() && end - start < MAX_SPRITES && sprites[end].texture == texture {
+                end += 1;
+            }
+
+            self.flush(texture, &sprites[start..end]);
+            start = end;
+        }
+
+        sprites.clear();
+        self.sprites = sprites;
+    }
+
+    fn flush(&mut self, texture: GLuint, sprites: &[Sprite]) {
+        self.vertices.clear();
+
+        for sprite in sprites {
+            self.vertices.extend_from_slice(&sprite.vertices);
+        }
+
+        texture::bind_handle(0, texture);
+
+        self.vbo.write_vertices(&self.vertices, 0).unwrap();
+        self.vbo.draw(0, sprites.len() * 6);
+        self.draw_calls += 1;
+    }
+
+    pub fn draw_calls(&self) -> usize {
+        self.draw_calls
+    }
+}
 
-    unsafe {
-        gl::GenVertexArrays(1, &mut vao);
-        gl::GenBuffers(1, &mut vbo);
-        gl::BindVertexArray(vao);
+#[cfg(all(test, feature = "headless"))]
+mod tests {
+    use super::*;
+    use crate::texture::{generate, MipPolicy};
 
-        gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-        gl::BufferData(gl::ARRAY_BUFFER, buffer_size, vert_ptr, gl::STATIC_DRAW);
+    #[test]
+    fn drawing_flushes_on_bind_mips() {
+        crate::headless::run(|| {
+            let buf = generate::checkerboard(4, 4, 1, Color::BLACK, Color::WHITE);
+            let texture = Texture::make_mipmapped(&buf, 4, 4, MipPolicy::OnBind).unwrap();
+            let mut batch = SpriteBatch::new();
 
-        gl::VertexAttribPointer(0, 3, gl::FLOAT, gl::FALSE, pos_size, ptr::null());
-        gl::EnableVertexAttribArray(0);
+            texture.write(&vec![255; 4 * 4 * 4], 0, 0, 4, 4);
 
-        gl::BindBuffer(gl::ARRAY_BUFFER, 0);
-        gl::BindVertexArray(0);
+            batch.begin();
+            batch.draw(&texture, Rect::make(0.0, 0.0, 4.0, 4.0), Rect::make(0.0, 0.0, 4.0, 4.0), Color::WHITE, 0.0);
+            batch.end();
 
-        vao
+            assert!(!texture.mips_dirty());
+        });
     }
 }
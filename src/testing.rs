@@ -0,0 +1,299 @@
+use crate::context::{self, ClearFlag, StateSnapshot};
+use crate::headless;
+use crate::readback;
+use crate::screenshot;
+use crate::texture::TextureFormat;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+// pixelmatch's YIQ weights; the largest possible delta is 35215
+const MAX_YIQ_DELTA: f32 = 35215.0;
+
+fn root_dir() -> PathBuf {
+    env::var_os("CARGO_MANIFEST_DIR").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."))
+}
+
+// GL_TOOLKIT_REFERENCE_DIR overrides the default of tests/reference in the crate under test
+pub fn reference_dir() -> PathBuf {
+    env::var_os("GL_TOOLKIT_REFERENCE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| root_dir().join("tests").join("reference"))
+}
+
+pub fn failure_dir() -> PathBuf {
+    env::var_os("GL_TOOLKIT_FAILURE_DIR")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| root_dir().join("target").join("golden-failures"))
+}
+
+fn updating_references() -> bool {
+    env::var_os("GL_TOOLKIT_UPDATE_REFERENCES").map_or(false, |value| value != "0")
+}
+
+fn blend_white(value: u8, alpha: f32) -> f32 {
+    255.0 + (value as f32 - 255.0) * alpha
+}
+
+fn yiq(pixel: &[u8]) -> (f32, f32, f32) {
+    let alpha = pixel[3] as f32 / 255.0;
+    let r = blend_white(pixel[0], alpha);
+    let g = blend_white(pixel[1], alpha);
+    let b = blend_white(pixel[2], alpha);
+
+    (
+        r * 0.29889531 + g * 0.58662247 + b * 0.11448223,
+        r * 0.59597799 - g * 0.27417610 - b * 0.32180189,
+        r * 0.21147017 - g * 0.52261711 + b * 0.31114694,
+    )
+}
+
+// 0 for identical pixels up to 1 for black against white, weighted toward what the eye notices
+fn perceptual_delta(a: &[u8], b: &[u8]) -> f32 {
+    if a == b {
+        return 0.0;
+    }
+
+    let (y1, i1, q1) = yiq(a);
+    let (y2, i2, q2) = yiq(b);
+    let (y, i, q) = (y1 - y2, i1 - i2, q1 - q2);
+
+    (0.5053 * y * y + 0.299 * i * i + 0.1957 * q * q) / MAX_YIQ_DELTA
+}
+
+#[derive(Debug, Clone)]
+pub struct FrameDiff {
+    pub width: usize,
+    pub height: usize,
+    pub mismatched: usize,
+    pub max_delta: f32,
+    pub image: Vec<u8>,
+}
+
+impl FrameDiff {
+    pub fn matches(&self) -> bool {
+        self.mismatched == 0
+    }
+}
+
+// both buffers are top-down RGBA8; pixels whose perceptual delta exceeds tolerance are painted red
+// in the diff image, everything else is a faded copy of the expected frame
+pub fn compare_pixels(expected: &[u8], actual: &[u8], width: usize, height: usize, tolerance: f32) -> FrameDiff {
+    assert_eq!(expected.len(), width * height * 4, "expected pixels don't match the given size");
+    assert_eq!(actual.len(), width * height * 4, "actual pixels don't match the given size");
+
+    let mut result = FrameDiff {
+        width,
+        height,
+        mismatched: 0,
+        max_delta: 0.0,
+        image: Vec::with_capacity(expected.len()),
+    };
+
+    for (a, b) in expected.chunks(4).zip(actual.chunks(4)) {
+        let delta = perceptual_delta(a, b);
+
+        result.max_delta = result.max_delta.max(delta);
+
+        if delta > tolerance {
+            result.mismatched += 1;
+            result.image.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let (y, _, _) = yiq(a);
+            let faded = (255.0 + (y - 255.0) * 0.1) as u8;
+
+            result.image.extend_from_slice(&[faded, faded, faded, 255]);
+        }
+    }
+
+    result
+}
+
+fn read_frame() -> (Vec<u8>, usize, usize) {
+    let viewport = context::viewport();
+    let (width, height) = (viewport.width as usize, viewport.height as usize);
+    let previous = context::bound_framebuffer();
+
+    context::bind_framebuffer(0);
+
+    let mut pixels = readback::read_pixels(viewport.x as i32, viewport.y as i32, width, height, TextureFormat::Rgba8);
+
+    context::bind_framebuffer(previous);
+    screenshot::flip_rows(&mut pixels, width * 4);
+
+    (pixels, width, height)
+}
+
+fn fail(name: &str, actual: &[u8], diff: Option<&FrameDiff>, width: usize, height: usize, msg: &str) -> ! {
+    let dir = failure_dir();
+    let actual_path = dir.join(format!("{}.actual.png", name));
+    let _ = fs::create_dir_all(&dir);
+    let _ = screenshot::save_png(&actual_path, actual, width, height);
+
+    if let Some(diff) = diff {
+        let _ = screenshot::save_png(&dir.join(format!("{}.diff.png", name)), &diff.image, width, height);
+    }
+
+    panic!(
+        "frame \"{}\" {}; wrote {} (set GL_TOOLKIT_UPDATE_REFERENCES=1 to accept it)",
+        name,
+        msg,
+        actual_path.display(),
+    );
+}
+
+// compares the current viewport of the default framebuffer against <reference_dir>/<name>.png;
+// tolerance is the largest perceptual delta (0..1) a pixel may have before it counts as different
+pub fn assert_frame_matches(name: &str, tolerance: f32) {
+    let (actual, width, height) = read_frame();
+    let reference = reference_dir().join(format!("{}.png", name));
+
+    if updating_references() {
+        let _ = fs::create_dir_all(reference_dir());
+
+        if let Err(err) = screenshot::save_png(&reference, &actual, width, height) {
+            panic!("failed to update reference for \"{}\": {}", name, err);
+        }
+
+        return;
+    }
+
+    let expected = match ::image::open(&reference) {
+        Ok(image) => image.into_rgba8(),
+        Err(err) => fail(name, &actual, None, width, height, &format!("has no usable reference ({})", err)),
+    };
+
+    let (expected_width, expected_height) = expected.dimensions();
+
+    if expected_width as usize != width || expected_height as usize != height {
+        let msg = format!("is {}x{}, but the reference is {}x{}", width, height, expected_width, expected_height);
+
+        fail(name, &actual, None, width, height, &msg);
+    }
+
+    let diff = compare_pixels(expected.as_raw(), &actual, width, height, tolerance);
+
+    if !diff.matches() {
+        let msg = format!("differs in {} pixels (max delta {:.4})", diff.mismatched, diff.max_delta);
+
+        fail(name, &actual, Some(&diff), width, height, &msg);
+    }
+}
+
+struct RestoreState(StateSnapshot);
+
+impl Drop for RestoreState {
+    fn drop(&mut self) {
+        context::restore(&self.0);
+    }
+}
+
+// renders f on the shared headless context from a cleared frame, then checks the result; GL state
+// is restored afterwards, even on failure, so tests can't leak into each other
+pub fn assert_render_matches<F>(name: &str, tolerance: f32, f: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    let name = name.to_string();
+
+    headless::run(move || {
+        let _restore = RestoreState(context::snapshot());
+
+        context::bind_framebuffer(0);
+        context::clear(ClearFlag::Color | ClearFlag::Depth | ClearFlag::Stencil);
+
+        f();
+
+        assert_frame_matches(&name, tolerance);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::math;
+    use crate::rect::Rect;
+    use crate::sprite::SpriteBatch;
+    use crate::texture::generate;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    fn solid(pixel: [u8; 4], count: usize) -> Vec<u8> {
+        pixel.iter().copied().cycle().take(count * 4).collect()
+    }
+
+    #[test]
+    fn identical_buffers_match() {
+        let pixels = generate::checkerboard(8, 8, 2, Color::BLACK, Color::WHITE);
+        let diff = compare_pixels(&pixels, &pixels, 8, 8, 0.0);
+
+        assert!(diff.matches());
+        assert_eq!(diff.max_delta, 0.0);
+        assert_eq!((diff.width, diff.height, diff.image.len()), (8, 8, pixels.len()));
+    }
+
+    #[test]
+    fn tolerance_is_inclusive() {
+        let expected = solid([100, 100, 100, 255], 1);
+        let actual = solid([110, 100, 100, 255], 1);
+        let delta = compare_pixels(&expected, &actual, 1, 1, 0.0).max_delta;
+
+        assert!(delta > 0.0);
+        assert!(compare_pixels(&expected, &actual, 1, 1, delta).matches());
+        assert_eq!(compare_pixels(&expected, &actual, 1, 1, delta * 0.99).mismatched, 1);
+
+        // deltas are normalized so nothing exceeds 1, not even black against white
+        let black_white = compare_pixels(&solid([0, 0, 0, 255], 1), &solid([255; 4], 1), 1, 1, 1.0);
+
+        assert!(black_white.matches());
+        assert!(black_white.max_delta > 0.9 && black_white.max_delta <= 1.0);
+    }
+
+    #[test]
+    fn transparent_pixels_compare_as_white() {
+        let clear_red = solid([255, 0, 0, 0], 1);
+        let clear_blue = solid([0, 0, 255, 0], 1);
+
+        assert_eq!(compare_pixels(&clear_red, &clear_blue, 1, 1, 0.0).max_delta, 0.0);
+        assert_eq!(compare_pixels(&clear_red, &solid([255; 4], 1), 1, 1, 0.0).max_delta, 0.0);
+
+        // half-transparent black blends to mid gray
+        let half = compare_pixels(&solid([0, 0, 0, 128], 1), &solid([127, 127, 127, 255], 1), 1, 1, 0.0);
+
+        assert!(half.max_delta < 0.0001);
+    }
+
+    #[test]
+    fn diff_images_mark_mismatches_red_over_a_faded_copy() {
+        let expected = [solid([0, 0, 0, 255], 2), solid([255; 4], 2)].concat();
+        let actual = [solid([0, 0, 0, 255], 1), solid([255; 4], 1), solid([255; 4], 2)].concat();
+        let diff = compare_pixels(&expected, &actual, 2, 2, 0.1);
+        let pixels = diff.image.chunks(4).collect::<Vec<_>>();
+
+        assert_eq!(diff.mismatched, 1);
+        assert_eq!(pixels[0], &[229, 229, 229, 255]);
+        assert_eq!(pixels[1], &RED);
+        assert_eq!(pixels[2], &[255; 4]);
+        assert_eq!(pixels[3], &[255; 4]);
+    }
+
+    // the reference lives in tests/reference; regenerate it with GL_TOOLKIT_UPDATE_REFERENCES=1
+    #[test]
+    fn checkerboard_sprite_matches_its_reference() {
+        assert_render_matches("checkerboard_sprite", 0.01, || {
+            let viewport = context::viewport();
+            let texture = generate::checkerboard_texture(8, 8, 2, Color::BLACK, Color::WHITE).unwrap();
+            let mut batch = SpriteBatch::new();
+
+            context::set_clear_color(Color::make(32, 64, 96, 255));
+            context::clear(ClearFlag::Color.into());
+
+            batch.set_projection(math::ortho(0.0, viewport.width as f32, viewport.height as f32, 0.0, -1.0, 1.0));
+            batch.begin();
+            batch.draw(&texture, Rect::make(0.0, 0.0, 8.0, 8.0), Rect::make(64.0, 64.0, 128.0, 128.0), Color::YELLOW, 0.0);
+            batch.end();
+        });
+    }
+}
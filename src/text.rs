@@ -0,0 +1,290 @@
+use crate::builtin::{SpriteVertex, SHADER_SPRITE};
+use crate::color::Color;
+use crate::error::{Error, Result};
+use crate::math;
+use crate::text_layout::{LayoutOptions, TextLayout};
+use crate::texture::{self, Texture};
+use crate::vbo::{BufferMode, PrimitiveKind, Vertex, VBO};
+
+use std::collections::HashMap;
+use vex::Matrix4;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Glyph {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+    pub x_advance: f32,
+}
+
+pub struct Font {
+    texture: Texture,
+    line_height: f32,
+    base: f32,
+    glyphs: HashMap<char, Glyph>,
+    kerning: HashMap<(char, char), f32>,
+}
+
+fn parse_pairs(line: &str) -> HashMap<&str, &str> {
+    line.split_whitespace()
+        .skip(1)
+        .filter_map(|pair| {
+            let mut parts = pair.splitn(2, '=');
+
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) => Some((key, value.trim_matches('"'))),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn parse_value(pairs: &HashMap<&str, &str>, key: &str) -> Result<f32> {
+    pairs.get(key)
+        .and_then(|value| value.parse::<f32>().ok())
+        .ok_or_else(|| Error::InvalidFont(format!("missing or malformed \"{}\"", key)))
+}
+
+fn parse_char(pairs: &HashMap<&str, &str>, key: &str) -> Result<char> {
+    let code = parse_value(pairs, key)?;
+
+    std::char::from_u32(code as u32)
+        .ok_or_else(|| Error::InvalidFont(format!("invalid character code {}", code)))
+}
+
+impl Font {
+    pub fn from_bmfont(source: &str, texture: Texture) -> Result<Font> {
+        let mut result = Font {
+            texture,
+            line_height: 0.0,
+            base: 0.0,
+            glyphs: HashMap::new(),
+            kerning: HashMap::new(),
+        };
+
+        for line in source.lines() {
+            let pairs = parse_pairs(line);
+
+            match line.split_whitespace().next() {
+                Some("common") => {
+                    result.line_height = parse_value(&pairs, "lineHeight")?;
+                    result.base = parse_value(&pairs, "base")?;
+                },
+                Some("char") => {
+                    let glyph = Glyph {
+                        x: parse_value(&pairs, "x")?,
+                        y: parse_value(&pairs, "y")?,
+                        width: parse_value(&pairs, "width")?,
+                        height: parse_value(&pairs, "height")?,
+                        x_offset: parse_value(&pairs, "xoffset")?,
+                        y_offset: parse_value(&pairs, "yoffset")?,
+                        x_advance: parse_value(&pairs, "xadvance")?,
+                    };
+
+                    result.glyphs.insert(parse_char(&pairs, "id")?, glyph);
+                },
+                Some("kerning") => {
+                    let first = parse_char(&pairs, "first")?;
+                    let second = parse_char(&pairs, "second")?;
+
+                    result.kerning.insert((first, second), parse_value(&pairs, "amount")?);
+                },
+                _ => {},
+            }
+        }
+
+        if result.line_height <= 0.0 {
+            return Err(Error::InvalidFont("missing \"common\" block".to_string()));
+        }
+
+        Ok(result)
+    }
+
+    pub fn from_grid(texture: Texture, cell_width: usize, cell_height: usize, first: char, count: usize) -> Result<Font> {
+        if cell_width == 0 || cell_height == 0 || cell_width > texture.width() {
+            return Err(Error::InvalidFont("grid cells do not fit the texture".to_string()));
+        }
+
+        let columns = texture.width() / cell_width;
+        let rows = texture.height() / cell_height;
+
+        if count > columns * rows {
+            return Err(Error::InvalidFont(format!("{} glyphs do not fit a {}x{} grid", count, columns, rows)));
+        }
+
+        let mut glyphs = HashMap::new();
+
+        for i in 0..count {
+            let c = std::char::from_u32(first as u32 + i as u32)
+                .ok_or_else(|| Error::InvalidFont(format!("invalid character code {}", first as u32 + i as u32)))?;
+
+            glyphs.insert(c, Glyph {
+                x: ((i % columns) * cell_width) as f32,
+                y: ((i / columns) * cell_height) as f32,
+                width: cell_width as f32,
+                height: cell_height as f32,
+                x_offset: 0.0,
+                y_offset: 0.0,
+                x_advance: cell_width as f32,
+            });
+        }
+
+        Ok(Font {
+            texture,
+            line_height: cell_height as f32,
+            base: cell_height as f32,
+            glyphs,
+            kerning: HashMap::new(),
+        })
+    }
+
+    pub fn glyph(&self, c: char) -> Option<&Glyph> {
+        self.glyphs.get(&c)
+    }
+
+    pub fn kerning(&self, first: char, second: char) -> f32 {
+        self.kerning.get(&(first, second)).copied().unwrap_or(0.0)
+    }
+
+    pub fn line_height(&self) -> f32 {
+        self.line_height
+    }
+
+    pub fn base(&self) -> f32 {
+        self.base
+    }
+
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn line_width(&self, line: &str) -> f32 {
+        let mut width = 0.0;
+        let mut prev = None;
+
+        for c in line.chars() {
+            if let Some(p) = prev {
+                width += self.kerning(p, c);
+            }
+
+            if let Some(glyph) = self.glyph(c) {
+                width += glyph.x_advance;
+            }
+
+            prev = Some(c);
+        }
+
+        width
+    }
+
+    pub fn wrap(&self, text: &str, max_width: Option<f32>) -> Vec<String> {
+        let mut lines = Vec::new();
+
+        for paragraph in text.split('\n') {
+            let max_width = match max_width {
+                Some(max_width) => max_width,
+                None => {
+                    lines.push(paragraph.to_string());
+                    continue;
+                },
+            };
+
+            let mut current = String::new();
+
+            for word in paragraph.split(' ') {
+                let candidate = if current.is_empty() {
+                    word.to_string()
+                } else {
+                    format!("{} {}", current, word)
+                };
+
+                if !current.is_empty() && self.line_width(&candidate) > max_width {
+                    lines.push(std::mem::replace(&mut current, word.to_string()));
+                } else {
+                    current = candidate;
+                }
+            }
+
+            lines.push(current);
+        }
+
+        lines
+    }
+
+    pub fn measure(&self, text: &str, max_width: Option<f32>) -> (f32, f32) {
+        let lines = self.wrap(text, max_width);
+        let width = lines.iter()
+            .map(|line| self.line_width(line))
+            .fold(0.0, f32::max);
+
+        (width, lines.len() as f32 * self.line_height)
+    }
+}
+
+pub struct TextRenderer {
+    vbo: VBO,
+    projection: Matrix4,
+    vertices: Vec<SpriteVertex>,
+    max_glyphs: usize,
+}
+
+impl TextRenderer {
+    pub fn new(max_glyphs: usize) -> TextRenderer {
+        assert!(max_glyphs > 0 && max_glyphs * 4 <= u16::MAX as usize + 1, "max_glyphs exceeds the 16-bit index range");
+
+        let vertices = vec![SpriteVertex::new(); max_glyphs * 4];
+        let indices: Vec<u16> = (0..max_glyphs as u16)
+            .flat_map(|i| {
+                let base = i * 4;
+
+                vec![base, base + 1, base + 2, base + 2, base + 3, base]
+            })
+            .collect();
+
+        TextRenderer {
+            vbo: VBO::new(BufferMode::DynamicDraw, PrimitiveKind::Triangles, &vertices, Some(&indices)),
+            projection: math::identity(),
+            vertices: Vec::with_capacity(max_glyphs * 4),
+            max_glyphs,
+        }
+    }
+
+    pub fn set_projection(&mut self, projection: Matrix4) {
+        self.projection = projection;
+    }
+
+    pub fn set_screen_projection(&mut self, width: f32, height: f32) {
+        self.projection = math::ortho(0.0, width, height, 0.0, -1.0, 1.0);
+    }
+
+    pub fn draw(&mut self, font: &Font, text: &str, x: f32, y: f32, color: Color, max_width: Option<f32>) {
+        let options = LayoutOptions {
+            max_width,
+            ..LayoutOptions::new()
+        };
+
+        self.draw_layout(font, &TextLayout::plain(font, text, color, &options), x, y);
+    }
+
+    // glyphs beyond max_glyphs are dropped
+    pub fn draw_layout(&mut self, font: &Font, layout: &TextLayout, x: f32, y: f32) {
+        self.vertices.clear();
+        self.vertices.extend(layout.vertices(font, x, y).into_iter().take(self.max_glyphs * 4));
+
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        SHADER_SPRITE.bind();
+        SHADER_SPRITE.set_uniform_mat4("u_projection", &self.projection);
+        SHADER_SPRITE.set_uniform_i32("u_tex", 0);
+
+        texture::bind_handle(0, font.texture.handle());
+
+        self.vbo.write_vertices(&self.vertices, 0).unwrap();
+        self.vbo.draw(0, self.vertices.len() / 4 * 6);
+    }
+}
@@ -0,0 +1,290 @@
+use crate::builtin::SpriteVertex;
+use crate::color::Color;
+use crate::rect::Rect;
+use crate::sprite::SpriteBatch;
+use crate::text::Font;
+
+use std::ops::Range;
+use vex::{Vector2, Vector3};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextAlign {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextSpan {
+    pub text: String,
+    pub color: Color,
+}
+
+impl TextSpan {
+    pub fn new(text: &str, color: Color) -> TextSpan {
+        TextSpan {
+            text: text.to_string(),
+            color,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LayoutOptions {
+    // None only breaks at newlines
+    pub max_width: Option<f32>,
+    // lines align within max_width, or within the widest line without one
+    pub align: TextAlign,
+    // a multiple of the font's line height
+    pub line_spacing: f32,
+}
+
+impl LayoutOptions {
+    pub fn new() -> LayoutOptions {
+        LayoutOptions {
+            max_width: None,
+            align: TextAlign::Left,
+            line_spacing: 1.0,
+        }
+    }
+}
+
+// positions relative to the layout's top-left corner; src_rect is in texels of the font's texture
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct LayoutGlyph {
+    pub c: char,
+    pub dst_rect: Rect,
+    pub src_rect: Rect,
+    pub color: Color,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutLine {
+    pub y: f32,
+    // excludes trailing whitespace
+    pub width: f32,
+    pub glyphs: Range<usize>,
+}
+
+type Run = Vec<(char, Color)>;
+
+// scripts without spaces between words may break between any two characters
+fn is_ideographic(c: char) -> bool {
+    matches!(c as u32, 0x3040..=0x30FF | 0x3400..=0x4DBF | 0x4E00..=0x9FFF | 0xAC00..=0xD7AF | 0xF900..=0xFAFF)
+}
+
+fn run_width(font: &Font, chars: &[(char, Color)], mut prev: Option<char>) -> f32 {
+    let mut width = 0.0;
+
+    for (c, _) in chars.iter() {
+        if let Some(p) = prev {
+            width += font.kerning(p, *c);
+        }
+
+        if let Some(glyph) = font.glyph(*c) {
+            width += glyph.x_advance;
+        }
+
+        prev = Some(*c);
+    }
+
+    width
+}
+
+fn trimmed_len(line: &[(char, Color)]) -> usize {
+    line.iter().rposition(|(c, _)| !c.is_whitespace()).map_or(0, |i| i + 1)
+}
+
+// words, whitespace runs and single ideographs: the pieces a line can break between
+fn tokenize(chars: &[(char, Color)]) -> Vec<&[(char, Color)]> {
+    let mut result = Vec::new();
+    let mut start = 0;
+
+    for i in 1..=chars.len() {
+        let boundary = i == chars.len() || {
+            let (a, b) = (chars[i - 1].0, chars[i].0);
+
+            a.is_whitespace() != b.is_whitespace() || is_ideographic(a) || is_ideographic(b)
+        };
+
+        if boundary {
+            result.push(&chars[start..i]);
+            start = i;
+        }
+    }
+
+    result
+}
+
+fn wrap_paragraph(font: &Font, chars: &[(char, Color)], max_width: Option<f32>) -> Vec<Run> {
+    let max_width = match max_width {
+        Some(max_width) => max_width,
+        None => return vec![chars.to_vec()],
+    };
+
+    let mut lines = Vec::new();
+    let mut line = Run::new();
+
+    for token in tokenize(chars) {
+        // whitespace never starts a wrapped line and never causes a break itself
+        if token[0].0.is_whitespace() {
+            if !line.is_empty() {
+                line.extend_from_slice(token);
+            }
+
+            continue;
+        }
+
+        let prev = line.last().map(|(c, _)| *c);
+
+        if !line.is_empty() && run_width(font, &line, None) + run_width(font, token, prev) > max_width {
+            lines.push(std::mem::take(&mut line));
+        }
+
+        if line.is_empty() && run_width(font, token, None) > max_width {
+            // a word wider than the whole line breaks between characters instead
+            for ch in token.iter() {
+                if !line.is_empty() && run_width(font, &line, None) + run_width(font, &[*ch], line.last().map(|(c, _)| *c)) > max_width {
+                    lines.push(std::mem::take(&mut line));
+                }
+
+                line.push(*ch);
+            }
+        } else {
+            line.extend_from_slice(token);
+        }
+    }
+
+    lines.push(line);
+    lines
+}
+
+// glyph placement for multi-line, multi-color text, independent of how it ends up drawn
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextLayout {
+    glyphs: Vec<LayoutGlyph>,
+    lines: Vec<LayoutLine>,
+    width: f32,
+    height: f32,
+}
+
+impl TextLayout {
+    pub fn new(font: &Font, spans: &[TextSpan], options: &LayoutOptions) -> TextLayout {
+        let chars = spans
+            .iter()
+            .flat_map(|span| span.text.chars().filter(|c| *c != '\r').map(move |c| (c, span.color)))
+            .collect::<Run>();
+
+        let lines = chars
+            .split(|(c, _)| *c == '\n')
+            .flat_map(|paragraph| wrap_paragraph(font, paragraph, options.max_width))
+            .collect::<Vec<Run>>();
+
+        let widths = lines
+            .iter()
+            .map(|line| run_width(font, &line[..trimmed_len(line)], None))
+            .collect::<Vec<f32>>();
+
+        let block_width = options.max_width.unwrap_or_else(|| widths.iter().copied().fold(0.0, f32::max));
+        let line_advance = font.line_height() * options.line_spacing;
+        let mut result = TextLayout {
+            glyphs: Vec::new(),
+            lines: Vec::with_capacity(lines.len()),
+            width: widths.iter().copied().fold(0.0, f32::max),
+            height: (lines.len().max(1) - 1) as f32 * line_advance + font.line_height(),
+        };
+
+        for (row, (line, width)) in lines.iter().zip(widths.iter()).enumerate() {
+            let y = row as f32 * line_advance;
+            let mut pen_x = match options.align {
+                TextAlign::Left => 0.0,
+                TextAlign::Center => (block_width - width) * 0.5,
+                TextAlign::Right => block_width - width,
+            };
+
+            let first = result.glyphs.len();
+            let mut prev = None;
+
+            for (c, color) in line.iter() {
+                if let Some(p) = prev {
+                    pen_x += font.kerning(p, *c);
+                }
+
+                prev = Some(*c);
+
+                let glyph = match font.glyph(*c) {
+                    Some(glyph) => *glyph,
+                    None => continue,
+                };
+
+                if glyph.width > 0.0 && glyph.height > 0.0 {
+                    result.glyphs.push(LayoutGlyph {
+                        c: *c,
+                        dst_rect: Rect::make(pen_x + glyph.x_offset, y + glyph.y_offset, glyph.width, glyph.height),
+                        src_rect: Rect::make(glyph.x, glyph.y, glyph.width, glyph.height),
+                        color: *color,
+                    });
+                }
+
+                pen_x += glyph.x_advance;
+            }
+
+            result.lines.push(LayoutLine {
+                y,
+                width: *width,
+                glyphs: first..result.glyphs.len(),
+            });
+        }
+
+        result
+    }
+
+    pub fn plain(font: &Font, text: &str, color: Color, options: &LayoutOptions) -> TextLayout {
+        TextLayout::new(font, &[TextSpan::new(text, color)], options)
+    }
+
+    pub fn glyphs(&self) -> &[LayoutGlyph] {
+        &self.glyphs
+    }
+
+    pub fn lines(&self) -> &[LayoutLine] {
+        &self.lines
+    }
+
+    // the widest line, and the top of the first line to the bottom of the last
+    pub fn size(&self) -> (f32, f32) {
+        (self.width, self.height)
+    }
+
+    // four corners per glyph for quad indices (0, 1, 2, 2, 3, 0), offset to x, y
+    pub fn vertices(&self, font: &Font, x: f32, y: f32) -> Vec<SpriteVertex> {
+        let tex_width = font.texture().width() as f32;
+        let tex_height = font.texture().height() as f32;
+        let mut result = Vec::with_capacity(self.glyphs.len() * 4);
+
+        for glyph in self.glyphs.iter() {
+            let (dst, src) = (&glyph.dst_rect, &glyph.src_rect);
+            let (x0, y0, x1, y1) = (x + dst.x, y + dst.y, x + dst.right(), y + dst.bottom());
+            let (u0, v0) = (src.x / tex_width, src.y / tex_height);
+            let (u1, v1) = (src.right() / tex_width, src.bottom() / tex_height);
+
+            result.extend_from_slice(&[
+                SpriteVertex::from_parts(Vector3::make(x0, y0, 0.0), Vector2::make(u0, v0), glyph.color),
+                SpriteVertex::from_parts(Vector3::make(x1, y0, 0.0), Vector2::make(u1, v0), glyph.color),
+                SpriteVertex::from_parts(Vector3::make(x1, y1, 0.0), Vector2::make(u1, v1), glyph.color),
+                SpriteVertex::from_parts(Vector3::make(x0, y1, 0.0), Vector2::make(u0, v1), glyph.color),
+            ]);
+        }
+
+        result
+    }
+
+    // queues every glyph as a sprite between SpriteBatch::begin and end
+    pub fn draw(&self, batch: &mut SpriteBatch, font: &Font, x: f32, y: f32) {
+        for glyph in self.glyphs.iter() {
+            let dst = glyph.dst_rect;
+
+            batch.draw(font.texture(), glyph.src_rect, Rect::make(x + dst.x, y + dst.y, dst.width, dst.height), glyph.color, 0.0);
+        }
+    }
+}
@@ -1,15 +1,31 @@
+use crate::color::{Color, LinearColor};
+use crate::context::{self, Garbage, Ownership};
+use crate::debug;
 use crate::error::{Result, Error};
+use crate::rect::Rect;
 
 use gl::types::*;
-use lazy_static::lazy_static;
+use std::fmt;
+use std::marker::PhantomData;
 use std::os::raw::c_void;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::vec::Vec;
 
-#[derive(Copy, Clone)]
+mod bindless;
+pub mod generate;
+
+#[cfg(feature = "image")]
+mod decode;
+
+pub use bindless::{is_bindless_supported, load_bindless_with};
+#[cfg(feature = "image")]
+pub use decode::ImageOptions;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum WrapCoord {
     S,
     T,
+    R,
 }
 
 impl WrapCoord {
@@ -17,15 +33,18 @@ impl WrapCoord {
         match self {
             WrapCoord::S => gl::TEXTURE_WRAP_S,
             WrapCoord::T => gl::TEXTURE_WRAP_T,
+            WrapCoord::R => gl::TEXTURE_WRAP_R,
         }
     }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ClampMode {
     Edge,
     Repeat,
     RepeatMirrored,
+    // samples outside 0..1 return the border color
+    Border,
 }
 
 impl ClampMode {
@@ -34,11 +53,19 @@ impl ClampMode {
             ClampMode::Edge => gl::CLAMP_TO_EDGE,
             ClampMode::Repeat => gl::REPEAT,
             ClampMode::RepeatMirrored => gl::MIRRORED_REPEAT,
+            ClampMode::Border => gl::CLAMP_TO_BORDER,
         }
     }
+
+    fn from_native(value: GLint) -> Option<ClampMode> {
+        [ClampMode::Edge, ClampMode::Repeat, ClampMode::RepeatMirrored, ClampMode::Border]
+            .iter()
+            .copied()
+            .find(|mode| mode.get_native() as GLint == value)
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MinFilter {
     Nearest,
     Linear,
@@ -55,13 +82,27 @@ impl MinFilter {
             MinFilter::Linear => gl::LINEAR,
             MinFilter::NearestMipmapNearest => gl::NEAREST_MIPMAP_NEAREST,
             MinFilter::NearestMipmapLinear => gl::NEAREST_MIPMAP_LINEAR,
-            MinFilter::LinearMipmapNearest => gl::LINEAR_MIPMAP_LINEAR,
+            MinFilter::LinearMipmapNearest => gl::LINEAR_MIPMAP_NEAREST,
             MinFilter::LinearMipmapLinear => gl::LINEAR_MIPMAP_LINEAR,
         }
     }
+
+    fn from_native(value: GLint) -> Option<MinFilter> {
+        [
+            MinFilter::Nearest,
+            MinFilter::Linear,
+            MinFilter::NearestMipmapNearest,
+            MinFilter::NearestMipmapLinear,
+            MinFilter::LinearMipmapNearest,
+            MinFilter::LinearMipmapLinear,
+        ]
+        .iter()
+        .copied()
+        .find(|filter| filter.get_native() as GLint == value)
+    }
 }
 
-#[derive(Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum MagFilter {
     Nearest,
     Linear,
@@ -74,28 +115,233 @@ impl MagFilter {
             MagFilter::Linear => gl::LINEAR,
         }
     }
+
+    fn from_native(value: GLint) -> Option<MagFilter> {
+        [MagFilter::Nearest, MagFilter::Linear].iter().copied().find(|filter| filter.get_native() as GLint == value)
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct TextureUnit {
-    d1_handle: GLuint,
-    d2_handle: GLuint,
-    d3_handle: GLuint,
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TextureFormat {
+    Rgba8,
+    Rgb8,
+    Rg8,
+    R8,
+    Srgb8,
+    Srgb8Alpha8,
+    Depth24,
+    Depth32F,
+    Depth24Stencil8,
+    Rgba16F,
+    Rgba32F,
 }
 
-impl TextureUnit {
-    fn new() -> TextureUnit {
+impl TextureFormat {
+    pub fn get_native(&self) -> GLenum {
+        match self {
+            TextureFormat::Rgba8 => gl::RGBA8,
+            TextureFormat::Rgb8 => gl::RGB8,
+            TextureFormat::Rg8 => gl::RG8,
+            TextureFormat::R8 => gl::R8,
+            TextureFormat::Srgb8 => gl::SRGB8,
+            TextureFormat::Srgb8Alpha8 => gl::SRGB8_ALPHA8,
+            TextureFormat::Depth24 => gl::DEPTH_COMPONENT24,
+            TextureFormat::Depth32F => gl::DEPTH_COMPONENT32F,
+            TextureFormat::Depth24Stencil8 => gl::DEPTH24_STENCIL8,
+            TextureFormat::Rgba16F => gl::RGBA16F,
+            TextureFormat::Rgba32F => gl::RGBA32F,
+        }
+    }
 
-        TextureUnit {
-            d1_handle: 0,
-            d2_handle: 0,
-            d3_handle: 0,
+    pub fn pixel_format(&self) -> GLenum {
+        match self {
+            TextureFormat::Rgba8
+            | TextureFormat::Srgb8Alpha8
+            | TextureFormat::Rgba16F
+            | TextureFormat::Rgba32F => gl::RGBA,
+            TextureFormat::Rgb8 | TextureFormat::Srgb8 => gl::RGB,
+            TextureFormat::Rg8 => gl::RG,
+            TextureFormat::R8 => gl::RED,
+            TextureFormat::Depth24 | TextureFormat::Depth32F => gl::DEPTH_COMPONENT,
+            TextureFormat::Depth24Stencil8 => gl::DEPTH_STENCIL,
         }
     }
+
+    pub fn pixel_type(&self) -> GLenum {
+        match self {
+            TextureFormat::Rgba8
+            | TextureFormat::Rgb8
+            | TextureFormat::Rg8
+            | TextureFormat::R8
+            | TextureFormat::Srgb8
+            | TextureFormat::Srgb8Alpha8 => gl::UNSIGNED_BYTE,
+            TextureFormat::Depth24 => gl::UNSIGNED_INT,
+            TextureFormat::Depth32F | TextureFormat::Rgba32F => gl::FLOAT,
+            TextureFormat::Depth24Stencil8 => gl::UNSIGNED_INT_24_8,
+            TextureFormat::Rgba16F => gl::HALF_FLOAT,
+        }
+    }
+
+    pub fn bytes_per_pixel(&self) -> usize {
+        match self {
+            TextureFormat::R8 => 1,
+            TextureFormat::Rg8 => 2,
+            TextureFormat::Rgb8 | TextureFormat::Srgb8 => 3,
+            TextureFormat::Rgba8
+            | TextureFormat::Srgb8Alpha8
+            | TextureFormat::Depth24
+            | TextureFormat::Depth32F
+            | TextureFormat::Depth24Stencil8 => 4,
+            TextureFormat::Rgba16F => 8,
+            TextureFormat::Rgba32F => 16,
+        }
+    }
+
+    pub fn is_depth(&self) -> bool {
+        match self {
+            TextureFormat::Depth24 | TextureFormat::Depth32F | TextureFormat::Depth24Stencil8 => true,
+            _ => false,
+        }
+    }
+
+    pub fn has_stencil(&self) -> bool {
+        *self == TextureFormat::Depth24Stencil8
+    }
+
+    // sampling decodes sRGB texels to linear, and rendering into one encodes when FramebufferSrgb is on
+    pub fn is_srgb(&self) -> bool {
+        *self == TextureFormat::Srgb8 || *self == TextureFormat::Srgb8Alpha8
+    }
+
+    // the sRGB counterpart with the same layout, or self when there isn't one
+    pub fn to_srgb(&self) -> TextureFormat {
+        match self {
+            TextureFormat::Rgb8 => TextureFormat::Srgb8,
+            TextureFormat::Rgba8 => TextureFormat::Srgb8Alpha8,
+            format => *format,
+        }
+    }
+
+    pub fn to_linear(&self) -> TextureFormat {
+        match self {
+            TextureFormat::Srgb8 => TextureFormat::Rgb8,
+            TextureFormat::Srgb8Alpha8 => TextureFormat::Rgba8,
+            format => *format,
+        }
+    }
+}
+
+// GL's initial TEXTURE_MAX_LEVEL
+const DEFAULT_MAX_LEVEL: usize = 1000;
+
+// core in 4.6, which is newer than the bindings
+const TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FE;
+pub(crate) const MAX_TEXTURE_MAX_ANISOTROPY: GLenum = 0x84FF;
+
+// S3TC and ASTC are extensions and absent from the core bindings
+const COMPRESSED_RGB_S3TC_DXT1: GLenum = 0x83F0;
+const COMPRESSED_RGBA_S3TC_DXT1: GLenum = 0x83F1;
+const COMPRESSED_RGBA_S3TC_DXT3: GLenum = 0x83F2;
+const COMPRESSED_RGBA_S3TC_DXT5: GLenum = 0x83F3;
+const COMPRESSED_RGBA_ASTC_4X4: GLenum = 0x93B0;
+const COMPRESSED_RGBA_ASTC_8X8: GLenum = 0x93B7;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CompressedFormat {
+    Dxt1Rgb,
+    Dxt1Rgba,
+    Dxt3,
+    Dxt5,
+    Etc2Rgb8,
+    Etc2Rgba8,
+    Astc4x4,
+    Astc8x8,
+}
+
+impl CompressedFormat {
+    pub const ALL: [CompressedFormat; 8] = [
+        CompressedFormat::Dxt1Rgb,
+        CompressedFormat::Dxt1Rgba,
+        CompressedFormat::Dxt3,
+        CompressedFormat::Dxt5,
+        CompressedFormat::Etc2Rgb8,
+        CompressedFormat::Etc2Rgba8,
+        CompressedFormat::Astc4x4,
+        CompressedFormat::Astc8x8,
+    ];
+
+    pub fn get_native(&self) -> GLenum {
+        match self {
+            CompressedFormat::Dxt1Rgb => COMPRESSED_RGB_S3TC_DXT1,
+            CompressedFormat::Dxt1Rgba => COMPRESSED_RGBA_S3TC_DXT1,
+            CompressedFormat::Dxt3 => COMPRESSED_RGBA_S3TC_DXT3,
+            CompressedFormat::Dxt5 => COMPRESSED_RGBA_S3TC_DXT5,
+            CompressedFormat::Etc2Rgb8 => gl::COMPRESSED_RGB8_ETC2,
+            CompressedFormat::Etc2Rgba8 => gl::COMPRESSED_RGBA8_ETC2_EAC,
+            CompressedFormat::Astc4x4 => COMPRESSED_RGBA_ASTC_4X4,
+            CompressedFormat::Astc8x8 => COMPRESSED_RGBA_ASTC_8X8,
+        }
+    }
+
+    pub fn block_size(&self) -> (usize, usize) {
+        match self {
+            CompressedFormat::Astc8x8 => (8, 8),
+            _ => (4, 4),
+        }
+    }
+
+    pub fn block_bytes(&self) -> usize {
+        match self {
+            CompressedFormat::Dxt1Rgb | CompressedFormat::Dxt1Rgba | CompressedFormat::Etc2Rgb8 => 8,
+            _ => 16,
+        }
+    }
+
+    pub fn level_size(&self, width: usize, height: usize) -> usize {
+        let (block_width, block_height) = self.block_size();
+        let columns = (width + block_width - 1) / block_width;
+        let rows = (height + block_height - 1) / block_height;
+
+        columns * rows * self.block_bytes()
+    }
+
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            CompressedFormat::Dxt1Rgb
+            | CompressedFormat::Dxt1Rgba
+            | CompressedFormat::Dxt3
+            | CompressedFormat::Dxt5 => "GL_EXT_texture_compression_s3tc",
+            CompressedFormat::Etc2Rgb8 | CompressedFormat::Etc2Rgba8 => "GL_ARB_ES3_compatibility",
+            CompressedFormat::Astc4x4 | CompressedFormat::Astc8x8 => "GL_KHR_texture_compression_astc_ldr",
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        context::capabilities().supports_compressed(*self)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum MipPolicy {
+    Manual,
+    OnBind,
+    OnWrite,
 }
 
 pub struct Texture {
+    format: TextureFormat,
+    compressed: Option<CompressedFormat>,
     mipmaps: bool,
+    mip_policy: MipPolicy,
+    mips_dirty: AtomicBool,
+    bindless_handle: AtomicU64,
+    resident: AtomicBool,
+    anisotropy: f32,
+    anisotropy_dirty: AtomicBool,
+    base_level: usize,
+    max_level: usize,
+    lod_bias: f32,
+    border_color: Color,
     handle: GLuint,
     s_clamp: ClampMode,
     t_clamp: ClampMode,
@@ -103,45 +349,85 @@ pub struct Texture {
     mag_filter: MagFilter,
     width: usize,
     height: usize,
+    ownership: Ownership,
+    label: Option<String>,
+    _thread: context::ThreadMarker,
+}
+
+impl fmt::Debug for Texture {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Texture")
+            .field("handle", &self.handle)
+            .field("label", &self.label)
+            .field("format", &self.format)
+            .field("width", &self.width)
+            .field("height", &self.height)
+            .field("ownership", &self.ownership)
+            .finish()
+    }
 }
 
 impl Texture {
     pub fn new(width: usize, height: usize) -> Texture {
-        let total_size = width * height * 4;
+        Texture::new_with_format(TextureFormat::Rgba8, width, height)
+    }
+
+    pub fn new_with_format(format: TextureFormat, width: usize, height: usize) -> Texture {
+        context::assert_initialized();
+
+        let total_size = width * height * format.bytes_per_pixel();
         let buf = vec![0u8; total_size];
 
-        Texture::build_texture(&buf, width, height, false).unwrap()
+        Texture::build_texture(format, &buf, width, height, false).unwrap()
     }
 
     pub fn make(buf: &Vec::<u8>, width: usize, height: usize, mipmaps: bool) -> Result<Texture> {
-        Texture::build_texture(buf, width, height, mipmaps)
+        Texture::make_with_format(TextureFormat::Rgba8, buf, width, height, mipmaps)
+    }
+
+    pub fn make_with_format(format: TextureFormat, buf: &[u8], width: usize, height: usize, mipmaps: bool) -> Result<Texture> {
+        context::check_initialized()?;
+
+        Texture::build_texture(format, buf, width, height, mipmaps)
     }
 
-    fn build_texture(buf: &[u8], width: usize, height: usize, mipmaps: bool) -> Result<Texture> {
+    pub fn make_mipmapped(buf: &Vec::<u8>, width: usize, height: usize, policy: MipPolicy) -> Result<Texture> {
+        let mut result = Texture::make(buf, width, height, true)?;
+
+        result.mip_policy = policy;
+        Ok(result)
+    }
+
+    fn build_texture(format: TextureFormat, buf: &[u8], width: usize, height: usize, mipmaps: bool) -> Result<Texture> {
         let mut handle = 0 as GLuint;
-        let total_size = width * height * 4;
+        let total_size = width * height * format.bytes_per_pixel();
 
-        if buf.len() != total_size {
+        if total_size == 0 || buf.len() != total_size {
             return Err(Error::InvalidTextureDimensions);
         }
 
+        let clamp = ClampMode::Edge;
+        let min_filter = MinFilter::Nearest;
+        let mag_filter = MagFilter::Nearest;
+
         unsafe {
             gl::GenTextures(1, &mut handle);
-            gl::BindTexture(gl::TEXTURE_2D, handle);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, gl::CLAMP_TO_EDGE as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, gl::NEAREST as i32);
-            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as i32);
+            bind_handle(0, handle);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, clamp.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, clamp.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter.get_native() as i32);
 
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
             gl::TexImage2D(
                 gl::TEXTURE_2D,
                 0,
-                gl::RGBA as i32,
+                format.get_native() as i32,
                 width as i32,
                 height as i32,
                 0,
-                gl::RGBA,
-                gl::UNSIGNED_BYTE,
+                format.pixel_format(),
+                format.pixel_type(),
                 &buf[0] as *const u8 as *const c_void,
             );
 
@@ -151,50 +437,647 @@ impl Texture {
         }
 
         Ok(Texture {
+            format,
+            compressed: None,
             mipmaps,
+            mip_policy: MipPolicy::Manual,
+            mips_dirty: AtomicBool::new(false),
+            bindless_handle: AtomicU64::new(0),
+            resident: AtomicBool::new(false),
+            anisotropy: 1.0,
+            anisotropy_dirty: AtomicBool::new(false),
+            base_level: 0,
+            max_level: DEFAULT_MAX_LEVEL,
+            lod_bias: 0.0,
+            border_color: Color::TRANSPARENT,
             handle,
-            s_clamp: ClampMode::Edge,
-            t_clamp: ClampMode::Edge,
-            min_filter: MinFilter::Nearest,
-            mag_filter: MagFilter::Nearest,
+            s_clamp: clamp,
+            t_clamp: clamp,
+            min_filter,
+            mag_filter,
             width,
             height,
+            ownership: Ownership::Owned,
+            label: None,
+            _thread: PhantomData,
         })
     }
 
-    pub fn bind(&self, unit: GLenum) {
-        let mut st = INTERNAL_STATE.lock().unwrap();
+    // wraps a 2D texture created by foreign code; its sampling parameters and mip chain are read back
+    // from GL so the getters start out truthful
+    pub fn from_raw_handle(
+        handle: GLuint,
+        width: usize,
+        height: usize,
+        format: TextureFormat,
+        ownership: Ownership,
+    ) -> Result<Texture> {
+        context::check_initialized()?;
+
+        if unsafe { gl::IsTexture(handle) } != gl::TRUE {
+            return Err(Error::InvalidHandle(format!("{} is not a texture object", handle)));
+        }
+
+        let previous = bound_handle(0, gl::TEXTURE_2D);
+        let (mut wrap_s, mut wrap_t, mut min_filter, mut mag_filter) = (0, 0, 0, 0);
+        let (mut base_level, mut max_level, mut level_1_width) = (0, 0, 0);
+        let (mut lod_bias, mut anisotropy) = (0.0, 1.0);
+        let mut border_color = [0.0; 4];
+
+        bind_handle(0, handle);
+
+        unsafe {
+            gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, &mut wrap_s);
+            gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, &mut wrap_t);
+            gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, &mut min_filter);
+            gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, &mut mag_filter);
+            gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_BASE_LEVEL, &mut base_level);
+            gl::GetTexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, &mut max_level);
+            gl::GetTexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_LOD_BIAS, &mut lod_bias);
+            gl::GetTexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, border_color.as_mut_ptr());
+            gl::GetTexLevelParameteriv(gl::TEXTURE_2D, 1, gl::TEXTURE_WIDTH, &mut level_1_width);
+
+            if context::capabilities().max_anisotropy > 1.0 {
+                gl::GetTexParameterfv(gl::TEXTURE_2D, TEXTURE_MAX_ANISOTROPY, &mut anisotropy);
+            }
+        }
+
+        bind_handle(0, previous);
+
+        let [r, g, b, a] = border_color;
+
+        Ok(Texture {
+            format,
+            compressed: None,
+            mipmaps: level_1_width > 0,
+            mip_policy: MipPolicy::Manual,
+            mips_dirty: AtomicBool::new(false),
+            bindless_handle: AtomicU64::new(0),
+            resident: AtomicBool::new(false),
+            anisotropy,
+            anisotropy_dirty: AtomicBool::new(false),
+            base_level: base_level as usize,
+            max_level: max_level as usize,
+            lod_bias,
+            border_color: Color::from_f32(r, g, b, a),
+            handle,
+            s_clamp: ClampMode::from_native(wrap_s).unwrap_or(ClampMode::Repeat),
+            t_clamp: ClampMode::from_native(wrap_t).unwrap_or(ClampMode::Repeat),
+            min_filter: MinFilter::from_native(min_filter).unwrap_or(MinFilter::NearestMipmapLinear),
+            mag_filter: MagFilter::from_native(mag_filter).unwrap_or(MagFilter::Linear),
+            width,
+            height,
+            ownership,
+            label: None,
+            _thread: PhantomData,
+        })
+    }
+
+    pub fn make_compressed(format: CompressedFormat, data: &[u8], width: usize, height: usize, mip_levels: usize) -> Result<Texture> {
+        context::check_initialized()?;
+
+        if !format.is_supported() {
+            return Err(Error::Unsupported(format!("{:?} compressed textures", format)));
+        }
+
+        let level_count = mip_levels.max(1);
+        let mut sizes = Vec::with_capacity(level_count);
+
+        for level in 0..level_count {
+            sizes.push(format.level_size((width >> level).max(1), (height >> level).max(1)));
+        }
+
+        if width == 0 || height == 0 || data.len() != sizes.iter().sum::<usize>() {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        let mut handle = 0 as GLuint;
+        let clamp = ClampMode::Edge;
+        let min_filter = if level_count > 1 { MinFilter::LinearMipmapLinear } else { MinFilter::Linear };
+        let mag_filter = MagFilter::Linear;
 
         unsafe {
-            if st.active_unit != unit {
-                gl::ActiveTexture(gl::TEXTURE0 + unit);
+            gl::GenTextures(1, &mut handle);
+            bind_handle(0, handle);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_S, clamp.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_WRAP_T, clamp.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MIN_FILTER, min_filter.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, mag_filter.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAX_LEVEL, (level_count - 1) as i32);
 
-                st.active_unit = unit;
+            let mut offset = 0;
+
+            for (level, size) in sizes.iter().enumerate() {
+                gl::CompressedTexImage2D(
+                    gl::TEXTURE_2D,
+                    level as i32,
+                    format.get_native(),
+                    (width >> level).max(1) as i32,
+                    (height >> level).max(1) as i32,
+                    0,
+                    *size as GLsizei,
+                    &data[offset] as *const u8 as *const c_void,
+                );
+
+                offset += size;
             }
+        }
+
+        Ok(Texture {
+            format: TextureFormat::Rgba8,
+            compressed: Some(format),
+            mipmaps: level_count > 1,
+            mip_policy: MipPolicy::Manual,
+            mips_dirty: AtomicBool::new(false),
+            bindless_handle: AtomicU64::new(0),
+            resident: AtomicBool::new(false),
+            anisotropy: 1.0,
+            anisotropy_dirty: AtomicBool::new(false),
+            base_level: 0,
+            max_level: level_count - 1,
+            lod_bias: 0.0,
+            border_color: Color::TRANSPARENT,
+            handle,
+            s_clamp: clamp,
+            t_clamp: clamp,
+            min_filter,
+            mag_filter,
+            width,
+            height,
+            ownership: Ownership::Owned,
+            label: None,
+            _thread: PhantomData,
+        })
+    }
+
+    pub fn bind(&self, unit: GLenum) {
+        bind_handle(unit, self.handle);
+
+        if self.mip_policy == MipPolicy::OnBind {
+            self.flush_mipmaps();
+        }
+
+        self.flush_anisotropy();
+    }
+
+    pub fn bind_scoped(&self, unit: GLuint) -> TextureBinding<'_> {
+        let previous = bound_handle(unit, gl::TEXTURE_2D);
+
+        bind_handle(unit, self.handle);
+
+        if self.mip_policy == MipPolicy::OnBind {
+            self.flush_mipmaps();
+        }
+
+        self.flush_anisotropy();
+
+        TextureBinding {
+            unit,
+            previous,
+            _texture: PhantomData,
+        }
+    }
+
+    // for callers that keep only the handle and bind it later: does what bind would, leaving bindings as they were
+    pub(crate) fn flush_pending(&self) {
+        let mips = self.mip_policy == MipPolicy::OnBind && self.mipmaps && self.mips_dirty();
+
+        if mips || self.anisotropy_dirty.load(Ordering::Acquire) {
+            let _binding = self.bind_scoped(0);
+        }
+    }
+
+    fn generate_mipmaps(&self) {
+        self.mips_dirty.store(false, Ordering::Release);
+
+        if gl::GenerateTextureMipmap::is_loaded() {
+            unsafe { gl::GenerateTextureMipmap(self.handle) };
+        } else {
+            self.bind(0);
+
+            unsafe { gl::GenerateMipmap(gl::TEXTURE_2D) };
+        }
+    }
+
+    pub fn flush_mipmaps(&self) {
+        context::assert_initialized();
+
+        if self.mipmaps && self.mips_dirty.load(Ordering::Acquire) {
+            self.generate_mipmaps();
+        }
+    }
+
+    pub fn mips_dirty(&self) -> bool {
+        self.mips_dirty.load(Ordering::Acquire)
+    }
+
+    pub fn mip_policy(&self) -> MipPolicy {
+        self.mip_policy
+    }
+
+    pub fn set_mip_policy(&mut self, policy: MipPolicy) {
+        self.mip_policy = policy;
+    }
+
+    fn set_parameter_i(&self, name: GLenum, value: GLint) {
+        if gl::TextureParameteri::is_loaded() {
+            unsafe { gl::TextureParameteri(self.handle, name, value) };
+        } else {
+            let _binding = self.bind_scoped(0);
+
+            unsafe { gl::TexParameteri(gl::TEXTURE_2D, name, value) };
+        }
+    }
+
+    fn set_parameter_f(&self, name: GLenum, value: f32) {
+        if gl::TextureParameterf::is_loaded() {
+            unsafe { gl::TextureParameterf(self.handle, name, value) };
+        } else {
+            let _binding = self.bind_scoped(0);
+
+            unsafe { gl::TexParameterf(gl::TEXTURE_2D, name, value) };
+        }
+    }
+
+    // GL_ARB_bindless_texture makes every parameter immutable once a handle has been taken, and GL
+    // would reject the change with INVALID_OPERATION while the cache recorded it anyway
+    fn check_parameters_mutable(&self) -> Result<()> {
+        if self.bindless_handle.load(Ordering::Acquire) != 0 {
+            return Err(Error::InvalidTextureParameter("the parameters of a texture with a bindless handle can't change".to_string()));
+        }
+
+        Ok(())
+    }
+
+    pub fn set_base_level(&mut self, level: usize) -> Result<()> {
+        context::check_initialized()?;
+        self.check_parameters_mutable()?;
 
-            if st.active_unit().d2_handle == self.handle {
-                gl::BindTexture(gl::TEXTURE_2D, self.handle);
+        self.set_parameter_i(gl::TEXTURE_BASE_LEVEL, level as GLint);
+        self.base_level = level;
+
+        Ok(())
+    }
+
+    pub fn set_max_level(&mut self, level: usize) -> Result<()> {
+        context::check_initialized()?;
+        self.check_parameters_mutable()?;
+
+        self.set_parameter_i(gl::TEXTURE_MAX_LEVEL, level as GLint);
+        self.max_level = level;
+
+        Ok(())
+    }
+
+    // positive values pick smaller mips (blurrier), negative values sharper ones
+    pub fn set_lod_bias(&mut self, bias: f32) -> Result<()> {
+        context::check_initialized()?;
+        self.check_parameters_mutable()?;
+
+        self.set_parameter_f(gl::TEXTURE_LOD_BIAS, bias);
+        self.lod_bias = bias;
+
+        Ok(())
+    }
+
+    // only sampled where a coordinate uses ClampMode::Border
+    pub fn set_border_color(&mut self, color: Color) -> Result<()> {
+        context::check_initialized()?;
+        self.check_parameters_mutable()?;
+
+        let values = color.to_array_f32();
+
+        if gl::TextureParameterfv::is_loaded() {
+            unsafe { gl::TextureParameterfv(self.handle, gl::TEXTURE_BORDER_COLOR, values.as_ptr()) };
+        } else {
+            let _binding = self.bind_scoped(0);
+
+            unsafe { gl::TexParameterfv(gl::TEXTURE_2D, gl::TEXTURE_BORDER_COLOR, values.as_ptr()) };
+        }
+
+        self.border_color = color;
+
+        Ok(())
+    }
+
+    pub fn border_color(&self) -> Color {
+        self.border_color
+    }
+
+    pub fn base_level(&self) -> usize {
+        self.base_level
+    }
+
+    pub fn max_level(&self) -> usize {
+        self.max_level
+    }
+
+    pub fn lod_bias(&self) -> f32 {
+        self.lod_bias
+    }
+
+    pub fn level_count(&self) -> usize {
+        (self.width.max(self.height) as f32).log2().floor() as usize + 1
+    }
+
+    // uploads a whole mip level in the texture's format (compressed blocks for compressed textures);
+    // pair with MipPolicy::Manual so generated mips don't overwrite the uploaded chain
+    pub fn write_level(&mut self, level: usize, buf: &[u8]) -> Result<()> {
+        context::check_initialized()?;
+
+        if level >= self.level_count() {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        let width = (self.width >> level).max(1);
+        let height = (self.height >> level).max(1);
+        let size = match self.compressed {
+            Some(format) => format.level_size(width, height),
+            None => width * height * self.format.bytes_per_pixel(),
+        };
+
+        if buf.len() != size {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        let binding = self.bind_scoped(0);
+
+        unsafe {
+            match self.compressed {
+                Some(format) => gl::CompressedTexImage2D(
+                    gl::TEXTURE_2D,
+                    level as i32,
+                    format.get_native(),
+                    width as i32,
+                    height as i32,
+                    0,
+                    size as GLsizei,
+                    buf.as_ptr() as *const c_void,
+                ),
+                None => {
+                    gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+                    gl::TexImage2D(
+                        gl::TEXTURE_2D,
+                        level as i32,
+                        self.format.get_native() as i32,
+                        width as i32,
+                        height as i32,
+                        0,
+                        self.format.pixel_format(),
+                        self.format.pixel_type(),
+                        buf.as_ptr() as *const c_void,
+                    );
+                },
             }
         }
+
+        drop(binding);
+
+        if level > 0 {
+            self.mipmaps = true;
+        }
+
+        Ok(())
     }
 
     pub fn write(&self, buf: &[u8], x: usize, y: usize, width: usize, height: usize) {
+        context::assert_initialized();
+
+        assert!(self.compressed.is_none(), "cannot write uncompressed pixels into a compressed texture");
+
+        assert!(
+            x + width <= self.width && y + height <= self.height,
+            "texture write region exceeds the texture bounds",
+        );
+        assert!(
+            width * height > 0 && buf.len() >= width * height * self.format.bytes_per_pixel(),
+            "texture write buffer is smaller than the region",
+        );
+
+        let format = self.format.pixel_format();
+        let kind = self.format.pixel_type();
+        let root_ptr = &buf[0] as *const u8 as *const c_void;
+
         unsafe {
-            gl::TextureSubImage2D(
-                self.handle,
-                0,
-                x as i32,
-                y as i32,
-                width as GLsizei,
-                height as GLsizei,
-                gl::RGBA as GLenum,
-                gl::UNSIGNED_BYTE as GLenum,
-                &buf[0] as *const u8 as *const c_void,
-            );
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+            if gl::TextureSubImage2D::is_loaded() {
+                gl::TextureSubImage2D(
+                    self.handle,
+                    0,
+                    x as i32,
+                    y as i32,
+                    width as GLsizei,
+                    height as GLsizei,
+                    format,
+                    kind,
+                    root_ptr,
+                );
+            } else {
+                self.bind(0);
+
+                gl::TexSubImage2D(
+                    gl::TEXTURE_2D,
+                    0,
+                    x as i32,
+                    y as i32,
+                    width as GLsizei,
+                    height as GLsizei,
+                    format,
+                    kind,
+                    root_ptr,
+                );
+            }
         }
+
+        self.mark_written();
+    }
+
+    pub fn read(&self) -> Vec<u8> {
+        context::assert_initialized();
+
+        assert!(self.compressed.is_none(), "cannot read uncompressed pixels from a compressed texture");
+
+        let size = self.width * self.height * self.format.bytes_per_pixel();
+        let mut buf = vec![0u8; size];
+        let format = self.format.pixel_format();
+        let kind = self.format.pixel_type();
+        let root_ptr = buf.as_mut_ptr() as *mut c_void;
+
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+
+            if gl::GetTextureImage::is_loaded() {
+                gl::GetTextureImage(self.handle, 0, format, kind, size as GLsizei, root_ptr);
+            } else {
+                self.bind(0);
+
+                gl::GetTexImage(gl::TEXTURE_2D, 0, format, kind, root_ptr);
+            }
+        }
+
+        buf
     }
 
-    pub fn set_clamp(&mut self, coord: WrapCoord, mode: ClampMode) {
+    fn mark_written(&self) {
+        if self.mipmaps {
+            self.mips_dirty.store(true, Ordering::Release);
+
+            if self.mip_policy == MipPolicy::OnWrite {
+                self.generate_mipmaps();
+            }
+        }
+    }
+
+    fn attachment_point(&self) -> GLenum {
+        match self.format {
+            TextureFormat::Depth24Stencil8 => gl::DEPTH_STENCIL_ATTACHMENT,
+            TextureFormat::Depth24 | TextureFormat::Depth32F => gl::DEPTH_ATTACHMENT,
+            _ => gl::COLOR_ATTACHMENT0,
+        }
+    }
+
+    // a throwaway framebuffer with level 0 attached, bound to target; the caller deletes it and
+    // calls context::rebind_framebuffer() afterwards
+    unsafe fn scratch_framebuffer(&self, target: GLenum) -> GLuint {
+        let mut handle = 0;
+
+        gl::GenFramebuffers(1, &mut handle);
+        gl::BindFramebuffer(target, handle);
+        gl::FramebufferTexture2D(target, self.attachment_point(), gl::TEXTURE_2D, self.handle, 0);
+
+        handle
+    }
+
+    // fallback for drivers without CopyImageSubData
+    #[allow(clippy::too_many_arguments)]
+    unsafe fn blit_from(&self, other: &Texture, x: GLint, y: GLint, width: GLint, height: GLint, dst_x: GLint, dst_y: GLint) {
+        let mask = match self.attachment_point() {
+            gl::COLOR_ATTACHMENT0 => gl::COLOR_BUFFER_BIT,
+            gl::DEPTH_ATTACHMENT => gl::DEPTH_BUFFER_BIT,
+            _ => gl::DEPTH_BUFFER_BIT | gl::STENCIL_BUFFER_BIT,
+        };
+
+        let read = other.scratch_framebuffer(gl::READ_FRAMEBUFFER);
+        let draw = self.scratch_framebuffer(gl::DRAW_FRAMEBUFFER);
+
+        gl::BlitFramebuffer(
+            x,
+            y,
+            x + width,
+            y + height,
+            dst_x,
+            dst_y,
+            dst_x + width,
+            dst_y + height,
+            mask,
+            gl::NEAREST,
+        );
+
+        context::rebind_framebuffer();
+        gl::DeleteFramebuffers(1, &read);
+        gl::DeleteFramebuffers(1, &draw);
+    }
+
+    // fallback for drivers without ClearTexImage
+    unsafe fn clear_through_framebuffer(&self, values: &[f32; 4]) {
+        let framebuffer = self.scratch_framebuffer(gl::DRAW_FRAMEBUFFER);
+
+        if self.format.is_depth() {
+            gl::ClearBufferfv(gl::DEPTH, 0, values.as_ptr());
+        } else {
+            gl::ClearBufferfv(gl::COLOR, 0, values.as_ptr());
+        }
+
+        context::rebind_framebuffer();
+        gl::DeleteFramebuffers(1, &framebuffer);
+    }
+
+    // src is in texels of other; both textures need the same format
+    pub fn copy_from(&self, other: &Texture, src: Rect, dst_offset: (usize, usize)) -> Result<()> {
+        context::check_initialized()?;
+
+        if self.compressed.is_some() || other.compressed.is_some() || self.format != other.format {
+            let msg = format!("can't copy {:?} texels into a {:?} texture", other.format, self.format);
+
+            return Err(Error::TextureParameterMismatch(msg));
+        }
+
+        let (x, y) = (src.x.round() as i64, src.y.round() as i64);
+        let (width, height) = (src.width.round() as i64, src.height.round() as i64);
+        let (dst_x, dst_y) = (dst_offset.0 as i64, dst_offset.1 as i64);
+
+        let fits_source = x >= 0 && y >= 0 && x + width <= other.width as i64 && y + height <= other.height as i64;
+        let fits_target = dst_x + width <= self.width as i64 && dst_y + height <= self.height as i64;
+
+        if width <= 0 || height <= 0 || !fits_source || !fits_target {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        unsafe {
+            if gl::CopyImageSubData::is_loaded() {
+                gl::CopyImageSubData(
+                    other.handle,
+                    gl::TEXTURE_2D,
+                    0,
+                    x as GLint,
+                    y as GLint,
+                    0,
+                    self.handle,
+                    gl::TEXTURE_2D,
+                    0,
+                    dst_x as GLint,
+                    dst_y as GLint,
+                    0,
+                    width as GLsizei,
+                    height as GLsizei,
+                    1,
+                );
+            } else {
+                with_unmasked_writes(|| self.blit_from(other, x as GLint, y as GLint, width as GLint, height as GLint, dst_x as GLint, dst_y as GLint));
+            }
+        }
+
+        self.mark_written();
+
+        Ok(())
+    }
+
+    // fills level 0; depth formats take the depth from color.r
+    pub fn clear(&self, color: LinearColor) -> Result<()> {
+        context::check_initialized()?;
+
+        if let Some(format) = self.compressed {
+            return Err(Error::InvalidTextureParameter(format!("{:?} textures can't be cleared", format)));
+        }
+
+        if self.format.has_stencil() {
+            return Err(Error::InvalidTextureParameter(format!("{:?} textures can't be cleared", self.format)));
+        }
+
+        let values = color.to_array();
+
+        unsafe {
+            if gl::ClearTexImage::is_loaded() {
+                let format = if self.format.is_depth() { gl::DEPTH_COMPONENT } else { gl::RGBA };
+
+                gl::ClearTexImage(self.handle, 0, format, gl::FLOAT, values.as_ptr() as *const c_void);
+            } else {
+                with_unmasked_writes(|| self.clear_through_framebuffer(&values));
+            }
+        }
+
+        self.mark_written();
+
+        Ok(())
+    }
+
+    pub fn set_clamp(&mut self, coord: WrapCoord, mode: ClampMode) -> Result<()> {
+        if coord == WrapCoord::R {
+            return Err(Error::InvalidTextureParameter("2D textures have no R coordinate to wrap".to_string()));
+        }
+
+        self.check_parameters_mutable()?;
         self.bind(0);
 
         unsafe {
@@ -203,12 +1086,14 @@ impl Texture {
 
         match coord {
             WrapCoord::S => self.s_clamp = mode,
-            WrapCoord::T => self.t_clamp = mode,
+            _ => self.t_clamp = mode,
         }
+
+        Ok(())
     }
 
     pub fn set_min_filter(&mut self, filter: MinFilter) -> Result<()> {
-        self.bind(0);
+        self.check_parameters_mutable()?;
 
         match filter {
             MinFilter::Nearest | MinFilter::Linear => (),
@@ -222,6 +1107,8 @@ impl Texture {
             }
         };
 
+        self.bind(0);
+
         unsafe {
             gl::TexParameteri(
                 gl::TEXTURE_2D,
@@ -234,7 +1121,8 @@ impl Texture {
         Ok(())
     }
 
-    pub fn set_mag_filter(&mut self, filter: MagFilter) {
+    pub fn set_mag_filter(&mut self, filter: MagFilter) -> Result<()> {
+        self.check_parameters_mutable()?;
         self.bind(0);
 
         unsafe {
@@ -246,6 +1134,111 @@ impl Texture {
         }
 
         self.mag_filter = filter;
+
+        Ok(())
+    }
+
+    // expects the texture to be bound on the active unit
+    fn flush_anisotropy(&self) {
+        if self.anisotropy_dirty.swap(false, Ordering::AcqRel) {
+            unsafe { gl::TexParameterf(gl::TEXTURE_2D, TEXTURE_MAX_ANISOTROPY, self.anisotropy) };
+        }
+    }
+
+    // clamped to the driver's limit and uploaded the next time the texture is bound; returns the level used
+    pub fn set_anisotropy(&mut self, level: f32) -> Result<f32> {
+        self.check_parameters_mutable()?;
+
+        let level = level.max(1.0).min(context::capabilities().max_anisotropy);
+
+        if level != self.anisotropy {
+            self.anisotropy = level;
+            self.anisotropy_dirty.store(true, Ordering::Release);
+        }
+
+        Ok(level)
+    }
+
+    pub fn anisotropy(&self) -> f32 {
+        self.anisotropy
+    }
+
+    pub fn clamp(&self, coord: WrapCoord) -> ClampMode {
+        match coord {
+            WrapCoord::S => self.s_clamp,
+            WrapCoord::T => self.t_clamp,
+            // 2D targets never sample along R, so it keeps GL's default
+            WrapCoord::R => ClampMode::Repeat,
+        }
+    }
+
+    pub fn min_filter(&self) -> MinFilter {
+        self.min_filter
+    }
+
+    pub fn mag_filter(&self) -> MagFilter {
+        self.mag_filter
+    }
+
+    fn get_parameter(&self, name: GLenum) -> GLenum {
+        let mut value = 0;
+
+        if gl::GetTextureParameteriv::is_loaded() {
+            unsafe { gl::GetTextureParameteriv(self.handle, name, &mut value) };
+        } else {
+            self.bind(0);
+
+            unsafe { gl::GetTexParameteriv(gl::TEXTURE_2D, name, &mut value) };
+        }
+
+        value as GLenum
+    }
+
+    pub fn verify_parameters(&self) -> Result<()> {
+        context::check_initialized()?;
+
+        let expected = [
+            ("TEXTURE_WRAP_S", gl::TEXTURE_WRAP_S, self.s_clamp.get_native()),
+            ("TEXTURE_WRAP_T", gl::TEXTURE_WRAP_T, self.t_clamp.get_native()),
+            ("TEXTURE_MIN_FILTER", gl::TEXTURE_MIN_FILTER, self.min_filter.get_native()),
+            ("TEXTURE_MAG_FILTER", gl::TEXTURE_MAG_FILTER, self.mag_filter.get_native()),
+        ];
+
+        let mismatches: Vec<String> = expected
+            .iter()
+            .filter_map(|(label, name, cached)| {
+                let actual = self.get_parameter(*name);
+
+                if actual != *cached {
+                    Some(format!("{}: cached 0x{:04X}, GL reports 0x{:04X}", label, cached, actual))
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        if mismatches.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::TextureParameterMismatch(mismatches.join(", ")))
+        }
+    }
+
+    pub fn set_label(&mut self, label: &str) {
+        debug::label_object(gl::TEXTURE, self.handle, label);
+        self.label = Some(label.to_string());
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn compressed_format(&self) -> Option<CompressedFormat> {
+        self.compressed
     }
 
     pub fn handle(&self) -> GLuint {
@@ -263,49 +1256,344 @@ impl Texture {
 
 impl Drop for Texture {
     fn drop(&mut self) {
-        unsafe { gl::DeleteTextures(1, &self.handle) };
+        if self.ownership == Ownership::Owned {
+            context::defer_delete(Garbage::Texture(self.handle));
+        }
+
         self.handle = 0;
     }
 }
 
-struct State {
-    active_unit: GLuint,
-    units: Vec::<TextureUnit>,
+#[must_use]
+pub struct TextureBinding<'a> {
+    unit: GLuint,
+    previous: GLuint,
+    _texture: PhantomData<&'a Texture>,
 }
 
-impl State {
-    fn active_unit(&self) -> TextureUnit {
-        self.units[self.active_unit as usize]
+impl Drop for TextureBinding<'_> {
+    fn drop(&mut self) {
+        bind_handle(self.unit, self.previous);
     }
 }
 
-lazy_static! {
-    static ref INTERNAL_STATE: Mutex<State> = {
-        Mutex::new(State {
-            active_unit: 0,
-            units: Vec::new(),
-        })
-    };
+pub(crate) fn bound_handle(unit: GLuint, target: GLenum) -> GLuint {
+    context::bound_texture(unit, target)
+}
+
+pub(crate) fn bind_handle(unit: GLuint, handle: GLuint) {
+    bind_target_handle(unit, gl::TEXTURE_2D, handle);
+}
+
+pub(crate) fn bind_target_handle(unit: GLuint, target: GLenum, handle: GLuint) {
+    context::bind_texture(unit, target, handle);
+}
+
+// the framebuffer fallbacks have to reach every texel, whatever scissor, masks and sRGB encoding the caller left set
+fn with_unmasked_writes<R>(f: impl FnOnce() -> R) -> R {
+    let scissor = context::disable(context::Feature::ScissorTest);
+    let srgb = context::disable(context::Feature::FramebufferSrgb);
+    let [r, g, b, a] = context::color_mask();
+    let depth_mask = context::depth_mask();
+
+    context::set_color_mask(true, true, true, true);
+    context::set_depth_mask(true);
+
+    let result = f();
+
+    context::set_color_mask(r, g, b, a);
+    context::set_depth_mask(depth_mask);
+
+    if srgb {
+        context::enable(context::Feature::FramebufferSrgb);
+    }
+
+    if scissor {
+        context::enable(context::Feature::ScissorTest);
+    }
+
+    result
 }
 
-pub fn init() {
-    let mut st = INTERNAL_STATE.lock().unwrap();
-    let max_units = unsafe {
-        let mut count: i32 = 0;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WRAP_COORDS: [(WrapCoord, GLenum); 3] = [
+        (WrapCoord::S, gl::TEXTURE_WRAP_S),
+        (WrapCoord::T, gl::TEXTURE_WRAP_T),
+        (WrapCoord::R, gl::TEXTURE_WRAP_R),
+    ];
+
+    const CLAMP_MODES: [(ClampMode, GLenum); 4] = [
+        (ClampMode::Edge, gl::CLAMP_TO_EDGE),
+        (ClampMode::Repeat, gl::REPEAT),
+        (ClampMode::RepeatMirrored, gl::MIRRORED_REPEAT),
+        (ClampMode::Border, gl::CLAMP_TO_BORDER),
+    ];
+
+    const MIN_FILTERS: [(MinFilter, GLenum); 6] = [
+        (MinFilter::Nearest, gl::NEAREST),
+        (MinFilter::Linear, gl::LINEAR),
+        (MinFilter::NearestMipmapNearest, gl::NEAREST_MIPMAP_NEAREST),
+        (MinFilter::NearestMipmapLinear, gl::NEAREST_MIPMAP_LINEAR),
+        (MinFilter::LinearMipmapNearest, gl::LINEAR_MIPMAP_NEAREST),
+        (MinFilter::LinearMipmapLinear, gl::LINEAR_MIPMAP_LINEAR),
+    ];
 
-        gl::GetIntegerv(gl::MAX_TEXTURE_IMAGE_UNITS, &mut count);
+    const MAG_FILTERS: [(MagFilter, GLenum); 2] = [(MagFilter::Nearest, gl::NEAREST), (MagFilter::Linear, gl::LINEAR)];
 
-        for i in 0..count {
-            gl::ActiveTexture(gl::TEXTURE0 + i as GLuint);
-            gl::BindTexture(gl::TEXTURE_1D, 0);
-            gl::BindTexture(gl::TEXTURE_2D, 0);
-            gl::BindTexture(gl::TEXTURE_3D, 0);
+    // no wildcard arms, so a new variant fails to compile until it's added to the tables above
+    fn wrap_coord_index(coord: WrapCoord) -> usize {
+        match coord {
+            WrapCoord::S => 0,
+            WrapCoord::T => 1,
+            WrapCoord::R => 2,
+        }
+    }
+
+    fn clamp_mode_index(mode: ClampMode) -> usize {
+        match mode {
+            ClampMode::Edge => 0,
+            ClampMode::Repeat => 1,
+            ClampMode::RepeatMirrored => 2,
+            ClampMode::Border => 3,
+        }
+    }
+
+    fn min_filter_index(filter: MinFilter) -> usize {
+        match filter {
+            MinFilter::Nearest => 0,
+            MinFilter::Linear => 1,
+            MinFilter::NearestMipmapNearest => 2,
+            MinFilter::NearestMipmapLinear => 3,
+            MinFilter::LinearMipmapNearest => 4,
+            MinFilter::LinearMipmapLinear => 5,
+        }
+    }
+
+    fn mag_filter_index(filter: MagFilter) -> usize {
+        match filter {
+            MagFilter::Nearest => 0,
+            MagFilter::Linear => 1,
+        }
+    }
+
+    #[test]
+    fn sampler_enums_map_to_their_gl_constants() {
+        for (i, (coord, native)) in WRAP_COORDS.iter().enumerate() {
+            assert_eq!(wrap_coord_index(*coord), i, "{:?} is out of place in the table", coord);
+            assert_eq!(coord.get_native(), *native, "{:?}", coord);
+        }
+
+        for (i, (mode, native)) in CLAMP_MODES.iter().enumerate() {
+            assert_eq!(clamp_mode_index(*mode), i, "{:?} is out of place in the table", mode);
+            assert_eq!(mode.get_native(), *native, "{:?}", mode);
+            assert_eq!(ClampMode::from_native(*native as GLint), Some(*mode));
+        }
+
+        for (i, (filter, native)) in MIN_FILTERS.iter().enumerate() {
+            assert_eq!(min_filter_index(*filter), i, "{:?} is out of place in the table", filter);
+            assert_eq!(filter.get_native(), *native, "{:?}", filter);
+            assert_eq!(MinFilter::from_native(*native as GLint), Some(*filter));
+        }
+
+        for (i, (filter, native)) in MAG_FILTERS.iter().enumerate() {
+            assert_eq!(mag_filter_index(*filter), i, "{:?} is out of place in the table", filter);
+            assert_eq!(filter.get_native(), *native, "{:?}", filter);
+            assert_eq!(MagFilter::from_native(*native as GLint), Some(*filter));
         }
 
-        gl::ActiveTexture(gl::TEXTURE0);
+        assert_eq!(ClampMode::from_native(0), None);
+        assert_eq!(MinFilter::from_native(0), None);
+        assert_eq!(MagFilter::from_native(gl::LINEAR_MIPMAP_LINEAR as GLint), None);
+    }
+
+    #[cfg(feature = "headless")]
+    fn read_level(texture: &Texture, level: GLint) -> Vec<u8> {
+        let size = (texture.width() >> level) * (texture.height() >> level) * 4;
+        let mut buf = vec![0u8; size];
+        let _binding = texture.bind_scoped(0);
+
+        unsafe {
+            gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+            gl::GetTexImage(gl::TEXTURE_2D, level, gl::RGBA, gl::UNSIGNED_BYTE, buf.as_mut_ptr() as *mut c_void);
+        }
+
+        buf
+    }
+
+    #[cfg(feature = "headless")]
+    fn mipmapped_checkerboard(policy: MipPolicy) -> Texture {
+        let buf = generate::checkerboard(4, 4, 1, Color::BLACK, Color::WHITE);
+
+        Texture::make_mipmapped(&buf, 4, 4, policy).unwrap()
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn on_bind_regenerates_mips_after_a_write() {
+        crate::headless::run(|| {
+            let texture = mipmapped_checkerboard(MipPolicy::OnBind);
+
+            // a 1-pixel checker averages to gray one level down
+            assert!(read_level(&texture, 1).chunks(4).all(|p| (120..=135).contains(&p[0])));
+
+            texture.write(&vec![255; 4 * 4 * 4], 0, 0, 4, 4);
+
+            assert!(texture.mips_dirty());
+
+            texture.bind(0);
+
+            assert!(!texture.mips_dirty());
+            assert!(read_level(&texture, 1).iter().all(|value| *value == 255));
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn manual_mips_wait_for_flush() {
+        crate::headless::run(|| {
+            let texture = mipmapped_checkerboard(MipPolicy::Manual);
+
+            texture.write(&vec![255; 4 * 4 * 4], 0, 0, 4, 4);
+            texture.bind(0);
+
+            assert!(texture.mips_dirty());
+            assert!(read_level(&texture, 1).chunks(4).all(|p| p[0] < 255));
+
+            texture.flush_mipmaps();
+
+            assert!(!texture.mips_dirty());
+            assert!(read_level(&texture, 1).iter().all(|value| *value == 255));
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn sampler_parameters_round_trip_through_gl() {
+        crate::headless::run(|| {
+            let mut texture = mipmapped_checkerboard(MipPolicy::Manual);
+
+            for (filter, native) in MIN_FILTERS.iter() {
+                texture.set_min_filter(*filter).unwrap();
+
+                assert_eq!(texture.min_filter(), *filter);
+                assert_eq!(texture.get_parameter(gl::TEXTURE_MIN_FILTER), *native);
+                assert!(texture.verify_parameters().is_ok(), "{:?}", filter);
+            }
+
+            for (filter, native) in MAG_FILTERS.iter() {
+                texture.set_mag_filter(*filter).unwrap();
+
+                assert_eq!(texture.mag_filter(), *filter);
+                assert_eq!(texture.get_parameter(gl::TEXTURE_MAG_FILTER), *native);
+                assert!(texture.verify_parameters().is_ok(), "{:?}", filter);
+            }
+
+            for (coord, name) in WRAP_COORDS[..2].iter() {
+                for (mode, native) in CLAMP_MODES.iter() {
+                    texture.set_clamp(*coord, *mode).unwrap();
+
+                    assert_eq!(texture.clamp(*coord), *mode);
+                    assert_eq!(texture.get_parameter(*name), *native);
+                    assert!(texture.verify_parameters().is_ok(), "{:?} {:?}", coord, mode);
+                }
+            }
+
+            assert!(texture.set_clamp(WrapCoord::R, ClampMode::Edge).is_err());
 
-        count as usize
-    };
+            // a change made behind the cache's back is reported
+            unsafe {
+                let _binding = texture.bind_scoped(0);
 
-    st.units = vec![TextureUnit::new(); max_units];
+                gl::TexParameteri(gl::TEXTURE_2D, gl::TEXTURE_MAG_FILTER, gl::NEAREST as GLint);
+            }
+
+            match texture.verify_parameters() {
+                Err(Error::TextureParameterMismatch(message)) => assert!(message.contains("TEXTURE_MAG_FILTER")),
+                other => panic!("expected a mismatch, got {:?}", other),
+            }
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn parameters_are_frozen_once_a_bindless_handle_exists() {
+        crate::headless::run(|| {
+            let mut texture = mipmapped_checkerboard(MipPolicy::Manual);
+            let clamp = texture.clamp(WrapCoord::S);
+
+            // stands in for make_resident, which needs a driver with GL_ARB_bindless_texture
+            texture.bindless_handle.store(1, Ordering::Release);
+
+            let results = [
+                texture.set_clamp(WrapCoord::S, ClampMode::Border),
+                texture.set_min_filter(MinFilter::Nearest),
+                texture.set_mag_filter(MagFilter::Nearest),
+                texture.set_anisotropy(4.0).map(|_| ()),
+                texture.set_border_color(Color::WHITE),
+                texture.set_lod_bias(1.0),
+                texture.set_base_level(1),
+                texture.set_max_level(1),
+            ];
+
+            for result in results.iter() {
+                assert!(matches!(result, Err(Error::InvalidTextureParameter(_))), "{:?}", result);
+            }
+
+            assert_eq!(texture.clamp(WrapCoord::S), clamp);
+            assert!(texture.verify_parameters().is_ok());
+
+            texture.bindless_handle.store(0, Ordering::Release);
+
+            assert!(texture.set_clamp(WrapCoord::S, ClampMode::Border).is_ok());
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn framebuffer_fallbacks_ignore_scissor_and_masks() {
+        crate::headless::run(|| {
+            let source = Texture::make(&vec![255; 4 * 4 * 4], 4, 4, false).unwrap();
+            let target = Texture::make(&vec![0; 4 * 4 * 4], 4, 4, false).unwrap();
+
+            context::set_scissor(0, 0, 1, 1);
+            context::enable(context::Feature::ScissorTest);
+            context::set_color_mask(false, false, false, false);
+
+            unsafe {
+                with_unmasked_writes(|| target.clear_through_framebuffer(&[1.0, 0.0, 0.0, 1.0]));
+            }
+
+            assert!(read_level(&target, 0).chunks(4).all(|p| p == [255, 0, 0, 255]));
+
+            unsafe {
+                with_unmasked_writes(|| target.blit_from(&source, 0, 0, 4, 4, 0, 0));
+            }
+
+            assert!(read_level(&target, 0).iter().all(|value| *value == 255));
+
+            // and the caller's state comes back afterwards
+            assert!(context::is_enabled(context::Feature::ScissorTest));
+            assert_eq!(context::color_mask(), [false; 4]);
+
+            context::set_color_mask(true, true, true, true);
+            context::disable(context::Feature::ScissorTest);
+        });
+    }
+
+    #[cfg(feature = "headless")]
+    #[test]
+    fn rejected_min_filters_leave_bindings_alone() {
+        crate::headless::run(|| {
+            let mut texture = Texture::make(&vec![0; 4], 1, 1, false).unwrap();
+            let bound = Texture::make(&vec![0; 4], 1, 1, false).unwrap();
+
+            bound.bind(0);
+
+            assert!(matches!(texture.set_min_filter(MinFilter::LinearMipmapLinear), Err(Error::NoMipmaps)));
+            assert_eq!(bound_handle(0, gl::TEXTURE_2D), bound.handle());
+        });
+    }
 }
@@ -0,0 +1,147 @@
+use super::Texture;
+use crate::context;
+use crate::error::{Error, Result};
+use crate::shader::Shader;
+
+use gl::types::*;
+use lazy_static::lazy_static;
+use std::mem;
+use std::os::raw::c_void;
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+
+const EXTENSION: &str = "GL_ARB_bindless_texture";
+
+#[derive(Copy, Clone)]
+struct Functions {
+    get_texture_handle: unsafe extern "system" fn(GLuint) -> GLuint64,
+    make_resident: unsafe extern "system" fn(GLuint64),
+    make_non_resident: unsafe extern "system" fn(GLuint64),
+    uniform_handle: unsafe extern "system" fn(GLint, GLuint64),
+    uniform_handles: unsafe extern "system" fn(GLint, GLsizei, *const GLuint64),
+}
+
+lazy_static! {
+    static ref FUNCTIONS: Mutex<Option<Functions>> = Mutex::new(None);
+}
+
+fn functions() -> Result<Functions> {
+    FUNCTIONS
+        .lock()
+        .unwrap()
+        .ok_or_else(|| Error::Unsupported(format!("{} (call load_bindless_with first)", EXTENSION)))
+}
+
+// the gl crate's bindings are core-only, so the extension entry points come from the same loader
+// passed to gl::load_with; returns whether bindless textures are usable
+pub fn load_bindless_with<F>(mut loader: F) -> bool
+where
+    F: FnMut(&'static str) -> *const c_void,
+{
+    context::assert_initialized();
+
+    let mut functions = FUNCTIONS.lock().unwrap();
+
+    *functions = None;
+
+    if !context::has_extension(EXTENSION) {
+        return false;
+    }
+
+    let pointers = [
+        loader("glGetTextureHandleARB"),
+        loader("glMakeTextureHandleResidentARB"),
+        loader("glMakeTextureHandleNonResidentARB"),
+        loader("glUniformHandleui64ARB"),
+        loader("glUniformHandleui64vARB"),
+    ];
+
+    if pointers.iter().any(|pointer| pointer.is_null()) {
+        return false;
+    }
+
+    unsafe {
+        *functions = Some(Functions {
+            get_texture_handle: mem::transmute(pointers[0]),
+            make_resident: mem::transmute(pointers[1]),
+            make_non_resident: mem::transmute(pointers[2]),
+            uniform_handle: mem::transmute(pointers[3]),
+            uniform_handles: mem::transmute(pointers[4]),
+        });
+    }
+
+    true
+}
+
+pub fn is_bindless_supported() -> bool {
+    FUNCTIONS.lock().unwrap().is_some()
+}
+
+impl Texture {
+    // the texture's parameters are frozen from the first call on, per the extension; the setters
+    // return InvalidTextureParameter from then on
+    pub fn make_resident(&self) -> Result<u64> {
+        context::check_initialized()?;
+
+        let functions = functions()?;
+        let mut handle = self.bindless_handle.load(Ordering::Acquire);
+
+        if handle == 0 {
+            // binding uploads a pending anisotropy change while that is still allowed
+            drop(self.bind_scoped(0));
+
+            handle = unsafe { (functions.get_texture_handle)(self.handle) };
+
+            if handle == 0 {
+                return Err(Error::Unsupported("the driver refused a bindless handle for this texture".to_string()));
+            }
+
+            self.bindless_handle.store(handle, Ordering::Release);
+        }
+
+        if !self.resident.swap(true, Ordering::AcqRel) {
+            unsafe { (functions.make_resident)(handle) };
+        }
+
+        Ok(handle)
+    }
+
+    // deleting the texture releases residency too, so this is only needed to trim the resident set
+    pub fn make_non_resident(&self) -> Result<()> {
+        context::check_initialized()?;
+
+        let functions = functions()?;
+
+        if self.resident.swap(false, Ordering::AcqRel) {
+            unsafe { (functions.make_non_resident)(self.bindless_handle.load(Ordering::Acquire)) };
+        }
+
+        Ok(())
+    }
+
+    pub fn is_resident(&self) -> bool {
+        self.resident.load(Ordering::Acquire)
+    }
+}
+
+impl Shader {
+    // for `layout(bindless_sampler) uniform sampler2D name;`
+    pub fn set_uniform_handle(&self, name: &str, handle: u64) -> Result<()> {
+        let functions = functions()?;
+
+        self.bind();
+        unsafe { (functions.uniform_handle)(self.uniform_location(name), handle) };
+
+        Ok(())
+    }
+
+    // for sampler arrays, e.g. `layout(bindless_sampler) uniform sampler2D textures[64];`
+    pub fn set_uniform_handles(&self, name: &str, handles: &[u64]) -> Result<()> {
+        let functions = functions()?;
+
+        self.bind();
+        unsafe { (functions.uniform_handles)(self.uniform_location(name), handles.len() as GLsizei, handles.as_ptr()) };
+
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use super::{Texture, TextureFormat};
+use crate::context;
+use crate::error::{Error, Result};
+
+use ::image::DynamicImage;
+use gl::types::*;
+use std::path::Path;
+
+#[derive(Debug, Copy, Clone)]
+pub struct ImageOptions {
+    pub flip_vertically: bool,
+    pub mipmaps: bool,
+    // color images (anything but single-channel) get an sRGB format, which is right for albedo/UI art
+    pub srgb: bool,
+}
+
+impl ImageOptions {
+    pub fn new() -> ImageOptions {
+        ImageOptions {
+            flip_vertically: false,
+            mipmaps: false,
+            srgb: false,
+        }
+    }
+}
+
+fn to_pixels(image: DynamicImage) -> (TextureFormat, Vec<u8>, usize, usize) {
+    match image {
+        DynamicImage::ImageLuma8(buf) => {
+            let (width, height) = buf.dimensions();
+
+            (TextureFormat::R8, buf.into_raw(), width as usize, height as usize)
+        },
+        DynamicImage::ImageRgb8(buf) => {
+            let (width, height) = buf.dimensions();
+
+            (TextureFormat::Rgb8, buf.into_raw(), width as usize, height as usize)
+        },
+        image => {
+            let buf = image.into_rgba8();
+            let (width, height) = buf.dimensions();
+
+            (TextureFormat::Rgba8, buf.into_raw(), width as usize, height as usize)
+        },
+    }
+}
+
+impl Texture {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Texture> {
+        Texture::from_file_with_options(path, ImageOptions::new())
+    }
+
+    pub fn from_file_with_options<P: AsRef<Path>>(path: P, options: ImageOptions) -> Result<Texture> {
+        context::check_initialized()?;
+
+        let path = path.as_ref();
+        let image = ::image::open(path)
+            .map_err(|err| Error::ImageLoadFailed(format!("{}: {}", path.display(), err)))?;
+
+        Texture::from_image(image, options)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Texture> {
+        Texture::from_bytes_with_options(bytes, ImageOptions::new())
+    }
+
+    pub fn from_bytes_with_options(bytes: &[u8], options: ImageOptions) -> Result<Texture> {
+        context::check_initialized()?;
+
+        let image = ::image::load_from_memory(bytes)
+            .map_err(|err| Error::ImageLoadFailed(err.to_string()))?;
+
+        Texture::from_image(image, options)
+    }
+
+    fn from_image(image: DynamicImage, options: ImageOptions) -> Result<Texture> {
+        let image = if options.flip_vertically { image.flipv() } else { image };
+        let (format, pixels, width, height) = to_pixels(image);
+        let format = if options.srgb { format.to_srgb() } else { format };
+
+        let texture = Texture::make_with_format(format, &pixels, width, height, options.mipmaps)?;
+
+        // grayscale stays a single channel on the GPU and samples as gray instead of red
+        if format == TextureFormat::R8 {
+            let swizzle = [gl::RED as GLint, gl::RED as GLint, gl::RED as GLint, gl::ONE as GLint];
+            let _binding = texture.bind_scoped(0);
+
+            unsafe { gl::TexParameteriv(gl::TEXTURE_2D, gl::TEXTURE_SWIZZLE_RGBA, swizzle.as_ptr()) };
+        }
+
+        Ok(texture)
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::math;
+    use crate::readback;
+    use crate::rect::Rect;
+    use crate::sprite::SpriteBatch;
+
+    use ::image::{GrayImage, ImageOutputFormat, Luma};
+
+    fn gray_png(value: u8) -> Vec<u8> {
+        let mut bytes = Vec::new();
+
+        DynamicImage::ImageLuma8(GrayImage::from_pixel(4, 4, Luma([value])))
+            .write_to(&mut bytes, ImageOutputFormat::Png)
+            .unwrap();
+
+        bytes
+    }
+
+    #[test]
+    fn grayscale_images_sample_as_gray() {
+        crate::headless::run(|| {
+            let texture = Texture::from_bytes(&gray_png(128)).unwrap();
+            let mut batch = SpriteBatch::new();
+
+            assert_eq!(texture.format(), TextureFormat::R8);
+
+            batch.set_projection(math::ortho(0.0, 4.0, 4.0, 0.0, -1.0, 1.0));
+            batch.begin();
+            batch.draw(&texture, Rect::make(0.0, 0.0, 4.0, 4.0), Rect::make(0.0, 0.0, 4.0, 4.0), Color::WHITE, 0.0);
+            batch.end();
+
+            let pixel = readback::read_pixels(0, 0, 1, 1, TextureFormat::Rgba8);
+
+            assert!((127..=129).contains(&pixel[0]), "{:?}", pixel);
+            assert_eq!(pixel[0], pixel[1]);
+            assert_eq!(pixel[0], pixel[2]);
+            assert_eq!(pixel[3], 255);
+        });
+    }
+}
@@ -0,0 +1,223 @@
+use crate::context::{self, Garbage};
+use crate::error::{Error, Result};
+use crate::texture::{self, ClampMode, MagFilter, MinFilter, TextureFormat, WrapCoord};
+
+use gl::types::*;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::ptr;
+
+pub struct Texture3D {
+    format: TextureFormat,
+    mipmaps: bool,
+    handle: GLuint,
+    s_clamp: ClampMode,
+    t_clamp: ClampMode,
+    r_clamp: ClampMode,
+    min_filter: MinFilter,
+    mag_filter: MagFilter,
+    width: usize,
+    height: usize,
+    depth: usize,
+    _thread: context::ThreadMarker,
+}
+
+impl Texture3D {
+    pub fn new(format: TextureFormat, width: usize, height: usize, depth: usize, mipmaps: bool) -> Result<Texture3D> {
+        Texture3D::build(format, None, width, height, depth, mipmaps)
+    }
+
+    pub fn make(format: TextureFormat, buf: &[u8], width: usize, height: usize, depth: usize, mipmaps: bool) -> Result<Texture3D> {
+        Texture3D::build(format, Some(buf), width, height, depth, mipmaps)
+    }
+
+    fn build(format: TextureFormat, buf: Option<&[u8]>, width: usize, height: usize, depth: usize, mipmaps: bool) -> Result<Texture3D> {
+        context::check_initialized()?;
+
+        let total_size = width * height * depth * format.bytes_per_pixel();
+
+        if total_size == 0 || buf.map_or(false, |buf| buf.len() != total_size) {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        let mut handle = 0 as GLuint;
+        let clamp = ClampMode::Edge;
+        // volumes are almost always sampled between voxels, e.g. color grading LUTs
+        let min_filter = if mipmaps { MinFilter::LinearMipmapLinear } else { MinFilter::Linear };
+        let mag_filter = MagFilter::Linear;
+        let data = buf.map_or(ptr::null(), |buf| &buf[0] as *const u8 as *const c_void);
+
+        unsafe {
+            gl::GenTextures(1, &mut handle);
+        }
+
+        texture::bind_target_handle(0, gl::TEXTURE_3D, handle);
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_S, clamp.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_T, clamp.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_WRAP_R, clamp.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, min_filter.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, mag_filter.get_native() as i32);
+
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexImage3D(
+                gl::TEXTURE_3D,
+                0,
+                format.get_native() as i32,
+                width as i32,
+                height as i32,
+                depth as i32,
+                0,
+                format.pixel_format(),
+                format.pixel_type(),
+                data,
+            );
+
+            if mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_3D);
+            }
+        }
+
+        Ok(Texture3D {
+            format,
+            mipmaps,
+            handle,
+            s_clamp: clamp,
+            t_clamp: clamp,
+            r_clamp: clamp,
+            min_filter,
+            mag_filter,
+            width,
+            height,
+            depth,
+            _thread: PhantomData,
+        })
+    }
+
+    pub fn bind(&self, unit: GLuint) {
+        texture::bind_target_handle(unit, gl::TEXTURE_3D, self.handle);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn write(&self, buf: &[u8], x: usize, y: usize, z: usize, width: usize, height: usize, depth: usize) {
+        assert!(
+            x + width <= self.width && y + height <= self.height && z + depth <= self.depth,
+            "texture write region exceeds the volume bounds",
+        );
+        assert!(
+            width * height * depth > 0 && buf.len() >= width * height * depth * self.format.bytes_per_pixel(),
+            "texture write buffer is smaller than the region",
+        );
+
+        self.bind(0);
+
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage3D(
+                gl::TEXTURE_3D,
+                0,
+                x as i32,
+                y as i32,
+                z as i32,
+                width as GLsizei,
+                height as GLsizei,
+                depth as GLsizei,
+                self.format.pixel_format(),
+                self.format.pixel_type(),
+                &buf[0] as *const u8 as *const c_void,
+            );
+
+            if self.mipmaps {
+                gl::GenerateMipmap(gl::TEXTURE_3D);
+            }
+        }
+    }
+
+    pub fn set_clamp(&mut self, coord: WrapCoord, mode: ClampMode) {
+        self.bind(0);
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_3D, coord.get_native(), mode.get_native() as i32);
+        }
+
+        match coord {
+            WrapCoord::S => self.s_clamp = mode,
+            WrapCoord::T => self.t_clamp = mode,
+            WrapCoord::R => self.r_clamp = mode,
+        }
+    }
+
+    pub fn set_min_filter(&mut self, filter: MinFilter) -> Result<()> {
+        match filter {
+            MinFilter::Nearest | MinFilter::Linear => (),
+            _ => {
+                if !self.mipmaps {
+                    return Err(Error::NoMipmaps);
+                }
+            },
+        };
+
+        self.bind(0);
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MIN_FILTER, filter.get_native() as i32);
+        }
+
+        self.min_filter = filter;
+        Ok(())
+    }
+
+    pub fn set_mag_filter(&mut self, filter: MagFilter) {
+        self.bind(0);
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_3D, gl::TEXTURE_MAG_FILTER, filter.get_native() as i32);
+        }
+
+        self.mag_filter = filter;
+    }
+
+    pub fn clamp(&self, coord: WrapCoord) -> ClampMode {
+        match coord {
+            WrapCoord::S => self.s_clamp,
+            WrapCoord::T => self.t_clamp,
+            WrapCoord::R => self.r_clamp,
+        }
+    }
+
+    pub fn min_filter(&self) -> MinFilter {
+        self.min_filter
+    }
+
+    pub fn mag_filter(&self) -> MagFilter {
+        self.mag_filter
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.handle
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl Drop for Texture3D {
+    fn drop(&mut self) {
+        context::defer_delete(Garbage::Texture(self.handle));
+        self.handle = 0;
+    }
+}
@@ -0,0 +1,215 @@
+use crate::context::{self, Garbage};
+use crate::error::{Error, Result};
+use crate::texture::{self, ClampMode, MagFilter, MinFilter, TextureFormat, WrapCoord};
+
+use gl::types::*;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::ptr;
+
+pub struct TextureArray {
+    format: TextureFormat,
+    mipmaps: bool,
+    handle: GLuint,
+    s_clamp: ClampMode,
+    t_clamp: ClampMode,
+    min_filter: MinFilter,
+    mag_filter: MagFilter,
+    width: usize,
+    height: usize,
+    layers: usize,
+    _thread: context::ThreadMarker,
+}
+
+impl TextureArray {
+    pub fn new(format: TextureFormat, width: usize, height: usize, layers: usize, mipmaps: bool) -> Result<TextureArray> {
+        context::check_initialized()?;
+
+        if width == 0 || height == 0 || layers == 0 {
+            return Err(Error::InvalidTextureDimensions);
+        }
+
+        let mut handle = 0 as GLuint;
+        let clamp = ClampMode::Edge;
+        let min_filter = MinFilter::Nearest;
+        let mag_filter = MagFilter::Nearest;
+
+        unsafe {
+            gl::GenTextures(1, &mut handle);
+        }
+
+        texture::bind_target_handle(0, gl::TEXTURE_2D_ARRAY, handle);
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_S, clamp.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_WRAP_T, clamp.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, min_filter.get_native() as i32);
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, mag_filter.get_native() as i32);
+
+            gl::TexImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                format.get_native() as i32,
+                width as i32,
+                height as i32,
+                layers as i32,
+                0,
+                format.pixel_format(),
+                format.pixel_type(),
+                ptr::null(),
+            );
+        }
+
+        Ok(TextureArray {
+            format,
+            mipmaps,
+            handle,
+            s_clamp: clamp,
+            t_clamp: clamp,
+            min_filter,
+            mag_filter,
+            width,
+            height,
+            layers,
+            _thread: PhantomData,
+        })
+    }
+
+    pub fn bind(&self, unit: GLuint) {
+        texture::bind_target_handle(unit, gl::TEXTURE_2D_ARRAY, self.handle);
+    }
+
+    pub fn write_layer(&self, layer: usize, buf: &[u8]) {
+        assert!(layer < self.layers, "texture array layer {} is out of range", layer);
+        assert!(
+            buf.len() >= self.width * self.height * self.format.bytes_per_pixel(),
+            "texture array layer buffer is smaller than the layer",
+        );
+
+        self.bind(0);
+
+        unsafe {
+            gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+            gl::TexSubImage3D(
+                gl::TEXTURE_2D_ARRAY,
+                0,
+                0,
+                0,
+                layer as i32,
+                self.width as GLsizei,
+                self.height as GLsizei,
+                1,
+                self.format.pixel_format(),
+                self.format.pixel_type(),
+                &buf[0] as *const u8 as *const c_void,
+            );
+        }
+    }
+
+    pub fn generate_mipmaps(&self) -> Result<()> {
+        if !self.mipmaps {
+            return Err(Error::NoMipmaps);
+        }
+
+        self.bind(0);
+
+        unsafe { gl::GenerateMipmap(gl::TEXTURE_2D_ARRAY) };
+
+        Ok(())
+    }
+
+    pub fn set_clamp(&mut self, coord: WrapCoord, mode: ClampMode) -> Result<()> {
+        if coord == WrapCoord::R {
+            let msg = "layers of a texture array are selected by index and never wrap along R".to_string();
+
+            return Err(Error::InvalidTextureParameter(msg));
+        }
+
+        self.bind(0);
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, coord.get_native(), mode.get_native() as i32);
+        }
+
+        match coord {
+            WrapCoord::S => self.s_clamp = mode,
+            _ => self.t_clamp = mode,
+        }
+
+        Ok(())
+    }
+
+    pub fn set_min_filter(&mut self, filter: MinFilter) -> Result<()> {
+        match filter {
+            MinFilter::Nearest | MinFilter::Linear => (),
+            _ => {
+                if !self.mipmaps {
+                    return Err(Error::NoMipmaps);
+                }
+            },
+        };
+
+        self.bind(0);
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MIN_FILTER, filter.get_native() as i32);
+        }
+
+        self.min_filter = filter;
+        Ok(())
+    }
+
+    pub fn set_mag_filter(&mut self, filter: MagFilter) {
+        self.bind(0);
+
+        unsafe {
+            gl::TexParameteri(gl::TEXTURE_2D_ARRAY, gl::TEXTURE_MAG_FILTER, filter.get_native() as i32);
+        }
+
+        self.mag_filter = filter;
+    }
+
+    pub fn clamp(&self, coord: WrapCoord) -> ClampMode {
+        match coord {
+            WrapCoord::S => self.s_clamp,
+            WrapCoord::T => self.t_clamp,
+            // 2D targets never sample along R, so it keeps GL's default
+            WrapCoord::R => ClampMode::Repeat,
+        }
+    }
+
+    pub fn min_filter(&self) -> MinFilter {
+        self.min_filter
+    }
+
+    pub fn mag_filter(&self) -> MagFilter {
+        self.mag_filter
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        self.format
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.handle
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+}
+
+impl Drop for TextureArray {
+    fn drop(&mut self) {
+        context::defer_delete(Garbage::Texture(self.handle));
+        self.handle = 0;
+    }
+}
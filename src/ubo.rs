@@ -0,0 +1,251 @@
+use crate::context::{self, Garbage};
+use crate::vbo::BufferMode;
+
+use gl::types::*;
+use std::marker::PhantomData;
+use std::os::raw::c_void;
+use std::ptr;
+use vex::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
+
+pub struct Std140Writer {
+    buf: Vec<u8>,
+}
+
+impl Std140Writer {
+    pub fn new() -> Std140Writer {
+        Std140Writer { buf: Vec::new() }
+    }
+
+    pub fn align(&mut self, alignment: usize) {
+        let padded = (self.buf.len() + alignment - 1) / alignment * alignment;
+
+        self.buf.resize(padded, 0);
+    }
+
+    fn push_floats(&mut self, alignment: usize, values: &[f32]) {
+        self.align(alignment);
+
+        for value in values {
+            self.buf.extend_from_slice(&value.to_ne_bytes());
+        }
+    }
+
+    pub fn push_f32(&mut self, value: f32) {
+        self.push_floats(4, &[value]);
+    }
+
+    pub fn push_i32(&mut self, value: i32) {
+        self.align(4);
+        self.buf.extend_from_slice(&value.to_ne_bytes());
+    }
+
+    pub fn push_u32(&mut self, value: u32) {
+        self.align(4);
+        self.buf.extend_from_slice(&value.to_ne_bytes());
+    }
+
+    pub fn push_vec2(&mut self, value: &Vector2) {
+        self.push_floats(8, &[value.x, value.y]);
+    }
+
+    pub fn push_vec3(&mut self, value: &Vector3) {
+        self.push_floats(16, &[value.x, value.y, value.z]);
+    }
+
+    pub fn push_vec4(&mut self, value: &Vector4) {
+        self.push_floats(16, &[value.x, value.y, value.z, value.w]);
+    }
+
+    pub fn push_mat3(&mut self, value: &Matrix3) {
+        let m = value.m;
+
+        for column in m.chunks(3) {
+            self.push_floats(16, column);
+        }
+
+        self.align(16);
+    }
+
+    pub fn push_mat4(&mut self, value: &Matrix4) {
+        let m = value.m;
+
+        self.push_floats(16, &m);
+    }
+
+    pub fn push_struct<T: Std140>(&mut self, value: &T) {
+        self.align(16);
+        value.write_std140(self);
+        self.align(16);
+    }
+
+    pub fn push_array<T: Std140>(&mut self, values: &[T]) {
+        for value in values {
+            self.push_struct(value);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+pub trait Std140 {
+    fn write_std140(&self, writer: &mut Std140Writer);
+
+    fn std140_bytes(&self) -> Vec<u8> {
+        let mut writer = Std140Writer::new();
+
+        self.write_std140(&mut writer);
+        writer.align(16);
+        writer.into_bytes()
+    }
+}
+
+impl Std140 for f32 {
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push_f32(*self);
+    }
+}
+
+impl Std140 for i32 {
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push_i32(*self);
+    }
+}
+
+impl Std140 for u32 {
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push_u32(*self);
+    }
+}
+
+impl Std140 for Vector2 {
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push_vec2(self);
+    }
+}
+
+impl Std140 for Vector3 {
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push_vec3(self);
+    }
+}
+
+impl Std140 for Vector4 {
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push_vec4(self);
+    }
+}
+
+impl Std140 for Matrix3 {
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push_mat3(self);
+    }
+}
+
+impl Std140 for Matrix4 {
+    fn write_std140(&self, writer: &mut Std140Writer) {
+        writer.push_mat4(self);
+    }
+}
+
+pub struct UniformBuffer {
+    handle: GLuint,
+    size: usize,
+    _thread: context::ThreadMarker,
+}
+
+impl UniformBuffer {
+    pub fn new(size: usize, mode: BufferMode) -> UniformBuffer {
+        context::assert_initialized();
+
+        let mut handle = 0;
+
+        unsafe {
+            gl::GenBuffers(1, &mut handle);
+            gl::BindBuffer(gl::UNIFORM_BUFFER, handle);
+            gl::BufferData(gl::UNIFORM_BUFFER, size as GLsizeiptr, ptr::null(), mode.to_raw_enum());
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+
+        UniformBuffer {
+            handle,
+            size,
+            _thread: PhantomData,
+        }
+    }
+
+    pub fn from_data<T: Std140>(data: &T, mode: BufferMode) -> UniformBuffer {
+        let bytes = data.std140_bytes();
+        let result = UniformBuffer::new(bytes.len(), mode);
+
+        result.write(0, &bytes);
+        result
+    }
+
+    pub fn bind(&self, binding: GLuint) {
+        context::assert_initialized();
+
+        unsafe { gl::BindBufferBase(gl::UNIFORM_BUFFER, binding, self.handle) };
+    }
+
+    pub fn bind_range(&self, binding: GLuint, offset: usize, size: usize) {
+        context::assert_initialized();
+
+        unsafe {
+            gl::BindBufferRange(
+                gl::UNIFORM_BUFFER,
+                binding,
+                self.handle,
+                offset as GLintptr,
+                size as GLsizeiptr,
+            );
+        }
+    }
+
+    pub fn write(&self, offset: usize, bytes: &[u8]) {
+        context::assert_initialized();
+
+        if bytes.is_empty() {
+            return;
+        }
+
+        assert!(offset + bytes.len() <= self.size, "uniform buffer write out of bounds");
+
+        unsafe {
+            gl::BindBuffer(gl::UNIFORM_BUFFER, self.handle);
+            gl::BufferSubData(
+                gl::UNIFORM_BUFFER,
+                offset as GLintptr,
+                bytes.len() as GLsizeiptr,
+                bytes.as_ptr() as *const c_void,
+            );
+            gl::BindBuffer(gl::UNIFORM_BUFFER, 0);
+        }
+
+        context::count_upload(bytes.len());
+    }
+
+    pub fn write_data<T: Std140>(&self, offset: usize, data: &T) {
+        self.write(offset, &data.std140_bytes());
+    }
+
+    pub fn handle(&self) -> GLuint {
+        self.handle
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Drop for UniformBuffer {
+    fn drop(&mut self) {
+        context::defer_delete(Garbage::Buffer(self.handle));
+        self.handle = 0;
+    }
+}
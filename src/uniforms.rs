@@ -0,0 +1,115 @@
+use crate::color::{Color, LinearColor};
+use crate::mesh::UniformValue;
+use crate::shader::Shader;
+
+use vex::{Matrix3, Matrix4, Vector2, Vector3, Vector4};
+
+// a value that can be set as the uniform called name; arrays and Vecs upload element-wise as name[i]
+pub trait Uniform {
+    fn upload(&self, shader: &Shader, name: &str);
+}
+
+// a group of uniforms set together, usually through #[derive(Uniforms)] with the "derive" feature;
+// nested groups upload their fields as name.field, matching GLSL struct uniforms
+pub trait Uniforms {
+    // prefix is prepended to every uniform name, e.g. "u_material." or "u_lights[2]."
+    fn apply_prefixed(&self, shader: &Shader, prefix: &str);
+
+    fn apply(&self, shader: &Shader) {
+        self.apply_prefixed(shader, "");
+    }
+}
+
+impl Uniform for f32 {
+    fn upload(&self, shader: &Shader, name: &str) {
+        shader.set_uniform_f32(name, *self);
+    }
+}
+
+impl Uniform for i32 {
+    fn upload(&self, shader: &Shader, name: &str) {
+        shader.set_uniform_i32(name, *self);
+    }
+}
+
+// GLSL bools are set through the integer setter
+impl Uniform for bool {
+    fn upload(&self, shader: &Shader, name: &str) {
+        shader.set_uniform_i32(name, *self as i32);
+    }
+}
+
+impl Uniform for Vector2 {
+    fn upload(&self, shader: &Shader, name: &str) {
+        shader.set_uniform_vec2(name, self);
+    }
+}
+
+impl Uniform for Vector3 {
+    fn upload(&self, shader: &Shader, name: &str) {
+        shader.set_uniform_vec3(name, self);
+    }
+}
+
+impl Uniform for Vector4 {
+    fn upload(&self, shader: &Shader, name: &str) {
+        shader.set_uniform_vec4(name, self);
+    }
+}
+
+impl Uniform for Matrix3 {
+    fn upload(&self, shader: &Shader, name: &str) {
+        shader.set_uniform_mat3(name, self);
+    }
+}
+
+impl Uniform for Matrix4 {
+    fn upload(&self, shader: &Shader, name: &str) {
+        shader.set_uniform_mat4(name, self);
+    }
+}
+
+// a vec4 with the components normalized to 0.0 to 1.0
+impl Uniform for Color {
+    fn upload(&self, shader: &Shader, name: &str) {
+        let [r, g, b, a] = self.to_array_f32();
+
+        shader.set_uniform_vec4(name, &Vector4::make(r, g, b, a));
+    }
+}
+
+impl Uniform for LinearColor {
+    fn upload(&self, shader: &Shader, name: &str) {
+        shader.set_uniform_vec4(name, &Vector4::make(self.r, self.g, self.b, self.a));
+    }
+}
+
+impl Uniform for UniformValue {
+    fn upload(&self, shader: &Shader, name: &str) {
+        UniformValue::upload(self, shader, name);
+    }
+}
+
+impl<T: Uniform, const N: usize> Uniform for [T; N] {
+    fn upload(&self, shader: &Shader, name: &str) {
+        upload_elements(self, shader, name);
+    }
+}
+
+impl<T: Uniform> Uniform for Vec<T> {
+    fn upload(&self, shader: &Shader, name: &str) {
+        upload_elements(self, shader, name);
+    }
+}
+
+impl<T: Uniforms> Uniform for T {
+    fn upload(&self, shader: &Shader, name: &str) {
+        self.apply_prefixed(shader, &format!("{}.", name));
+    }
+}
+
+fn upload_elements<T: Uniform>(elements: &[T], shader: &Shader, name: &str) {
+    for (i, element) in elements.iter().enumerate() {
+        element.upload(shader, &format!("{}[{}]", name, i));
+    }
+}
@@ -1,6 +1,19 @@
+use crate::context::{self, Garbage};
+use crate::debug;
+
+use crate::error::{Error, Result};
+use crate::indirect::{self, DrawIndirectBuffer};
+use crate::multi_context;
+use crate::shader::{GlslType, Shader};
+
 use gl::types::*;
+use std::cell::Cell;
+use std::fmt;
+use std::marker::PhantomData;
 use std::mem;
+use std::ops::Range;
 use std::os::raw::c_void;
+use std::ptr;
 
 #[derive(Debug, Copy, Clone)]
 pub enum BufferKind {
@@ -94,39 +107,171 @@ impl AttributeKind {
 
 pub enum PrimitiveKind {
     Points,
+    Lines,
+    LineStrip,
+    LineLoop,
     Triangles,
     TriangleFan,
     TriangleStrip,
+    Patches,
 }
 
 impl PrimitiveKind {
     pub fn to_raw_enum(&self) -> GLenum {
         match self {
             PrimitiveKind::Points => gl::POINTS,
+            PrimitiveKind::Lines => gl::LINES,
+            PrimitiveKind::LineStrip => gl::LINE_STRIP,
+            PrimitiveKind::LineLoop => gl::LINE_LOOP,
             PrimitiveKind::Triangles => gl::TRIANGLES,
             PrimitiveKind::TriangleFan => gl::TRIANGLE_FAN,
             PrimitiveKind::TriangleStrip => gl::TRIANGLE_STRIP,
+            PrimitiveKind::Patches => gl::PATCHES,
+        }
+    }
+
+    // only triangle primitives count; patches depend on what the tessellator makes of them
+    pub(crate) fn triangle_count(&self, vertices: usize) -> usize {
+        match self {
+            PrimitiveKind::Triangles => vertices / 3,
+            PrimitiveKind::TriangleFan | PrimitiveKind::TriangleStrip => vertices.saturating_sub(2),
+            _ => 0,
         }
     }
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum IndexKind {
+    UnsignedByte,
+    UnsignedShort,
+    UnsignedInt,
+}
+
+impl IndexKind {
+    pub fn to_raw_enum(&self) -> GLenum {
+        match self {
+            IndexKind::UnsignedByte => gl::UNSIGNED_BYTE,
+            IndexKind::UnsignedShort => gl::UNSIGNED_SHORT,
+            IndexKind::UnsignedInt => gl::UNSIGNED_INT,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        match self {
+            IndexKind::UnsignedByte => mem::size_of::<GLubyte>(),
+            IndexKind::UnsignedShort => mem::size_of::<GLushort>(),
+            IndexKind::UnsignedInt => mem::size_of::<GLuint>(),
+        }
+    }
+}
+
+pub trait Index: Copy {
+    const KIND: IndexKind;
+}
+
+impl Index for u8 {
+    const KIND: IndexKind = IndexKind::UnsignedByte;
+}
+
+impl Index for u16 {
+    const KIND: IndexKind = IndexKind::UnsignedShort;
+}
+
+impl Index for u32 {
+    const KIND: IndexKind = IndexKind::UnsignedInt;
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum UpdateStrategy {
+    SubData,
+    Orphan,
+    Unsynchronized,
+    Persistent,
+    Ring,
+}
+
+const RING_REGIONS: usize = 3;
+
 pub trait Vertex: Sized {
     fn attrs() -> Vec<(bool, usize, AttributeKind)>;
     fn new() -> Self;
 }
 
+pub(crate) struct VertexLayout {
+    pub(crate) attrs: Vec<(bool, usize, AttributeKind)>,
+    // bytes per vertex; for planar layouts, the sum of every attribute's size
+    pub(crate) stride: usize,
+    pub(crate) planar: bool,
+}
+
+pub(crate) fn as_bytes<T>(items: &[T]) -> &[u8] {
+    unsafe { std::slice::from_raw_parts(items.as_ptr() as *const u8, items.len() * mem::size_of::<T>()) }
+}
+
 pub struct VBO {
     mode: BufferMode,
     primitive_kind: PrimitiveKind,
     handle: GLuint,
     vbo_handle: GLuint,
     ibo_handle: GLuint,
+    index_kind: IndexKind,
     index_count: usize,
     vertex_count: usize,
+    vertex_capacity: usize,
+    vertex_stride: usize,
+    attrs: Vec<(bool, usize, AttributeKind)>,
+    planar: bool,
+    strategy: UpdateStrategy,
+    region: Cell<usize>,
+    // one per region, set when a draw reads from it; a CPU write waits on it before touching the region
+    fences: Cell<[GLsync; RING_REGIONS]>,
+    mapped: *mut c_void,
+    label: Option<String>,
+    // vertex arrays aren't shared between contexts, so the VAO is deleted in the one that made it
+    owner: Option<u64>,
+}
+
+impl fmt::Debug for VBO {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("VBO")
+            .field("handle", &self.handle)
+            .field("label", &self.label)
+            .field("vertex_count", &self.vertex_count)
+            .field("index_count", &self.index_count)
+            .finish()
+    }
 }
 
 impl VBO {
     pub fn new<T: Vertex>(mode: BufferMode, primitive_kind: PrimitiveKind, vertices: &Vec::<T>, indices: Option<&Vec::<u16>>) -> VBO {
+        VBO::build(mode, primitive_kind, vertices, indices)
+    }
+
+    pub fn new_indexed<T: Vertex, I: Index>(mode: BufferMode, primitive_kind: PrimitiveKind, vertices: &Vec::<T>, indices: &Vec::<I>) -> VBO {
+        VBO::build(mode, primitive_kind, vertices, Some(indices))
+    }
+
+    fn build<T: Vertex, I: Index>(mode: BufferMode, primitive_kind: PrimitiveKind, vertices: &Vec::<T>, indices: Option<&Vec::<I>>) -> VBO {
+        let layout = VertexLayout {
+            attrs: T::attrs(),
+            stride: mem::size_of::<T>(),
+            planar: false,
+        };
+
+        VBO::from_parts(mode, primitive_kind, layout, as_bytes(vertices), vertices.len(), indices.map(|list| (I::KIND, as_bytes(list), list.len())))
+    }
+
+    pub(crate) fn from_parts(
+        mode: BufferMode,
+        primitive_kind: PrimitiveKind,
+        layout: VertexLayout,
+        vertex_bytes: &[u8],
+        vertex_count: usize,
+        indices: Option<(IndexKind, &[u8], usize)>,
+    ) -> VBO {
+        context::assert_initialized();
+
+        let mut index_kind = IndexKind::UnsignedShort;
         let mut index_count = 0;
         let mut ibo_handle = 0;
 
@@ -134,19 +279,20 @@ impl VBO {
             let mut vao = 0;
 
             gl::GenVertexArrays(1, &mut vao);
-            gl::BindVertexArray(vao);
+            context::bind_vertex_array(vao);
 
             vao
         };
 
-        let vbo_handle = VBO::build_vertex_buffer(mode, &vertices);
+        let vbo_handle = VBO::build_vertex_buffer(mode, vertex_bytes.len(), vertex_bytes.as_ptr() as *const c_void);
 
-        if let Some(list) = indices {
-            index_count = list.len();
-            ibo_handle = VBO::build_index_buffer(list);
-        }
+        VBO::setup_attributes(&layout, vertex_count);
 
-        unsafe { gl::BindVertexArray(0) };
+        if let Some((kind, bytes, count)) = indices {
+            index_kind = kind;
+            index_count = count;
+            ibo_handle = VBO::build_index_buffer(bytes);
+        }
 
         VBO {
             mode,
@@ -154,51 +300,70 @@ impl VBO {
             handle,
             vbo_handle,
             ibo_handle,
+            index_kind,
             index_count,
-            vertex_count: vertices.len(),
+            vertex_count,
+            vertex_capacity: vertex_count,
+            vertex_stride: layout.stride,
+            attrs: layout.attrs,
+            planar: layout.planar,
+            strategy: UpdateStrategy::SubData,
+            region: Cell::new(0),
+            fences: Cell::new([ptr::null(); RING_REGIONS]),
+            mapped: ptr::null_mut(),
+            label: None,
+            owner: multi_context::current_id(),
         }
     }
 
-    fn build_vertex_buffer<T: Vertex>(mode: BufferMode, vertices: &Vec::<T>) -> GLuint {
-        let stride = mem::size_of::<T>() as GLsizei;
-        let total_size = (vertices.len() * stride as usize) as GLsizeiptr;
-        let root_ptr = &vertices[0] as *const T as *const c_void;
-
+    fn build_vertex_buffer(mode: BufferMode, total_size: usize, root_ptr: *const c_void) -> GLuint {
         unsafe {
             let mut vbo = 0;
-            let mut offset = 0;
 
             gl::GenBuffers(1, &mut vbo);
             gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
-            gl::BufferData(gl::ARRAY_BUFFER, total_size, root_ptr, mode.to_raw_enum());
+            gl::BufferData(gl::ARRAY_BUFFER, total_size as GLsizeiptr, root_ptr, mode.to_raw_enum());
+
+            if !root_ptr.is_null() {
+                context::count_upload(total_size);
+            }
+
+            vbo
+        }
+    }
 
-            for (i, attr) in T::attrs().iter().enumerate() {
-                let offset_ptr = offset as *const c_void;
-                let normalized = match attr.0 {
-                    false => gl::FALSE,
-                    true => gl::TRUE,
-                };
+    // planar layouts store each attribute as its own tightly packed block, one after another
+    fn setup_attributes(layout: &VertexLayout, vertex_count: usize) {
+        let mut offset = 0;
 
+        for (i, attr) in layout.attrs.iter().enumerate() {
+            let attr_size = attr.2.size() * attr.1;
+            let stride = if layout.planar { attr_size } else { layout.stride };
+            let offset_ptr = offset as *const c_void;
+            let normalized = match attr.0 {
+                false => gl::FALSE,
+                true => gl::TRUE,
+            };
+
+            unsafe {
                 gl::EnableVertexAttribArray(i as u32);
                 gl::VertexAttribPointer(
                     i as GLuint,
                     attr.1 as GLint,
                     attr.2.to_raw_enum(),
                     normalized,
-                    stride,
+                    stride as GLsizei,
                     offset_ptr,
                 );
-
-                offset += attr.2.size() * attr.1;
             }
 
-            vbo
+            offset += if layout.planar { attr_size * vertex_count } else { attr_size };
         }
     }
 
-    fn build_index_buffer(indices: &Vec::<u16>) -> GLuint {
-        let total_size = (indices.len() * 2) as GLsizeiptr;
-        let root_ptr = &indices[0] as *const u16 as *const c_void;
+    fn build_index_buffer(indices: &[u8]) -> GLuint {
+        let total_size = indices.len() as GLsizeiptr;
+        let root_ptr = indices.as_ptr() as *const c_void;
 
         unsafe {
             let mut ibo = 0;
@@ -206,6 +371,7 @@ impl VBO {
             gl::GenBuffers(1, &mut ibo);
             gl::BindBuffer(gl::ELEMENT_ARRAY_BUFFER, ibo);
             gl::BufferData(gl::ELEMENT_ARRAY_BUFFER, total_size, root_ptr, gl::STATIC_DRAW);
+            context::count_upload(indices.len());
 
             ibo as GLuint
         }
@@ -218,55 +384,817 @@ impl VBO {
         }
     }
 
-    fn write<T: Sized>(&self, kind: BufferKind, vertices: &Vec::<T>, offset: usize) {
+    pub fn update_strategy(&self) -> UpdateStrategy {
+        self.strategy
+    }
+
+    pub fn set_update_strategy(&mut self, strategy: UpdateStrategy) -> Result<()> {
+        context::check_initialized()?;
+
+        if strategy == UpdateStrategy::Persistent && !gl::BufferStorage::is_loaded() {
+            return Err(Error::Unsupported("persistent buffer mapping requires glBufferStorage".to_string()));
+        }
+
+        if strategy == self.strategy {
+            return Ok(());
+        }
+
+        if strategy == UpdateStrategy::Ring && self.planar {
+            return Err(Error::Unsupported("ring buffering requires an interleaved vertex layout".to_string()));
+        }
+
+        let source = self.region.get() * self.vertex_capacity * self.vertex_stride;
+
+        self.replace_vertex_buffer(strategy, source, self.vertex_count * self.vertex_stride);
+        self.strategy = strategy;
+
+        Ok(())
+    }
+
+    // moves the live vertices into fresh storage sized for the current capacity and strategy
+    fn replace_vertex_buffer(&mut self, strategy: UpdateStrategy, source: usize, size: usize) {
+        let persistent = strategy == UpdateStrategy::Persistent || strategy == UpdateStrategy::Ring;
+        let region_size = self.vertex_capacity * self.vertex_stride;
+        let total_size = match strategy {
+            UpdateStrategy::Ring => region_size * RING_REGIONS,
+            _ => region_size,
+        };
+
+        unsafe {
+            let mut vbo = 0;
+
+            context::bind_vertex_array(self.handle);
+            gl::GenBuffers(1, &mut vbo);
+            gl::BindBuffer(gl::ARRAY_BUFFER, vbo);
+
+            let mapped = if persistent && gl::BufferStorage::is_loaded() {
+                let mut flags = gl::MAP_WRITE_BIT | gl::MAP_PERSISTENT_BIT | gl::MAP_COHERENT_BIT;
+
+                // moving to a new ring region reads the vertices a write doesn't replace back out of the old one
+                if strategy == UpdateStrategy::Ring {
+                    flags |= gl::MAP_READ_BIT;
+                }
+
+                gl::BufferStorage(gl::ARRAY_BUFFER, total_size as GLsizeiptr, ptr::null(), gl::DYNAMIC_STORAGE_BIT | flags);
+                gl::MapBufferRange(gl::ARRAY_BUFFER, 0, total_size as GLsizeiptr, flags)
+            } else {
+                gl::BufferData(gl::ARRAY_BUFFER, total_size as GLsizeiptr, ptr::null(), self.mode.to_raw_enum());
+                ptr::null_mut()
+            };
+
+            gl::BindBuffer(gl::COPY_READ_BUFFER, self.vbo_handle);
+            gl::CopyBufferSubData(gl::COPY_READ_BUFFER, gl::ARRAY_BUFFER, source as GLintptr, 0, size as GLsizeiptr);
+            gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+            gl::DeleteBuffers(1, &self.vbo_handle);
+
+            // the mapping is written and read directly, so the copy has to land before anything touches it
+            if !mapped.is_null() {
+                let fence = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+
+                gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, gl::TIMEOUT_IGNORED);
+                gl::DeleteSync(fence);
+            }
+
+            VBO::setup_attributes(&self.layout(), self.vertex_capacity);
+
+            self.vbo_handle = vbo;
+            self.mapped = mapped;
+        }
+
+        if let Some(label) = self.label.as_ref() {
+            debug::label_object(gl::BUFFER, self.vbo_handle, &format!("{} vertices", label));
+        }
+
+        self.clear_fences();
+        self.region.set(0);
+    }
+
+    // marks the current region as read by the draws just issued; only mapped strategies write without
+    // GL's implicit synchronization, so the others skip it
+    fn fence_region(&self) {
+        if self.strategy != UpdateStrategy::Persistent && self.strategy != UpdateStrategy::Ring {
+            return;
+        }
+
+        let mut fences = self.fences.get();
+        let region = self.region.get();
+
+        unsafe {
+            if !fences[region].is_null() {
+                gl::DeleteSync(fences[region]);
+            }
+
+            fences[region] = gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0);
+        }
+
+        self.fences.set(fences);
+    }
+
+    fn is_region_fenced(&self, region: usize) -> bool {
+        !self.fences.get()[region].is_null()
+    }
+
+    // blocks until the GPU has finished every draw that read from the region
+    fn wait_region(&self, region: usize) {
+        let mut fences = self.fences.get();
+
+        if fences[region].is_null() {
+            return;
+        }
+
+        unsafe {
+            gl::ClientWaitSync(fences[region], gl::SYNC_FLUSH_COMMANDS_BIT, gl::TIMEOUT_IGNORED);
+            gl::DeleteSync(fences[region]);
+        }
+
+        fences[region] = ptr::null();
+        self.fences.set(fences);
+    }
+
+    fn clear_fences(&self) {
+        for fence in self.fences.replace([ptr::null(); RING_REGIONS]).iter() {
+            if !fence.is_null() {
+                unsafe { gl::DeleteSync(*fence) };
+            }
+        }
+    }
+
+    // moves on to the next region once the GPU is done with it, carrying over every byte of the current
+    // region outside of the range about to be written
+    unsafe fn advance_region(&self, offset: isize, size: isize) {
+        let region_size = (self.vertex_capacity * self.vertex_stride) as isize;
+        let current = self.region.get();
+        let next = (current + 1) % RING_REGIONS;
+        let src = region_size * current as isize;
+        let dst = region_size * next as isize;
+        let untouched = [(0, offset), (offset + size, region_size - offset - size)];
+
+        self.wait_region(next);
+
+        for (start, len) in untouched.iter().filter(|(_, len)| *len > 0) {
+            if self.mapped.is_null() {
+                gl::CopyBufferSubData(gl::ARRAY_BUFFER, gl::ARRAY_BUFFER, src + start, dst + start, *len);
+            } else {
+                let base = self.mapped as *mut u8;
+
+                ptr::copy_nonoverlapping(base.offset(src + start), base.offset(dst + start), *len as usize);
+            }
+        }
+
+        self.region.set(next);
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.vertex_count
+    }
+
+    pub fn vertex_capacity(&self) -> usize {
+        self.vertex_capacity
+    }
+
+    // shrinking below the current vertex count drops the vertices past the new end
+    pub fn reallocate(&mut self, new_capacity: usize) -> Result<()> {
+        context::check_initialized()?;
+
+        if self.planar {
+            return Err(Error::Unsupported("reallocating requires an interleaved vertex layout".to_string()));
+        }
+
+        if new_capacity == self.vertex_capacity {
+            return Ok(());
+        }
+
+        let source = self.region.get() * self.vertex_capacity * self.vertex_stride;
+        let kept = self.vertex_count.min(new_capacity);
+
+        self.vertex_capacity = new_capacity;
+        self.replace_vertex_buffer(self.strategy, source, kept * self.vertex_stride);
+        self.vertex_count = kept;
+
+        Ok(())
+    }
+
+    // grows the buffer geometrically when full; returns the range the new vertices occupy
+    pub fn append_vertices<T: Vertex>(&mut self, vertices: &[T]) -> Result<Range<usize>> {
+        context::check_initialized()?;
+        self.check_vertex_type::<T>()?;
+
+        let start = self.vertex_count;
+        let end = start + vertices.len();
+
+        if vertices.is_empty() {
+            return Ok(start..end);
+        }
+
+        if end > self.vertex_capacity {
+            let capacity = end.max(self.vertex_capacity * 2);
+
+            self.reallocate(capacity)?;
+        }
+
+        self.write(BufferKind::Vertex, vertices, start);
+        self.vertex_count = end;
+
+        Ok(start..end)
+    }
+
+    fn check_vertex_type<T: Vertex>(&self) -> Result<()> {
+        if self.planar {
+            return Err(Error::Unsupported("planar VBOs can't be written with interleaved vertices".to_string()));
+        }
+
+        if mem::size_of::<T>() != self.vertex_stride {
+            let msg = format!("{}-byte vertices written to a VBO with a {}-byte stride", mem::size_of::<T>(), self.vertex_stride);
+
+            return Err(Error::VertexLayoutMismatch(msg));
+        }
+
+        Ok(())
+    }
+
+
+    // callers check the bounds and skip empty writes
+    fn write<T: Sized>(&self, kind: BufferKind, vertices: &[T], offset: usize) {
         let size = mem::size_of::<T>() as isize;
         let offset = offset as isize * size;
         let total_size = vertices.len()  as isize * size;
-        let root_ptr = &vertices[0] as *const T as *const c_void;
+        let root_ptr = vertices.as_ptr() as *const c_void;
         let raw_kind = kind.to_raw_enum();
         let handle = self.get_buffer_handle(kind);
 
         unsafe {
-            gl::BindVertexArray(self.handle);
+            context::bind_vertex_array(self.handle);
             gl::BindBuffer(raw_kind, handle);
-            gl::BufferSubData(raw_kind, offset, total_size, root_ptr);
+
+            match (kind, self.strategy) {
+                (BufferKind::Index, _) | (_, UpdateStrategy::SubData) => {
+                    gl::BufferSubData(raw_kind, offset, total_size, root_ptr);
+                }
+                (_, UpdateStrategy::Orphan) => {
+                    let region_size = (self.vertex_capacity * self.vertex_stride) as GLsizeiptr;
+
+                    // orphaning leaves the new storage undefined, so only a write replacing all of it may do it
+                    if offset == 0 && total_size == region_size {
+                        gl::BufferData(raw_kind, region_size, ptr::null(), self.mode.to_raw_enum());
+                    }
+
+                    gl::BufferSubData(raw_kind, offset, total_size, root_ptr);
+                }
+                (_, UpdateStrategy::Unsynchronized) => {
+                    let flags = gl::MAP_WRITE_BIT | gl::MAP_UNSYNCHRONIZED_BIT | gl::MAP_INVALIDATE_RANGE_BIT;
+                    let dst = gl::MapBufferRange(raw_kind, offset, total_size, flags);
+
+                    ptr::copy_nonoverlapping(root_ptr as *const u8, dst as *mut u8, total_size as usize);
+                    gl::UnmapBuffer(raw_kind);
+                }
+                (_, UpdateStrategy::Persistent) | (_, UpdateStrategy::Ring) => {
+                    if self.strategy == UpdateStrategy::Persistent {
+                        // a single region has to wait out the draws reading it; Ring writes elsewhere instead
+                        self.wait_region(0);
+                    } else if self.is_region_fenced(self.region.get()) {
+                        self.advance_region(offset, total_size);
+                    }
+
+                    let region_size = (self.vertex_capacity * self.vertex_stride) as isize;
+                    let base = region_size * self.region.get() as isize + offset;
+
+                    if self.mapped.is_null() {
+                        // without a mapping, the writes stay ordered after the region copies above
+                        gl::BufferSubData(raw_kind, base, total_size, root_ptr);
+                    } else {
+                        let dst = (self.mapped as *mut u8).offset(base);
+
+                        ptr::copy_nonoverlapping(root_ptr as *const u8, dst, total_size as usize);
+                    }
+                }
+            }
         };
+
+        context::count_upload(total_size as usize);
+    }
+
+    fn layout(&self) -> VertexLayout {
+        VertexLayout {
+            attrs: self.attrs.clone(),
+            stride: self.vertex_stride,
+            planar: self.planar,
+        }
+    }
+
+    // labels the vertex array and both buffers, which show up as "<label> vertices" and "<label> indices"
+    pub fn set_label(&mut self, label: &str) {
+        debug::label_object(gl::VERTEX_ARRAY, self.handle, label);
+        debug::label_object(gl::BUFFER, self.vbo_handle, &format!("{} vertices", label));
+        debug::label_object(gl::BUFFER, self.ibo_handle, &format!("{} indices", label));
+
+        self.label = Some(label.to_string());
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    pub fn is_planar(&self) -> bool {
+        self.planar
     }
 
     pub fn mode(&self) -> BufferMode {
         self.mode
     }
 
-    pub fn write_vertices<T: Vertex>(&self, vertices: &Vec::<T>, offset: usize) {
-        self.write(BufferKind::Vertex, vertices, offset);
+    pub fn index_kind(&self) -> IndexKind {
+        self.index_kind
+    }
+
+    // offset is in vertices, not bytes
+    pub fn write_vertices<T: Vertex>(&self, vertices: &[T], offset: usize) -> Result<()> {
+        context::check_initialized()?;
+        self.check_vertex_type::<T>()?;
+
+        if offset + vertices.len() > self.vertex_capacity {
+            let msg = format!(
+                "writing {} vertices at offset {} overflows the {}-vertex buffer",
+                vertices.len(),
+                offset,
+                self.vertex_capacity,
+            );
+
+            return Err(Error::InvalidVertexData(msg));
+        }
+
+        if !vertices.is_empty() {
+            self.write(BufferKind::Vertex, vertices, offset);
+        }
+
+        Ok(())
+    }
+
+    // offset and length are in indices, not bytes
+    pub fn write_indices<I: Index>(&self, indices: &[I], offset: usize) -> Result<()> {
+        context::check_initialized()?;
+
+        if self.ibo_handle == 0 {
+            return Err(Error::InvalidIndexData("the VBO has no index buffer".to_string()));
+        }
+
+        if I::KIND != self.index_kind {
+            let msg = format!("expected {:?} indices, got {:?}", self.index_kind, I::KIND);
+
+            return Err(Error::InvalidIndexData(msg));
+        }
+
+        if offset + indices.len() > self.index_count {
+            let msg = format!(
+                "writing {} indices at offset {} overflows the {}-index buffer",
+                indices.len(),
+                offset,
+                self.index_count,
+            );
+
+            return Err(Error::InvalidIndexData(msg));
+        }
+
+        if !indices.is_empty() {
+            self.write(BufferKind::Index, indices, offset);
+        }
+
+        Ok(())
+    }
+
+    pub fn bind_scoped(&self) -> VboBinding<'_> {
+        context::assert_initialized();
+
+        let previous = context::bound_vertex_array();
+
+        context::bind_vertex_array(self.handle);
+
+        VboBinding {
+            previous,
+            _vbo: PhantomData,
+        }
     }
 
-    pub fn write_indices<T: Vertex>(&self, indices: &Vec::<u16>, offset: usize) {
-        self.write(BufferKind::Index, indices, offset);
+    // attribute i of the vertex type feeds location i, so compare slot by slot
+    pub fn validate(&self, shader: &Shader) -> Result<()> {
+        for attribute in shader.attributes() {
+            if attribute.location < 0 {
+                continue;
+            }
+
+            let location = attribute.location as usize;
+            let (_, components, kind) = match self.attrs.get(location) {
+                Some(attr) => *attr,
+                None => {
+                    let msg = format!(
+                        "shader input `{}` reads location {}, but the vertex type only provides {} attribute(s)",
+                        attribute.name,
+                        location,
+                        self.attrs.len(),
+                    );
+
+                    return Err(Error::VertexLayoutMismatch(msg));
+                },
+            };
+
+            if attribute.kind.is_integer() || attribute.kind == GlslType::Double {
+                let msg = format!(
+                    "shader input `{}` at location {} is {:?}, but vertex attributes are uploaded as floats",
+                    attribute.name,
+                    location,
+                    attribute.kind,
+                );
+
+                return Err(Error::VertexLayoutMismatch(msg));
+            }
+
+            let expected = attribute.kind.components();
+
+            if expected > 0 && expected != components {
+                let msg = format!(
+                    "shader input `{}` at location {} is {:?} ({} components), but the vertex supplies {} {:?} component(s)",
+                    attribute.name,
+                    location,
+                    attribute.kind,
+                    expected,
+                    components,
+                    kind,
+                );
+
+                return Err(Error::VertexLayoutMismatch(msg));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn render_validated(&self, shader: &Shader) -> Result<()> {
+        self.validate(shader)?;
+
+        shader.bind();
+        self.render();
+
+        Ok(())
+    }
+
+    // indices when the VBO is indexed, vertices otherwise
+    pub fn element_count(&self) -> usize {
+        match self.index_count {
+            0 => self.vertex_count,
+            n => n,
+        }
     }
 
     pub fn render(&self) {
+        self.draw(0, self.element_count());
+    }
+
+    pub fn render_range(&self, first: usize, count: usize) {
+        self.check_range(first, count);
+        self.draw(first, count);
+    }
+
+    // base_vertex is added to every index, so several meshes can share one index buffer layout
+    pub fn render_base_vertex(&self, first: usize, count: usize, base_vertex: usize) {
+        self.check_range(first, count);
+        self.draw_base_vertex(first, count, base_vertex);
+    }
+
+    pub fn render_ranges(&self, ranges: &[(usize, usize)]) {
+        context::assert_initialized();
+
+        if ranges.is_empty() {
+            return;
+        }
+
+        for (first, count) in ranges.iter() {
+            self.check_range(*first, *count);
+        }
+
+        let kind = self.primitive_kind.to_raw_enum();
+        let base_vertex = self.region.get() * self.vertex_capacity;
+        let counts = ranges.iter().map(|(_, count)| *count as GLsizei).collect::<Vec<GLsizei>>();
+
+        context::bind_vertex_array(self.handle);
+
+        unsafe {
+            if self.index_count > 0 {
+                let index_kind = self.index_kind.to_raw_enum();
+                let offsets = ranges
+                    .iter()
+                    .map(|(first, _)| (first * self.index_kind.size()) as *const c_void)
+                    .collect::<Vec<*const c_void>>();
+
+                if base_vertex > 0 {
+                    let base_vertices = vec![base_vertex as GLint; ranges.len()];
+
+                    gl::MultiDrawElementsBaseVertex(
+                        kind,
+                        counts.as_ptr(),
+                        index_kind,
+                        offsets.as_ptr(),
+                        ranges.len() as GLsizei,
+                        base_vertices.as_ptr(),
+                    );
+                } else {
+                    gl::MultiDrawElements(kind, counts.as_ptr(), index_kind, offsets.as_ptr(), ranges.len() as GLsizei);
+                }
+            } else {
+                let firsts = ranges
+                    .iter()
+                    .map(|(first, _)| (base_vertex + first) as GLint)
+                    .collect::<Vec<GLint>>();
+
+                gl::MultiDrawArrays(kind, firsts.as_ptr(), counts.as_ptr(), ranges.len() as GLsizei);
+            }
+        }
+
+        self.fence_region();
+
+        for (_, count) in ranges.iter() {
+            context::count_draw(self.primitive_kind.triangle_count(*count));
+        }
+    }
+
+    // with instanced set, every attribute advances once per instance instead of once per vertex, so
+    // the buffer holds per-instance data and the shader builds the shape from gl_VertexID
+    pub fn set_instanced(&self, instanced: bool) {
+        context::assert_initialized();
+        context::bind_vertex_array(self.handle);
+
+        for i in 0..self.attrs.len() {
+            unsafe { gl::VertexAttribDivisor(i as GLuint, instanced as GLuint) };
+        }
+    }
+
+    // count is the number of elements per instance; for non-indexed VBOs it's independent of the
+    // buffer's size, which is what instance-rate buffers need
+    pub fn render_instanced(&self, count: usize, instance_count: usize) {
+        context::assert_initialized();
+
+        assert_eq!(self.region.get(), 0, "instanced draws can't start from a ring buffer region");
+
+        if instance_count == 0 {
+            return;
+        }
+
+        let kind = self.primitive_kind.to_raw_enum();
+
+        context::bind_vertex_array(self.handle);
+
+        unsafe {
+            if self.index_count > 0 {
+                self.check_range(0, count);
+                gl::DrawElementsInstanced(kind, count as GLsizei, self.index_kind.to_raw_enum(), ptr::null(), instance_count as GLsizei);
+            } else {
+                gl::DrawArraysInstanced(kind, 0, count as GLsizei, instance_count as GLsizei);
+            }
+        }
+
+        self.fence_region();
+        context::count_draw(self.primitive_kind.triangle_count(count) * instance_count);
+    }
+
+    // reads one DrawElementsCommand (indexed VBOs) or DrawArraysCommand from buffer at a byte offset;
+    // the command's own base vertex applies, so ring-buffered VBOs aren't supported
+    pub fn render_indirect(&self, buffer: &DrawIndirectBuffer, offset: usize) -> Result<()> {
+        indirect::check_indirect_support(false)?;
+
+        assert_eq!(self.region.get(), 0, "indirect draws can't start from a ring buffer region");
+
         let kind = self.primitive_kind.to_raw_enum();
+        let root_ptr = offset as *const c_void;
+
+        context::bind_vertex_array(self.handle);
 
         unsafe {
-            gl::BindVertexArray(self.handle);
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, buffer.handle());
 
             if self.index_count > 0 {
-                let root_ptr = 0 as *const u16 as *const c_void;
+                gl::DrawElementsIndirect(kind, self.index_kind.to_raw_enum(), root_ptr);
+            } else {
+                gl::DrawArraysIndirect(kind, root_ptr);
+            }
+
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+        }
+
+        self.fence_region();
+
+        // the element counts live on the GPU
+        context::count_draw(0);
+
+        Ok(())
+    }
 
-                gl::DrawElements(kind, self.index_count as i32, gl::UNSIGNED_SHORT, root_ptr);
+    // count commands starting at a byte offset; a stride of 0 means tightly packed
+    pub fn render_multi_indirect(&self, buffer: &DrawIndirectBuffer, offset: usize, count: usize, stride: usize) -> Result<()> {
+        indirect::check_indirect_support(true)?;
+
+        assert_eq!(self.region.get(), 0, "indirect draws can't start from a ring buffer region");
+
+        if count == 0 {
+            return Ok(());
+        }
+
+        let kind = self.primitive_kind.to_raw_enum();
+        let root_ptr = offset as *const c_void;
+
+        context::bind_vertex_array(self.handle);
+
+        unsafe {
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, buffer.handle());
+
+            if self.index_count > 0 {
+                gl::MultiDrawElementsIndirect(kind, self.index_kind.to_raw_enum(), root_ptr, count as GLsizei, stride as GLsizei);
             } else {
-                gl::DrawArrays(kind, 0, self.vertex_count as i32);
+                gl::MultiDrawArraysIndirect(kind, root_ptr, count as GLsizei, stride as GLsizei);
             }
 
-            gl::BindVertexArray(0);
+            gl::BindBuffer(gl::DRAW_INDIRECT_BUFFER, 0);
+        }
+
+        self.fence_region();
+
+        for _ in 0..count {
+            context::count_draw(0);
+        }
+
+        Ok(())
+    }
+
+    fn check_range(&self, first: usize, count: usize) {
+        let total = self.element_count();
+
+        assert!(first + count <= total, "draw range {}..{} exceeds the {} available elements", first, first + count, total);
+    }
+
+    pub(crate) fn draw(&self, first: usize, count: usize) {
+        self.draw_base_vertex(first, count, 0);
+    }
+
+    fn draw_base_vertex(&self, first: usize, count: usize, base_vertex: usize) {
+        context::assert_initialized();
+
+        let kind = self.primitive_kind.to_raw_enum();
+
+        let base_vertex = self.region.get() * self.vertex_capacity + base_vertex;
+
+        context::bind_vertex_array(self.handle);
+
+        unsafe {
+            if self.index_count > 0 {
+                let root_ptr = (first * self.index_kind.size()) as *const c_void;
+                let index_kind = self.index_kind.to_raw_enum();
+
+                if base_vertex > 0 {
+                    gl::DrawElementsBaseVertex(kind, count as i32, index_kind, root_ptr, base_vertex as i32);
+                } else {
+                    gl::DrawElements(kind, count as i32, index_kind, root_ptr);
+                }
+            } else {
+                gl::DrawArrays(kind, (base_vertex + first) as i32, count as i32);
+            }
         };
+
+        self.fence_region();
+        context::count_draw(self.primitive_kind.triangle_count(count));
+    }
+}
+
+#[must_use]
+pub struct VboBinding<'a> {
+    previous: GLuint,
+    _vbo: PhantomData<&'a VBO>,
+}
+
+impl Drop for VboBinding<'_> {
+    fn drop(&mut self) {
+        context::bind_vertex_array(self.previous);
     }
 }
 
 impl Drop for VBO {
     fn drop(&mut self) {
-        unsafe { gl::DeleteVertexArrays(1, &self.handle) };
+        context::defer_delete_owned(self.owner, Garbage::VertexArray(self.handle));
+        context::defer_delete(Garbage::Buffer(self.vbo_handle));
+        context::defer_delete(Garbage::Buffer(self.ibo_handle));
+
+        for fence in self.fences.replace([ptr::null(); RING_REGIONS]).iter() {
+            context::defer_delete(Garbage::Sync(*fence as usize));
+        }
+
         self.handle = 0;
+        self.vbo_handle = 0;
+        self.ibo_handle = 0;
+        self.mapped = ptr::null_mut();
+    }
+}
+
+#[cfg(all(test, feature = "headless"))]
+mod tests {
+    use super::*;
+    use crate::builtin::{BasicVertex, SpriteVertex, SHADER_BASIC};
+
+    fn line(xs: &[f32]) -> Vec<BasicVertex> {
+        xs.iter().map(|x| BasicVertex::new(*x, 0.0, 0.0)).collect()
+    }
+
+    fn with_strategy(strategy: UpdateStrategy) -> VBO {
+        let mut vbo = VBO::new(BufferMode::DynamicDraw, PrimitiveKind::Points, &line(&[0.0, 1.0, 2.0, 3.0]), None);
+
+        vbo.set_update_strategy(strategy).unwrap();
+        vbo
+    }
+
+    // the x of every vertex in the region draws read from
+    fn region_xs(vbo: &VBO) -> Vec<f32> {
+        let region_size = vbo.vertex_capacity * vbo.vertex_stride;
+        let mut buf = vec![0f32; region_size / mem::size_of::<f32>()];
+
+        unsafe {
+            gl::BindBuffer(gl::COPY_READ_BUFFER, vbo.vbo_handle);
+            gl::GetBufferSubData(
+                gl::COPY_READ_BUFFER,
+                (vbo.region.get() * region_size) as GLintptr,
+                region_size as GLsizeiptr,
+                buf.as_mut_ptr() as *mut c_void,
+            );
+            gl::BindBuffer(gl::COPY_READ_BUFFER, 0);
+        }
+
+        buf.chunks(3).map(|vertex| vertex[0]).collect()
+    }
+
+    #[test]
+    fn orphaned_partial_writes_keep_the_rest() {
+        crate::headless::run(|| {
+            let vbo = with_strategy(UpdateStrategy::Orphan);
+
+            vbo.write_vertices(&line(&[5.0]), 0).unwrap();
+            vbo.write_vertices(&line(&[7.0]), 3).unwrap();
+
+            assert_eq!(region_xs(&vbo), vec![5.0, 1.0, 2.0, 7.0]);
+
+            vbo.write_vertices(&line(&[8.0, 9.0, 10.0, 11.0]), 0).unwrap();
+
+            assert_eq!(region_xs(&vbo), vec![8.0, 9.0, 10.0, 11.0]);
+        });
+    }
+
+    #[test]
+    fn ring_writes_only_move_on_after_a_draw() {
+        crate::headless::run(|| {
+            let vbo = with_strategy(UpdateStrategy::Ring);
+
+            SHADER_BASIC.bind();
+
+            vbo.write_vertices(&line(&[5.0]), 1).unwrap();
+            vbo.write_vertices(&line(&[6.0]), 0).unwrap();
+
+            assert_eq!(vbo.region.get(), 0);
+            assert_eq!(region_xs(&vbo), vec![6.0, 5.0, 2.0, 3.0]);
+
+            for expected in 1..=RING_REGIONS {
+                vbo.render();
+                vbo.write_vertices(&line(&[expected as f32 * 10.0]), 3).unwrap();
+
+                // wraps back around to the first region, once its fence has been waited on
+                assert_eq!(vbo.region.get(), expected % RING_REGIONS);
+                assert!(!vbo.is_region_fenced(vbo.region.get()));
+                assert_eq!(region_xs(&vbo), vec![6.0, 5.0, 2.0, expected as f32 * 10.0]);
+            }
+        });
+    }
+
+    #[test]
+    fn persistent_writes_wait_for_the_draws_reading_them() {
+        crate::headless::run(|| {
+            let vbo = with_strategy(UpdateStrategy::Persistent);
+
+            SHADER_BASIC.bind();
+            vbo.render();
+
+            assert!(vbo.is_region_fenced(0));
+
+            vbo.write_vertices(&line(&[4.0]), 2).unwrap();
+
+            assert!(!vbo.is_region_fenced(0));
+            assert_eq!(region_xs(&vbo), vec![0.0, 1.0, 4.0, 3.0]);
+        });
+    }
+
+    #[test]
+    fn bad_vertex_writes_are_errors() {
+        crate::headless::run(|| {
+            let mut vbo = with_strategy(UpdateStrategy::SubData);
+
+            assert!(vbo.write_vertices(&line(&[]), 4).is_ok());
+            assert!(matches!(vbo.write_vertices(&line(&[5.0]), 4), Err(Error::InvalidVertexData(_))));
+            assert!(matches!(vbo.write_vertices(&vec![SpriteVertex::new()], 0), Err(Error::VertexLayoutMismatch(_))));
+            assert_eq!(region_xs(&vbo), vec![0.0, 1.0, 2.0, 3.0]);
+
+            assert_eq!(vbo.append_vertices(&line(&[4.0, 5.0])).unwrap(), 4..6);
+            assert!(vbo.vertex_capacity() >= 6);
+        });
     }
 }
@@ -0,0 +1,152 @@
+use crate::error::{Error, Result};
+use crate::vbo::{self, AttributeKind, BufferMode, Index, IndexKind, PrimitiveKind, Vertex, VertexLayout, VBO};
+
+use std::mem;
+
+struct Stream {
+    attr: (bool, usize, AttributeKind),
+    bytes: Vec<u8>,
+}
+
+impl Stream {
+    fn element_size(&self) -> usize {
+        self.attr.1 * self.attr.2.size()
+    }
+}
+
+struct Interleaved {
+    attrs: Vec<(bool, usize, AttributeKind)>,
+    stride: usize,
+    bytes: Vec<u8>,
+    count: usize,
+}
+
+pub struct VboBuilder {
+    mode: BufferMode,
+    primitive_kind: PrimitiveKind,
+    interleaved: Option<Interleaved>,
+    streams: Vec<Stream>,
+    indices: Option<(IndexKind, Vec<u8>, usize)>,
+}
+
+impl VboBuilder {
+    pub fn new(primitive_kind: PrimitiveKind) -> VboBuilder {
+        VboBuilder {
+            mode: BufferMode::StaticDraw,
+            primitive_kind,
+            interleaved: None,
+            streams: Vec::new(),
+            indices: None,
+        }
+    }
+
+    pub fn mode(mut self, mode: BufferMode) -> VboBuilder {
+        self.mode = mode;
+        self
+    }
+
+    pub fn vertices<T: Vertex>(mut self, vertices: &[T]) -> VboBuilder {
+        self.interleaved = Some(Interleaved {
+            attrs: T::attrs(),
+            stride: mem::size_of::<T>(),
+            bytes: vbo::as_bytes(vertices).to_vec(),
+            count: vertices.len(),
+        });
+
+        self
+    }
+
+    // streams are assigned attribute locations in the order they're added
+    pub fn attribute<S: Copy>(mut self, normalized: bool, components: usize, kind: AttributeKind, data: &[S]) -> VboBuilder {
+        self.streams.push(Stream {
+            attr: (normalized, components, kind),
+            bytes: vbo::as_bytes(data).to_vec(),
+        });
+
+        self
+    }
+
+    pub fn positions(self, data: &[[f32; 3]]) -> VboBuilder {
+        self.attribute(false, 3, AttributeKind::Float, data)
+    }
+
+    pub fn normals(self, data: &[[f32; 3]]) -> VboBuilder {
+        self.attribute(false, 3, AttributeKind::Float, data)
+    }
+
+    pub fn uvs(self, data: &[[f32; 2]]) -> VboBuilder {
+        self.attribute(false, 2, AttributeKind::Float, data)
+    }
+
+    pub fn colors(self, data: &[[f32; 4]]) -> VboBuilder {
+        self.attribute(false, 4, AttributeKind::Float, data)
+    }
+
+    pub fn colors_u8(self, data: &[[u8; 4]]) -> VboBuilder {
+        self.attribute(true, 4, AttributeKind::UnsignedByte, data)
+    }
+
+    pub fn indices<I: Index>(mut self, indices: &[I]) -> VboBuilder {
+        self.indices = Some((I::KIND, vbo::as_bytes(indices).to_vec(), indices.len()));
+        self
+    }
+
+    pub fn build(self) -> Result<VBO> {
+        let indices = self.indices.as_ref().map(|(kind, bytes, count)| (*kind, bytes.as_slice(), *count));
+
+        match (self.interleaved, self.streams.is_empty()) {
+            (Some(_), false) => {
+                let msg = "interleaved vertices and separate attribute streams can't be combined".to_string();
+
+                Err(Error::InvalidVertexData(msg))
+            },
+            (None, true) => Err(Error::InvalidVertexData("no vertex data was supplied".to_string())),
+            (Some(data), true) => {
+                let layout = VertexLayout {
+                    attrs: data.attrs,
+                    stride: data.stride,
+                    planar: false,
+                };
+
+                Ok(VBO::from_parts(self.mode, self.primitive_kind, layout, &data.bytes, data.count, indices))
+            },
+            (None, false) => {
+                let mut count = None;
+
+                for (i, stream) in self.streams.iter().enumerate() {
+                    let size = stream.element_size();
+
+                    if size == 0 || stream.bytes.len() % size != 0 {
+                        let msg = format!("stream {} isn't a whole number of {}-byte elements", i, size);
+
+                        return Err(Error::InvalidVertexData(msg));
+                    }
+
+                    let stream_count = stream.bytes.len() / size;
+
+                    match count {
+                        Some(expected) if expected != stream_count => {
+                            let msg = format!("stream {} has {} vertices, expected {}", i, stream_count, expected);
+
+                            return Err(Error::InvalidVertexData(msg));
+                        },
+                        _ => count = Some(stream_count),
+                    }
+                }
+
+                let layout = VertexLayout {
+                    attrs: self.streams.iter().map(|stream| stream.attr).collect(),
+                    stride: self.streams.iter().map(|stream| stream.element_size()).sum(),
+                    planar: true,
+                };
+
+                let bytes = self.streams
+                    .iter()
+                    .flat_map(|stream| stream.bytes.iter().copied())
+                    .collect::<Vec<u8>>();
+
+                Ok(VBO::from_parts(self.mode, self.primitive_kind, layout, &bytes, count.unwrap_or(0), indices))
+            },
+        }
+    }
+}
@@ -0,0 +1,52 @@
+#![cfg(feature = "headless")]
+
+use gl_toolkit::headless::HeadlessContext;
+use gl_toolkit::{ClearFlag, Color, Error, Texture, TextureFormat};
+
+use std::thread;
+
+fn clear_and_read(color: Color) -> Vec<u8> {
+    gl_toolkit::set_clear_color(color);
+    gl_toolkit::clear(ClearFlag::Color.into());
+
+    gl_toolkit::read_pixels(0, 0, 1, 1, TextureFormat::Rgba8)
+}
+
+// a single test, since only one context can be initialized at a time and tests run in parallel
+#[test]
+fn contexts_can_be_created_again_after_drop() {
+    let first = HeadlessContext::new(32, 32).unwrap();
+
+    assert!(matches!(HeadlessContext::new(32, 32), Err(Error::AlreadyInitialized)));
+
+    drop(Texture::make(&vec![255; 4], 1, 1, false).unwrap());
+
+    assert_eq!(gl_toolkit::pending_garbage(), 1);
+    assert_eq!(clear_and_read(Color::make(255, 0, 0, 255)), vec![255, 0, 0, 255]);
+
+    drop(first);
+
+    assert!(!gl_toolkit::is_initialized());
+    assert_eq!(gl_toolkit::pending_garbage(), 0);
+    assert!(matches!(Texture::make(&vec![255; 4], 1, 1, false), Err(Error::NotInitialized)));
+
+    // the caches start over with the new context instead of trusting the old one's
+    let second = HeadlessContext::new(16, 16).unwrap();
+
+    assert_eq!(gl_toolkit::viewport().width, 16);
+    assert_eq!(clear_and_read(Color::make(0, 255, 0, 255)), vec![0, 255, 0, 255]);
+    assert!(Texture::make(&vec![255; 4], 1, 1, false).is_ok());
+
+    drop(second);
+
+    // with the latch released, another thread can take over
+    thread::spawn(|| {
+        let _third = HeadlessContext::new(8, 8).unwrap();
+
+        assert_eq!(clear_and_read(Color::make(0, 0, 255, 255)), vec![0, 0, 255, 255]);
+    })
+    .join()
+    .unwrap();
+
+    assert!(HeadlessContext::new(8, 8).is_ok());
+}